@@ -0,0 +1,174 @@
+/*! ADS-B receiver.
+
+Receives Mode S on 1090MHz at 2Msps, live from an RTL SDR, or from a
+capture. Decoded ADS-B messages are printed, and frames can be served to
+`readsb` or `tar1090` in Beast or AVR format.
+
+```text
+cargo run --features rtlsdr --example adsb_rx -- --rtlsdr --beast '[::]:30005'
+cargo run --example adsb_rx -- -r adsb.sigmf-data
+cargo run --example adsb_rx -- -r adsb.u8 --rtlsdr-file --frames
+```
+
+To view on a map, point `readsb` at the Beast port:
+
+```text
+readsb --net-only --net-connector localhost,30005,beast_in
+```
+*/
+use anyhow::Result;
+use clap::Parser;
+
+use rustradio::adsb::{Message, SAMPLE_RATE, Squitter};
+use rustradio::beast_server::{BeastFormat, avr_encode};
+use rustradio::blocks::*;
+use rustradio::graph::{Graph, GraphRunner};
+use rustradio::stream::{ReadStream, Tag};
+use rustradio::{Complex, Error, blockchain, parse_verbosity};
+
+#[derive(clap::Parser, Debug)]
+#[command(version, about)]
+struct Opt {
+    /// Input SigMF file, or rtl_sdr file with --rtlsdr-file.
+    #[arg(short)]
+    read: Option<std::path::PathBuf>,
+
+    /// Input is unsigned 8 bit I/Q, as written by rtl_sdr.
+    #[arg(long)]
+    rtlsdr_file: bool,
+
+    /// Receive live from an RTL SDR.
+    #[arg(long)]
+    rtlsdr: bool,
+
+    /// RTL SDR gain.
+    #[cfg(feature = "rtlsdr")]
+    #[arg(long, default_value = "40")]
+    gain: i32,
+
+    /// Verbosity level.
+    #[arg(short, value_parser=parse_verbosity, default_value = "info")]
+    verbose: usize,
+
+    /// Serve frames in Beast binary format on this address, e.g. `[::]:30005`.
+    #[arg(long)]
+    beast: Option<String>,
+
+    /// Serve frames in AVR format on this address, e.g. `[::]:30002`.
+    #[arg(long)]
+    avr: Option<String>,
+
+    /// Print all frames in AVR format, instead of decoded ADS-B messages.
+    #[arg(long)]
+    frames: bool,
+}
+
+fn get_input(g: &mut Graph, opt: &Opt) -> Result<ReadStream<Complex>> {
+    if opt.rtlsdr {
+        #[cfg(feature = "rtlsdr")]
+        return Ok(blockchain![
+            g,
+            prev,
+            RtlSdrSource::new(1_090_000_000, SAMPLE_RATE as u32, opt.gain)?,
+            RtlSdrDecode::new(prev),
+        ]);
+        #[cfg(not(feature = "rtlsdr"))]
+        return Err(Error::msg("rtlsdr feature not enabled").into());
+    }
+    let read = opt.read.as_ref().ok_or(Error::msg("need -r or --rtlsdr"))?;
+    if opt.rtlsdr_file {
+        return Ok(blockchain![
+            g,
+            prev,
+            FileSource::new(read)?,
+            RtlSdrDecode::new(prev),
+        ]);
+    }
+    let (b, prev) = SigMFSource::builder(read.clone()).build()?;
+    if b.sample_rate().is_some_and(|s| s != SAMPLE_RATE) {
+        return Err(Error::msg(format!("sample rate needs to be {SAMPLE_RATE}")).into());
+    }
+    g.add(Box::new(b));
+    Ok(prev)
+}
+
+fn describe(msg: &Message) -> String {
+    let what = match &msg.squitter {
+        Squitter::Identification { callsign, .. } => format!("callsign {callsign}"),
+        Squitter::AirbornePosition {
+            altitude, position, ..
+        } => {
+            let alt = altitude.map_or("?".into(), |a| format!("{a}ft"));
+            match position {
+                Some(p) => format!("position {:.5} {:.5} {alt}", p.lat, p.lon),
+                None => format!("position ? {alt}"),
+            }
+        }
+        Squitter::Velocity(v) => {
+            let speed = v.speed.map_or("?".into(), |s| format!("{s:.0}kt"));
+            let dir = v
+                .track
+                .or(v.heading)
+                .map_or("?".into(), |d| format!("{d:.0}°"));
+            let vr = v.vertical_rate.map_or("?".into(), |r| format!("{r}fpm"));
+            format!("velocity {speed} {dir} {vr}")
+        }
+        Squitter::Other { type_code } => format!("type code {type_code}"),
+    };
+    format!("{:06X} {what}\n", msg.icao)
+}
+
+fn main() -> Result<()> {
+    let opt = Opt::parse();
+    stderrlog::new()
+        .module(module_path!())
+        .module("rustradio")
+        .quiet(false)
+        .verbosity(opt.verbose)
+        .timestamp(stderrlog::Timestamp::Second)
+        .init()?;
+
+    let mut g = Graph::new();
+    let prev = get_input(&mut g, &opt)?;
+    let mut prev = blockchain![g, prev, ComplexToMag2::new(prev), ModeSDemod::new(prev)];
+    for (addr, format) in [
+        (&opt.beast, BeastFormat::Binary),
+        (&opt.avr, BeastFormat::Avr),
+    ] {
+        if let Some(addr) = addr {
+            let (mut b, out) = BeastServer::new(prev, addr)?;
+            b.set_format(format);
+            g.add(Box::new(b));
+            prev = out;
+        }
+    }
+    let prev = if opt.frames {
+        blockchain![
+            g,
+            prev,
+            NCMap::new(prev, "format", |frame: Vec<u8>, tags: Vec<Tag>| {
+                vec![(avr_encode(&frame).into_bytes(), tags)]
+            }),
+        ]
+    } else {
+        blockchain![
+            g,
+            prev,
+            AdsbDecode::new(prev),
+            NCMap::new(prev, "format", |msg: Message, tags: Vec<Tag>| {
+                vec![(describe(&msg).into_bytes(), tags)]
+            }),
+        ]
+    };
+    let prev = blockchain![g, prev, PduToStream::new(prev)];
+    g.add(Box::new(WriterSink::new(prev, std::io::stdout())));
+
+    let cancel = g.cancel_token();
+    ctrlc::set_handler(move || {
+        eprintln!("Received Ctrl+C!");
+        cancel.cancel();
+    })
+    .expect("Error setting Ctrl-C handler");
+    g.run()?;
+    Ok(())
+}
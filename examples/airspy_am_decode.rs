@@ -65,7 +65,7 @@ pub fn main() -> Result<()> {
                 &rustradio::window::WindowType::Hamming,
             )
         ),
-        Map::keep_tags(prev, "AM decode", |v| v.norm()),
+        AmDemod::new(prev, opt.sample_rate),
         FftFilterFloat::new(
             prev,
             &rustradio::fir::low_pass(
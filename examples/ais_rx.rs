@@ -0,0 +1,113 @@
+/*! AIS receiver.
+
+Decodes both AIS channels from an I/Q capture, and prints NMEA sentences
+suitable for feeding to chart plotters and AIS aggregators.
+
+```text
+cargo run --example ais_rx -- -r ships.sigmf-data
+cargo run --example ais_rx -- -r ships.c32 --raw --sample-rate 96k --center 162M
+```
+*/
+use anyhow::Result;
+use clap::Parser;
+
+use rustradio::ais::{CHANNEL_TAG, Message, NMEA_TAG};
+use rustradio::blocks::*;
+use rustradio::graph::{Graph, GraphRunner};
+use rustradio::stream::{ReadStream, Tag, TagValue};
+use rustradio::{Complex, Error, blockchain, parse_frequency, parse_verbosity};
+
+#[derive(clap::Parser, Debug)]
+#[command(version, about)]
+struct Opt {
+    /// Input SigMF file, or c32 file with --raw.
+    #[arg(short)]
+    read: std::path::PathBuf,
+
+    /// Input is a raw c32 file, not SigMF.
+    #[arg(long)]
+    raw: bool,
+
+    /// Verbosity level.
+    #[arg(short, value_parser=parse_verbosity, default_value = "info")]
+    verbose: usize,
+
+    /// Input sample rate. Required for raw files.
+    #[arg(long, value_parser=parse_frequency)]
+    sample_rate: Option<f64>,
+
+    /// Center frequency of the capture.
+    #[arg(long, value_parser=parse_frequency, default_value = "162M")]
+    center: f64,
+
+    /// Print decoded messages instead of NMEA.
+    #[arg(long)]
+    decode: bool,
+}
+
+fn get_input(g: &mut Graph, opt: &Opt) -> Result<(ReadStream<Complex>, f64)> {
+    if opt.raw {
+        let samp_rate = opt
+            .sample_rate
+            .ok_or(Error::msg("raw input requires --sample-rate"))?;
+        let (b, prev) = FileSource::new(&opt.read)?;
+        g.add(Box::new(b));
+        return Ok((prev, samp_rate));
+    }
+    let mut b = SigMFSource::builder(opt.read.clone());
+    if let Some(s) = opt.sample_rate {
+        b = b.sample_rate(s);
+    }
+    let (b, prev) = b.build()?;
+    let samp_rate = b
+        .sample_rate()
+        .ok_or(Error::msg("SigMF file does not specify sample rate"))?;
+    g.add(Box::new(b));
+    Ok((prev, samp_rate))
+}
+
+fn tag_string<'a>(tags: &'a [Tag], key: &str) -> &'a str {
+    tags.iter()
+        .find(|t| t.key() == key)
+        .and_then(|t| match t.val() {
+            TagValue::String(s) => Some(s.as_str()),
+            _ => None,
+        })
+        .unwrap_or("")
+}
+
+fn main() -> Result<()> {
+    let opt = Opt::parse();
+    stderrlog::new()
+        .module(module_path!())
+        .module("rustradio")
+        .quiet(false)
+        .verbosity(opt.verbose)
+        .timestamp(stderrlog::Timestamp::Second)
+        .init()?;
+
+    let mut g = Graph::new();
+    let (prev, samp_rate) = get_input(&mut g, &opt)?;
+    let prev = rustradio::ais::receiver(&mut g, prev, samp_rate, opt.center)?;
+    let decode = opt.decode;
+    let prev = blockchain![
+        g,
+        prev,
+        NCMap::new(prev, "format", move |msg: Message, tags: Vec<Tag>| {
+            let s = if decode {
+                format!(
+                    "{} {:09} {msg:?}\n",
+                    tag_string(&tags, CHANNEL_TAG),
+                    msg.mmsi()
+                )
+            } else {
+                format!("{}\n", tag_string(&tags, NMEA_TAG))
+            };
+            vec![(s.into_bytes(), tags)]
+        }),
+        PduToStream::new(prev),
+    ];
+    g.add(Box::new(WriterSink::new(prev, std::io::stdout())));
+    g.run()?;
+    Ok(())
+}
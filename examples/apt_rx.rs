@@ -0,0 +1,118 @@
+/*! NOAA APT weather satellite receiver.
+
+Decodes an APT pass into a greyscale image, from FM demodulated audio in an
+.au file, or from an I/Q capture with the satellite at 0Hz.
+
+```text
+cargo run --example apt_rx -- -r pass.au --sample-rate 11025 -o pass.pgm
+cargo run --example apt_rx -- -r pass.sigmf-data --iq -o pass.pgm
+cargo run --features png --example apt_rx -- -r pass.au -o pass.png
+```
+
+WAV recordings can be converted with e.g. `sox pass.wav -e signed -b 16 pass.au`.
+*/
+use anyhow::Result;
+use clap::Parser;
+
+use rustradio::blocks::*;
+use rustradio::graph::{Graph, GraphRunner};
+use rustradio::stream::ReadStream;
+use rustradio::window::WindowType;
+use rustradio::{Error, Float, blockchain, parse_frequency, parse_verbosity};
+
+#[derive(clap::Parser, Debug)]
+#[command(version, about)]
+struct Opt {
+    /// Input .au audio file, raw f32 audio with --raw, or SigMF I/Q with
+    /// --iq.
+    #[arg(short)]
+    read: std::path::PathBuf,
+
+    /// Output image. PGM, or PNG if the name ends in .png.
+    #[arg(short, default_value = "apt.pgm")]
+    output: std::path::PathBuf,
+
+    /// Input is raw f32 audio.
+    #[arg(long)]
+    raw: bool,
+
+    /// Input is SigMF I/Q, not audio.
+    #[arg(long)]
+    iq: bool,
+
+    /// Audio sample rate.
+    #[arg(long, value_parser=parse_frequency, default_value = "11025")]
+    sample_rate: f64,
+
+    /// Verbosity level.
+    #[arg(short, value_parser=parse_verbosity, default_value = "info")]
+    verbose: usize,
+}
+
+fn get_input(g: &mut Graph, opt: &Opt) -> Result<(ReadStream<Float>, Float)> {
+    if opt.iq {
+        let (b, prev) = SigMFSource::builder(opt.read.clone()).build()?;
+        let samp_rate = b
+            .sample_rate()
+            .ok_or(Error::msg("SigMF file does not specify sample rate"))?;
+        g.add(Box::new(b));
+        let audio_rate = 48_000.0;
+        let prev = blockchain![
+            g,
+            prev,
+            // ±17kHz deviation.
+            FftFilter::new(
+                prev,
+                rustradio::fir::low_pass_complex(
+                    samp_rate as Float,
+                    20_000.0,
+                    5_000.0,
+                    &WindowType::Hamming,
+                )
+            ),
+            RationalResampler::builder()
+                .deci(samp_rate as usize)
+                .interp(audio_rate as usize)
+                .build(prev)?,
+            QuadratureDemod::new(prev, 1.0),
+        ];
+        return Ok((prev, audio_rate));
+    }
+    let samp_rate = opt.sample_rate as Float;
+    let prev = if opt.raw {
+        blockchain![g, prev, FileSource::new(&opt.read)?]
+    } else {
+        blockchain![
+            g,
+            prev,
+            FileSource::new(&opt.read)?,
+            AuDecode::new(prev, opt.sample_rate as u32),
+        ]
+    };
+    Ok((prev, samp_rate))
+}
+
+fn main() -> Result<()> {
+    let opt = Opt::parse();
+    stderrlog::new()
+        .module(module_path!())
+        .module("rustradio")
+        .quiet(false)
+        .verbosity(opt.verbose)
+        .timestamp(stderrlog::Timestamp::Second)
+        .init()?;
+
+    let mut g = Graph::new();
+    let (prev, samp_rate) = get_input(&mut g, &opt)?;
+    let prev = blockchain![g, prev, AptDecode::new(prev, samp_rate)?];
+    g.add(Box::new(ImageSink::new(prev, &opt.output)?));
+
+    let cancel = g.cancel_token();
+    ctrlc::set_handler(move || {
+        eprintln!("Received Ctrl+C!");
+        cancel.cancel();
+    })
+    .expect("Error setting Ctrl-C handler");
+    g.run()?;
+    Ok(())
+}
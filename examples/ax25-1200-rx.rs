@@ -9,8 +9,16 @@ $ ./ax25-1200-rx -r captured.c32 --samp_rate 50000 -o captured
 […]
 $ ./ax25-1200-rx --rtlsdr -o captured -v 2
 […]
+$ ./ax25-1200-rx --rtlsdr --kiss '[::]:8001'
+[…]
 ```
 
+With `--kiss` it acts as a receive only KISS TNC, that e.g. Xastir or YAAC can
+connect to.
+
+With `--aprs-json` decoded APRS packets are printed as JSON lines, e.g. for
+piping into `jq`.
+
 Test recordings for this code are at
 <http://wa8lmf.net/TNCtest/index.htm>. Note that track 2 should not
 be used, as it's incorrectly de-emphasized.
@@ -54,6 +62,14 @@ struct Opt {
     #[arg(long = "out", short, help = "Directory to write packets to")]
     output: Option<PathBuf>,
 
+    /// Serve decoded packets as a KISS TNC on this address, e.g. `[::]:8001`.
+    #[arg(long)]
+    kiss: Option<String>,
+
+    /// Print decoded APRS packets to stdout, as JSON lines.
+    #[arg(long)]
+    aprs_json: bool,
+
     #[cfg(feature = "rtlsdr")]
     #[arg(long = "freq", default_value = "144800000")]
     freq: u64,
@@ -315,6 +331,20 @@ fn main() -> Result<()> {
     ];
     if let Some(o) = opt.output {
         g.add(Box::new(PduWriter::new(prev, o)));
+    } else if let Some(addr) = opt.kiss {
+        // This is a receiver only, so just print what clients ask to send.
+        let prev = blockchain![g, prev, KissServer::new(prev, addr)?];
+        g.add(Box::new(DebugSinkNoCopy::new(prev)));
+    } else if opt.aprs_json {
+        let prev = blockchain![
+            g,
+            prev,
+            Ax25Decode::new(prev),
+            AprsDecode::new(prev),
+            ToJson::new(prev),
+            PduToStream::new(prev),
+        ];
+        g.add(Box::new(WriterSink::new(prev, std::io::stdout())));
     } else {
         g.add(Box::new(DebugSinkNoCopy::new(prev)));
     }
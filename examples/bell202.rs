@@ -152,7 +152,6 @@ pub fn main() -> Result<()> {
     {
         info!("Setting up transmitter");
         let cancel = g.cancel_token();
-        let baud = 1200.0;
         let audio_rate = 48000.0;
         let prev = blockchain![
             g,
@@ -164,17 +163,7 @@ pub fn main() -> Result<()> {
             HdlcFramer::new(prev),
             PduToStream::new(prev),
             NrziEncode::new(prev),
-            RationalResampler::builder()
-                .deci(baud as usize)
-                .interp(audio_rate as usize)
-                .build(prev)?,
-            Map::keep_tags(prev, "bits_to_pn", |s| if s > 0 {
-                2200.0 as Float
-            } else {
-                1200.0
-            }),
-            Vco::new(prev, 2.0 * std::f64::consts::PI / audio_rate),
-            Map::keep_tags(prev, "ComplexToFloat", |s| s.re),
+            AfskModulator::new(prev, audio_rate),
             MultiplyConst::new(prev, 0.5),
             RationalResampler::builder()
                 .deci(audio_rate as usize)
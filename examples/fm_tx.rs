@@ -4,8 +4,9 @@
 //! * Amateur radio: 5KHz
 //! * Broadcast FM: 75KHz
 //!
-//! TODO:
-//! * Add preemphasis.
+//! Pre-emphasis:
+//! * Amateur radio: usually none.
+//! * Broadcast FM: 75µs in the Americas, 50µs in most other places.
 use anyhow::Result;
 use clap::Parser;
 use log::warn;
@@ -53,6 +54,10 @@ struct Opt {
     /// FM deviation.
     #[arg(long, default_value_t = 5000)]
     deviation: usize,
+
+    /// Pre-emphasis time constant, in microseconds. 0 to disable.
+    #[arg(long, default_value_t = 0.0)]
+    preemphasis: f64,
 }
 
 fn main() -> Result<()> {
@@ -85,6 +90,19 @@ fn main() -> Result<()> {
             .deci(opt.audio_rate)
             .interp(opt.sample_rate)
             .build(prev)?,
+    ];
+    let prev = if opt.preemphasis != 0.0 {
+        blockchain![
+            g,
+            prev,
+            Preemphasis::new(prev, opt.sample_rate as f64, opt.preemphasis * 1e-6)?,
+        ]
+    } else {
+        prev
+    };
+    let prev = blockchain![
+        g,
+        prev,
         Vco::new(
             prev,
             2.0 * std::f64::consts::PI * opt.deviation as f64 / opt.sample_rate as f64
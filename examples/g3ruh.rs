@@ -255,16 +255,10 @@ pub fn main() -> Result<()> {
             PduToStream::new(prev),
             Scrambler::g3ruh(prev),
             NrziEncode::new(prev),
-            RationalResampler::builder()
-                .deci(baud as usize)
-                .interp(if_rate as usize)
-                .build(prev)?,
-            Map::keep_tags(prev, "bits_to_pn", |s| if s > 0 {
-                3000.0 as Float
-            } else {
-                -3000.0
-            }),
-            Vco::new(prev, 2.0 * std::f64::consts::PI / if_rate),
+            // ±3kHz deviation.
+            GfskModulator::builder(prev, (if_rate / baud) as usize)
+                .modulation_index(6000.0 / baud as Float)
+                .build()?,
             MultiplyConst::new(prev, 0.5.into()),
             RationalResampler::builder()
                 .deci(if_rate as usize)
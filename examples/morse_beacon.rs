@@ -9,6 +9,8 @@
 //!     --clock-source gpsdo \
 //!     'M0XXX TESTING'
 //! ```
+//!
+//! Use the `morse_rx` example to decode it.
 use anyhow::Result;
 use clap::Parser;
 use log::info;
@@ -51,6 +53,10 @@ struct Opt {
     #[arg(long, default_value_t = 20.0)]
     wpm: f32,
 
+    /// Use Farnsworth spacing, for this overall words per minute.
+    #[arg(long)]
+    farnsworth: Option<f32>,
+
     /// Set clock source. Valid values are SDR dependent.
     #[arg(long)]
     clock_source: Option<String>,
@@ -128,22 +134,19 @@ pub fn main() -> Result<()> {
     });
 
     let amp = opt.amplitude;
-    // 20 WPM is 60ms time unit.
-    let raw_sps = (opt.wpm / 20.0) / 0.06;
+    let prev = blockchain![g, prev, Strobe::new(opt.interval, &opt.msg)];
+    let mut encoder = MorseEncode::builder(prev).wpm(opt.wpm);
+    if let Some(wpm) = opt.farnsworth {
+        encoder = encoder.farnsworth(wpm);
+    }
     let prev = blockchain![
         g,
         prev,
-        Strobe::new(opt.interval, &opt.msg),
-        MorseEncode::new(prev),
+        encoder.build(),
         PduToStream::new(prev),
-        RationalResampler::builder()
-            // Multiply by 100 to get more significant digits on raw SPS, which
-            // otherwise rounds 20 WPM 16.666 to 16.
-            .deci((100.0 * raw_sps) as usize)
-            .interp((100.0 * opt.sample_rate) as usize)
-            .build(prev)?,
+        CwKeyer::new(prev, opt.sample_rate, opt.wpm.into()),
         Map::keep_tags(prev, "ToComplex", move |s| Complex::new(
-            amp as Float * s as Float,
+            amp as Float * s,
             0.0
         )),
     ];
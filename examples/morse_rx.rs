@@ -0,0 +1,108 @@
+/*! Morse code receiver.
+
+Decodes morse from an I/Q capture, such as the one the disabled receiver in
+`morse_beacon` writes. The carrier should be within the filter bandwidth of
+0Hz.
+
+```text
+cargo run --example morse_rx -- -r morse-300ksps.c32 --sample-rate 300k
+```
+*/
+use anyhow::Result;
+use clap::Parser;
+
+use rustradio::blocks::*;
+use rustradio::graph::{Graph, GraphRunner};
+use rustradio::window::WindowType;
+use rustradio::{Float, blockchain, parse_frequency, parse_verbosity};
+
+#[derive(clap::Parser, Debug)]
+#[command(version, about)]
+struct Opt {
+    /// Input c32 file.
+    #[arg(short)]
+    read: String,
+
+    /// Verbosity level.
+    #[arg(short, value_parser=parse_verbosity, default_value = "info")]
+    verbose: usize,
+
+    /// Input sample rate.
+    #[arg(long, value_parser=parse_frequency, default_value = "300k")]
+    sample_rate: f64,
+
+    /// Filter bandwidth, in Hz. Must cover any frequency error.
+    #[arg(long, default_value_t = 200.0)]
+    bandwidth: Float,
+
+    /// Initial speed estimate, in words per minute.
+    #[arg(long, default_value_t = 20.0)]
+    wpm: f64,
+
+    /// Print time and speed before each character.
+    #[arg(long)]
+    timestamps: bool,
+}
+
+fn main() -> Result<()> {
+    let opt = Opt::parse();
+    stderrlog::new()
+        .module(module_path!())
+        .module("rustradio")
+        .quiet(false)
+        .verbosity(opt.verbose)
+        .timestamp(stderrlog::Timestamp::Second)
+        .init()?;
+
+    let mut g = Graph::new();
+    let envelope_rate = 8000.0;
+    let timestamps = opt.timestamps;
+    let prev = blockchain![
+        g,
+        prev,
+        FileSource::new(&opt.read)?,
+        FftFilter::new(
+            prev,
+            rustradio::fir::low_pass_complex(
+                opt.sample_rate as Float,
+                opt.bandwidth,
+                opt.bandwidth / 2.0,
+                &WindowType::Hamming,
+            )
+        ),
+        ComplexToMag2::new(prev),
+        RationalResampler::builder()
+            .deci(opt.sample_rate as usize)
+            .interp(envelope_rate as usize)
+            .build(prev)?,
+        CwDecode::builder(prev, envelope_rate).wpm(opt.wpm).build(),
+        NCMap::new(prev, "format", move |s: String, tags| {
+            let s = if timestamps {
+                let mut ms = 0;
+                let mut wpm = 0.0;
+                for tag in &tags {
+                    match (tag.key(), tag.val()) {
+                        (rustradio::cw_decode::TIME_TAG, rustradio::stream::TagValue::U64(v)) => {
+                            ms = *v
+                        }
+                        (rustradio::cw_decode::WPM_TAG, rustradio::stream::TagValue::Float(v)) => {
+                            wpm = *v
+                        }
+                        _ => {}
+                    }
+                }
+                format!("{:10.3}s {wpm:4.1} WPM '{s}'\n", ms as f64 / 1000.0)
+            } else {
+                s
+            };
+            vec![(s.into_bytes(), tags)]
+        }),
+        PduToStream::new(prev),
+    ];
+    g.add(Box::new(WriterSink::new(prev, std::io::stdout())));
+    g.run()?;
+    if !timestamps {
+        println!();
+    }
+    Ok(())
+}
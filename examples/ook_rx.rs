@@ -0,0 +1,325 @@
+/*! OOK receiver for ISM band devices.
+
+Detects on-off keyed packets in an I/Q capture with the signal near 0Hz,
+and either decodes a known device, or prints the sliced bits for writing a
+new decoder.
+
+```text
+cargo run --example ook_rx -- -r 433.sigmf-data --device nexus
+cargo run --features rtlsdr --example ook_rx -- --rtlsdr --device ev1527
+cargo run --example ook_rx -- -r 433.c32 --raw --sample-rate 250k --coding ppm --short 1000 --long 2000 --gap-limit 3000
+cargo run --example ook_rx -- -r 433.c32 --raw --sample-rate 250k --pulses
+```
+*/
+use anyhow::Result;
+use clap::Parser;
+
+use rustradio::blocks::*;
+use rustradio::graph::{Graph, GraphRunner};
+use rustradio::ook::{BitBuffer, Coding, Pulse};
+use rustradio::stream::{ReadStream, Tag};
+use rustradio::{Complex, Error, Float, blockchain, parse_frequency, parse_verbosity};
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+enum Device {
+    /// Nexus, and rebrands, temperature and humidity sensors.
+    Nexus,
+    /// Prologue temperature and humidity sensors.
+    Prologue,
+    /// EV1527 based remote controls and door sensors.
+    Ev1527,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+enum CodingArg {
+    Pwm,
+    Ppm,
+    Manchester,
+}
+
+#[derive(clap::Parser, Debug)]
+#[command(version, about)]
+struct Opt {
+    /// Input SigMF file, c32 file with --raw, or rtl_sdr file with
+    /// --rtlsdr-file.
+    #[arg(short)]
+    read: Option<std::path::PathBuf>,
+
+    /// Input is a raw c32 file, not SigMF.
+    #[arg(long)]
+    raw: bool,
+
+    /// Input is unsigned 8 bit I/Q, as written by rtl_sdr.
+    #[arg(long)]
+    rtlsdr_file: bool,
+
+    /// Receive live from an RTL SDR.
+    #[arg(long)]
+    rtlsdr: bool,
+
+    /// RTL SDR frequency.
+    #[cfg(feature = "rtlsdr")]
+    #[arg(long, value_parser=parse_frequency, default_value = "433.92M")]
+    freq: f64,
+
+    /// RTL SDR gain.
+    #[cfg(feature = "rtlsdr")]
+    #[arg(long, default_value = "40")]
+    gain: i32,
+
+    /// Input sample rate. Required for raw files.
+    #[arg(long, value_parser=parse_frequency)]
+    sample_rate: Option<f64>,
+
+    /// Verbosity level.
+    #[arg(short, value_parser=parse_verbosity, default_value = "info")]
+    verbose: usize,
+
+    /// Device to decode.
+    #[arg(long, value_enum)]
+    device: Option<Device>,
+
+    /// Line coding, for printing bits of unknown devices.
+    #[arg(long, value_enum)]
+    coding: Option<CodingArg>,
+
+    /// Short width in µs. For Manchester, the half bit width.
+    #[arg(long, default_value_t = 500)]
+    short: u32,
+
+    /// Long width in µs.
+    #[arg(long, default_value_t = 1_000)]
+    long: u32,
+
+    /// Gap in µs ending a row.
+    #[arg(long, default_value_t = 3_000)]
+    gap_limit: u32,
+
+    /// Gap in µs ending a packet.
+    #[arg(long)]
+    reset_limit: Option<u32>,
+
+    /// Print pulse timings, instead of bits.
+    #[arg(long)]
+    pulses: bool,
+}
+
+impl Device {
+    fn coding(self) -> Coding {
+        match self {
+            Device::Nexus => Coding::Ppm {
+                short: 1_000,
+                long: 2_000,
+                gap_limit: 3_000,
+            },
+            Device::Prologue => Coding::Ppm {
+                short: 2_000,
+                long: 4_000,
+                gap_limit: 7_000,
+            },
+            Device::Ev1527 => Coding::Pwm {
+                short: 350,
+                long: 1_050,
+                gap_limit: 2_000,
+            },
+        }
+    }
+
+    fn reset_limit(self) -> u32 {
+        match self {
+            Device::Nexus => 5_000,
+            Device::Prologue => 10_000,
+            // Longer than the sync gap, to get all repeats in one packet.
+            Device::Ev1527 => 15_000,
+        }
+    }
+
+    fn decode(self, bits: &BitBuffer) -> Option<String> {
+        match self {
+            Device::Nexus => decode_nexus(bits),
+            Device::Prologue => decode_prologue(bits),
+            Device::Ev1527 => decode_ev1527(bits),
+        }
+    }
+}
+
+// Sign extend a 12 bit temperature, in 0.1°C.
+fn temperature(raw: u64) -> Float {
+    Float::from(((raw as i16) << 4) >> 4) / 10.0
+}
+
+// 36 bits: id:8 battery:1 0:1 channel:2 temp:12 1111 humidity:8.
+fn decode_nexus(bits: &BitBuffer) -> Option<String> {
+    let r = bits.find_repeated_row(3, 36)?;
+    if bits.rows[r].len() != 36 || bits.get(r, 24, 4)? != 0xF {
+        return None;
+    }
+    Some(format!(
+        "Nexus id={} channel={} battery={} temp={:.1}C humidity={}%",
+        bits.get(r, 0, 8)?,
+        bits.get(r, 10, 2)? + 1,
+        if bits.get(r, 8, 1)? == 1 { "ok" } else { "low" },
+        temperature(bits.get(r, 12, 12)?),
+        bits.get(r, 28, 8)?,
+    ))
+}
+
+// 36 bits: type:4 id:8 battery:1 button:1 channel:2 temp:12 humidity:8.
+fn decode_prologue(bits: &BitBuffer) -> Option<String> {
+    let r = bits.find_repeated_row(2, 36)?;
+    if bits.rows[r].len() != 36 || ![5, 9].contains(&bits.get(r, 0, 4)?) {
+        return None;
+    }
+    Some(format!(
+        "Prologue id={} channel={} battery={} button={} temp={:.1}C humidity={}%",
+        bits.get(r, 4, 8)?,
+        bits.get(r, 14, 2)? + 1,
+        if bits.get(r, 12, 1)? == 1 {
+            "ok"
+        } else {
+            "low"
+        },
+        bits.get(r, 13, 1)?,
+        temperature(bits.get(r, 16, 12)?),
+        bits.get(r, 28, 8)?,
+    ))
+}
+
+// 24 bits, plus the sync pulse: id:20 data:4. A 0 is a short pulse.
+fn decode_ev1527(bits: &BitBuffer) -> Option<String> {
+    let mut bits = bits.clone();
+    bits.invert();
+    let r = bits.find_repeated_row(2, 25)?;
+    if bits.rows[r].len() != 25 {
+        return None;
+    }
+    Some(format!(
+        "EV1527 id={:05x} data={:04b}",
+        bits.get(r, 0, 20)?,
+        bits.get(r, 20, 4)?
+    ))
+}
+
+fn get_input(g: &mut Graph, opt: &Opt) -> Result<(ReadStream<Complex>, f64)> {
+    if opt.rtlsdr {
+        #[cfg(feature = "rtlsdr")]
+        {
+            let samp_rate = opt.sample_rate.unwrap_or(250_000.0);
+            let prev = blockchain![
+                g,
+                prev,
+                RtlSdrSource::new(opt.freq as u64, samp_rate as u32, opt.gain)?,
+                RtlSdrDecode::new(prev),
+            ];
+            return Ok((prev, samp_rate));
+        }
+        #[cfg(not(feature = "rtlsdr"))]
+        return Err(Error::msg("rtlsdr feature not enabled").into());
+    }
+    let read = opt.read.as_ref().ok_or(Error::msg("need -r or --rtlsdr"))?;
+    if opt.raw || opt.rtlsdr_file {
+        let samp_rate = opt
+            .sample_rate
+            .ok_or(Error::msg("raw input requires --sample-rate"))?;
+        let prev = if opt.rtlsdr_file {
+            blockchain![g, prev, FileSource::new(read)?, RtlSdrDecode::new(prev)]
+        } else {
+            blockchain![g, prev, FileSource::new(read)?]
+        };
+        return Ok((prev, samp_rate));
+    }
+    let mut b = SigMFSource::builder(read.clone());
+    if let Some(s) = opt.sample_rate {
+        b = b.sample_rate(s);
+    }
+    let (b, prev) = b.build()?;
+    let samp_rate = b
+        .sample_rate()
+        .ok_or(Error::msg("SigMF file does not specify sample rate"))?;
+    g.add(Box::new(b));
+    Ok((prev, samp_rate))
+}
+
+fn main() -> Result<()> {
+    let opt = Opt::parse();
+    stderrlog::new()
+        .module(module_path!())
+        .module("rustradio")
+        .quiet(false)
+        .verbosity(opt.verbose)
+        .timestamp(stderrlog::Timestamp::Second)
+        .init()?;
+    let coding = match (opt.device, opt.coding) {
+        (Some(d), _) => d.coding(),
+        (None, Some(CodingArg::Pwm)) => Coding::Pwm {
+            short: opt.short,
+            long: opt.long,
+            gap_limit: opt.gap_limit,
+        },
+        (None, Some(CodingArg::Ppm)) => Coding::Ppm {
+            short: opt.short,
+            long: opt.long,
+            gap_limit: opt.gap_limit,
+        },
+        (None, Some(CodingArg::Manchester)) => Coding::Manchester { half: opt.short },
+        (None, None) if opt.pulses => Coding::Manchester { half: opt.short },
+        (None, None) => {
+            return Err(Error::msg("need --device, --coding, or --pulses").into());
+        }
+    };
+    let reset_limit = opt
+        .reset_limit
+        .or(opt.device.map(Device::reset_limit))
+        .unwrap_or(10_000);
+
+    let mut g = Graph::new();
+    let (prev, samp_rate) = get_input(&mut g, &opt)?;
+    let prev = blockchain![
+        g,
+        prev,
+        ComplexToMag2::new(prev),
+        OokDetect::builder(prev, samp_rate as Float)
+            .reset_limit(reset_limit)
+            .build()?,
+    ];
+    let prev = if opt.pulses {
+        blockchain![
+            g,
+            prev,
+            NCMap::new(prev, "format", |pulses: Vec<Pulse>, tags: Vec<Tag>| {
+                let s: Vec<_> = pulses
+                    .iter()
+                    .map(|p| format!("{}/{}", p.pulse, p.gap))
+                    .collect();
+                vec![(format!("{}\n", s.join(" ")).into_bytes(), tags)]
+            }),
+        ]
+    } else {
+        let device = opt.device;
+        blockchain![
+            g,
+            prev,
+            OokSlicer::new(prev, coding),
+            NCMap::new(prev, "format", move |bits: BitBuffer, tags: Vec<Tag>| {
+                let s = match device {
+                    Some(d) => d.decode(&bits),
+                    None => Some(bits.to_string()),
+                };
+                s.map(|s| (format!("{s}\n").into_bytes(), tags))
+                    .into_iter()
+                    .collect()
+            }),
+        ]
+    };
+    let prev = blockchain![g, prev, PduToStream::new(prev)];
+    g.add(Box::new(WriterSink::new(prev, std::io::stdout())));
+
+    let cancel = g.cancel_token();
+    ctrlc::set_handler(move || {
+        eprintln!("Received Ctrl+C!");
+        cancel.cancel();
+    })
+    .expect("Error setting Ctrl-C handler");
+    g.run()?;
+    Ok(())
+}
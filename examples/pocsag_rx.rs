@@ -0,0 +1,126 @@
+/*! POCSAG pager receiver.
+
+Decodes POCSAG from an I/Q capture with the channel at 0Hz.
+
+```text
+cargo run --example pocsag_rx -- -r pager.sigmf-data --baud 1200
+cargo run --example pocsag_rx -- -r pager.c32 --raw --sample-rate 1024k --baud 512
+```
+*/
+use anyhow::Result;
+use clap::Parser;
+
+use rustradio::blocks::*;
+use rustradio::graph::{Graph, GraphRunner};
+use rustradio::pocsag::{Message, SYNC_TAG, sync_code};
+use rustradio::stream::{ReadStream, Tag};
+use rustradio::window::WindowType;
+use rustradio::{Complex, Error, Float, blockchain, parse_frequency, parse_verbosity};
+
+#[derive(clap::Parser, Debug)]
+#[command(version, about)]
+struct Opt {
+    /// Input SigMF file, or c32 file with --raw.
+    #[arg(short)]
+    read: std::path::PathBuf,
+
+    /// Input is a raw c32 file, not SigMF.
+    #[arg(long)]
+    raw: bool,
+
+    /// Verbosity level.
+    #[arg(short, value_parser=parse_verbosity, default_value = "info")]
+    verbose: usize,
+
+    /// Input sample rate. Required for raw files.
+    #[arg(long, value_parser=parse_frequency)]
+    sample_rate: Option<f64>,
+
+    /// Baud rate: 512, 1200, or 2400.
+    #[arg(long, default_value_t = 1200)]
+    baud: u32,
+
+    /// Invert bits, for receivers with inverted spectrum.
+    #[arg(long)]
+    invert: bool,
+
+    /// Number of bit errors allowed in the sync codeword.
+    #[arg(long, default_value_t = 2)]
+    sync_errors: usize,
+}
+
+fn get_input(g: &mut Graph, opt: &Opt) -> Result<(ReadStream<Complex>, f64)> {
+    if opt.raw {
+        let samp_rate = opt
+            .sample_rate
+            .ok_or(Error::msg("raw input requires --sample-rate"))?;
+        let (b, prev) = FileSource::new(&opt.read)?;
+        g.add(Box::new(b));
+        return Ok((prev, samp_rate));
+    }
+    let mut b = SigMFSource::builder(opt.read.clone());
+    if let Some(s) = opt.sample_rate {
+        b = b.sample_rate(s);
+    }
+    let (b, prev) = b.build()?;
+    let samp_rate = b
+        .sample_rate()
+        .ok_or(Error::msg("SigMF file does not specify sample rate"))?;
+    g.add(Box::new(b));
+    Ok((prev, samp_rate))
+}
+
+fn main() -> Result<()> {
+    let opt = Opt::parse();
+    stderrlog::new()
+        .module(module_path!())
+        .module("rustradio")
+        .quiet(false)
+        .verbosity(opt.verbose)
+        .timestamp(stderrlog::Timestamp::Second)
+        .init()?;
+    if ![512, 1200, 2400].contains(&opt.baud) {
+        return Err(Error::msg(format!("unsupported baud rate {}", opt.baud)).into());
+    }
+
+    let mut g = Graph::new();
+    let (prev, samp_rate) = get_input(&mut g, &opt)?;
+    let new_samp_rate = 48_000.0;
+    let baud = opt.baud as Float;
+    // POCSAG sends a 1 as the lower frequency.
+    let polarity = if opt.invert { 1.0 } else { -1.0 };
+    let prev = blockchain![
+        g,
+        prev,
+        // ±4.5kHz deviation.
+        FftFilter::new(
+            prev,
+            rustradio::fir::low_pass_complex(
+                samp_rate as Float,
+                7_000.0,
+                2_000.0,
+                &WindowType::Hamming,
+            )
+        ),
+        RationalResampler::builder()
+            .deci(samp_rate as usize)
+            .interp(new_samp_rate as usize)
+            .build(prev)?,
+        QuadratureDemod::new(prev, polarity),
+        FftFilterFloat::new(
+            prev,
+            &rustradio::fir::low_pass(new_samp_rate, baud, baud / 2.0, &WindowType::Hamming),
+        ),
+        ZeroCrossing::new(prev, new_samp_rate / baud, 0.1),
+        BinarySlicer::new(prev),
+        CorrelateAccessCodeTag::new(prev, sync_code(), SYNC_TAG, opt.sync_errors),
+        PocsagDecode::new(prev),
+        NCMap::new(prev, "format", |msg: Message, tags: Vec<Tag>| {
+            vec![(format!("{msg}\n").into_bytes(), tags)]
+        }),
+        PduToStream::new(prev),
+    ];
+    g.add(Box::new(WriterSink::new(prev, std::io::stdout())));
+    g.run()?;
+    Ok(())
+}
@@ -0,0 +1,146 @@
+/*! RDS receiver.
+
+Decodes RDS, the station name and text sent along with broadcast FM, from
+an I/Q capture with the station at 0Hz.
+
+```text
+cargo run --example rds_rx -- -r fm.sigmf-data
+cargo run --example rds_rx -- -r fm.c32 --raw --sample-rate 1024k
+```
+*/
+use anyhow::Result;
+use clap::Parser;
+
+use rustradio::blocks::*;
+use rustradio::graph::{Graph, GraphRunner};
+use rustradio::rds::{Group, GroupData, PS_TAG, RADIOTEXT_TAG};
+use rustradio::stream::{ReadStream, Tag, TagValue};
+use rustradio::window::WindowType;
+use rustradio::{Complex, Error, Float, blockchain, parse_frequency, parse_verbosity};
+
+#[derive(clap::Parser, Debug)]
+#[command(version, about)]
+struct Opt {
+    /// Input SigMF file, or c32 file with --raw.
+    #[arg(short)]
+    read: std::path::PathBuf,
+
+    /// Input is a raw c32 file, not SigMF.
+    #[arg(long)]
+    raw: bool,
+
+    /// Verbosity level.
+    #[arg(short, value_parser=parse_verbosity, default_value = "info")]
+    verbose: usize,
+
+    /// Input sample rate. Required for raw files.
+    #[arg(long, value_parser=parse_frequency)]
+    sample_rate: Option<f64>,
+
+    /// Print every group, not just complete names and texts.
+    #[arg(long)]
+    groups: bool,
+}
+
+fn get_input(g: &mut Graph, opt: &Opt) -> Result<(ReadStream<Complex>, f64)> {
+    if opt.raw {
+        let samp_rate = opt
+            .sample_rate
+            .ok_or(Error::msg("raw input requires --sample-rate"))?;
+        let (b, prev) = FileSource::new(&opt.read)?;
+        g.add(Box::new(b));
+        return Ok((prev, samp_rate));
+    }
+    let mut b = SigMFSource::builder(opt.read.clone());
+    if let Some(s) = opt.sample_rate {
+        b = b.sample_rate(s);
+    }
+    let (b, prev) = b.build()?;
+    let samp_rate = b
+        .sample_rate()
+        .ok_or(Error::msg("SigMF file does not specify sample rate"))?;
+    g.add(Box::new(b));
+    Ok((prev, samp_rate))
+}
+
+fn format_group(group: &Group) -> String {
+    let version = if group.version_b { 'B' } else { 'A' };
+    let data = match &group.data {
+        GroupData::ProgramService { segment, chars, .. } => {
+            format!("PS {segment} '{}'", String::from_utf8_lossy(chars))
+        }
+        GroupData::RadioText { segment, chars, .. } => {
+            format!("RT {segment} '{}'", String::from_utf8_lossy(chars))
+        }
+        GroupData::ClockTime(ct) => format!("CT {ct}"),
+        GroupData::Other => format!("{:04X?}", &group.blocks[1..]),
+    };
+    format!(
+        "{:04X} {:>2}{version} PTY {:2} {data}\n",
+        group.pi, group.group_type, group.pty
+    )
+}
+
+fn main() -> Result<()> {
+    let opt = Opt::parse();
+    stderrlog::new()
+        .module(module_path!())
+        .module("rustradio")
+        .quiet(false)
+        .verbosity(opt.verbose)
+        .timestamp(stderrlog::Timestamp::Second)
+        .init()?;
+
+    let mut g = Graph::new();
+    let (prev, samp_rate) = get_input(&mut g, &opt)?;
+    // Multiplex signal goes up to 60kHz, so resample to four times the RDS
+    // subcarrier.
+    let mpx_rate = 228_000.0;
+    let groups = opt.groups;
+    let prev = blockchain![
+        g,
+        prev,
+        FftFilter::new(
+            prev,
+            rustradio::fir::low_pass_complex(
+                samp_rate as Float,
+                100_000.0,
+                20_000.0,
+                &WindowType::Hamming,
+            )
+        ),
+        RationalResampler::builder()
+            .deci(samp_rate as usize)
+            .interp(mpx_rate as usize)
+            .build(prev)?,
+        QuadratureDemod::new(prev, 1.0),
+        RdsDemod::new(prev, mpx_rate),
+        RdsDecode::new(prev),
+        NCMap::new(prev, "format", move |group: Group, tags: Vec<Tag>| {
+            let mut s = if groups {
+                format_group(&group)
+            } else {
+                String::new()
+            };
+            for tag in &tags {
+                match (tag.key(), tag.val()) {
+                    (PS_TAG, TagValue::String(ps)) => {
+                        s += &format!("{:04X} PS: {ps}\n", group.pi);
+                    }
+                    (RADIOTEXT_TAG, TagValue::String(rt)) => {
+                        s += &format!("{:04X} RT: {rt}\n", group.pi);
+                    }
+                    _ => {}
+                }
+            }
+            if !groups && matches!(group.data, GroupData::ClockTime(_)) {
+                s += &format_group(&group);
+            }
+            vec![(s.into_bytes(), tags)]
+        }),
+        PduToStream::new(prev),
+    ];
+    g.add(Box::new(WriterSink::new(prev, std::io::stdout())));
+    g.run()?;
+    Ok(())
+}
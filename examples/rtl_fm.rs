@@ -2,6 +2,8 @@
 #![allow(clippy::collapsible_if)]
 /*!
 Example broadcast FM receiver, sending output to an Au file.
+
+With `--stereo`, the output file or live audio has two channels.
  */
 use std::collections::VecDeque;
 
@@ -16,6 +18,7 @@ use rustradio::file_sink::Mode;
 use rustradio::graph::Graph;
 use rustradio::graph::GraphRunner;
 use rustradio::mtgraph::MTGraph;
+use rustradio::stream::ReadStream;
 
 const SPECTRUM_SIZE: usize = 1024;
 
@@ -64,6 +67,15 @@ struct Opt {
     #[arg(long, default_value_t = 10.0)]
     fps: f32,
 
+    /// Demodulate stereo.
+    #[arg(long)]
+    stereo: bool,
+
+    /// De-emphasis time constant, in microseconds. 75 in the Americas, 50 in
+    /// most other places. 0 to disable.
+    #[arg(long, default_value_t = 75.0)]
+    deemphasis: f64,
+
     /// Audio output rate.
     #[arg(default_value = "48000")]
     audio_rate: u32,
@@ -394,7 +406,42 @@ fn build(g: &mut dyn GraphRunner, opt: &Opt) -> Result<std::thread::JoinHandle<(
             .deci(samp_rate as usize)
             .interp(samp_rate_2 as usize)
             .build(prev)?,
-        QuadratureDemod::new(prev, 1.0),
+    ];
+
+    if opt.stereo {
+        let (block, left, right) = WbfmStereoDemod::builder(prev, samp_rate_2.into())
+            .deemphasis(opt.deemphasis * 1e-6)
+            .build()?;
+        g.add(Box::new(block));
+        let [left, right] = [left, right].map(|prev| -> Result<_> {
+            Ok(blockchain![
+                g,
+                prev,
+                RationalResampler::builder()
+                    .deci(samp_rate_2 as usize)
+                    .interp(opt.audio_rate as usize)
+                    .build(prev)?,
+                MultiplyConst::new(prev, opt.volume),
+            ])
+        });
+        let prev = blockchain![g, prev, Interleave::new(left?, right?)];
+        output(g, prev, opt, 2)?;
+        return Ok(ui_thread);
+    }
+
+    let prev = blockchain![g, prev, QuadratureDemod::new(prev, 1.0)];
+    let prev = if opt.deemphasis != 0.0 {
+        blockchain![
+            g,
+            prev,
+            Deemphasis::new(prev, samp_rate_2.into(), opt.deemphasis * 1e-6)?,
+        ]
+    } else {
+        prev
+    };
+    let prev = blockchain![
+        g,
+        prev,
         FftFilterFloat::new(
             prev,
             &rustradio::fir::low_pass(
@@ -418,12 +465,29 @@ fn build(g: &mut dyn GraphRunner, opt: &Opt) -> Result<std::thread::JoinHandle<(
         MultiplyConst::new(prev, opt.volume),
     ];
 
+    output(g, prev, opt, 1)?;
+    Ok(ui_thread)
+}
+
+// Write audio to file, or play it live. With more than one channel, samples
+// are interleaved.
+fn output(
+    g: &mut dyn GraphRunner,
+    prev: ReadStream<Float>,
+    opt: &Opt,
+    channels: u16,
+) -> Result<()> {
     if let Some(ref out) = opt.output {
         // Convert to .au.
         let prev = blockchain![
             g,
             prev,
-            AuEncode::new(prev, rustradio::au::Encoding::Pcm16, opt.audio_rate, 1)
+            AuEncode::new(
+                prev,
+                rustradio::au::Encoding::Pcm16,
+                opt.audio_rate,
+                channels.into()
+            )
         ];
         // Save to file.
         g.add(Box::new(FileSink::new(prev, out, Mode::Overwrite)?));
@@ -436,9 +500,11 @@ fn build(g: &mut dyn GraphRunner, opt: &Opt) -> Result<std::thread::JoinHandle<(
         {
             // Play live.
             g.add(Box::new(
-                AudioSink::builder().build(prev, opt.audio_rate as u64)?,
+                AudioSink::builder()
+                    .channels(channels)
+                    .build(prev, opt.audio_rate as u64)?,
             ));
         }
     }
-    Ok(ui_thread)
+    Ok(())
 }
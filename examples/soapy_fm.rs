@@ -37,6 +37,11 @@ mod internal {
 
         #[arg(long = "volume", default_value = "1.0")]
         volume: Float,
+
+        /// De-emphasis time constant, in microseconds. 75 in the Americas,
+        /// 50 in most other places. 0 to disable.
+        #[arg(long, default_value_t = 75.0)]
+        deemphasis: f64,
     }
 
     pub fn main() -> Result<()> {
@@ -83,10 +88,17 @@ mod internal {
         ];
         let samp_rate = new_samp_rate;
 
-        // TODO: Add broadcast FM deemph.
-
         // Quad demod.
         let prev = blockchain![g, prev, QuadratureDemod::new(prev, 1.0)];
+        let prev = if opt.deemphasis != 0.0 {
+            blockchain![
+                g,
+                prev,
+                Deemphasis::new(prev, samp_rate.into(), opt.deemphasis * 1e-6)?,
+            ]
+        } else {
+            prev
+        };
 
         let taps = rustradio::fir::low_pass(
             samp_rate,
@@ -0,0 +1,154 @@
+//! Simple analog audio SSB and AM transmitter.
+//!
+//! Examples:
+//! * HF voice: `--mode usb --freq 14.2`
+//! * Airband: `--mode am --freq 118.0`
+use anyhow::Result;
+use clap::Parser;
+use log::warn;
+
+use rustradio::Repeat;
+use rustradio::blockchain;
+use rustradio::blocks::*;
+use rustradio::graph::GraphRunner;
+use rustradio::mtgraph::MTGraph;
+use rustradio::ssb::Sideband;
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+enum Mode {
+    /// AM, with carrier.
+    Am,
+    /// Double sideband, suppressed carrier.
+    Dsb,
+    /// Upper sideband.
+    Usb,
+    /// Lower sideband.
+    Lsb,
+}
+
+#[derive(clap::Parser, Debug)]
+#[command(version, about)]
+struct Opt {
+    #[arg(long, default_value_t = 0)]
+    verbose: usize,
+
+    /// soapysdr driver string.
+    #[arg(long)]
+    driver: String,
+
+    /// Input .au file.
+    #[arg(long)]
+    input: std::path::PathBuf,
+
+    /// Output gain, between 0 and 1.
+    #[arg(long, default_value_t = 0.1)]
+    ogain: f32,
+
+    /// Frequency in MHz.
+    #[arg(long, default_value_t = 14.2)]
+    freq: f32,
+
+    /// Modulation.
+    #[arg(long, value_enum, default_value_t = Mode::Usb)]
+    mode: Mode,
+
+    /// Audio bandwidth, for SSB.
+    #[arg(long, default_value_t = 2700.0)]
+    bandwidth: f64,
+
+    /// Audio rate.
+    #[arg(long, default_value_t = 48000)]
+    audio_rate: usize,
+
+    /// Sample rate on RF side.
+    #[arg(long, default_value_t = 480000)]
+    sample_rate: usize,
+
+    /// List SDR devices.
+    #[arg(long)]
+    list_devices: bool,
+}
+
+fn main() -> Result<()> {
+    let opt = Opt::parse();
+    stderrlog::new()
+        .module(module_path!())
+        .module("rustradio")
+        .quiet(false)
+        .verbosity(opt.verbose)
+        .timestamp(stderrlog::Timestamp::Second)
+        .init()?;
+    soapysdr::configure_logging();
+    if opt.list_devices {
+        for dev in soapysdr::enumerate("").unwrap() {
+            println!("{dev}");
+        }
+        return Ok(());
+    }
+    let mut g = MTGraph::new();
+    let dev = soapysdr::Device::new(&*opt.driver)?;
+
+    let prev = blockchain![
+        g,
+        prev,
+        FileSource::builder(&opt.input)
+            .repeat(Repeat::infinite())
+            .build()?,
+        AuDecode::new(prev, opt.audio_rate as u32),
+    ];
+    let audio_rate = opt.audio_rate as f64;
+    let prev = match opt.mode {
+        Mode::Am | Mode::Dsb => {
+            let b = AmModulator::builder(prev);
+            let b = if matches!(opt.mode, Mode::Dsb) {
+                b.dsb_sc()
+            } else {
+                b
+            };
+            blockchain![g, prev, b.build()]
+        }
+        Mode::Usb | Mode::Lsb => {
+            let sideband = if matches!(opt.mode, Mode::Usb) {
+                Sideband::Usb
+            } else {
+                Sideband::Lsb
+            };
+            blockchain![
+                g,
+                prev,
+                SsbModulator::builder(prev, audio_rate, sideband)
+                    .bandwidth(opt.bandwidth)
+                    .build()
+            ]
+        }
+    };
+    let prev = blockchain![
+        g,
+        prev,
+        RationalResampler::builder()
+            .deci(opt.audio_rate)
+            .interp(opt.sample_rate)
+            .build(prev)?,
+    ];
+    g.add(Box::new(
+        SoapySdrSink::builder(
+            &dev,
+            (1_000_000.0 * opt.freq).into(),
+            opt.sample_rate as f64,
+        )
+        .ogain(opt.ogain.into())
+        .build(prev)?,
+    ));
+
+    let cancel = g.cancel_token();
+    ctrlc::set_handler(move || {
+        warn!("Got Ctrl-C");
+        eprintln!("\n");
+        cancel.cancel();
+    })
+    .expect("failed to set Ctrl-C handler");
+    eprintln!("Running loop");
+    g.run()?;
+    eprintln!("{}", g.generate_stats().unwrap());
+    Ok(())
+}
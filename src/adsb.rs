@@ -0,0 +1,945 @@
+/*! ADS-B and Mode S, aircraft transponders on 1090MHz.
+
+Mode S replies are 56 or 112 bits of pulse position modulation at 1Mbps,
+after an 8µs preamble. The last 24 bits are a CRC, which for most downlink
+formats is also XORed with the aircraft address.
+
+[`ModeSDemod`] finds frames in the output of [`ComplexToMag2`] at 2Msps,
+e.g. from an RTL SDR, checks and corrects their CRC, and outputs them as
+PDUs. [`AdsbDecode`] parses DF17 and DF18 extended squitters, ADS-B, into
+[`Message`]s, resolving the CPR encoded positions.
+
+The frames can also be served to tools like `readsb` and `tar1090` with
+[`BeastServer`](crate::beast_server::BeastServer).
+
+## Example
+
+```
+use rustradio::graph::{Graph, GraphRunner};
+use rustradio::blocks::{AdsbDecode, ComplexToMag2, ModeSDemod};
+# fn main() -> rustradio::Result<()> {
+let (_, prev) = rustradio::stream::new_stream();
+let mut g = Graph::new();
+let prev = rustradio::blockchain![
+    g,
+    prev,
+    ComplexToMag2::new(prev),
+    ModeSDemod::new(prev),
+    AdsbDecode::new(prev),
+];
+# Ok(())
+# }
+```
+
+<https://mode-s.org/1090mhz/>
+
+[`ComplexToMag2`]: crate::blocks::ComplexToMag2
+*/
+use std::collections::HashMap;
+
+use log::{debug, info};
+
+use crate::block::{Block, BlockRet};
+use crate::stream::{NCReadStream, NCWriteStream, ReadStream, Tag, TagValue};
+use crate::{Error, Float, Result};
+
+/// Sample rate [`ModeSDemod`] works at.
+pub const SAMPLE_RATE: f64 = 2_000_000.0;
+
+/// Tag with the sample position of the frame preamble, as U64.
+pub const SAMPLE_TAG: &str = "ModeS:sample";
+
+/// Tag with the average power of the frame pulses, as Float.
+pub const SIGNAL_TAG: &str = "ModeS:signal";
+
+/// Tag with the number of bits corrected, as U64. Only set if nonzero.
+pub const CORRECTED_TAG: &str = "ModeS:corrected";
+
+// CRC-24 generator polynomial, without the leading bit.
+const POLY: u32 = 0xFF_F409;
+
+// Preamble, and the longest frame, in samples.
+const PREAMBLE: usize = 16;
+const MAX_FRAME: usize = PREAMBLE + 2 * 112;
+
+// Positions older than this many seconds don't pair up.
+const CPR_MAX_AGE: f64 = 10.0;
+
+// Forget aircraft not heard from for this many seconds.
+const AIRCRAFT_TIMEOUT: f64 = 300.0;
+
+/// Calculate the Mode S CRC-24 of `data`.
+#[must_use]
+pub fn crc24(data: &[u8]) -> u32 {
+    let mut crc = 0u32;
+    for &b in data {
+        crc ^= u32::from(b) << 16;
+        for _ in 0..8 {
+            crc <<= 1;
+            if crc & 0x100_0000 != 0 {
+                crc ^= POLY;
+            }
+        }
+    }
+    crc & 0xFF_FFFF
+}
+
+/// Return the CRC of a frame XORed with its parity field.
+///
+/// For DF11, DF17, and DF18 this is zero for a correct frame. For most other
+/// downlink formats it's the address of the aircraft.
+#[must_use]
+pub fn residual(frame: &[u8]) -> u32 {
+    let n = frame.len() - 3;
+    let parity = u32::from_be_bytes([0, frame[n], frame[n + 1], frame[n + 2]]);
+    crc24(&frame[..n]) ^ parity
+}
+
+/// Return the downlink format of a frame.
+#[must_use]
+pub fn downlink_format(frame: &[u8]) -> u8 {
+    frame[0] >> 3
+}
+
+/// Return the frame length in bytes, for a downlink format.
+#[must_use]
+pub fn frame_len(df: u8) -> usize {
+    if df >= 16 { 14 } else { 7 }
+}
+
+/// Return the ICAO address of the aircraft sending an all call reply or an
+/// extended squitter.
+#[must_use]
+pub fn icao(frame: &[u8]) -> u32 {
+    u32::from_be_bytes([0, frame[1], frame[2], frame[3]])
+}
+
+/// Mode S demodulator.
+///
+/// Takes the square of the magnitude of the signal at [`SAMPLE_RATE`], and
+/// outputs frames whose CRC is correct, or could be corrected by flipping
+/// one bit.
+///
+/// Frames whose CRC field is overlaid with the aircraft address can't be
+/// checked on their own. They're only output if the address has been seen in
+/// an all call reply (DF11) or extended squitter in the last five minutes.
+///
+/// Frames are tagged with [`SAMPLE_TAG`] and [`SIGNAL_TAG`], and if corrected,
+/// [`CORRECTED_TAG`].
+#[derive(rustradio_macros::Block)]
+#[rustradio(crate)]
+pub struct ModeSDemod {
+    #[rustradio(in)]
+    src: ReadStream<Float>,
+    #[rustradio(out)]
+    dst: NCWriteStream<Vec<u8>>,
+
+    // Sample position of the start of the input buffer.
+    pos: u64,
+    // Syndrome of each single bit error in long frames.
+    syndromes: HashMap<u32, usize>,
+    // Aircraft addresses seen in self checking frames, and when, in seconds.
+    known: HashMap<u32, f64>,
+    now: f64,
+    frames: usize,
+    corrected: usize,
+}
+
+impl ModeSDemod {
+    /// Create new Mode S demodulator.
+    #[must_use]
+    pub fn new(src: ReadStream<Float>) -> (Self, NCReadStream<Vec<u8>>) {
+        let (dst, dr) = crate::stream::new_nocopy_stream();
+        let syndromes = (0..112)
+            .map(|bit| {
+                let mut frame = [0u8; 14];
+                frame[bit / 8] = 0x80 >> (bit % 8);
+                (residual(&frame), bit)
+            })
+            .collect();
+        (
+            Self {
+                src,
+                dst,
+                pos: 0,
+                syndromes,
+                known: HashMap::new(),
+                now: 0.0,
+                frames: 0,
+                corrected: 0,
+            },
+            dr,
+        )
+    }
+
+    /// Check the preamble at the start of `m`, and return the pulse power.
+    fn preamble(m: &[Float]) -> Option<Float> {
+        // Pulses at 0, 1, 3.5, and 4.5µs, with room for them to straddle two
+        // samples.
+        if !(m[0] > m[1]
+            && m[1] < m[2]
+            && m[2] > m[3]
+            && m[3] < m[0]
+            && m[4] < m[0]
+            && m[5] < m[0]
+            && m[6] < m[0]
+            && m[7] > m[8]
+            && m[8] < m[9]
+            && m[9] > m[6])
+        {
+            return None;
+        }
+        let power = (m[0] + m[2] + m[7] + m[9]) / 4.0;
+        // The quiet parts need to be quiet, here two thirds of the pulse
+        // amplitude.
+        let quiet = power * 4.0 / 9.0;
+        if [4, 5, 11, 12, 13, 14].iter().any(|&n| m[n] >= quiet) {
+            return None;
+        }
+        Some(power)
+    }
+
+    /// Slice `bits` bits of pulse position modulation.
+    fn slice(m: &[Float], bits: usize) -> Vec<u8> {
+        let mut frame = vec![0u8; bits / 8];
+        for bit in 0..bits {
+            if m[2 * bit] > m[2 * bit + 1] {
+                frame[bit / 8] |= 0x80 >> (bit % 8);
+            }
+        }
+        frame
+    }
+
+    /// Remember an aircraft address as seen now, and forget old ones.
+    fn seen(&mut self, icao: u32) {
+        let now = self.now;
+        self.known.insert(icao, now);
+        self.known.retain(|_, seen| now - *seen < AIRCRAFT_TIMEOUT);
+    }
+
+    /// Check the CRC, maybe fixing a bit. Return the number of fixed bits.
+    fn check(&mut self, frame: &mut [u8]) -> Option<usize> {
+        let df = downlink_format(frame);
+        let r = residual(frame);
+        match df {
+            17 | 18 => {
+                if r == 0 {
+                    self.seen(icao(frame));
+                    return Some(0);
+                }
+                let bit = *self.syndromes.get(&r)?;
+                // Don't fix bits that would change the downlink format.
+                if bit < 5 {
+                    return None;
+                }
+                frame[bit / 8] ^= 0x80 >> (bit % 8);
+                self.seen(icao(frame));
+                Some(1)
+            }
+            11 => {
+                // The low bits are the interrogator ID.
+                if r & !0x7F != 0 {
+                    return None;
+                }
+                if r == 0 {
+                    self.seen(icao(frame));
+                }
+                Some(0)
+            }
+            0 | 4 | 5 | 16 | 20 | 21 => self
+                .known
+                .get(&r)
+                .is_some_and(|seen| self.now - seen < AIRCRAFT_TIMEOUT)
+                .then_some(0),
+            _ => None,
+        }
+    }
+}
+
+impl Drop for ModeSDemod {
+    fn drop(&mut self) {
+        info!(
+            "ModeSDemod: {} frames, {} corrected, {} aircraft",
+            self.frames,
+            self.corrected,
+            self.known.len()
+        );
+    }
+}
+
+impl Block for ModeSDemod {
+    fn work(&mut self) -> Result<BlockRet<'_>> {
+        let (i, _) = self.src.read_buf()?;
+        let m = i.slice();
+        if m.len() < MAX_FRAME {
+            return Ok(BlockRet::WaitForStream(&self.src, MAX_FRAME));
+        }
+        let end = m.len() - MAX_FRAME;
+        let mut n = 0;
+        while n < end {
+            if self.dst.remaining() == 0 {
+                break;
+            }
+            let Some(power) = Self::preamble(&m[n..]) else {
+                n += 1;
+                continue;
+            };
+            let data = &m[n + PREAMBLE..];
+            let first = Self::slice(data, 8);
+            let bits = 8 * frame_len(downlink_format(&first));
+            let mut frame = Self::slice(data, bits);
+            self.now = (self.pos + n as u64) as f64 / SAMPLE_RATE;
+            let Some(fixed) = self.check(&mut frame) else {
+                n += 1;
+                continue;
+            };
+            self.frames += 1;
+            let mut tags = vec![
+                Tag::new(0, SAMPLE_TAG, TagValue::U64(self.pos + n as u64)),
+                Tag::new(0, SIGNAL_TAG, TagValue::Float(power)),
+            ];
+            if fixed > 0 {
+                self.corrected += 1;
+                tags.push(Tag::new(0, CORRECTED_TAG, TagValue::U64(fixed as u64)));
+            }
+            debug!("ModeSDemod: {frame:02X?}");
+            self.dst.push(frame, tags);
+            n += PREAMBLE + 2 * bits;
+        }
+        i.consume(n);
+        self.pos += n as u64;
+        if self.dst.remaining() == 0 {
+            return Ok(BlockRet::WaitForStream(&self.dst, 1));
+        }
+        if n == 0 {
+            return Ok(BlockRet::WaitForStream(&self.src, MAX_FRAME + 1));
+        }
+        Ok(BlockRet::Again)
+    }
+}
+
+/// Airborne position, as its two halves are CPR encoded.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Cpr {
+    /// Odd or even frame.
+    pub odd: bool,
+    /// Encoded latitude, 17 bits.
+    pub lat: u32,
+    /// Encoded longitude, 17 bits.
+    pub lon: u32,
+}
+
+/// Position in degrees.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Position {
+    /// Latitude, positive north.
+    pub lat: f64,
+    /// Longitude, positive east.
+    pub lon: f64,
+}
+
+// 2^17, the CPR resolution.
+const CPR_MAX: f64 = 131_072.0;
+
+// Number of longitude zones at a latitude.
+fn nl(lat: f64) -> u32 {
+    let lat = lat.abs();
+    if lat < 1e-9 {
+        return 59;
+    }
+    if lat > 87.0 {
+        return 1;
+    }
+    let nz = 15.0;
+    let a = 1.0 - (std::f64::consts::PI / (2.0 * nz)).cos();
+    let b = lat.to_radians().cos().powi(2);
+    (2.0 * std::f64::consts::PI / (1.0 - a / b).acos()).floor() as u32
+}
+
+fn wrap_lon(lon: f64) -> f64 {
+    (lon + 180.0).rem_euclid(360.0) - 180.0
+}
+
+/// Decode an even and an odd CPR position into a global position.
+///
+/// `latest` is the most recently received of the two. Returns `None` if they
+/// are in different longitude zones, meaning the aircraft moved too far
+/// between them.
+#[must_use]
+pub fn cpr_global(even: &Cpr, odd: &Cpr, latest: &Cpr) -> Option<Position> {
+    let (lat0, lat1) = (f64::from(even.lat) / CPR_MAX, f64::from(odd.lat) / CPR_MAX);
+    let (lon0, lon1) = (f64::from(even.lon) / CPR_MAX, f64::from(odd.lon) / CPR_MAX);
+    let j = (59.0 * lat0 - 60.0 * lat1 + 0.5).floor();
+    let fix = |lat: f64| if lat >= 270.0 { lat - 360.0 } else { lat };
+    let rlat0 = fix(360.0 / 60.0 * (j.rem_euclid(60.0) + lat0));
+    let rlat1 = fix(360.0 / 59.0 * (j.rem_euclid(59.0) + lat1));
+    if nl(rlat0) != nl(rlat1) {
+        return None;
+    }
+    let (lat, n) = if latest.odd {
+        (rlat1, nl(rlat1))
+    } else {
+        (rlat0, nl(rlat0))
+    };
+    let m = (lon0 * f64::from(n - 1) - lon1 * f64::from(n) + 0.5).floor();
+    let (ni, lon) = if latest.odd {
+        ((n - 1).max(1), lon1)
+    } else {
+        (n.max(1), lon0)
+    };
+    let ni = f64::from(ni);
+    Some(Position {
+        lat,
+        lon: wrap_lon(360.0 / ni * (m.rem_euclid(ni) + lon)),
+    })
+}
+
+/// Decode a CPR position relative to a reference position.
+///
+/// The reference needs to be within about 180 nautical miles.
+#[must_use]
+pub fn cpr_local(reference: &Position, cpr: &Cpr) -> Position {
+    let dlat = if cpr.odd { 360.0 / 59.0 } else { 360.0 / 60.0 };
+    let y = f64::from(cpr.lat) / CPR_MAX;
+    let j =
+        (reference.lat / dlat).floor() + (reference.lat.rem_euclid(dlat) / dlat - y + 0.5).floor();
+    let lat = dlat * (j + y);
+    let ni = nl(lat).saturating_sub(u32::from(cpr.odd)).max(1);
+    let dlon = 360.0 / f64::from(ni);
+    let x = f64::from(cpr.lon) / CPR_MAX;
+    let m =
+        (reference.lon / dlon).floor() + (reference.lon.rem_euclid(dlon) / dlon - x + 0.5).floor();
+    Position {
+        lat,
+        lon: wrap_lon(dlon * (m + x)),
+    }
+}
+
+/// Decode a 12 bit altitude field, in feet.
+///
+/// Only the 25ft increment encoding is supported.
+fn altitude(field: u32) -> Option<i32> {
+    if field == 0 || field & 0x10 == 0 {
+        return None;
+    }
+    let n = ((field & 0xFE0) >> 1) | (field & 0xF);
+    Some(n as i32 * 25 - 1000)
+}
+
+/// Speed and direction.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Velocity {
+    /// Speed in knots.
+    ///
+    /// Ground speed, or airspeed if `heading` is set.
+    pub speed: Option<f64>,
+    /// Track over ground in degrees, if ground speed.
+    pub track: Option<f64>,
+    /// Heading in degrees, if airspeed.
+    pub heading: Option<f64>,
+    /// Airspeed is true airspeed, as opposed to indicated.
+    pub true_airspeed: bool,
+    /// Vertical rate in feet per minute, positive up.
+    pub vertical_rate: Option<i32>,
+}
+
+/// The payload of an extended squitter.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Squitter {
+    /// Aircraft identification, type codes 1-4.
+    Identification {
+        /// Emitter category, with the type code as high bits.
+        category: u8,
+        /// Flight number or registration.
+        callsign: String,
+    },
+    /// Airborne position, type codes 9-18 and 20-22.
+    AirbornePosition {
+        /// Barometric or GNSS altitude, in feet.
+        altitude: Option<i32>,
+        /// CPR encoded position.
+        cpr: Cpr,
+        /// Decoded position. Set by [`AdsbDecode`], if it could.
+        position: Option<Position>,
+    },
+    /// Airborne velocity, type code 19.
+    Velocity(Velocity),
+    /// Other type codes.
+    Other {
+        /// Type code.
+        type_code: u8,
+    },
+}
+
+/// A decoded extended squitter.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Message {
+    /// Downlink format, 17 or 18.
+    pub df: u8,
+    /// ICAO address of the aircraft.
+    pub icao: u32,
+    /// Payload.
+    pub squitter: Squitter,
+}
+
+// Read big endian bit fields from the 56 bit ME field.
+fn bits(me: u64, start: usize, len: usize) -> u32 {
+    ((me >> (56 - start - len)) & ((1 << len) - 1)) as u32
+}
+
+const CALLSIGN_CHARS: &[u8] = b"#ABCDEFGHIJKLMNOPQRSTUVWXYZ##### ###############0123456789######";
+
+impl Message {
+    /// Parse an extended squitter.
+    ///
+    /// # Errors
+    ///
+    /// Errors if the frame is not a DF17 or DF18 long frame.
+    pub fn parse(frame: &[u8]) -> Result<Self> {
+        let df = downlink_format(frame);
+        if frame.len() != 14 || !matches!(df, 17 | 18) {
+            return Err(Error::msg(format!(
+                "not an extended squitter: DF{df}, {} bytes",
+                frame.len()
+            )));
+        }
+        let me = frame[4..11]
+            .iter()
+            .fold(0u64, |acc, &b| (acc << 8) | u64::from(b));
+        let type_code = bits(me, 0, 5) as u8;
+        let squitter = match type_code {
+            1..=4 => Squitter::Identification {
+                category: (type_code << 3) | bits(me, 5, 3) as u8,
+                callsign: (0..8)
+                    .map(|n| char::from(CALLSIGN_CHARS[bits(me, 8 + 6 * n, 6) as usize]))
+                    .collect::<String>()
+                    .trim_end()
+                    .to_string(),
+            },
+            9..=18 | 20..=22 => Squitter::AirbornePosition {
+                altitude: if type_code >= 20 {
+                    // GNSS height, in meters.
+                    Some(bits(me, 8, 12))
+                        .filter(|&h| h != 0)
+                        .map(|h| (f64::from(h) * 3.280_84).round() as i32)
+                } else {
+                    altitude(bits(me, 8, 12))
+                },
+                cpr: Cpr {
+                    odd: bits(me, 21, 1) == 1,
+                    lat: bits(me, 22, 17),
+                    lon: bits(me, 39, 17),
+                },
+                position: None,
+            },
+            19 => Squitter::Velocity(Self::velocity(me)),
+            _ => Squitter::Other { type_code },
+        };
+        Ok(Self {
+            df,
+            icao: icao(frame),
+            squitter,
+        })
+    }
+
+    fn velocity(me: u64) -> Velocity {
+        let subtype = bits(me, 5, 3);
+        let scale = if matches!(subtype, 2 | 4) { 4.0 } else { 1.0 };
+        let vr = bits(me, 37, 9);
+        let vertical_rate = (vr != 0).then(|| {
+            let rate = (vr as i32 - 1) * 64;
+            if bits(me, 36, 1) == 1 { -rate } else { rate }
+        });
+        let mut v = Velocity {
+            speed: None,
+            track: None,
+            heading: None,
+            true_airspeed: false,
+            vertical_rate,
+        };
+        match subtype {
+            1 | 2 => {
+                let (ew, ns) = (bits(me, 14, 10), bits(me, 25, 10));
+                if ew == 0 || ns == 0 {
+                    return v;
+                }
+                let sign = |s: u32| if s == 1 { -1.0 } else { 1.0 };
+                let vx = sign(bits(me, 13, 1)) * f64::from(ew - 1) * scale;
+                let vy = sign(bits(me, 24, 1)) * f64::from(ns - 1) * scale;
+                v.speed = Some(vx.hypot(vy));
+                v.track = Some(vx.atan2(vy).to_degrees().rem_euclid(360.0));
+            }
+            3 | 4 => {
+                if bits(me, 13, 1) == 1 {
+                    v.heading = Some(f64::from(bits(me, 14, 10)) * 360.0 / 1024.0);
+                }
+                let airspeed = bits(me, 25, 10);
+                v.speed = (airspeed != 0).then(|| f64::from(airspeed - 1) * scale);
+                v.true_airspeed = bits(me, 24, 1) == 1;
+            }
+            _ => {}
+        }
+        v
+    }
+}
+
+// What's known about one aircraft.
+#[derive(Default)]
+struct Aircraft {
+    even: Option<(Cpr, f64)>,
+    odd: Option<(Cpr, f64)>,
+    position: Option<Position>,
+    seen: f64,
+}
+
+/// ADS-B decoder.
+///
+/// Takes Mode S frames, e.g. from [`ModeSDemod`], and outputs the extended
+/// squitters as [`Message`]s. Other frames are dropped.
+///
+/// Positions are decoded once an odd and even position have been received
+/// within 10 seconds of each other, and after that relative to the previous
+/// position. Time is taken from [`SAMPLE_TAG`], if present.
+#[derive(rustradio_macros::Block)]
+#[rustradio(crate)]
+pub struct AdsbDecode {
+    #[rustradio(in)]
+    src: NCReadStream<Vec<u8>>,
+    #[rustradio(out)]
+    dst: NCWriteStream<Message>,
+    aircraft: HashMap<u32, Aircraft>,
+    now: f64,
+}
+
+impl AdsbDecode {
+    /// Create new ADS-B decoder.
+    #[must_use]
+    pub fn new(src: NCReadStream<Vec<u8>>) -> (Self, NCReadStream<Message>) {
+        let (dst, dr) = crate::stream::new_nocopy_stream();
+        (
+            Self {
+                src,
+                dst,
+                aircraft: HashMap::new(),
+                now: 0.0,
+            },
+            dr,
+        )
+    }
+
+    fn position(&mut self, icao: u32, cpr: &Cpr) -> Option<Position> {
+        let now = self.now;
+        let a = self.aircraft.entry(icao).or_default();
+        a.seen = now;
+        if cpr.odd {
+            a.odd = Some((*cpr, now));
+        } else {
+            a.even = Some((*cpr, now));
+        }
+        let global = match (&a.even, &a.odd) {
+            (Some((even, t0)), Some((odd, t1))) if (t0 - t1).abs() <= CPR_MAX_AGE => {
+                cpr_global(even, odd, cpr)
+            }
+            _ => None,
+        };
+        let pos = global.or_else(|| a.position.map(|r| cpr_local(&r, cpr)))?;
+        a.position = Some(pos);
+        Some(pos)
+    }
+}
+
+impl Block for AdsbDecode {
+    fn work(&mut self) -> Result<BlockRet<'_>> {
+        loop {
+            if self.dst.remaining() == 0 {
+                return Ok(BlockRet::WaitForStream(&self.dst, 1));
+            }
+            let Some((frame, tags)) = self.src.pop() else {
+                return Ok(BlockRet::WaitForStream(&self.src, 1));
+            };
+            if let Some(TagValue::U64(pos)) =
+                tags.iter().find(|t| t.key() == SAMPLE_TAG).map(Tag::val)
+            {
+                self.now = *pos as f64 / SAMPLE_RATE;
+            }
+            let mut msg = match Message::parse(&frame) {
+                Ok(msg) => msg,
+                Err(e) => {
+                    debug!("AdsbDecode: {e}");
+                    continue;
+                }
+            };
+            if let Squitter::AirbornePosition { cpr, position, .. } = &mut msg.squitter {
+                *position = self.position(msg.icao, cpr);
+            } else {
+                self.aircraft.entry(msg.icao).or_default().seen = self.now;
+            }
+            let now = self.now;
+            self.aircraft.retain(|_, a| now - a.seen < AIRCRAFT_TIMEOUT);
+            self.dst.push(msg, tags);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(s: &str) -> Vec<u8> {
+        (0..s.len())
+            .step_by(2)
+            .map(|n| u8::from_str_radix(&s[n..n + 2], 16).unwrap())
+            .collect()
+    }
+
+    // Example frames from "The 1090MHz Riddle".
+    const IDENT: &str = "8D4840D6202CC371C32CE0576098";
+    const POS_EVEN: &str = "8D40621D58C382D690C8AC2863A7";
+    const POS_ODD: &str = "8D40621D58C386435CC412692AD6";
+    const GROUND_SPEED: &str = "8D485020994409940838175B284F";
+    const AIRSPEED: &str = "8DA05F219B06B6AF189400CBC33F";
+
+    #[test]
+    fn crc() {
+        for f in [IDENT, POS_EVEN, POS_ODD, GROUND_SPEED, AIRSPEED] {
+            assert_eq!(residual(&hex(f)), 0, "{f}");
+        }
+        let mut f = hex(IDENT);
+        f[5] ^= 1;
+        assert_ne!(residual(&f), 0);
+    }
+
+    #[test]
+    fn identification() -> Result<()> {
+        let msg = Message::parse(&hex(IDENT))?;
+        assert_eq!(msg.df, 17);
+        assert_eq!(msg.icao, 0x48_40D6);
+        assert_eq!(
+            msg.squitter,
+            Squitter::Identification {
+                category: 0x20,
+                callsign: "KLM1023".into(),
+            }
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn position() -> Result<()> {
+        let Squitter::AirbornePosition { altitude, cpr, .. } =
+            Message::parse(&hex(POS_EVEN))?.squitter
+        else {
+            panic!("wrong type");
+        };
+        assert_eq!(altitude, Some(38_000));
+        assert_eq!(
+            cpr,
+            Cpr {
+                odd: false,
+                lat: 93_000,
+                lon: 51_372,
+            }
+        );
+        let Squitter::AirbornePosition { cpr: odd, .. } = Message::parse(&hex(POS_ODD))?.squitter
+        else {
+            panic!("wrong type");
+        };
+        assert!(odd.odd);
+
+        let pos = cpr_global(&cpr, &odd, &cpr).unwrap();
+        assert!((pos.lat - 52.257_20).abs() < 1e-5, "{pos:?}");
+        assert!((pos.lon - 3.919_37).abs() < 1e-5, "{pos:?}");
+
+        let local = cpr_local(
+            &Position {
+                lat: 52.258,
+                lon: 3.918,
+            },
+            &cpr,
+        );
+        assert!((local.lat - pos.lat).abs() < 1e-5, "{local:?}");
+        assert!((local.lon - pos.lon).abs() < 1e-5, "{local:?}");
+        Ok(())
+    }
+
+    #[test]
+    fn velocity() -> Result<()> {
+        let Squitter::Velocity(v) = Message::parse(&hex(GROUND_SPEED))?.squitter else {
+            panic!("wrong type");
+        };
+        assert!((v.speed.unwrap() - 159.20).abs() < 0.01, "{v:?}");
+        assert!((v.track.unwrap() - 182.88).abs() < 0.01, "{v:?}");
+        assert_eq!(v.vertical_rate, Some(-832));
+        assert_eq!(v.heading, None);
+
+        let Squitter::Velocity(v) = Message::parse(&hex(AIRSPEED))?.squitter else {
+            panic!("wrong type");
+        };
+        assert_eq!(v.speed, Some(375.0));
+        assert!(v.true_airspeed);
+        assert!((v.heading.unwrap() - 243.98).abs() < 0.01, "{v:?}");
+        assert_eq!(v.vertical_rate, Some(-2304));
+        Ok(())
+    }
+
+    #[test]
+    fn not_squitter() {
+        assert!(Message::parse(&hex("5D4840D6C8F4AB")).is_err());
+        assert!(Message::parse(&hex(&IDENT[..14])).is_err());
+    }
+
+    // Pulse position modulate frames into magnitude squared samples.
+    // `split` of each pulse goes into the next sample, as if the sample clock
+    // is offset.
+    fn modulate(frames: &[Vec<u8>], split: Float) -> Vec<Float> {
+        let mut out = vec![0.01; 300];
+        for frame in frames {
+            let start = out.len();
+            out.resize(start + PREAMBLE + 16 * frame.len() + 100, 0.01);
+            let mut pulse = |n: usize| {
+                out[start + n] += 1.0 - split;
+                out[start + n + 1] += split;
+            };
+            for n in [0, 2, 7, 9] {
+                pulse(n);
+            }
+            for bit in 0..8 * frame.len() {
+                let one = frame[bit / 8] & (0x80 >> (bit % 8)) != 0;
+                pulse(PREAMBLE + 2 * bit + usize::from(!one));
+            }
+        }
+        out.resize(out.len() + MAX_FRAME, 0.01);
+        out
+    }
+
+    fn demod(samples: Vec<Float>) -> Result<Vec<(Vec<u8>, Vec<Tag>)>> {
+        let (mut src, prev) = crate::blocks::VectorSource::new(samples);
+        src.work()?;
+        let (mut b, out) = ModeSDemod::new(prev);
+        b.work()?;
+        let mut got = Vec::new();
+        while let Some(f) = out.pop() {
+            got.push(f);
+        }
+        Ok(got)
+    }
+
+    #[test]
+    fn demodulate() -> Result<()> {
+        // All call reply, with interrogator ID.
+        let mut all_call = hex("5D4840D6000000");
+        let crc = crc24(&all_call[..4]) ^ 3;
+        all_call[4..].copy_from_slice(&crc.to_be_bytes()[1..]);
+        // Surveillance reply, with the address as parity.
+        let mut surveillance = hex("20001838000000");
+        let crc = crc24(&surveillance[..4]) ^ 0x48_40D6;
+        surveillance[4..].copy_from_slice(&crc.to_be_bytes()[1..]);
+        let mut unknown = surveillance.clone();
+        let crc = crc24(&unknown[..4]) ^ 0x12_3456;
+        unknown[4..].copy_from_slice(&crc.to_be_bytes()[1..]);
+
+        let frames = vec![
+            // Not known yet.
+            surveillance.clone(),
+            hex(IDENT),
+            surveillance.clone(),
+            unknown,
+            all_call.clone(),
+            hex(POS_EVEN),
+        ];
+        for split in [0.0, 0.3] {
+            let got = demod(modulate(&frames, split))?;
+            let want = vec![
+                hex(IDENT),
+                surveillance.clone(),
+                all_call.clone(),
+                hex(POS_EVEN),
+            ];
+            assert_eq!(
+                got.iter().map(|(f, _)| f.clone()).collect::<Vec<_>>(),
+                want,
+                "split {split}"
+            );
+            let (_, tags) = &got[0];
+            assert_eq!(
+                tags[0],
+                Tag::new(0, SAMPLE_TAG, TagValue::U64(300 + 16 + 56 * 2 + 100))
+            );
+            let TagValue::Float(power) = tags[1].val() else {
+                panic!("bad tag {tags:?}");
+            };
+            assert!((power - (1.0 - split + 0.01)).abs() < 0.01, "{tags:?}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn correct() -> Result<()> {
+        let mut bad = hex(GROUND_SPEED);
+        bad[6] ^= 0x10;
+        let mut worse = bad.clone();
+        worse[9] ^= 0x01;
+        let got = demod(modulate(&[bad, worse], 0.0))?;
+        assert_eq!(got.len(), 1);
+        assert_eq!(got[0].0, hex(GROUND_SPEED));
+        assert_eq!(got[0].1[2], Tag::new(0, CORRECTED_TAG, TagValue::U64(1)));
+        Ok(())
+    }
+
+    #[test]
+    fn known_expiry() {
+        let mut surveillance = hex("20001838000000");
+        let crc = crc24(&surveillance[..4]) ^ 0x48_40D6;
+        surveillance[4..].copy_from_slice(&crc.to_be_bytes()[1..]);
+        let (_, prev) = crate::stream::new_stream();
+        let (mut b, _out) = ModeSDemod::new(prev);
+        assert_eq!(b.check(&mut surveillance.clone()), None);
+        b.now = 10.0;
+        assert_eq!(b.check(&mut hex(IDENT)), Some(0));
+        b.now = 10.0 + AIRCRAFT_TIMEOUT - 1.0;
+        assert_eq!(b.check(&mut surveillance.clone()), Some(0));
+        // Address overlaid frames don't keep it known.
+        b.now = 10.0 + AIRCRAFT_TIMEOUT;
+        assert_eq!(b.check(&mut surveillance.clone()), None);
+        // Other aircraft expire the old one.
+        assert_eq!(b.check(&mut hex(POS_EVEN)), Some(0));
+        assert_eq!(b.known.len(), 1);
+    }
+
+    #[test]
+    fn decode() -> Result<()> {
+        let (tx, rx) = crate::stream::new_nocopy_stream();
+        let push = |f: &str, sec: f64| {
+            tx.push(
+                hex(f),
+                &[Tag::new(
+                    0,
+                    SAMPLE_TAG,
+                    TagValue::U64((sec * SAMPLE_RATE) as u64),
+                )],
+            );
+        };
+        push(POS_ODD, 1.0);
+        push(IDENT, 1.5);
+        push(POS_EVEN, 2.0);
+        push(POS_EVEN, 3.0);
+        // Too old to pair, but decodes relative to the previous position.
+        push(POS_ODD, 20.0);
+        let (mut b, out) = AdsbDecode::new(rx);
+        b.work()?;
+        let mut positions = Vec::new();
+        while let Some((msg, _)) = out.pop() {
+            if let Squitter::AirbornePosition { position, .. } = msg.squitter {
+                positions.push(position);
+            }
+        }
+        assert_eq!(positions.len(), 4);
+        assert_eq!(positions[0], None);
+        for pos in &positions[1..] {
+            let pos = pos.unwrap();
+            assert!((pos.lat - 52.26).abs() < 0.01, "{pos:?}");
+            assert!((pos.lon - 3.93).abs() < 0.02, "{pos:?}");
+        }
+        Ok(())
+    }
+}
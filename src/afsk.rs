@@ -0,0 +1,320 @@
+/*! AFSK modulator.
+
+Audio Frequency Shift Keying, as used by Bell 202 (1200 baud AX.25 packet
+radio and APRS) and V.23.
+
+## Example
+
+Sending an AX.25 frame as audio:
+
+```
+use rustradio::graph::{Graph, GraphRunner};
+use rustradio::blocks::*;
+use rustradio::stream::new_nocopy_stream;
+let (tx, prev) = new_nocopy_stream();
+let mut g = Graph::new();
+let prev = rustradio::blockchain![
+    g,
+    prev,
+    FcsAdder::new(prev),
+    HdlcFramer::new(prev),
+    PduToStream::new(prev),
+    NrziEncode::new(prev),
+    AfskModulator::new(prev, 48000.0),
+];
+g.add(Box::new(NullSink::new(prev)));
+```
+*/
+use std::collections::VecDeque;
+
+use crate::block::{Block, BlockEOF, BlockRet};
+use crate::hdlc_framer::FRAME_END_TAG;
+use crate::stream::{ReadStream, Tag, TagValue, WriteStream};
+use crate::{Float, Result};
+
+const TAU: f64 = 2.0 * std::f64::consts::PI;
+
+// Don't read more input bits than this ahead of the output.
+const MAX_QUEUE: usize = 10_000;
+
+// HDLC flag, in transmit order.
+const FLAG: [u8; 8] = [0, 1, 1, 1, 1, 1, 1, 0];
+
+/// Builder for [`AfskModulator`].
+pub struct AfskModulatorBuilder {
+    block: AfskModulator,
+    out: ReadStream<Float>,
+}
+
+impl AfskModulatorBuilder {
+    /// Set tone frequency, in Hz, for 1 bits. Default 1200.
+    #[must_use]
+    pub fn mark(mut self, freq: f64) -> Self {
+        self.block.mark = freq;
+        self
+    }
+
+    /// Set tone frequency, in Hz, for 0 bits. Default 2200.
+    #[must_use]
+    pub fn space(mut self, freq: f64) -> Self {
+        self.block.space = freq;
+        self
+    }
+
+    /// Set baud rate. Default 1200.
+    #[must_use]
+    pub fn baud(mut self, baud: f64) -> Self {
+        self.block.baud = baud;
+        self
+    }
+
+    /// Build the `AfskModulator`.
+    #[must_use]
+    pub fn build(self) -> (AfskModulator, ReadStream<Float>) {
+        (self.block, self.out)
+    }
+}
+
+/// AFSK modulator.
+///
+/// Takes bits (one per `u8`), and outputs audio with amplitude 1.0. Phase is
+/// continuous across tone changes.
+///
+/// Defaults are Bell 202. For V.23, set mark to 1300 and space to 2100.
+///
+/// The input is normally NRZI encoded HDLC frames, from `HdlcFramer`,
+/// `PduToStream`, and `NrziEncode`. For those, the TX delay and TX tail can be
+/// extended by tags:
+///
+/// * `…:txdelay-ms` adds HDLC flags for this duration before the tagged bit.
+/// * `…:txtail-ms` sets the duration of HDLC flags to add after every
+///   subsequent frame, as marked by the `HdlcFramer:frame-end` tag.
+///
+/// Any tag prefix is accepted, so the TX parameters set by KISS clients and
+/// passed on as tags by `KissDecode` and `KissServer` just work.
+///
+/// Other tags are passed on, at the first sample of the bit they were on.
+#[derive(rustradio_macros::Block)]
+#[rustradio(crate, noeof)]
+pub struct AfskModulator {
+    #[rustradio(in)]
+    src: ReadStream<u8>,
+    #[rustradio(out)]
+    dst: WriteStream<Float>,
+
+    sample_rate: f64,
+    mark: f64,
+    space: f64,
+    baud: f64,
+
+    // Bits, with tags, waiting to be modulated.
+    queue: VecDeque<(u8, Vec<Tag>)>,
+    // Last queued bit.
+    level: u8,
+    txtail_ms: u64,
+    // Fraction of a sample left over from the previous bit.
+    clock: f64,
+    phase: f64,
+}
+
+impl AfskModulator {
+    /// Create new Bell 202 AFSK modulator.
+    #[must_use]
+    pub fn new(src: ReadStream<u8>, sample_rate: f64) -> (Self, ReadStream<Float>) {
+        let (dst, dr) = crate::stream::new_stream();
+        (
+            Self {
+                src,
+                dst,
+                sample_rate,
+                mark: 1200.0,
+                space: 2200.0,
+                baud: 1200.0,
+                queue: VecDeque::new(),
+                level: 0,
+                txtail_ms: 0,
+                clock: 0.0,
+                phase: 0.0,
+            },
+            dr,
+        )
+    }
+
+    /// Create a builder, for non-default tones or baud rate.
+    #[must_use]
+    pub fn builder(src: ReadStream<u8>, sample_rate: f64) -> AfskModulatorBuilder {
+        let (block, out) = Self::new(src, sample_rate);
+        AfskModulatorBuilder { block, out }
+    }
+
+    fn flags_for(&self, ms: u64) -> usize {
+        (ms as f64 * self.baud / 8000.0).ceil() as usize
+    }
+}
+
+/// Queue NRZI encoded HDLC flags.
+fn queue_flags(queue: &mut VecDeque<(u8, Vec<Tag>)>, level: u8, n: usize) {
+    let mut level = level;
+    for _ in 0..n {
+        for bit in FLAG {
+            if bit == 0 {
+                level ^= 1;
+            }
+            queue.push_back((level, Vec::new()));
+        }
+    }
+}
+
+impl BlockEOF for AfskModulator {
+    fn eof(&mut self) -> bool {
+        self.src.eof() && self.queue.is_empty()
+    }
+}
+
+impl Block for AfskModulator {
+    fn work(&mut self) -> Result<BlockRet<'_>> {
+        // Queue up input bits, adding flags as requested.
+        let (i, tags) = self.src.read_buf()?;
+        let n = i.len().min(MAX_QUEUE.saturating_sub(self.queue.len()));
+        for (pos, &bit) in i.slice()[..n].iter().enumerate() {
+            let here: Vec<Tag> = tags.iter().filter(|t| t.pos() == pos).cloned().collect();
+            let start = self.queue.len();
+            let mut end = false;
+            for tag in &here {
+                match (tag.key(), tag.val()) {
+                    (k, TagValue::U64(ms)) if k.ends_with(":txdelay-ms") => {
+                        let flags = self.flags_for(*ms);
+                        queue_flags(&mut self.queue, self.level, flags);
+                    }
+                    (k, TagValue::U64(ms)) if k.ends_with(":txtail-ms") => self.txtail_ms = *ms,
+                    (FRAME_END_TAG, _) => end = true,
+                    _ => {}
+                }
+            }
+            self.queue.push_back((bit, Vec::new()));
+            self.queue[start].1 = here;
+            self.level = bit;
+            if end {
+                let flags = self.flags_for(self.txtail_ms);
+                queue_flags(&mut self.queue, self.level, flags);
+            }
+        }
+        i.consume(n);
+
+        // Modulate.
+        let mut o = self.dst.write_buf()?;
+        let samples_per_bit = self.sample_rate / self.baud;
+        let mut opos = 0;
+        let mut otags = Vec::new();
+        while let Some(&(level, _)) = self.queue.front() {
+            let n = (self.clock + samples_per_bit) as usize;
+            if opos + n > o.len() {
+                break;
+            }
+            let step = TAU * if level > 0 { self.mark } else { self.space } / self.sample_rate;
+            for s in &mut o.slice()[opos..opos + n] {
+                *s = self.phase.sin() as Float;
+                self.phase = (self.phase + step) % TAU;
+            }
+            let (_, tags) = self.queue.pop_front().unwrap();
+            otags.extend(tags.into_iter().map(|mut t| {
+                t.set_pos(opos);
+                t
+            }));
+            self.clock += samples_per_bit - n as f64;
+            opos += n;
+        }
+        if opos == 0 {
+            if self.queue.is_empty() {
+                return Ok(BlockRet::WaitForStream(&self.src, 1));
+            }
+            return Ok(BlockRet::WaitForStream(
+                &self.dst,
+                samples_per_bit.ceil() as usize,
+            ));
+        }
+        o.produce(opos, &otags);
+        Ok(BlockRet::Again)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::VectorSource;
+
+    // Count zero crossings, to get the tone.
+    fn crossings(s: &[Float]) -> usize {
+        s.windows(2)
+            .filter(|w| (w[0] < 0.0) != (w[1] < 0.0))
+            .count()
+    }
+
+    #[test]
+    fn tones() -> Result<()> {
+        let bits: Vec<u8> = [1u8; 120].into_iter().chain([0; 120]).collect();
+        let (mut src, prev) = VectorSource::new(bits);
+        src.work()?;
+        let (mut b, out) = AfskModulator::new(prev, 48000.0);
+        b.work()?;
+        let (o, _) = out.read_buf()?;
+        // 0.1s of each.
+        assert_eq!(o.len(), 9600);
+        let mark = crossings(&o.slice()[..4800]);
+        let space = crossings(&o.slice()[4800..]);
+        assert!((239..=241).contains(&mark), "{mark}");
+        assert!((439..=441).contains(&space), "{space}");
+
+        // Phase continuous.
+        for w in o.slice().windows(2) {
+            assert!((w[0] - w[1]).abs() < 0.3, "{w:?}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn fractional_samples_per_bit() -> Result<()> {
+        let (mut src, prev) = VectorSource::new(vec![1u8; 1200]);
+        src.work()?;
+        let (mut b, out) = AfskModulator::builder(prev, 44100.0)
+            .mark(1300.0)
+            .space(2100.0)
+            .build();
+        b.work()?;
+        let (o, _) = out.read_buf()?;
+        assert_eq!(o.len(), 44100);
+        let mark = crossings(o.slice());
+        assert!((2599..=2601).contains(&mark), "{mark}");
+        Ok(())
+    }
+
+    #[test]
+    fn delay_and_tail() -> Result<()> {
+        let tags = [
+            Tag::new(0, "KissDecode:txdelay-ms", TagValue::U64(20)),
+            Tag::new(0, "KissDecode:txtail-ms", TagValue::U64(10)),
+            Tag::new(1, FRAME_END_TAG, TagValue::Bool(true)),
+        ];
+        let (mut src, prev) = VectorSource::builder(vec![1u8, 1]).tags(&tags).build()?;
+        src.work()?;
+        let (mut b, out) = AfskModulator::new(prev, 12000.0);
+        b.work()?;
+        let (o, otags) = out.read_buf()?;
+        // 20ms is three flags, 10ms two flags, and the two bits.
+        assert_eq!(o.len(), (3 * 8 + 2 + 2 * 8) * 10);
+        let otags: Vec<_> = otags
+            .into_iter()
+            .filter(|t| !t.key().starts_with("VectorSource"))
+            .collect();
+        assert_eq!(
+            otags,
+            &[
+                Tag::new(0, "KissDecode:txdelay-ms", TagValue::U64(20)),
+                Tag::new(0, "KissDecode:txtail-ms", TagValue::U64(10)),
+                Tag::new(250, FRAME_END_TAG, TagValue::Bool(true)),
+            ]
+        );
+        assert!(b.queue.is_empty());
+        Ok(())
+    }
+}
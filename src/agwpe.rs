@@ -0,0 +1,432 @@
+/*! AGWPE TCP server.
+
+Some packet radio applications, especially ones from Windows, only speak the
+AGWPE TCP API, not KISS. This block provides the subset of it needed to
+monitor and to send raw frames, like Direwolf's port 8000.
+
+Every message, in both directions, is a 36 byte header, followed by the number
+of bytes of data the header says.
+
+Supported messages from clients:
+* `R`: Version.
+* `G`: Port information.
+* `g`: Port capabilities.
+* `X`/`x`: Register/unregister callsign.
+* `m`: Toggle monitoring, which sends `U` frames for received UI frames.
+* `k`: Toggle raw frames, which sends `K` frames for all received frames.
+* `K`: Send raw AX.25 frame.
+
+<https://www.on7lds.net/42/sites/default/files/AGWPEAPI.HTM>
+*/
+use std::net::{SocketAddr, ToSocketAddrs};
+
+use log::{debug, warn};
+
+use crate::Result;
+use crate::ax25::{Ax25Frame, Control, Unnumbered};
+use crate::block::{Block, BlockRet};
+use crate::stream::{NCReadStream, NCWriteStream, Tag, TagValue};
+use crate::tcp_server::TcpServer;
+
+/// Tag used to get and set the AGWPE port of a frame.
+///
+/// Frames to send to clients are sent as coming from this port, or 0 if
+/// missing. Frames received from clients are tagged with the port the client
+/// sent them on.
+pub const PORT_TAG: &str = "AgwpeServer:port";
+
+const HEADER_LEN: usize = 36;
+const CALL_LEN: usize = 10;
+const MAX_DATA_LEN: usize = 65536;
+
+// Version reported to clients. Same as Direwolf.
+const VERSION_MAJOR: u32 = 2005;
+const VERSION_MINOR: u32 = 127;
+
+/// AGWPE message header.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+struct Header {
+    port: u8,
+    kind: u8,
+    pid: u8,
+    call_from: String,
+    call_to: String,
+    data_len: u32,
+}
+
+fn parse_call(data: &[u8]) -> String {
+    let end = data.iter().position(|&b| b == 0).unwrap_or(data.len());
+    String::from_utf8_lossy(&data[..end]).trim().to_string()
+}
+
+fn put_call(out: &mut Vec<u8>, call: &str) {
+    let mut buf = [0u8; CALL_LEN];
+    // Leave room for the NUL.
+    let n = call.len().min(CALL_LEN - 1);
+    buf[..n].copy_from_slice(&call.as_bytes()[..n]);
+    out.extend(buf);
+}
+
+impl Header {
+    fn parse(data: &[u8]) -> Self {
+        assert!(data.len() >= HEADER_LEN);
+        Self {
+            port: data[0],
+            kind: data[4],
+            pid: data[6],
+            call_from: parse_call(&data[8..18]),
+            call_to: parse_call(&data[18..28]),
+            data_len: u32::from_le_bytes(data[28..32].try_into().unwrap()),
+        }
+    }
+
+    /// Serialize header followed by data.
+    fn serialize(&self, data: &[u8]) -> Vec<u8> {
+        let mut out = Vec::with_capacity(HEADER_LEN + data.len());
+        out.extend([self.port, 0, 0, 0, self.kind, 0, self.pid, 0]);
+        put_call(&mut out, &self.call_from);
+        put_call(&mut out, &self.call_to);
+        out.extend(u32::try_from(data.len()).unwrap().to_le_bytes());
+        out.extend([0; 4]);
+        out.extend(data);
+        out
+    }
+}
+
+/// Time of day, as `HH:MM:SS` UTC.
+fn time_of_day() -> String {
+    let secs = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+        % 86400;
+    format!(
+        "{:02}:{:02}:{:02}",
+        secs / 3600,
+        (secs / 60) % 60,
+        secs % 60
+    )
+}
+
+/// Create the text of a monitoring `U` frame.
+///
+/// Return None if it's not a UI frame.
+fn monitor_text(port: u8, m: &Ax25Frame) -> Option<Vec<u8>> {
+    if !matches!(
+        m.control,
+        Control::U {
+            kind: Unnumbered::Ui,
+            ..
+        }
+    ) {
+        return None;
+    }
+    let mut text = format!(" {}:Fm {} To {}", u16::from(port) + 1, m.src, m.dst);
+    if !m.digis.is_empty() {
+        let via: Vec<_> = m
+            .digis
+            .iter()
+            .map(|d| {
+                if d.flag {
+                    format!("{d}*")
+                } else {
+                    d.to_string()
+                }
+            })
+            .collect();
+        text = format!("{text} Via {}", via.join(","));
+    }
+    let text = format!(
+        "{text} <UI pid={:02X} Len={} >[{}]\r",
+        m.pid.unwrap_or(0),
+        m.info.len(),
+        time_of_day()
+    );
+    let mut out = text.into_bytes();
+    out.extend(&m.info);
+    out.push(b'\r');
+    Some(out)
+}
+
+/// Per client state.
+#[derive(Default)]
+struct AgwClient {
+    monitor: bool,
+    raw: bool,
+    callsigns: Vec<String>,
+}
+
+/// AGWPE TCP server.
+///
+/// Takes frames (e.g. from `HdlcDeframer`) and sends them to connected clients
+/// that have asked for monitoring or raw frames. Raw frames sent by clients are
+/// tagged with [`PORT_TAG`] and output for transmission (e.g. to `FcsAdder`
+/// and `HdlcFramer`).
+///
+/// ```no_run
+/// use rustradio::blocks::AgwpeServer;
+/// use rustradio::stream::new_nocopy_stream;
+/// let (_tx, rx) = new_nocopy_stream();
+/// let (server, to_transmit) = AgwpeServer::new(rx, "[::]:8000")?;
+/// # Ok::<(), rustradio::Error>(())
+/// ```
+#[derive(rustradio_macros::Block)]
+#[rustradio(crate)]
+pub struct AgwpeServer {
+    #[rustradio(in)]
+    src: NCReadStream<Vec<u8>>,
+    #[rustradio(out)]
+    dst: NCWriteStream<Vec<u8>>,
+    server: TcpServer<AgwClient>,
+    ports: Vec<String>,
+}
+
+impl AgwpeServer {
+    /// Create a new AGWPE server listening on the given address.
+    pub fn new<A: ToSocketAddrs>(
+        src: NCReadStream<Vec<u8>>,
+        addr: A,
+    ) -> Result<(Self, NCReadStream<Vec<u8>>)> {
+        let server = TcpServer::new("AgwpeServer", addr)?;
+        let (dst, dr) = crate::stream::new_nocopy_stream();
+        Ok((
+            Self {
+                src,
+                dst,
+                server,
+                ports: vec!["rustradio".to_string()],
+            },
+            dr,
+        ))
+    }
+
+    /// Set the port descriptions, which also sets the number of ports.
+    ///
+    /// Default is a single port.
+    pub fn set_ports(&mut self, descriptions: Vec<String>) {
+        assert!(!descriptions.is_empty(), "AGWPE needs at least one port");
+        self.ports = descriptions;
+    }
+
+    /// Return the address the server is listening on.
+    ///
+    /// Useful when binding to port 0.
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        self.server.local_addr()
+    }
+
+    /// Return the number of currently connected clients.
+    #[must_use]
+    pub fn clients(&self) -> usize {
+        self.server.clients.len()
+    }
+
+    /// Handle one message from client `n`.
+    fn handle(&mut self, n: usize, h: Header, data: Vec<u8>) {
+        let reply = |kind: u8, call_from: &str, data: &[u8]| {
+            Header {
+                port: h.port,
+                kind,
+                call_from: call_from.to_string(),
+                ..Default::default()
+            }
+            .serialize(data)
+        };
+        match h.kind {
+            b'R' => {
+                let mut v = VERSION_MAJOR.to_le_bytes().to_vec();
+                v.extend(VERSION_MINOR.to_le_bytes());
+                self.server.clients[n].outbuf.extend(reply(b'R', "", &v));
+            }
+            b'G' => {
+                let ports: String = self
+                    .ports
+                    .iter()
+                    .enumerate()
+                    .map(|(i, desc)| format!("Port{} {desc};", i + 1))
+                    .collect();
+                let mut v = format!("{};{ports}", self.ports.len()).into_bytes();
+                v.push(0);
+                self.server.clients[n].outbuf.extend(reply(b'G', "", &v));
+            }
+            b'g' => {
+                // Baud rate code, traffic level, txdelay, txtail, persist,
+                // slottime, maxframe, active connections, and bytes received in
+                // the last two minutes.
+                let mut v = vec![0, 0, 30, 10, 63, 10, 7, 0];
+                v.extend(0u32.to_le_bytes());
+                self.server.clients[n].outbuf.extend(reply(b'g', "", &v));
+            }
+            b'X' => {
+                let call = h.call_from;
+                let taken = self
+                    .server
+                    .clients
+                    .iter()
+                    .any(|c| c.state.callsigns.contains(&call));
+                let ok = !call.is_empty() && !taken;
+                if ok {
+                    self.server.clients[n].state.callsigns.push(call.clone());
+                }
+                self.server.clients[n]
+                    .outbuf
+                    .extend(reply(b'X', &call, &[u8::from(ok)]));
+            }
+            b'x' => self.server.clients[n]
+                .state
+                .callsigns
+                .retain(|c| *c != h.call_from),
+            b'm' => {
+                let c = &mut self.server.clients[n].state;
+                c.monitor = !c.monitor;
+            }
+            b'k' => {
+                let c = &mut self.server.clients[n].state;
+                c.raw = !c.raw;
+            }
+            b'K' => {
+                if usize::from(h.port) >= self.ports.len() {
+                    debug!("AgwpeServer: raw frame for invalid port {}", h.port);
+                } else if data.len() < 2 {
+                    debug!("AgwpeServer: raw frame too short");
+                } else if self.dst.remaining() == 0 {
+                    warn!("AgwpeServer: output full, dropping frame from client");
+                } else {
+                    // First byte is a KISS style port/command byte.
+                    self.dst.push(
+                        data[1..].to_vec(),
+                        &[Tag::new(0, PORT_TAG, TagValue::U64(h.port.into()))],
+                    );
+                }
+            }
+            other => debug!(
+                "AgwpeServer: unsupported message kind {:?}",
+                char::from(other)
+            ),
+        }
+    }
+
+    /// Send a received frame to interested clients.
+    fn send_frame(&mut self, port: u8, frame: &[u8]) {
+        let m = match Ax25Frame::parse(frame) {
+            Ok(m) => m,
+            Err(e) => {
+                debug!("AgwpeServer: not sending invalid AX.25 frame to clients: {e}");
+                return;
+            }
+        };
+        let header = |kind: u8| Header {
+            port,
+            kind,
+            pid: m.pid.unwrap_or(0),
+            call_from: m.src.to_string(),
+            call_to: m.dst.to_string(),
+            data_len: 0,
+        };
+        let raw = {
+            let mut v = Vec::with_capacity(frame.len() + 1);
+            v.push(port << 4);
+            v.extend(frame);
+            header(b'K').serialize(&v)
+        };
+        let monitor = monitor_text(port, &m).map(|text| header(b'U').serialize(&text));
+        for client in &mut self.server.clients {
+            if client.state.raw {
+                client.outbuf.extend(&raw);
+            }
+            if client.state.monitor
+                && let Some(monitor) = &monitor
+            {
+                client.outbuf.extend(monitor);
+            }
+        }
+    }
+}
+
+fn port_from_tags(tags: &[Tag]) -> u8 {
+    match tags
+        .iter()
+        .find(|t| t.key() == PORT_TAG)
+        .map_or(&TagValue::U64(0), Tag::val)
+    {
+        TagValue::U64(port) if *port < 0x100 => *port as u8,
+        other => {
+            debug!("AgwpeServer: invalid port tag value: {other:?}");
+            0
+        }
+    }
+}
+
+impl Block for AgwpeServer {
+    fn work(&mut self) -> Result<BlockRet<'_>> {
+        let mut active = self.server.accept()?;
+
+        while let Some((frame, tags)) = self.src.pop() {
+            active = true;
+            self.send_frame(port_from_tags(&tags), &frame);
+        }
+
+        self.server.flush_and_fill();
+        for n in 0..self.server.clients.len() {
+            loop {
+                let inbuf = &mut self.server.clients[n].inbuf;
+                if inbuf.len() < HEADER_LEN {
+                    break;
+                }
+                let h = Header::parse(inbuf);
+                let len = h.data_len as usize;
+                if len > MAX_DATA_LEN {
+                    // No way to resync.
+                    warn!("AgwpeServer: message too long: {len}");
+                    inbuf.clear();
+                    break;
+                }
+                if inbuf.len() < HEADER_LEN + len {
+                    break;
+                }
+                let data = inbuf[HEADER_LEN..HEADER_LEN + len].to_vec();
+                inbuf.drain(..HEADER_LEN + len);
+                active = true;
+                self.handle(n, h, data);
+            }
+        }
+        Ok(if active {
+            BlockRet::Again
+        } else {
+            BlockRet::Pending
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn header_roundtrip() {
+        let h = Header {
+            port: 1,
+            kind: b'K',
+            pid: 0xF0,
+            call_from: "M0THC-1".to_string(),
+            call_to: "APZ001".to_string(),
+            data_len: 3,
+        };
+        let b = h.serialize(b"abc");
+        assert_eq!(b.len(), HEADER_LEN + 3);
+        assert_eq!(Header::parse(&b), h);
+        assert_eq!(&b[HEADER_LEN..], b"abc");
+    }
+
+    #[test]
+    fn monitor() {
+        let frame = b"\x82\xa0\xb4\x60\x60\x62\x60\x9a\x60\xa8\x90\x86\x40\xe4\xae\x92\x88\x8a\x62\x40\xe3\x03\xf0hello";
+        let m = Ax25Frame::parse(frame).unwrap();
+        let text = String::from_utf8(monitor_text(0, &m).unwrap()).unwrap();
+        assert!(
+            text.starts_with(" 1:Fm M0THC-2 To APZ001 Via WIDE1-1* <UI pid=F0 Len=5 >["),
+            "{text}"
+        );
+        assert!(text.ends_with("]\rhello\r"), "{text}");
+    }
+}
@@ -0,0 +1,1154 @@
+/*! AIS, the Automatic Identification System for ships.
+
+AIS is sent on two channels, [`Channel::A`] at 161.975MHz and [`Channel::B`]
+at 162.025MHz, as 9600 baud GMSK. The bits are NRZI encoded HDLC frames,
+same as AX.25, except that the payload bits within each byte are the other
+way around.
+
+[`AisDemod`] turns one channel into bits, [`NrziDecode`] and
+[`HdlcDeframer`] find the frames, and [`AisDecode`] parses them into
+[`Message`]s, each tagged with the equivalent `!AIVDM` NMEA sentences.
+
+[`receiver()`] sets up all that, for both channels.
+
+## Example
+
+```
+use rustradio::graph::{Graph, GraphRunner};
+# fn main() -> rustradio::Result<()> {
+let (_, prev) = rustradio::stream::new_stream();
+let mut g = Graph::new();
+// Tuned to 162MHz, between the two channels.
+let messages = rustradio::ais::receiver(&mut g, prev, 96_000.0, 162_000_000.0)?;
+# Ok(())
+# }
+```
+
+References:
+* ITU-R M.1371
+* <https://gpsd.gitlab.io/gpsd/AIVDM.html>
+
+[`NrziDecode`]: crate::blocks::NrziDecode
+[`HdlcDeframer`]: crate::blocks::HdlcDeframer
+*/
+use std::collections::VecDeque;
+
+use log::debug;
+
+use crate::block::{Block, BlockEOF, BlockRet};
+use crate::blocks::{HdlcDeframer, NrziDecode, Tee};
+use crate::fir::Fir;
+use crate::graph::GraphRunner;
+use crate::stream::{NCReadStream, NCWriteStream, ReadStream, Tag, TagValue, WriteStream};
+use crate::window::WindowType;
+use crate::{Complex, Error, Float, Result};
+
+/// Tag with the `!AIVDM` NMEA sentences of the message, as String.
+///
+/// Long messages are split over several sentences, separated by newlines.
+pub const NMEA_TAG: &str = "Ais:nmea";
+
+/// Tag with the channel the message was received on, as String.
+pub const CHANNEL_TAG: &str = "Ais:channel";
+
+const TAU: f64 = 2.0 * std::f64::consts::PI;
+
+const BAUD: f64 = 9600.0;
+
+// GMSK at 9600 baud deviates ±2400Hz.
+const DEVIATION: f64 = 2400.0;
+
+// Frame sizes for the HDLC deframer, including the CRC. The shortest message
+// is 96 bits, and the longest five slots.
+const MIN_FRAME: usize = 12;
+const MAX_FRAME: usize = 160;
+
+// Most payload characters in one NMEA sentence.
+const NMEA_MAX_CHARS: usize = 60;
+
+// Clock recovery loop gain, per zero crossing.
+const CLOCK_GAIN: f64 = 0.1;
+
+/// AIS channel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Channel {
+    /// Channel 87B, 161.975MHz.
+    A,
+    /// Channel 88B, 162.025MHz.
+    B,
+}
+
+impl Channel {
+    /// Channel frequency, in Hz.
+    #[must_use]
+    pub fn frequency(self) -> f64 {
+        match self {
+            Channel::A => 161_975_000.0,
+            Channel::B => 162_025_000.0,
+        }
+    }
+}
+
+impl std::fmt::Display for Channel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Channel::A => write!(f, "A"),
+            Channel::B => write!(f, "B"),
+        }
+    }
+}
+
+/// AIS GMSK demodulator.
+///
+/// Takes complex baseband, and outputs the bits of the AIS channel at
+/// `offset` Hz, one per `u8`. The bits are still NRZI encoded.
+///
+/// The channel is mixed down to 0Hz, filtered, and FM demodulated. Tuning
+/// errors of a kHz or so are removed, and the symbol clock is recovered from
+/// the zero crossings.
+#[derive(rustradio_macros::Block)]
+#[rustradio(crate)]
+pub struct AisDemod {
+    #[rustradio(in)]
+    src: ReadStream<Complex>,
+    #[rustradio(out)]
+    dst: WriteStream<u8>,
+
+    // Mix down, and low pass filter while decimating.
+    nco_phase: f64,
+    nco_step: f64,
+    history: VecDeque<Complex>,
+    fir: Fir<Complex>,
+    decim: usize,
+    decim_pos: usize,
+
+    // FM demodulation, and frequency error.
+    last: Complex,
+    high: Float,
+    low: Float,
+    limit: Float,
+    attack: Float,
+    decay: Float,
+
+    // Clock recovery. Symbols are sampled when the clock wraps.
+    prev: Float,
+    clock: f64,
+    clock_step: f64,
+}
+
+impl AisDemod {
+    /// Create new AIS demodulator, for the channel at `offset` Hz.
+    ///
+    /// # Errors
+    ///
+    /// Errors if the channel doesn't fit within the sample rate.
+    pub fn new(
+        src: ReadStream<Complex>,
+        sample_rate: f64,
+        offset: f64,
+    ) -> Result<(Self, ReadStream<u8>)> {
+        if sample_rate < 4.0 * BAUD {
+            return Err(Error::msg(format!(
+                "AisDemod: sample rate {sample_rate} is too low, needs at least {}",
+                4.0 * BAUD
+            )));
+        }
+        if offset.abs() + BAUD > sample_rate / 2.0 {
+            return Err(Error::msg(format!(
+                "AisDemod: channel offset {offset} is outside sample rate {sample_rate}"
+            )));
+        }
+        let (dst, dr) = crate::stream::new_stream();
+        // Decimate to at least four samples per symbol.
+        let decim = ((sample_rate / (4.0 * BAUD)) as usize).max(1);
+        let rate = sample_rate / decim as f64;
+        let deviation = (TAU * DEVIATION / rate) as Float;
+        let taps = crate::fir::low_pass_complex(
+            sample_rate as Float,
+            7_000.0,
+            3_000.0,
+            &WindowType::Hamming,
+        );
+        Ok((
+            Self {
+                src,
+                dst,
+                nco_phase: 0.0,
+                nco_step: -TAU * offset / sample_rate,
+                history: std::iter::repeat_n(Complex::default(), taps.len()).collect(),
+                fir: Fir::new(&taps),
+                decim,
+                decim_pos: 0,
+                last: Complex::default(),
+                high: deviation,
+                low: -deviation,
+                limit: 2.0 * deviation,
+                // Peaks are found within a symbol, and forgotten over about
+                // 15 symbols.
+                attack: 0.5,
+                decay: (BAUD / (15.0 * rate)) as Float,
+                prev: 0.0,
+                clock: 0.0,
+                clock_step: BAUD / rate,
+            },
+            dr,
+        ))
+    }
+
+    // Process one decimated sample. Maybe return a bit.
+    fn sample(&mut self, s: Complex) -> Option<u8> {
+        let f = (self.last.conj() * s).arg();
+        self.last = s;
+        // The frequency error is halfway between the peaks. A plain average
+        // would be pulled by the unbalanced HDLC flags. Noise between bursts
+        // shouldn't push the peaks apart.
+        let raw = f.clamp(-self.limit, self.limit);
+        let (attack, decay) = (self.attack, self.decay);
+        let track = |peak: &mut Float, above: bool| {
+            *peak += (raw - *peak) * if above { attack } else { decay };
+        };
+        let (high, low) = (self.high, self.low);
+        track(&mut self.high, raw > high);
+        track(&mut self.low, raw < low);
+        let f = f - (self.high + self.low) / 2.0;
+
+        let prev = self.prev;
+        self.prev = f;
+        let old = self.clock;
+        self.clock += self.clock_step;
+        if (prev > 0.0) != (f > 0.0) {
+            // Zero crossings should be halfway between symbols.
+            let frac = f64::from(prev / (prev - f));
+            let cross = old + frac * self.clock_step;
+            let error = cross.rem_euclid(1.0) - 0.5;
+            self.clock -= CLOCK_GAIN * error;
+        }
+        if self.clock < 1.0 {
+            return None;
+        }
+        self.clock -= 1.0;
+        Some(u8::from(f > 0.0))
+    }
+}
+
+impl Block for AisDemod {
+    fn work(&mut self) -> Result<BlockRet<'_>> {
+        let (i, _) = self.src.read_buf()?;
+        if i.is_empty() {
+            return Ok(BlockRet::WaitForStream(&self.src, 1));
+        }
+        let mut o = self.dst.write_buf()?;
+        if o.is_empty() {
+            return Ok(BlockRet::WaitForStream(&self.dst, 1));
+        }
+        let mut opos = 0;
+        let mut consumed = 0;
+        for &x in i.slice() {
+            if opos == o.len() {
+                break;
+            }
+            consumed += 1;
+            let (sin, cos) = self.nco_phase.sin_cos();
+            self.nco_phase = (self.nco_phase + self.nco_step).rem_euclid(TAU);
+            self.history.pop_front();
+            self.history
+                .push_back(x * Complex::new(cos as Float, sin as Float));
+            self.decim_pos += 1;
+            if self.decim_pos < self.decim {
+                continue;
+            }
+            self.decim_pos = 0;
+            let s = self.fir.filter(self.history.make_contiguous());
+            if let Some(bit) = self.sample(s) {
+                o.slice()[opos] = bit;
+                opos += 1;
+            }
+        }
+        i.consume(consumed);
+        o.produce(opos, &[]);
+        Ok(BlockRet::Again)
+    }
+}
+
+/// Read fields from the message bits, most significant bit first.
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    fn uint(&mut self, bits: usize) -> u32 {
+        let mut ret = 0;
+        for _ in 0..bits {
+            let bit = (self.data[self.pos / 8] >> (7 - self.pos % 8)) & 1;
+            ret = (ret << 1) | u32::from(bit);
+            self.pos += 1;
+        }
+        ret
+    }
+
+    fn int(&mut self, bits: usize) -> i32 {
+        let v = self.uint(bits);
+        // Sign extend.
+        ((v << (32 - bits)) as i32) >> (32 - bits)
+    }
+
+    fn bool(&mut self) -> bool {
+        self.uint(1) == 1
+    }
+
+    // Six bit characters, with trailing '@' padding and spaces removed.
+    fn text(&mut self, chars: usize) -> String {
+        let s: String = (0..chars)
+            .map(|_| {
+                let v = self.uint(6) as u8;
+                if v < 32 { (v + 64) as char } else { v as char }
+            })
+            .collect();
+        s.trim_end_matches(['@', ' ']).to_string()
+    }
+
+    fn lon(&mut self) -> Option<f64> {
+        let v = self.int(28);
+        (v != 181 * 600_000).then(|| f64::from(v) / 600_000.0)
+    }
+
+    fn lat(&mut self) -> Option<f64> {
+        let v = self.int(27);
+        (v != 91 * 600_000).then(|| f64::from(v) / 600_000.0)
+    }
+
+    fn speed(&mut self) -> Option<f32> {
+        let v = self.uint(10);
+        (v != 1023).then(|| v as f32 / 10.0)
+    }
+
+    fn course(&mut self) -> Option<f32> {
+        let v = self.uint(12);
+        (v != 3600).then(|| v as f32 / 10.0)
+    }
+
+    fn heading(&mut self) -> Option<u16> {
+        let v = self.uint(9) as u16;
+        (v != 511).then_some(v)
+    }
+
+    fn dimensions(&mut self) -> Dimensions {
+        Dimensions {
+            to_bow: self.uint(9) as u16,
+            to_stern: self.uint(9) as u16,
+            to_port: self.uint(6) as u8,
+            to_starboard: self.uint(6) as u8,
+        }
+    }
+}
+
+/// Ship dimensions, in meters from the position reference point.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Dimensions {
+    /// Distance to bow.
+    pub to_bow: u16,
+    /// Distance to stern.
+    pub to_stern: u16,
+    /// Distance to port.
+    pub to_port: u8,
+    /// Distance to starboard.
+    pub to_starboard: u8,
+}
+
+/// Class A position report, message types 1, 2, and 3.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PositionReport {
+    /// Message type, 1-3.
+    pub msg_type: u8,
+    /// Ship MMSI.
+    pub mmsi: u32,
+    /// Navigation status, e.g. 0 under way using engine, 5 moored.
+    pub status: u8,
+    /// Raw rate of turn indicator.
+    pub turn: Option<i8>,
+    /// Speed over ground, in knots.
+    pub speed: Option<f32>,
+    /// True for position accuracy better than 10m.
+    pub accuracy: bool,
+    /// Longitude, in degrees.
+    pub lon: Option<f64>,
+    /// Latitude, in degrees.
+    pub lat: Option<f64>,
+    /// Course over ground, in degrees.
+    pub course: Option<f32>,
+    /// True heading, in degrees.
+    pub heading: Option<u16>,
+    /// UTC second of the position.
+    pub second: u8,
+}
+
+/// Base station report, message type 4.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BaseStationReport {
+    /// Base station MMSI.
+    pub mmsi: u32,
+    /// UTC year.
+    pub year: u16,
+    /// UTC month.
+    pub month: u8,
+    /// UTC day.
+    pub day: u8,
+    /// UTC hour.
+    pub hour: u8,
+    /// UTC minute.
+    pub minute: u8,
+    /// UTC second.
+    pub second: u8,
+    /// True for position accuracy better than 10m.
+    pub accuracy: bool,
+    /// Longitude, in degrees.
+    pub lon: Option<f64>,
+    /// Latitude, in degrees.
+    pub lat: Option<f64>,
+    /// Type of position fixing device, e.g. 1 for GPS.
+    pub epfd: u8,
+}
+
+/// Static and voyage related data, message type 5.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StaticVoyageData {
+    /// Ship MMSI.
+    pub mmsi: u32,
+    /// AIS version.
+    pub ais_version: u8,
+    /// IMO ship number.
+    pub imo: u32,
+    /// Call sign.
+    pub callsign: String,
+    /// Ship name.
+    pub name: String,
+    /// Ship type, e.g. 70 for cargo.
+    pub ship_type: u8,
+    /// Ship dimensions.
+    pub dimensions: Dimensions,
+    /// Type of position fixing device, e.g. 1 for GPS.
+    pub epfd: u8,
+    /// ETA month, 1-12, or 0 if not available.
+    pub eta_month: u8,
+    /// ETA day, 1-31, or 0 if not available.
+    pub eta_day: u8,
+    /// ETA hour, 0-23, or 24 if not available.
+    pub eta_hour: u8,
+    /// ETA minute, 0-59, or 60 if not available.
+    pub eta_minute: u8,
+    /// Draught, in meters.
+    pub draught: f32,
+    /// Destination.
+    pub destination: String,
+}
+
+/// Class B position report, message type 18.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClassBPositionReport {
+    /// Ship MMSI.
+    pub mmsi: u32,
+    /// Speed over ground, in knots.
+    pub speed: Option<f32>,
+    /// True for position accuracy better than 10m.
+    pub accuracy: bool,
+    /// Longitude, in degrees.
+    pub lon: Option<f64>,
+    /// Latitude, in degrees.
+    pub lat: Option<f64>,
+    /// Course over ground, in degrees.
+    pub course: Option<f32>,
+    /// True heading, in degrees.
+    pub heading: Option<u16>,
+    /// UTC second of the position.
+    pub second: u8,
+}
+
+/// Static data report, message type 24.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StaticDataReport {
+    /// Ship MMSI.
+    pub mmsi: u32,
+    /// Part A or B.
+    pub part: StaticDataPart,
+}
+
+/// The two parts of a static data report.
+#[derive(Debug, Clone, PartialEq)]
+pub enum StaticDataPart {
+    /// Part A, with the name.
+    A {
+        /// Ship name.
+        name: String,
+    },
+    /// Part B, with the rest.
+    B {
+        /// Ship type, e.g. 37 for pleasure craft.
+        ship_type: u8,
+        /// Vendor ID.
+        vendor_id: String,
+        /// Call sign.
+        callsign: String,
+        /// Ship dimensions.
+        dimensions: Dimensions,
+    },
+}
+
+/// Decoded AIS message.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Message {
+    /// Types 1, 2, and 3.
+    Position(PositionReport),
+    /// Type 4.
+    BaseStation(BaseStationReport),
+    /// Type 5.
+    StaticVoyage(StaticVoyageData),
+    /// Type 18.
+    ClassBPosition(ClassBPositionReport),
+    /// Type 24.
+    StaticData(StaticDataReport),
+    /// Other message types.
+    Other {
+        /// Message type.
+        msg_type: u8,
+        /// Source MMSI.
+        mmsi: u32,
+    },
+}
+
+impl Message {
+    /// Parse message, from its bits packed into bytes, most significant bit
+    /// first.
+    ///
+    /// # Errors
+    ///
+    /// Errors if the message is too short for its type.
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        let bits = data.len() * 8;
+        let mut r = BitReader::new(data);
+        if bits < 38 {
+            return Err(Error::msg(format!("AIS message too short: {bits} bits")));
+        }
+        let msg_type = r.uint(6) as u8;
+        let _repeat = r.uint(2);
+        let mmsi = r.uint(30);
+        let need = match msg_type {
+            1..=4 | 18 => 168,
+            5 => 424,
+            // Part A is shorter.
+            24 if data[4] & 3 == 0 => 160,
+            24 => 168,
+            _ => 0,
+        };
+        if bits < need {
+            return Err(Error::msg(format!(
+                "AIS message type {msg_type} too short: {bits} < {need} bits"
+            )));
+        }
+        Ok(match msg_type {
+            1..=3 => Message::Position(PositionReport {
+                msg_type,
+                mmsi,
+                status: r.uint(4) as u8,
+                turn: Some(r.int(8) as i8).filter(|&t| t != -128),
+                speed: r.speed(),
+                accuracy: r.bool(),
+                lon: r.lon(),
+                lat: r.lat(),
+                course: r.course(),
+                heading: r.heading(),
+                second: r.uint(6) as u8,
+            }),
+            4 => Message::BaseStation(BaseStationReport {
+                mmsi,
+                year: r.uint(14) as u16,
+                month: r.uint(4) as u8,
+                day: r.uint(5) as u8,
+                hour: r.uint(5) as u8,
+                minute: r.uint(6) as u8,
+                second: r.uint(6) as u8,
+                accuracy: r.bool(),
+                lon: r.lon(),
+                lat: r.lat(),
+                epfd: r.uint(4) as u8,
+            }),
+            5 => Message::StaticVoyage(StaticVoyageData {
+                mmsi,
+                ais_version: r.uint(2) as u8,
+                imo: r.uint(30),
+                callsign: r.text(7),
+                name: r.text(20),
+                ship_type: r.uint(8) as u8,
+                dimensions: r.dimensions(),
+                epfd: r.uint(4) as u8,
+                eta_month: r.uint(4) as u8,
+                eta_day: r.uint(5) as u8,
+                eta_hour: r.uint(5) as u8,
+                eta_minute: r.uint(6) as u8,
+                draught: r.uint(8) as f32 / 10.0,
+                destination: r.text(20),
+            }),
+            18 => {
+                let _reserved = r.uint(8);
+                Message::ClassBPosition(ClassBPositionReport {
+                    mmsi,
+                    speed: r.speed(),
+                    accuracy: r.bool(),
+                    lon: r.lon(),
+                    lat: r.lat(),
+                    course: r.course(),
+                    heading: r.heading(),
+                    second: r.uint(6) as u8,
+                })
+            }
+            24 => {
+                let part = if r.uint(2) == 0 {
+                    StaticDataPart::A { name: r.text(20) }
+                } else {
+                    let ship_type = r.uint(8) as u8;
+                    let vendor_id = r.text(3);
+                    // Unit model code, and serial number.
+                    let _serial = r.uint(24);
+                    StaticDataPart::B {
+                        ship_type,
+                        vendor_id,
+                        callsign: r.text(7),
+                        dimensions: r.dimensions(),
+                    }
+                };
+                Message::StaticData(StaticDataReport { mmsi, part })
+            }
+            _ => Message::Other { msg_type, mmsi },
+        })
+    }
+
+    /// Source MMSI.
+    #[must_use]
+    pub fn mmsi(&self) -> u32 {
+        match self {
+            Message::Position(m) => m.mmsi,
+            Message::BaseStation(m) => m.mmsi,
+            Message::StaticVoyage(m) => m.mmsi,
+            Message::ClassBPosition(m) => m.mmsi,
+            Message::StaticData(m) => m.mmsi,
+            Message::Other { mmsi, .. } => *mmsi,
+        }
+    }
+}
+
+/// Encode message bits as `!AIVDM` NMEA sentences.
+///
+/// Messages too long for one sentence are split, and get the sequential
+/// message ID `seq`, 0-9.
+#[must_use]
+pub fn nmea(data: &[u8], channel: Channel, seq: u8) -> Vec<String> {
+    let mut r = BitReader::new(data);
+    let bits = data.len() * 8;
+    let payload: Vec<u8> = (0..bits.div_ceil(6))
+        .map(|n| {
+            let v = if bits - n * 6 >= 6 {
+                r.uint(6)
+            } else {
+                let left = bits - n * 6;
+                r.uint(left) << (6 - left)
+            } as u8;
+            if v < 40 { v + 48 } else { v + 56 }
+        })
+        .collect();
+    let fill = payload.len() * 6 - bits;
+    let parts: Vec<_> = payload.chunks(NMEA_MAX_CHARS).collect();
+    let seq = if parts.len() > 1 {
+        seq.to_string()
+    } else {
+        String::new()
+    };
+    parts
+        .iter()
+        .enumerate()
+        .map(|(n, chunk)| {
+            let fill = if n + 1 == parts.len() { fill } else { 0 };
+            let body = format!(
+                "AIVDM,{},{},{seq},{channel},{},{fill}",
+                parts.len(),
+                n + 1,
+                String::from_utf8_lossy(chunk)
+            );
+            let checksum = body.bytes().fold(0, |acc, b| acc ^ b);
+            format!("!{body}*{checksum:02X}")
+        })
+        .collect()
+}
+
+/// AIS message decoder.
+///
+/// Takes HDLC frames from one or more channels, and outputs the decoded
+/// messages. Each message is tagged with [`CHANNEL_TAG`], and with the
+/// `!AIVDM` sentences in [`NMEA_TAG`].
+///
+/// Frames too short for their message type are dropped.
+#[derive(rustradio_macros::Block)]
+#[rustradio(crate, noeof)]
+pub struct AisDecode {
+    srcs: Vec<(Channel, NCReadStream<Vec<u8>>)>,
+    #[rustradio(out)]
+    dst: NCWriteStream<Message>,
+    // Sequential message ID, for multi sentence messages.
+    seq: u8,
+}
+
+impl AisDecode {
+    /// Create new AIS decoder, for frames from the given channels.
+    #[must_use]
+    pub fn new(srcs: Vec<(Channel, NCReadStream<Vec<u8>>)>) -> (Self, NCReadStream<Message>) {
+        let (dst, dr) = crate::stream::new_nocopy_stream();
+        (Self { srcs, dst, seq: 0 }, dr)
+    }
+
+    fn decode(&mut self, channel: Channel, frame: &[u8]) {
+        // HDLC sends the least significant bit first, but AIS fields are
+        // most significant bit first.
+        let data: Vec<u8> = frame.iter().map(|b| b.reverse_bits()).collect();
+        let msg = match Message::parse(&data) {
+            Ok(msg) => msg,
+            Err(e) => {
+                debug!("AisDecode: {e}");
+                return;
+            }
+        };
+        let sentences = nmea(&data, channel, self.seq);
+        if sentences.len() > 1 {
+            self.seq = (self.seq + 1) % 10;
+        }
+        debug!("AisDecode: {msg:?}");
+        self.dst.push(
+            msg,
+            &[
+                Tag::new(0, CHANNEL_TAG, TagValue::String(channel.to_string())),
+                Tag::new(0, NMEA_TAG, TagValue::String(sentences.join("\n"))),
+            ],
+        );
+    }
+}
+
+impl BlockEOF for AisDecode {
+    fn eof(&mut self) -> bool {
+        self.srcs.iter().all(|(_, s)| s.eof())
+    }
+}
+
+impl Block for AisDecode {
+    fn work(&mut self) -> Result<BlockRet<'_>> {
+        let mut any = false;
+        for n in 0..self.srcs.len() {
+            if self.dst.remaining() == 0 {
+                return Ok(BlockRet::WaitForStream(&self.dst, 1));
+            }
+            let (channel, src) = &self.srcs[n];
+            let channel = *channel;
+            if let Some((frame, _)) = src.pop() {
+                any = true;
+                self.decode(channel, &frame);
+            }
+        }
+        if any {
+            return Ok(BlockRet::Again);
+        }
+        // Frames can arrive on any of the channels, so wait for whichever
+        // is still open.
+        match self.srcs.iter().find(|(_, src)| !src.eof()) {
+            Some((_, src)) => Ok(BlockRet::WaitForStream(src, 1)),
+            None => Ok(BlockRet::EOF),
+        }
+    }
+}
+
+/// Add a dual channel AIS receiver to the graph.
+///
+/// Takes complex baseband at `sample_rate`, tuned to `center_freq`, which
+/// needs to include both AIS channels. E.g. 96kHz tuned to 162MHz.
+///
+/// # Errors
+///
+/// Errors if the channels are outside the sample rate.
+pub fn receiver(
+    g: &mut dyn GraphRunner,
+    src: ReadStream<Complex>,
+    sample_rate: f64,
+    center_freq: f64,
+) -> Result<NCReadStream<Message>> {
+    let (tee, a, b) = Tee::new(src);
+    g.add(Box::new(tee));
+    let mut frames = Vec::new();
+    for (channel, src) in [(Channel::A, a), (Channel::B, b)] {
+        let prev = crate::blockchain![
+            g,
+            prev,
+            AisDemod::new(src, sample_rate, channel.frequency() - center_freq)?,
+            NrziDecode::new(prev),
+            HdlcDeframer::new(prev, MIN_FRAME, MAX_FRAME),
+        ];
+        frames.push((channel, prev));
+    }
+    let (decode, prev) = AisDecode::new(frames);
+    g.add(Box::new(decode));
+    Ok(prev)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::{
+        FcsAdder, GfskModulator, HdlcFramer, NrziEncode, PduToStream, VectorSource,
+    };
+
+    // Sample messages from the gpsd AIVDM documentation.
+    const TYPE1: &str = "!AIVDM,1,1,,B,177KQJ5000G?tO`K>RA1wUbN0TKH,0*5C";
+    const TYPE5: [&str; 2] = [
+        "!AIVDM,2,1,1,A,55?MbV02;H;s<HtKR20EHE:0@T4@Dn2222222216L961O5Gf0NSQEp6ClRp8,0*1C",
+        "!AIVDM,2,2,1,A,88888888880,2*25",
+    ];
+
+    // Decode the payload of NMEA sentences into bytes.
+    fn dearmor(sentences: &[&str]) -> Vec<u8> {
+        let mut bits = Vec::new();
+        let mut fill = 0;
+        for s in sentences {
+            let fields: Vec<_> = s.split(',').collect();
+            for c in fields[5].bytes() {
+                let v = c - 48;
+                let v = if v > 40 { v - 8 } else { v };
+                bits.extend((0..6).rev().map(|i| (v >> i) & 1));
+            }
+            fill = usize::from(fields[6].as_bytes()[0] - b'0');
+        }
+        bits.truncate(bits.len() - fill);
+        pack(&bits)
+    }
+
+    fn pack(bits: &[u8]) -> Vec<u8> {
+        bits.chunks(8)
+            .map(|c| {
+                c.iter()
+                    .chain(std::iter::repeat(&0))
+                    .take(8)
+                    .fold(0, |acc, &b| (acc << 1) | b)
+            })
+            .collect()
+    }
+
+    // Message builder, for tests.
+    #[derive(Default)]
+    struct BitWriter {
+        bits: Vec<u8>,
+    }
+
+    impl BitWriter {
+        fn uint(mut self, bits: usize, v: i64) -> Self {
+            self.bits
+                .extend((0..bits).rev().map(|i| ((v >> i) & 1) as u8));
+            self
+        }
+        fn text(mut self, chars: usize, s: &str) -> Self {
+            for c in s.bytes().chain(std::iter::repeat(b'@')).take(chars) {
+                let v = if c >= 64 { c - 64 } else { c };
+                self = self.uint(6, i64::from(v));
+            }
+            self
+        }
+        fn bytes(&self) -> Vec<u8> {
+            pack(&self.bits)
+        }
+    }
+
+    #[test]
+    fn position() -> Result<()> {
+        let data = dearmor(&[TYPE1]);
+        assert_eq!(data.len() * 8, 168);
+        let Message::Position(m) = Message::parse(&data)? else {
+            panic!("wrong type");
+        };
+        assert_eq!(m.msg_type, 1);
+        assert_eq!(m.mmsi, 477_553_000);
+        assert_eq!(m.status, 5);
+        assert_eq!(m.turn, Some(0));
+        assert_eq!(m.speed, Some(0.0));
+        assert!(!m.accuracy);
+        assert!((m.lon.unwrap() + 122.345_833).abs() < 1e-5, "{:?}", m.lon);
+        assert!((m.lat.unwrap() - 47.582_833).abs() < 1e-5, "{:?}", m.lat);
+        assert_eq!(m.course, Some(51.0));
+        assert_eq!(m.heading, Some(181));
+        assert_eq!(m.second, 15);
+        assert_eq!(nmea(&data, Channel::B, 0), vec![TYPE1]);
+        Ok(())
+    }
+
+    #[test]
+    fn static_voyage() -> Result<()> {
+        let data = dearmor(&TYPE5);
+        assert_eq!(data.len() * 8, 424);
+        assert_eq!(
+            Message::parse(&data)?,
+            Message::StaticVoyage(StaticVoyageData {
+                mmsi: 351_759_000,
+                ais_version: 0,
+                imo: 9_134_270,
+                callsign: "3FOF8".into(),
+                name: "EVER DIADEM".into(),
+                ship_type: 70,
+                dimensions: Dimensions {
+                    to_bow: 225,
+                    to_stern: 70,
+                    to_port: 1,
+                    to_starboard: 31,
+                },
+                epfd: 1,
+                eta_month: 5,
+                eta_day: 15,
+                eta_hour: 14,
+                eta_minute: 0,
+                draught: 12.2,
+                destination: "NEW YORK".into(),
+            })
+        );
+        assert_eq!(nmea(&data, Channel::A, 1), TYPE5);
+        Ok(())
+    }
+
+    #[test]
+    fn base_station() -> Result<()> {
+        let data = BitWriter::default()
+            .uint(6, 4)
+            .uint(2, 0)
+            .uint(30, 2_573_135)
+            .uint(14, 2026)
+            .uint(4, 10)
+            .uint(5, 18)
+            .uint(5, 12)
+            .uint(6, 34)
+            .uint(6, 56)
+            .uint(1, 1)
+            .uint(28, (10.5 * 600_000.0) as i64)
+            .uint(27, (-59.25 * 600_000.0) as i64)
+            .uint(4, 1)
+            .uint(30, 0)
+            .bytes();
+        assert_eq!(
+            Message::parse(&data)?,
+            Message::BaseStation(BaseStationReport {
+                mmsi: 2_573_135,
+                year: 2026,
+                month: 10,
+                day: 18,
+                hour: 12,
+                minute: 34,
+                second: 56,
+                accuracy: true,
+                lon: Some(10.5),
+                lat: Some(-59.25),
+                epfd: 1,
+            })
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn class_b() -> Result<()> {
+        let data = BitWriter::default()
+            .uint(6, 18)
+            .uint(2, 0)
+            .uint(30, 338_123_456)
+            .uint(8, 0)
+            .uint(10, 1023)
+            .uint(1, 0)
+            .uint(28, 181 * 600_000)
+            .uint(27, 91 * 600_000)
+            .uint(12, 3600)
+            .uint(9, 511)
+            .uint(6, 60)
+            .uint(28, 0)
+            .bytes();
+        assert_eq!(
+            Message::parse(&data)?,
+            Message::ClassBPosition(ClassBPositionReport {
+                mmsi: 338_123_456,
+                speed: None,
+                accuracy: false,
+                lon: None,
+                lat: None,
+                course: None,
+                heading: None,
+                second: 60,
+            })
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn static_data() -> Result<()> {
+        let a = BitWriter::default()
+            .uint(6, 24)
+            .uint(2, 0)
+            .uint(30, 338_123_456)
+            .uint(2, 0)
+            .text(20, "SEA RUST")
+            .bytes();
+        assert_eq!(a.len() * 8, 160);
+        assert_eq!(
+            Message::parse(&a)?,
+            Message::StaticData(StaticDataReport {
+                mmsi: 338_123_456,
+                part: StaticDataPart::A {
+                    name: "SEA RUST".into()
+                },
+            })
+        );
+        let b = BitWriter::default()
+            .uint(6, 24)
+            .uint(2, 0)
+            .uint(30, 338_123_456)
+            .uint(2, 1)
+            .uint(8, 37)
+            .text(3, "ABC")
+            .uint(24, 0x12345)
+            .text(7, "K1ABC")
+            .uint(9, 8)
+            .uint(9, 4)
+            .uint(6, 2)
+            .uint(6, 2)
+            .uint(6, 0)
+            .bytes();
+        assert_eq!(
+            Message::parse(&b)?,
+            Message::StaticData(StaticDataReport {
+                mmsi: 338_123_456,
+                part: StaticDataPart::B {
+                    ship_type: 37,
+                    vendor_id: "ABC".into(),
+                    callsign: "K1ABC".into(),
+                    dimensions: Dimensions {
+                        to_bow: 8,
+                        to_stern: 4,
+                        to_port: 2,
+                        to_starboard: 2,
+                    },
+                },
+            })
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn short() -> Result<()> {
+        let data = dearmor(&[TYPE1]);
+        assert!(Message::parse(&data[..20]).is_err());
+        assert!(Message::parse(&data[..4]).is_err());
+        // Unknown types only need the MMSI.
+        let other = BitWriter::default()
+            .uint(6, 8)
+            .uint(2, 0)
+            .uint(30, 1234)
+            .bytes();
+        assert_eq!(
+            Message::parse(&other)?,
+            Message::Other {
+                msg_type: 8,
+                mmsi: 1234
+            }
+        );
+        Ok(())
+    }
+
+    // GMSK modulate a frame, like an AIS transmitter.
+    fn modulate(data: &[u8], sps: usize) -> Result<Vec<Complex>> {
+        let (tx, rx) = crate::stream::new_nocopy_stream();
+        tx.push(data.iter().map(|b| b.reverse_bits()).collect(), &[]);
+        let (mut fcs, prev) = FcsAdder::new(rx);
+        fcs.work()?;
+        let (mut framer, prev) = HdlcFramer::new(prev);
+        framer.work()?;
+        let (mut p2s, prev) = PduToStream::new(prev);
+        p2s.work()?;
+        // Bursts start with a training sequence of alternating bits.
+        let (bits, _) = prev.read_buf()?;
+        let bits: Vec<u8> = (0..24)
+            .map(|n| n % 2)
+            .chain(bits.slice().iter().copied())
+            .collect();
+        let (mut src, prev) = VectorSource::new(bits);
+        src.work()?;
+        let (mut nrzi, prev) = NrziEncode::new(prev);
+        nrzi.work()?;
+        let (mut gmsk, out) = GfskModulator::gmsk(prev, sps, 0.4)?;
+        gmsk.work()?;
+        let (o, _) = out.read_buf()?;
+        Ok(o.slice().to_vec())
+    }
+
+    #[test]
+    fn receive() -> Result<()> {
+        use crate::graph::Graph;
+        use rand::{Rng, SeedableRng, rngs::StdRng};
+        let sr = 96_000.0;
+        let center = 162_000_000.0;
+        let a = modulate(&dearmor(&TYPE5), 10)?;
+        let b = modulate(&dearmor(&[TYPE1]), 10)?;
+        // Channel B starts a bit later, and the receiver is a bit off
+        // frequency, and noisy.
+        let error = 1_000.0;
+        let mut rng = StdRng::seed_from_u64(1);
+        let len = (a.len() + 1000).max(b.len() + 3333);
+        let samples: Vec<Complex> = (0..len)
+            .map(|n| {
+                let t = n as f64 / sr;
+                let mix = |f: f64| {
+                    let p = TAU * (f + error - center) * t;
+                    Complex::new(p.cos() as Float, p.sin() as Float)
+                };
+                let burst = |s: &[Complex], start: usize| {
+                    n.checked_sub(start)
+                        .and_then(|n| s.get(n).copied())
+                        .unwrap_or_default()
+                };
+                let noise = Complex::new(rng.random_range(-0.1..0.1), rng.random_range(-0.1..0.1));
+                burst(&a, 1000) * mix(Channel::A.frequency())
+                    + burst(&b, 3333) * mix(Channel::B.frequency())
+                    + noise
+            })
+            .collect();
+        let mut g = Graph::new();
+        let (src, prev) = VectorSource::new(samples);
+        g.add(Box::new(src));
+        let out = receiver(&mut g, prev, sr, center)?;
+        g.run()?;
+        let mut got = Vec::new();
+        while let Some((msg, tags)) = out.pop() {
+            let tag = |key: &str| {
+                tags.iter()
+                    .find(|t| t.key() == key)
+                    .map(|t| match t.val() {
+                        TagValue::String(s) => s.clone(),
+                        _ => panic!("bad tag {t:?}"),
+                    })
+                    .unwrap()
+            };
+            got.push((msg.mmsi(), tag(CHANNEL_TAG), tag(NMEA_TAG)));
+        }
+        got.sort();
+        assert_eq!(
+            got,
+            vec![
+                (
+                    351_759_000,
+                    "A".into(),
+                    nmea(&dearmor(&TYPE5), Channel::A, 0).join("\n")
+                ),
+                (477_553_000, "B".into(), TYPE1.into()),
+            ]
+        );
+        Ok(())
+    }
+}
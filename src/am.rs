@@ -0,0 +1,315 @@
+/*! AM modulation and demodulation.
+
+[`AmDemod`] is a plain envelope detector. [`SyncAmDemod`] locks onto the
+carrier with a PLL, which handles selective fading and overmodulation better.
+
+[`AmModulator`] turns audio into complex baseband, with or without a carrier.
+
+## Example
+
+```
+use rustradio::blocks::{AmDemod, FftFilter};
+use rustradio::window::WindowType;
+let samp_rate = 50_000.0;
+let (_, prev) = rustradio::stream::new_stream();
+let taps = rustradio::fir::low_pass_complex(samp_rate, 5_000.0, 1_000.0, &WindowType::Hamming);
+let (filter, prev) = FftFilter::new(prev, taps);
+let (demod, prev) = AmDemod::new(prev, samp_rate.into());
+```
+*/
+use crate::pll::Pll;
+use crate::stream::{ReadStream, WriteStream};
+use crate::{Complex, Float};
+
+const TAU: f64 = 2.0 * std::f64::consts::PI;
+
+// Cutoff frequency for removing the carrier from the demodulated audio.
+const DC_CUTOFF: f64 = 10.0;
+
+/// Remove DC with a single pole high pass filter.
+pub(crate) struct DcBlocker {
+    alpha: Float,
+    dc: Float,
+}
+
+impl DcBlocker {
+    pub(crate) fn new(sample_rate: f64, cutoff: f64) -> Self {
+        Self {
+            alpha: (1.0 - (-TAU * cutoff / sample_rate).exp()) as Float,
+            dc: 0.0,
+        }
+    }
+
+    pub(crate) fn filter(&mut self, x: Float) -> Float {
+        self.dc += self.alpha * (x - self.dc);
+        x - self.dc
+    }
+}
+
+/// AM envelope demodulator.
+///
+/// Outputs the magnitude of the input, with the carrier (DC) removed.
+#[derive(rustradio_macros::Block)]
+#[rustradio(crate, sync)]
+pub struct AmDemod {
+    #[rustradio(in)]
+    src: ReadStream<Complex>,
+    #[rustradio(out)]
+    dst: WriteStream<Float>,
+    dc: DcBlocker,
+}
+
+impl AmDemod {
+    /// Create new AM demodulator.
+    #[must_use]
+    pub fn new(src: ReadStream<Complex>, sample_rate: f64) -> (Self, ReadStream<Float>) {
+        let (dst, dr) = crate::stream::new_stream();
+        (
+            Self {
+                src,
+                dst,
+                dc: DcBlocker::new(sample_rate, DC_CUTOFF),
+            },
+            dr,
+        )
+    }
+
+    fn process_sync(&mut self, s: Complex) -> Float {
+        self.dc.filter(s.norm())
+    }
+}
+
+/// Builder for [`AmModulator`].
+pub struct AmModulatorBuilder {
+    block: AmModulator,
+    out: ReadStream<Complex>,
+}
+
+impl AmModulatorBuilder {
+    /// Set carrier level. Default 1.0.
+    ///
+    /// Audio peaks above the carrier level overmodulate.
+    #[must_use]
+    pub fn carrier(mut self, level: Float) -> Self {
+        self.block.carrier = level;
+        self
+    }
+
+    /// Suppress the carrier, for double sideband suppressed carrier (DSB-SC).
+    #[must_use]
+    pub fn dsb_sc(self) -> Self {
+        self.carrier(0.0)
+    }
+
+    /// Build the `AmModulator`.
+    #[must_use]
+    pub fn build(self) -> (AmModulator, ReadStream<Complex>) {
+        (self.block, self.out)
+    }
+}
+
+/// AM modulator.
+///
+/// Takes audio, and outputs complex baseband with the carrier at 0Hz. The
+/// output is the carrier level plus the audio, so with the default carrier
+/// level of 1.0, audio between -1.0 and 1.0 is up to 100% modulation.
+#[derive(rustradio_macros::Block)]
+#[rustradio(crate, sync)]
+pub struct AmModulator {
+    #[rustradio(in)]
+    src: ReadStream<Float>,
+    #[rustradio(out)]
+    dst: WriteStream<Complex>,
+    carrier: Float,
+}
+
+impl AmModulator {
+    /// Create new AM modulator, with a carrier level of 1.0.
+    #[must_use]
+    pub fn new(src: ReadStream<Float>) -> (Self, ReadStream<Complex>) {
+        let (dst, dr) = crate::stream::new_stream();
+        (
+            Self {
+                src,
+                dst,
+                carrier: 1.0,
+            },
+            dr,
+        )
+    }
+
+    /// Create a builder, for non-default settings.
+    #[must_use]
+    pub fn builder(src: ReadStream<Float>) -> AmModulatorBuilder {
+        let (block, out) = Self::new(src);
+        AmModulatorBuilder { block, out }
+    }
+
+    fn process_sync(&mut self, s: Float) -> Complex {
+        Complex::new(self.carrier + s, 0.0)
+    }
+}
+
+/// Synchronous AM demodulator.
+///
+/// A PLL locks onto the carrier, and the output is the in-phase component,
+/// with the carrier removed. Unlike envelope detection, this doesn't distort
+/// when the carrier fades.
+#[derive(rustradio_macros::Block)]
+#[rustradio(crate, sync)]
+pub struct SyncAmDemod {
+    #[rustradio(in)]
+    src: ReadStream<Complex>,
+    #[rustradio(out)]
+    dst: WriteStream<Float>,
+    pll: Pll,
+    dc: DcBlocker,
+}
+
+impl SyncAmDemod {
+    /// Create new synchronous AM demodulator.
+    ///
+    /// `loop_bandwidth` is the PLL bandwidth in Hz, e.g. 50. The carrier can
+    /// be up to a tenth of the sample rate off.
+    #[must_use]
+    pub fn new(
+        src: ReadStream<Complex>,
+        sample_rate: f64,
+        loop_bandwidth: f64,
+    ) -> (Self, ReadStream<Float>) {
+        let (dst, dr) = crate::stream::new_stream();
+        (
+            Self {
+                src,
+                dst,
+                pll: Pll::new(TAU * loop_bandwidth / sample_rate, -TAU / 10.0, TAU / 10.0),
+                dc: DcBlocker::new(sample_rate, DC_CUTOFF),
+            },
+            dr,
+        )
+    }
+
+    fn process_sync(&mut self, s: Complex) -> Float {
+        let (sin, cos) = self.pll.phase.sin_cos();
+        let v = s * Complex::new(cos as Float, -sin as Float);
+        self.pll.update(f64::from(v.arg()));
+        self.dc.filter(v.re)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Result;
+    use crate::block::Block;
+    use crate::blocks::VectorSource;
+
+    /// Generate AM signal, with a 1kHz tone at 50% modulation.
+    fn am_signal(sample_rate: f64, offset: f64, len: usize) -> Vec<Complex> {
+        (0..len)
+            .map(|n| {
+                let t = n as f64 / sample_rate;
+                let a = 1.0 + 0.5 * (TAU * 1000.0 * t).cos();
+                let p = TAU * offset * t + 1.0;
+                Complex::new((a * p.cos()) as Float, (a * p.sin()) as Float)
+            })
+            .collect()
+    }
+
+    /// Compare output to the 1kHz tone, returning the correlation.
+    fn tone_match(out: &[Float], sample_rate: f64, amplitude: f64) -> f64 {
+        // Best phase, to be robust against filter delays.
+        (0..16)
+            .map(|p| {
+                let p = TAU * f64::from(p) / 16.0;
+                let sum: f64 = out
+                    .iter()
+                    .enumerate()
+                    .map(|(n, &v)| {
+                        let want = amplitude * (TAU * 1000.0 * n as f64 / sample_rate + p).cos();
+                        f64::from(v) * want
+                    })
+                    .sum();
+                sum / (out.len() as f64 * amplitude * amplitude / 2.0)
+            })
+            .fold(f64::MIN, f64::max)
+    }
+
+    fn rms(out: &[Float]) -> f64 {
+        (out.iter().map(|&x| f64::from(x * x)).sum::<f64>() / out.len() as f64).sqrt()
+    }
+
+    #[test]
+    fn envelope() -> Result<()> {
+        let sr = 48000.0;
+        let (mut src, prev) = VectorSource::new(am_signal(sr, 300.0, 48000));
+        src.work()?;
+        let (mut b, out) = AmDemod::new(prev, sr);
+        b.work()?;
+        let (o, _) = out.read_buf()?;
+        let tail = &o.slice()[24000..];
+        let m = tone_match(tail, sr, 0.5);
+        assert!((0.95..1.05).contains(&m), "{m}");
+        // Only the tone, no DC.
+        assert!(
+            (rms(tail) - 0.5 / 2.0f64.sqrt()).abs() < 0.02,
+            "{}",
+            rms(tail)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn sync() -> Result<()> {
+        let sr = 48000.0;
+        for offset in [-200.0, 0.0, 35.0] {
+            let (mut src, prev) = VectorSource::new(am_signal(sr, offset, 48000));
+            src.work()?;
+            let (mut b, out) = SyncAmDemod::new(prev, sr, 50.0);
+            b.work()?;
+            let (o, _) = out.read_buf()?;
+            let tail = &o.slice()[24000..];
+            let m = tone_match(tail, sr, 0.5);
+            assert!((0.95..1.05).contains(&m), "{offset}: {m}");
+            assert!(
+                (rms(tail) - 0.5 / 2.0f64.sqrt()).abs() < 0.02,
+                "{offset}: {}",
+                rms(tail)
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn modulate() -> Result<()> {
+        let sr = 48000.0;
+        let audio: Vec<Float> = (0..48000)
+            .map(|n| 0.5 * (TAU * 1000.0 * f64::from(n) / sr).cos() as Float)
+            .collect();
+        for dsb_sc in [false, true] {
+            let (mut src, prev) = VectorSource::new(audio.clone());
+            src.work()?;
+            let b = AmModulator::builder(prev);
+            let (mut b, prev) = if dsb_sc { b.dsb_sc() } else { b }.build();
+            b.work()?;
+            {
+                let (o, _) = prev.read_buf()?;
+                let o = o.slice();
+                assert_eq!(o.len(), audio.len());
+                let carrier = if dsb_sc { 0.0 } else { 1.0 };
+                for (got, want) in o.iter().zip(&audio) {
+                    assert_eq!(*got, Complex::new(carrier + want, 0.0));
+                }
+            }
+            if !dsb_sc {
+                // Loopback.
+                let (mut b, out) = AmDemod::new(prev, sr);
+                b.work()?;
+                let (o, _) = out.read_buf()?;
+                let m = tone_match(&o.slice()[24000..], sr, 0.5);
+                assert!((0.95..1.05).contains(&m), "{m}");
+            }
+        }
+        Ok(())
+    }
+}
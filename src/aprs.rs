@@ -0,0 +1,1048 @@
+/*! APRS information field decoder.
+
+Decodes the information field of AX.25 UI frames into an [`AprsPacket`],
+which can be serialized with serde, e.g. with [`ToJson`](crate::to_json::ToJson)
+to produce JSON lines.
+
+Supported packet types:
+* Position, with and without timestamp. Uncompressed, compressed, and Mic-E.
+* Status.
+* Message, ack, and rej.
+* Object and item.
+* Weather, both positionless and as part of a position report.
+* Telemetry.
+* Third party, decoding the inner packet.
+
+Other packet types are output as [`AprsData::Unknown`].
+
+Units are those used by APRS: degrees, knots, feet, mph, Fahrenheit, and
+inches.
+
+<https://www.aprs.org/doc/APRS101.PDF>
+*/
+use log::debug;
+
+use crate::ax25::{Ax25Frame, Control, Unnumbered};
+use crate::block::{Block, BlockRet};
+use crate::stream::{NCReadStream, NCWriteStream};
+use crate::{Error, Result};
+
+const FEET_PER_METER: f64 = 3.280_84;
+
+/// Decoded APRS packet.
+#[derive(Clone, Debug, PartialEq, serde::Serialize)]
+pub struct AprsPacket {
+    /// Source callsign, with SSID.
+    pub src: String,
+
+    /// Destination callsign, with SSID.
+    pub dst: String,
+
+    /// Digipeater path, `*` marking digipeaters that have repeated it.
+    pub path: Vec<String>,
+
+    /// Decoded information field.
+    #[serde(flatten)]
+    pub data: AprsData,
+}
+
+impl AprsPacket {
+    /// Decode an APRS packet from an AX.25 frame.
+    ///
+    /// Only UI frames can be APRS.
+    pub fn from_frame(frame: &Ax25Frame) -> Result<Self> {
+        if !matches!(
+            frame.control,
+            Control::U {
+                kind: Unnumbered::Ui,
+                ..
+            }
+        ) {
+            return Err(Error::msg("APRS packets must be UI frames"));
+        }
+        Ok(Self {
+            src: frame.src.to_string(),
+            dst: frame.dst.to_string(),
+            path: frame
+                .digis
+                .iter()
+                .map(|d| {
+                    if d.flag {
+                        format!("{d}*")
+                    } else {
+                        d.to_string()
+                    }
+                })
+                .collect(),
+            data: AprsData::parse(&frame.dst.call, &frame.info)?,
+        })
+    }
+
+    /// Decode a packet in TNC2 text format, like `SRC>DST,PATH:info`.
+    pub fn from_tnc2(data: &[u8]) -> Result<Self> {
+        let Some(colon) = data.iter().position(|&b| b == b':') else {
+            return Err(Error::msg("TNC2 packet missing ':'"));
+        };
+        let header = std::str::from_utf8(&data[..colon])
+            .map_err(|e| Error::wrap(e, "TNC2 header not UTF-8"))?;
+        let Some((src, rest)) = header.split_once('>') else {
+            return Err(Error::msg(format!("TNC2 header missing '>': {header}")));
+        };
+        let mut rest = rest.split(',');
+        let dst = rest.next().unwrap_or_default();
+        if src.is_empty() || dst.is_empty() {
+            return Err(Error::msg(format!("bad TNC2 header: {header}")));
+        }
+        let dst_call = dst.split('-').next().unwrap_or_default();
+        Ok(Self {
+            src: src.to_string(),
+            dst: dst.to_string(),
+            path: rest.map(str::to_string).collect(),
+            data: AprsData::parse(dst_call, &data[colon + 1..])?,
+        })
+    }
+}
+
+/// Decoded APRS information field.
+#[derive(Clone, Debug, PartialEq, serde::Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AprsData {
+    /// Position report.
+    Position(Position),
+
+    /// Status report.
+    Status {
+        /// Timestamp, as sent. E.g. `092345z`.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        timestamp: Option<String>,
+        /// Status text.
+        text: String,
+    },
+
+    /// Message.
+    Message {
+        /// Addressee.
+        addressee: String,
+        /// Message text.
+        text: String,
+        /// Message ID, if the sender wants an ack.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        id: Option<String>,
+    },
+
+    /// Message acknowledgement.
+    Ack {
+        /// Addressee.
+        addressee: String,
+        /// ID of the acknowledged message.
+        id: String,
+    },
+
+    /// Message rejection.
+    Rej {
+        /// Addressee.
+        addressee: String,
+        /// ID of the rejected message.
+        id: String,
+    },
+
+    /// Object report.
+    Object {
+        /// Object name.
+        name: String,
+        /// False if the object has been killed.
+        live: bool,
+        /// Object position.
+        position: Position,
+    },
+
+    /// Item report.
+    Item {
+        /// Item name.
+        name: String,
+        /// False if the item has been killed.
+        live: bool,
+        /// Item position.
+        position: Position,
+    },
+
+    /// Positionless weather report.
+    Weather {
+        /// Timestamp, as sent. `MMDDHHMM`.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        timestamp: Option<String>,
+        /// Weather data.
+        weather: Weather,
+        /// Comment, usually identifying the weather station software.
+        comment: String,
+    },
+
+    /// Telemetry report.
+    Telemetry {
+        /// Sequence number. Usually numeric, but can be e.g. `MIC`.
+        sequence: String,
+        /// Analog values.
+        analog: Vec<f64>,
+        /// Digital values, MSB first.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        digital: Option<u8>,
+        /// Comment.
+        comment: String,
+    },
+
+    /// Third party packet, e.g. from an igate.
+    ThirdParty {
+        /// The inner packet.
+        packet: Box<AprsPacket>,
+    },
+
+    /// Unsupported packet type.
+    Unknown {
+        /// Data type identifier.
+        data_type: char,
+        /// Rest of the information field.
+        text: String,
+    },
+}
+
+/// Position report.
+#[derive(Clone, Debug, Default, PartialEq, serde::Serialize)]
+pub struct Position {
+    /// Latitude, in degrees. Positive is north.
+    pub latitude: f64,
+
+    /// Longitude, in degrees. Positive is east.
+    pub longitude: f64,
+
+    /// Symbol table identifier, or overlay character.
+    pub symbol_table: char,
+
+    /// Symbol code.
+    pub symbol_code: char,
+
+    /// Timestamp, as sent. E.g. `092345z`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<String>,
+
+    /// True if the station supports APRS messaging.
+    pub messaging: bool,
+
+    /// Number of digits of position ambiguity. Only for uncompressed and
+    /// Mic-E positions.
+    pub ambiguity: u8,
+
+    /// Course, in degrees.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub course: Option<u16>,
+
+    /// Speed, in knots.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub speed: Option<f64>,
+
+    /// Altitude, in feet.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub altitude: Option<f64>,
+
+    /// Weather, for the weather station symbol.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub weather: Option<Weather>,
+
+    /// Mic-E message, like `Off Duty` or `Emergency`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mic_e_message: Option<String>,
+
+    /// Comment.
+    pub comment: String,
+}
+
+/// Weather report.
+#[derive(Clone, Debug, Default, PartialEq, serde::Serialize)]
+pub struct Weather {
+    /// Wind direction, in degrees.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub wind_direction: Option<u16>,
+
+    /// Sustained one-minute wind speed, in mph.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub wind_speed: Option<f64>,
+
+    /// Peak wind speed in the last 5 minutes, in mph.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub wind_gust: Option<f64>,
+
+    /// Temperature, in degrees Fahrenheit.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f64>,
+
+    /// Rain in the last hour, in inches.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rain_1h: Option<f64>,
+
+    /// Rain in the last 24 hours, in inches.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rain_24h: Option<f64>,
+
+    /// Rain since midnight, in inches.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rain_since_midnight: Option<f64>,
+
+    /// Relative humidity, in percent.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub humidity: Option<u8>,
+
+    /// Barometric pressure, in millibar.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pressure: Option<f64>,
+
+    /// Luminosity, in W/m².
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub luminosity: Option<u16>,
+}
+
+fn text(b: &[u8]) -> String {
+    String::from_utf8_lossy(b).into_owned()
+}
+
+/// Parse fixed width decimal number.
+fn num(b: &[u8]) -> Option<f64> {
+    std::str::from_utf8(b).ok()?.trim().parse().ok()
+}
+
+/// Decode base 91 number, as used for compressed positions.
+fn base91(b: &[u8]) -> Result<u32> {
+    b.iter().try_fold(0u32, |acc, &c| {
+        if !(33..=123).contains(&c) {
+            return Err(Error::msg(format!("invalid base91 character {c:#04x}")));
+        }
+        Ok(acc * 91 + u32::from(c - 33))
+    })
+}
+
+/// Split off timestamp, if there is one.
+fn timestamp(b: &[u8]) -> (Option<String>, &[u8]) {
+    if b.len() >= 7 && b[..6].iter().all(u8::is_ascii_digit) && b"zh/".contains(&b[6]) {
+        (Some(text(&b[..7])), &b[7..])
+    } else {
+        (None, b)
+    }
+}
+
+impl AprsData {
+    /// Parse an information field.
+    ///
+    /// The destination callsign, without SSID, is needed for Mic-E.
+    pub fn parse(dst: &str, info: &[u8]) -> Result<Self> {
+        let Some((&t, rest)) = info.split_first() else {
+            return Err(Error::msg("empty APRS information field"));
+        };
+        Ok(match t {
+            b'!' | b'=' => Self::Position(parse_position(rest, t == b'=', None)?),
+            b'/' | b'@' => {
+                if rest.len() < 7 {
+                    return Err(Error::msg("APRS position with truncated timestamp"));
+                }
+                Self::Position(parse_position(
+                    &rest[7..],
+                    t == b'@',
+                    Some(text(&rest[..7])),
+                )?)
+            }
+            b'`' | b'\'' => Self::Position(parse_mic_e(dst, rest)?),
+            b'>' => {
+                let (timestamp, rest) = timestamp(rest);
+                Self::Status {
+                    timestamp,
+                    text: text(rest),
+                }
+            }
+            b':' => parse_message(rest)?,
+            b';' => parse_object(rest)?,
+            b')' => parse_item(rest)?,
+            b'_' => parse_positionless_weather(rest)?,
+            b'T' => parse_telemetry(rest)?,
+            b'}' => Self::ThirdParty {
+                packet: Box::new(AprsPacket::from_tnc2(rest)?),
+            },
+            other => Self::Unknown {
+                data_type: char::from(other),
+                text: text(rest),
+            },
+        })
+    }
+}
+
+/// Parse uncompressed or compressed position, followed by comment.
+fn parse_position(b: &[u8], messaging: bool, timestamp: Option<String>) -> Result<Position> {
+    let mut pos = if b.first().is_some_and(u8::is_ascii_digit) {
+        parse_uncompressed(b)?
+    } else {
+        parse_compressed(b)?
+    };
+    pos.messaging = messaging;
+    pos.timestamp = timestamp;
+    Ok(pos)
+}
+
+/// Parse `DDMM.hhN/DDDMM.hhW$` followed by comment.
+fn parse_uncompressed(b: &[u8]) -> Result<Position> {
+    const LEN: usize = 19;
+    if b.len() < LEN || b[4] != b'.' || b[14] != b'.' {
+        return Err(Error::msg(format!(
+            "invalid APRS uncompressed position: {}",
+            text(b)
+        )));
+    }
+    // Ambiguity is expressed by replacing digits, from the right, with
+    // spaces.
+    let ambiguity = b[2..7].iter().filter(|&&c| c == b' ').count() as u8;
+    let fixed = |b: &[u8]| -> Vec<u8> {
+        b.iter()
+            .map(|&c| if c == b' ' { b'0' } else { c })
+            .collect()
+    };
+    let lat = fixed(&b[0..7]);
+    let lon = fixed(&b[9..17]);
+    let bad = || Error::msg(format!("invalid APRS position: {}", text(&b[..LEN])));
+    let lat = num(&lat[..2]).ok_or_else(bad)? + num(&lat[2..]).ok_or_else(bad)? / 60.0;
+    let lon = num(&lon[..3]).ok_or_else(bad)? + num(&lon[3..]).ok_or_else(bad)? / 60.0;
+    let latitude = match b[7] {
+        b'N' => lat,
+        b'S' => -lat,
+        _ => return Err(bad()),
+    };
+    let longitude = match b[17] {
+        b'E' => lon,
+        b'W' => -lon,
+        _ => return Err(bad()),
+    };
+    let mut pos = Position {
+        latitude,
+        longitude,
+        symbol_table: char::from(b[8]),
+        symbol_code: char::from(b[18]),
+        ambiguity,
+        ..Default::default()
+    };
+    parse_comment(&mut pos, &b[LEN..], true);
+    Ok(pos)
+}
+
+/// Parse compressed position followed by comment.
+fn parse_compressed(b: &[u8]) -> Result<Position> {
+    const LEN: usize = 13;
+    if b.len() < LEN {
+        return Err(Error::msg(format!(
+            "invalid APRS compressed position: {}",
+            text(b)
+        )));
+    }
+    let mut pos = Position {
+        latitude: 90.0 - f64::from(base91(&b[1..5])?) / 380_926.0,
+        longitude: -180.0 + f64::from(base91(&b[5..9])?) / 190_463.0,
+        symbol_table: char::from(b[0]),
+        symbol_code: char::from(b[9]),
+        ..Default::default()
+    };
+    let (c, s, t) = (b[10], b[11], b[12]);
+    if c != b' ' {
+        let cs = base91(&[c, s])?;
+        if t.wrapping_sub(33) & 0x18 == 0x10 {
+            pos.altitude = Some(1.002f64.powi(cs as i32));
+        } else if c != b'{' && c <= b'z' {
+            pos.course = Some(u16::from(c - 33) * 4);
+            pos.speed = Some(1.08f64.powi(i32::from(s - 33)) - 1.0);
+        }
+    }
+    parse_comment(&mut pos, &b[LEN..], false);
+    Ok(pos)
+}
+
+/// Decode Mic-E destination character into digit, message bit, and the
+/// N/+100/W flag.
+fn mic_e_dst(c: u8) -> Result<(u8, Option<bool>, bool)> {
+    // Message bit is Some(true) for standard, Some(false) for custom.
+    Ok(match c {
+        b'0'..=b'9' => (c - b'0', None, false),
+        b'A'..=b'J' => (c - b'A', Some(false), false),
+        b'K' => (0, Some(false), false),
+        b'L' => (0, None, false),
+        b'P'..=b'Y' => (c - b'P', Some(true), true),
+        b'Z' => (0, Some(true), true),
+        _ => {
+            return Err(Error::msg(format!(
+                "invalid Mic-E destination character {:?}",
+                char::from(c)
+            )));
+        }
+    })
+}
+
+fn mic_e_message(bits: [Option<bool>; 3]) -> String {
+    let n = bits
+        .iter()
+        .fold(0, |acc, b| (acc << 1) | usize::from(b.is_some()));
+    if n == 0 {
+        return "Emergency".to_string();
+    }
+    let standard = bits.iter().flatten().all(|&s| s);
+    let custom = bits.iter().flatten().all(|&s| !s);
+    if standard {
+        [
+            "",
+            "Priority",
+            "Special",
+            "Committed",
+            "Returning",
+            "In Service",
+            "En Route",
+            "Off Duty",
+        ][n]
+            .to_string()
+    } else if custom {
+        format!("Custom-{}", 7 - n)
+    } else {
+        "Unknown".to_string()
+    }
+}
+
+/// Parse Mic-E, where the latitude is encoded in the destination address.
+fn parse_mic_e(dst: &str, b: &[u8]) -> Result<Position> {
+    let dst = dst.as_bytes();
+    if dst.len() != 6 || b.len() < 8 {
+        return Err(Error::msg("truncated Mic-E packet"));
+    }
+    let mut digits = [0u8; 6];
+    let mut msg = [None; 3];
+    let mut flags = [false; 6];
+    let mut ambiguity = 0;
+    for (i, &c) in dst.iter().enumerate() {
+        let (d, m, f) = mic_e_dst(c)?;
+        digits[i] = d;
+        flags[i] = f;
+        if i < 3 {
+            msg[i] = m;
+        }
+        if matches!(c, b'K' | b'L' | b'Z') {
+            ambiguity += 1;
+        }
+    }
+    let lat = f64::from(digits[0] * 10 + digits[1])
+        + (f64::from(digits[2] * 10 + digits[3]) + f64::from(digits[4] * 10 + digits[5]) / 100.0)
+            / 60.0;
+    let latitude = if flags[3] { lat } else { -lat };
+
+    let v: Vec<i32> = b[..6].iter().map(|&c| i32::from(c) - 28).collect();
+    let mut deg = v[0] + if flags[4] { 100 } else { 0 };
+    if (180..=189).contains(&deg) {
+        deg -= 80;
+    } else if (190..=199).contains(&deg) {
+        deg -= 190;
+    }
+    let mut min = v[1];
+    if min >= 60 {
+        min -= 60;
+    }
+    let lon = f64::from(deg) + (f64::from(min) + f64::from(v[2]) / 100.0) / 60.0;
+    let longitude = if flags[5] { -lon } else { lon };
+
+    let mut speed = v[3] * 10 + v[4] / 10;
+    if speed >= 800 {
+        speed -= 800;
+    }
+    let mut course = (v[4] % 10) * 100 + v[5];
+    if course >= 400 {
+        course -= 400;
+    }
+
+    let mut pos = Position {
+        latitude,
+        longitude,
+        symbol_code: char::from(b[6]),
+        symbol_table: char::from(b[7]),
+        ambiguity,
+        course: u16::try_from(course).ok(),
+        speed: Some(f64::from(speed)),
+        mic_e_message: Some(mic_e_message(msg)),
+        ..Default::default()
+    };
+
+    // Altitude is `xxx}`, optionally after a one byte radio type.
+    let mut comment = &b[8..];
+    for skip in [0, 1] {
+        if comment.len() >= skip + 4
+            && comment[skip + 3] == b'}'
+            && let Ok(alt) = base91(&comment[skip..skip + 3])
+        {
+            pos.altitude = Some((f64::from(alt) - 10_000.0) * FEET_PER_METER);
+            comment = &comment[skip + 4..];
+            break;
+        }
+    }
+    pos.comment = text(comment);
+    Ok(pos)
+}
+
+/// Parse the comment, extracting course/speed, weather, and altitude.
+fn parse_comment(pos: &mut Position, b: &[u8], extension: bool) {
+    let mut b = b;
+    let ext = |b: &[u8]| {
+        b.len() >= 7
+            && b[3] == b'/'
+            && b[..3]
+                .iter()
+                .chain(&b[4..7])
+                .all(|&c| c.is_ascii_digit() || c == b'.' || c == b' ')
+    };
+    if pos.symbol_code == '_' {
+        let mut w = Weather::default();
+        if ext(b) {
+            w.wind_direction = num(&b[..3]).map(|v| v as u16);
+            w.wind_speed = num(&b[4..7]);
+            b = &b[7..];
+        }
+        b = parse_weather(b, &mut w);
+        pos.weather = Some(w);
+    } else if extension && ext(b) {
+        pos.course = num(&b[..3]).map(|v| v as u16);
+        pos.speed = num(&b[4..7]);
+        b = &b[7..];
+    }
+    let mut comment = text(b);
+    if let Some(i) = comment.find("/A=")
+        && let Some(alt) = comment.get(i + 3..i + 9).and_then(|a| a.parse().ok())
+    {
+        pos.altitude = Some(alt);
+        comment.replace_range(i..i + 9, "");
+    }
+    pos.comment = comment;
+}
+
+/// Parse weather fields, returning the rest.
+fn parse_weather<'a>(b: &'a [u8], w: &mut Weather) -> &'a [u8] {
+    let mut b = b;
+    while let Some(&c) = b.first() {
+        let width = match c {
+            b'g' | b't' | b'r' | b'p' | b'P' | b'L' | b'l' | b's' | b'#' => 3,
+            b'h' => 2,
+            b'b' => 5,
+            _ => break,
+        };
+        let Some(v) = b.get(1..=width) else {
+            break;
+        };
+        let valid = |c: &u8| c.is_ascii_digit() || b" .-".contains(c);
+        if !v.iter().all(valid) {
+            break;
+        }
+        let val = num(v);
+        match c {
+            b'g' => w.wind_gust = val,
+            b't' => w.temperature = val,
+            b'r' => w.rain_1h = val.map(|v| v / 100.0),
+            b'p' => w.rain_24h = val.map(|v| v / 100.0),
+            b'P' => w.rain_since_midnight = val.map(|v| v / 100.0),
+            b'h' => w.humidity = val.map(|v| if v == 0.0 { 100 } else { v as u8 }),
+            b'b' => w.pressure = val.map(|v| v / 10.0),
+            b'L' => w.luminosity = val.map(|v| v as u16),
+            b'l' => w.luminosity = val.map(|v| v as u16 + 1000),
+            // Snow and raw rain counter.
+            _ => {}
+        }
+        b = &b[width + 1..];
+    }
+    b
+}
+
+/// Parse `_MMDDHHMMcxxxsxxx...`.
+fn parse_positionless_weather(b: &[u8]) -> Result<AprsData> {
+    if b.len() < 8 || !b[..8].iter().all(u8::is_ascii_digit) {
+        return Err(Error::msg(format!(
+            "invalid APRS positionless weather: {}",
+            text(b)
+        )));
+    }
+    let timestamp = Some(text(&b[..8]));
+    let mut b = &b[8..];
+    let mut w = Weather::default();
+    if b.len() >= 4 && b[0] == b'c' {
+        w.wind_direction = num(&b[1..4]).map(|v| v as u16);
+        b = &b[4..];
+    }
+    if b.len() >= 4 && b[0] == b's' {
+        w.wind_speed = num(&b[1..4]);
+        b = &b[4..];
+    }
+    let rest = parse_weather(b, &mut w);
+    Ok(AprsData::Weather {
+        timestamp,
+        weather: w,
+        comment: text(rest),
+    })
+}
+
+/// Parse `:ADDRESSEE:text{id`.
+fn parse_message(b: &[u8]) -> Result<AprsData> {
+    if b.len() < 10 || b[9] != b':' {
+        return Err(Error::msg(format!("invalid APRS message: {}", text(b))));
+    }
+    let addressee = text(&b[..9]).trim_end().to_string();
+    let msg = text(&b[10..]);
+    for (prefix, ack) in [("ack", true), ("rej", false)] {
+        if let Some(id) = msg.strip_prefix(prefix)
+            && !id.is_empty()
+            && id.len() <= 5
+            && !id.contains(' ')
+        {
+            let id = id.trim_end_matches('}').to_string();
+            return Ok(if ack {
+                AprsData::Ack { addressee, id }
+            } else {
+                AprsData::Rej { addressee, id }
+            });
+        }
+    }
+    let (text, id) = match msg.rsplit_once('{') {
+        Some((text, id)) => (text.to_string(), Some(id.to_string())),
+        None => (msg, None),
+    };
+    Ok(AprsData::Message {
+        addressee,
+        text,
+        id,
+    })
+}
+
+/// Parse `;NAME_____*DDHHMMzPOSITION`.
+fn parse_object(b: &[u8]) -> Result<AprsData> {
+    if b.len() < 17 || !matches!(b[9], b'*' | b'_') {
+        return Err(Error::msg(format!("invalid APRS object: {}", text(b))));
+    }
+    Ok(AprsData::Object {
+        name: text(&b[..9]).trim_end().to_string(),
+        live: b[9] == b'*',
+        position: parse_position(&b[17..], false, Some(text(&b[10..17])))?,
+    })
+}
+
+/// Parse `)NAME!POSITION`.
+fn parse_item(b: &[u8]) -> Result<AprsData> {
+    let Some(end) = b
+        .iter()
+        .take(10)
+        .skip(3)
+        .position(|&c| c == b'!' || c == b'_')
+        .map(|p| p + 3)
+    else {
+        return Err(Error::msg(format!("invalid APRS item: {}", text(b))));
+    };
+    Ok(AprsData::Item {
+        name: text(&b[..end]),
+        live: b[end] == b'!',
+        position: parse_position(&b[end + 1..], false, None)?,
+    })
+}
+
+/// Parse `T#sss,aaa,aaa,aaa,aaa,aaa,bbbbbbbb`.
+fn parse_telemetry(b: &[u8]) -> Result<AprsData> {
+    let s = text(b);
+    let Some(s) = s.strip_prefix('#') else {
+        return Err(Error::msg(format!("invalid APRS telemetry: {s}")));
+    };
+    let mut parts = s.splitn(7, ',');
+    let sequence = parts.next().unwrap_or_default().to_string();
+    let mut analog = Vec::with_capacity(5);
+    let mut digital = None;
+    let mut comment = String::new();
+    for (n, part) in parts.enumerate() {
+        if n < 5 {
+            analog.push(
+                part.trim()
+                    .parse()
+                    .map_err(|e| Error::wrap(e, format!("invalid APRS telemetry value {part}")))?,
+            );
+        } else {
+            let bits = part.get(..8).unwrap_or(part);
+            digital = u8::from_str_radix(bits, 2).ok();
+            comment = part.get(8..).unwrap_or_default().to_string();
+        }
+    }
+    Ok(AprsData::Telemetry {
+        sequence,
+        analog,
+        digital,
+        comment,
+    })
+}
+
+/// Decode APRS packets.
+///
+/// Takes [`Ax25Frame`]s from e.g. [`Ax25Decode`](crate::ax25::Ax25Decode),
+/// and outputs [`AprsPacket`]s. Frames that are not APRS are dropped.
+#[derive(rustradio_macros::Block)]
+#[rustradio(crate, new)]
+pub struct AprsDecode {
+    #[rustradio(in)]
+    src: NCReadStream<Ax25Frame>,
+    #[rustradio(out)]
+    dst: NCWriteStream<AprsPacket>,
+}
+
+impl Block for AprsDecode {
+    fn work(&mut self) -> Result<BlockRet<'_>> {
+        loop {
+            if self.dst.remaining() == 0 {
+                return Ok(BlockRet::WaitForStream(&self.dst, 1));
+            }
+            let Some((frame, tags)) = self.src.pop() else {
+                return Ok(BlockRet::WaitForStream(&self.src, 1));
+            };
+            match AprsPacket::from_frame(&frame) {
+                Ok(packet) => self.dst.push(packet, tags),
+                Err(e) => debug!("AprsDecode: not decoding {frame}: {e}"),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(s: &str) -> AprsData {
+        AprsPacket::from_tnc2(s.as_bytes())
+            .unwrap_or_else(|e| panic!("{s}: {e}"))
+            .data
+    }
+
+    fn position(s: &str) -> Position {
+        match parse(s) {
+            AprsData::Position(p) => p,
+            other => panic!("{s}: not a position: {other:?}"),
+        }
+    }
+
+    fn assert_near(got: f64, want: f64) {
+        assert!((got - want).abs() < 0.0001, "got {got}, want {want}");
+    }
+
+    #[test]
+    fn uncompressed() {
+        let p = position("N0CALL>APRS:!4903.50N/07201.75W-Test 001234 /A=001234");
+        assert_near(p.latitude, 49.058_333);
+        assert_near(p.longitude, -72.029_167);
+        assert_eq!((p.symbol_table, p.symbol_code), ('/', '-'));
+        assert!(!p.messaging);
+        assert_eq!(p.altitude, Some(1234.0));
+        assert_eq!(p.comment, "Test 001234 ");
+
+        let p = position("N0CALL>APRS:@092345z4903.50S\\07201.75E>088/036");
+        assert_near(p.latitude, -49.058_333);
+        assert_near(p.longitude, 72.029_167);
+        assert!(p.messaging);
+        assert_eq!(p.timestamp.as_deref(), Some("092345z"));
+        assert_eq!(p.course, Some(88));
+        assert_eq!(p.speed, Some(36.0));
+
+        let p = position("N0CALL>APRS:!4903.  N/07201.  W-");
+        assert_eq!(p.ambiguity, 2);
+        assert_near(p.latitude, 49.05);
+    }
+
+    #[test]
+    fn compressed() {
+        let p = position("N0CALL>APRS:=/5L!!<*e7>7P[");
+        assert_near(p.latitude, 49.5);
+        assert_near(p.longitude, -72.75);
+        assert_eq!(p.symbol_code, '>');
+        assert_eq!(p.course, Some(88));
+        assert!((p.speed.unwrap() - 36.2).abs() < 0.1);
+
+        let p = position("N0CALL>APRS:!/5L!!<*e7OS]S");
+        assert!((p.altitude.unwrap() - 10004.0).abs() < 1.0);
+    }
+
+    #[test]
+    fn mic_e() {
+        let p = position("N0CALL>S32U6T:`d#f\x1e\x1eO>/]\"4T}Hello");
+        assert_near(p.latitude, 33.427_333);
+        assert_near(p.longitude, -72.129);
+        assert_eq!(p.speed, Some(20.0));
+        assert_eq!(p.course, Some(251));
+        assert_eq!((p.symbol_table, p.symbol_code), ('/', '>'));
+        assert_eq!(p.mic_e_message.as_deref(), Some("Returning"));
+        assert!((p.altitude.unwrap() - 61.0 * FEET_PER_METER).abs() < 0.01);
+        assert_eq!(p.comment, "Hello");
+    }
+
+    #[test]
+    fn weather() {
+        let p =
+            position("N0CALL>APRS:!4903.50N/07201.75W_220/004g005t-07r001p010P100h00b09900wRSW");
+        let w = p.weather.unwrap();
+        assert_eq!(w.wind_direction, Some(220));
+        assert_eq!(w.wind_speed, Some(4.0));
+        assert_eq!(w.wind_gust, Some(5.0));
+        assert_eq!(w.temperature, Some(-7.0));
+        assert_eq!(w.rain_1h, Some(0.01));
+        assert_eq!(w.rain_24h, Some(0.1));
+        assert_eq!(w.rain_since_midnight, Some(1.0));
+        assert_eq!(w.humidity, Some(100));
+        assert_eq!(w.pressure, Some(990.0));
+        assert_eq!(p.comment, "wRSW");
+
+        assert_eq!(
+            parse("N0CALL>APRS:_10090556c220s004g...t077h50wRSW"),
+            AprsData::Weather {
+                timestamp: Some("10090556".to_string()),
+                weather: Weather {
+                    wind_direction: Some(220),
+                    wind_speed: Some(4.0),
+                    temperature: Some(77.0),
+                    humidity: Some(50),
+                    ..Default::default()
+                },
+                comment: "wRSW".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn status() {
+        assert_eq!(
+            parse("N0CALL>APRS:>092345zNet Control Center"),
+            AprsData::Status {
+                timestamp: Some("092345z".to_string()),
+                text: "Net Control Center".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn messages() {
+        assert_eq!(
+            parse("N0CALL>APRS::WU2Z     :Testing{003"),
+            AprsData::Message {
+                addressee: "WU2Z".to_string(),
+                text: "Testing".to_string(),
+                id: Some("003".to_string()),
+            }
+        );
+        assert_eq!(
+            parse("N0CALL>APRS::KB2ICI-14:ack003"),
+            AprsData::Ack {
+                addressee: "KB2ICI-14".to_string(),
+                id: "003".to_string(),
+            }
+        );
+        assert_eq!(
+            parse("N0CALL>APRS::KB2ICI-14:rej003"),
+            AprsData::Rej {
+                addressee: "KB2ICI-14".to_string(),
+                id: "003".to_string(),
+            }
+        );
+        assert_eq!(
+            parse("N0CALL>APRS::KB2ICI-14:acknowledged, thanks"),
+            AprsData::Message {
+                addressee: "KB2ICI-14".to_string(),
+                text: "acknowledged, thanks".to_string(),
+                id: None,
+            }
+        );
+    }
+
+    #[test]
+    fn object_and_item() {
+        let AprsData::Object {
+            name,
+            live,
+            position,
+        } = parse("N0CALL>APRS:;LEADER   _092345z4903.50N/07201.75W>088/036")
+        else {
+            panic!("not an object");
+        };
+        assert_eq!(name, "LEADER");
+        assert!(!live);
+        assert_eq!(position.timestamp.as_deref(), Some("092345z"));
+        assert_eq!(position.course, Some(88));
+
+        let AprsData::Item {
+            name,
+            live,
+            position,
+        } = parse("N0CALL>APRS:)AID #2!4903.50N/07201.75WA")
+        else {
+            panic!("not an item");
+        };
+        assert_eq!(name, "AID #2");
+        assert!(live);
+        assert_eq!(position.symbol_code, 'A');
+    }
+
+    #[test]
+    fn telemetry() {
+        assert_eq!(
+            parse("N0CALL>APRS:T#005,199,000,255,073,123,01101001 hello"),
+            AprsData::Telemetry {
+                sequence: "005".to_string(),
+                analog: vec![199.0, 0.0, 255.0, 73.0, 123.0],
+                digital: Some(0b0110_1001),
+                comment: " hello".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn third_party() {
+        let AprsData::ThirdParty { packet } =
+            parse("N0CALL>APRS,TCPIP*:}M0THC-1>APZ001,TCPIP,N0CALL*:>hello")
+        else {
+            panic!("not third party");
+        };
+        assert_eq!(packet.src, "M0THC-1");
+        assert_eq!(packet.path, &["TCPIP", "N0CALL*"]);
+        assert_eq!(
+            packet.data,
+            AprsData::Status {
+                timestamp: None,
+                text: "hello".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn bad() {
+        for s in [
+            "N0CALL>APRS:",
+            "N0CALL>APRS:!4903.50N/07201.75",
+            "N0CALL>APRS:!4903.50X/07201.75W-",
+            "N0CALL>APRS:=/5L!!<*e7",
+            "N0CALL>APRS::WU2Z:Testing",
+            "N0CALL>APRS:;LEADER",
+            "N0CALL>APRS:`d#f",
+            "N0CALL>APRS:}garbage",
+        ] {
+            assert!(AprsPacket::from_tnc2(s.as_bytes()).is_err(), "{s}");
+        }
+    }
+
+    #[test]
+    fn from_frame() -> Result<()> {
+        let frame = Ax25Frame::ui(
+            "M0THC-2".parse()?,
+            "APZ001".parse()?,
+            vec!["WIDE1-1*".parse()?],
+            b"!4903.50N/07201.75W-".to_vec(),
+        );
+        let p = AprsPacket::from_frame(&frame)?;
+        assert_eq!(p.src, "M0THC-2");
+        assert_eq!(p.dst, "APZ001");
+        assert_eq!(p.path, &["WIDE1-1*"]);
+        let json = serde_json::to_string(&p).unwrap();
+        assert!(
+            json.starts_with(r#"{"src":"M0THC-2","dst":"APZ001","path":["WIDE1-1*"],"type":"position","latitude":49.05833"#),
+            "{json}"
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,436 @@
+/*! NOAA APT weather satellite image decoding.
+
+NOAA 15, 18, and 19 send Automatic Picture Transmission (APT) images on
+137MHz, as FM with a 2400Hz AM subcarrier. The subcarrier carries 4160 words
+per second, two lines per second of 2080 words each. A line is two images,
+visible and infrared, side by side, each preceded by a sync pattern:
+
+| Words | Content                             |
+|-------|-------------------------------------|
+| 39    | Sync A, seven cycles of 1040Hz      |
+| 47    | Space A, with minute markers        |
+| 909   | Image A                             |
+| 45    | Telemetry A                         |
+| 39    | Sync B, seven pulses at 832Hz       |
+| 47    | Space B                             |
+| 909   | Image B                             |
+| 45    | Telemetry B                         |
+
+[`AptDecode`] takes FM demodulated audio, and outputs aligned lines as
+greyscale pixels, which [`ImageSink`] can write to a file.
+
+## Example
+
+```
+use rustradio::graph::{Graph, GraphRunner};
+use rustradio::blocks::*;
+# fn main() -> rustradio::Result<()> {
+let samp_rate = 11_025.0;
+let (_, prev) = rustradio::stream::new_stream();
+let mut g = Graph::new();
+let prev = rustradio::blockchain![g, prev, AptDecode::new(prev, samp_rate)?];
+g.add(Box::new(ImageSink::new(prev, "pass.pgm")?));
+# Ok(())
+# }
+```
+
+Reference: NOAA KLM User's Guide, section 4.2.
+
+[`ImageSink`]: crate::blocks::ImageSink
+*/
+use log::{debug, info};
+
+use crate::block::{Block, BlockRet};
+use crate::fir::Fir;
+use crate::stream::{NCReadStream, NCWriteStream, ReadStream, Tag, TagValue};
+use crate::window::WindowType;
+use crate::{Error, Float, Result};
+
+/// Words per second.
+pub const WORD_RATE: Float = 4160.0;
+
+/// Words per line.
+pub const LINE_WORDS: usize = 2080;
+
+/// Subcarrier frequency.
+pub const CARRIER: Float = 2400.0;
+
+/// Tag on each line, with the sync correlation, from -1.0 to 1.0.
+///
+/// Lines where the sync was not found are placed where the sync was
+/// expected, and tagged with the correlation found there.
+pub const SYNC_TAG: &str = "Apt:sync";
+
+/// Sync A: seven cycles of 1040Hz, i.e. two words high, two words low.
+pub const SYNC_A: [u8; 39] = [
+    0, 0, 0, 0, 1, 1, 0, 0, 1, 1, 0, 0, 1, 1, 0, 0, 1, 1, 0, 0, 1, 1, 0, 0, 1, 1, 0, 0, 1, 1, 0, 0,
+    0, 0, 0, 0, 0, 0, 0,
+];
+
+/// Sync B: seven pulses at 832Hz, i.e. three words high, two words low.
+pub const SYNC_B: [u8; 39] = [
+    0, 0, 0, 0, 1, 1, 1, 0, 0, 1, 1, 1, 0, 0, 1, 1, 1, 0, 0, 1, 1, 1, 0, 0, 1, 1, 1, 0, 0, 1, 1, 1,
+    0, 0, 1, 1, 1, 0, 0,
+];
+
+// Offset from sync A to sync B.
+const SYNC_B_OFFSET: usize = LINE_WORDS / 2;
+
+// Correlation needed to count as sync.
+const SYNC_THRESHOLD: Float = 0.5;
+
+// Once locked, sync is searched for this many words either side of where
+// it's expected.
+const MARGIN: usize = 8;
+
+// Lose lock after this many lines without sync.
+const MAX_MISSES: usize = 60;
+
+// Pixel values of the sync pattern low and high.
+const BLACK: Float = 11.0;
+const WHITE: Float = 244.0;
+
+// Correlation between words and a pattern.
+fn correlate(words: &[Float], pattern: &[u8]) -> Float {
+    let n = pattern.len() as Float;
+    let wmean = words.iter().sum::<Float>() / n;
+    let pmean = pattern.iter().map(|&p| Float::from(p)).sum::<Float>() / n;
+    let (mut num, mut wvar, mut pvar) = (0.0, 0.0, 0.0);
+    for (&w, &p) in words.iter().zip(pattern) {
+        let (w, p) = (w - wmean, Float::from(p) - pmean);
+        num += w * p;
+        wvar += w * w;
+        pvar += p * p;
+    }
+    if wvar == 0.0 {
+        return 0.0;
+    }
+    num / (wvar * pvar).sqrt()
+}
+
+// Sync correlation of a line starting at `words[0]`.
+fn sync_score(words: &[Float]) -> Float {
+    let a = correlate(&words[..SYNC_A.len()], &SYNC_A);
+    let b = correlate(&words[SYNC_B_OFFSET..SYNC_B_OFFSET + SYNC_B.len()], &SYNC_B);
+    (a + b) / 2.0
+}
+
+// Find the best sync in `words`, starting at positions `0..n`.
+fn find_sync(words: &[Float], n: usize) -> (usize, Float) {
+    (0..n)
+        .map(|p| (p, sync_score(&words[p..])))
+        .fold(
+            (0, Float::MIN),
+            |best, x| if x.1 > best.1 { x } else { best },
+        )
+}
+
+/// NOAA APT decoder.
+///
+/// Takes FM demodulated audio, and outputs lines of [`LINE_WORDS`] greyscale
+/// pixels, starting with sync A.
+///
+/// The 2400Hz subcarrier is AM demodulated, low pass filtered, and
+/// resampled to [`WORD_RATE`]. Lines are aligned using the correlation with
+/// both sync A and B. Until sync is found, nothing is output. Once found, a
+/// line is output every 2080 words, following small timing drifts, and if
+/// sync is lost for a while, it's searched for again.
+///
+/// Pixel values are scaled so that the sync pattern is approximately 11
+/// (black) and 244 (white), averaged over lines.
+#[derive(rustradio_macros::Block)]
+#[rustradio(crate)]
+pub struct AptDecode {
+    #[rustradio(in)]
+    src: ReadStream<Float>,
+    #[rustradio(out)]
+    dst: NCWriteStream<Vec<u8>>,
+
+    // AM demodulation.
+    cos: Float,
+    sin2: Float,
+    last: Float,
+
+    // Low pass filter and resampler.
+    fir: Fir<Float>,
+    ntaps: usize,
+    env: Vec<Float>,
+    step: f64,
+    t: f64,
+
+    // Line sync.
+    words: Vec<Float>,
+    locked: bool,
+    misses: usize,
+    levels: Option<(Float, Float)>,
+    lines: u64,
+}
+
+impl AptDecode {
+    /// Create a new APT decoder.
+    ///
+    /// # Errors
+    ///
+    /// Errors if the sample rate is too low for the subcarrier.
+    pub fn new(src: ReadStream<Float>, samp_rate: Float) -> Result<(Self, NCReadStream<Vec<u8>>)> {
+        let min_rate = 2.0 * (CARRIER + WORD_RATE / 2.0);
+        if samp_rate < min_rate {
+            return Err(Error::msg(format!(
+                "AptDecode: sample rate must be at least {min_rate}, got {samp_rate}"
+            )));
+        }
+        let taps = crate::fir::low_pass(
+            samp_rate,
+            WORD_RATE / 2.0,
+            WORD_RATE / 5.0,
+            &WindowType::Hamming,
+        );
+        let ntaps = taps.len();
+        let phi = 2.0 * std::f64::consts::PI as Float * CARRIER / samp_rate;
+        let (dst, dr) = crate::stream::new_nocopy_stream();
+        Ok((
+            Self {
+                src,
+                dst,
+                cos: phi.cos(),
+                sin2: phi.sin().powi(2),
+                last: 0.0,
+                fir: Fir::new(&taps),
+                ntaps,
+                env: Vec::new(),
+                step: f64::from(samp_rate) / f64::from(WORD_RATE),
+                t: (ntaps - 1) as f64,
+                words: Vec::new(),
+                locked: false,
+                misses: 0,
+                levels: None,
+                lines: 0,
+            },
+            dr,
+        ))
+    }
+
+    // AM demodulate, using two consecutive samples of the subcarrier.
+    fn envelope(&mut self, s: Float) -> Float {
+        let p = self.last;
+        self.last = s;
+        ((s * s + p * p - 2.0 * s * p * self.cos) / self.sin2)
+            .max(0.0)
+            .sqrt()
+    }
+
+    // Filter and resample the envelope into words.
+    fn resample(&mut self) {
+        while (self.t as usize) + 1 < self.env.len() {
+            let i = self.t as usize;
+            let frac = (self.t - i as f64) as Float;
+            let a = self.fir.filter_float(&self.env[i + 1 - self.ntaps..=i]);
+            let b = self.fir.filter_float(&self.env[i + 2 - self.ntaps..=i + 1]);
+            self.words.push(a + (b - a) * frac);
+            self.t += self.step;
+        }
+        let drop = (self.t as usize + 1).saturating_sub(self.ntaps);
+        self.env.drain(..drop);
+        self.t -= drop as f64;
+    }
+
+    fn update_levels(&mut self, p: usize) {
+        let line = &self.words[p..];
+        // The trailing words of sync A are black. The first few are skipped,
+        // as the low pass filter smears the last pulse into them.
+        let low = line[34..39].iter().sum::<Float>() / 5.0;
+        // Only the 1040Hz fundamental of the pulses is left after the low
+        // pass filter. For a square wave that's 2/π of the swing, whatever
+        // the sampling phase.
+        let (re, im) = line[4..32]
+            .iter()
+            .zip(
+                [(1.0, 0.0), (0.0, -1.0), (-1.0, 0.0), (0.0, 1.0)]
+                    .iter()
+                    .cycle(),
+            )
+            .fold((0.0, 0.0), |(re, im), (w, (c, s))| (re + w * c, im + w * s));
+        let fundamental = 2.0 * re.hypot(im) / 28.0;
+        let high = low + fundamental * std::f64::consts::FRAC_PI_2 as Float;
+        self.levels = Some(match self.levels {
+            None => (low, high),
+            Some((l, h)) => (l + (low - l) * 0.1, h + (high - h) * 0.1),
+        });
+    }
+
+    fn emit(&mut self, p: usize, score: Float) {
+        if score >= SYNC_THRESHOLD {
+            self.update_levels(p);
+        }
+        let (low, high) = self.levels.unwrap_or((0.0, 1.0));
+        let scale = (WHITE - BLACK) / (high - low).max(Float::EPSILON);
+        let pixels = self.words[p..p + LINE_WORDS]
+            .iter()
+            .map(|&w| (BLACK + (w - low) * scale).round().clamp(0.0, 255.0) as u8)
+            .collect();
+        self.dst
+            .push(pixels, [Tag::new(0, SYNC_TAG, TagValue::Float(score))]);
+        self.lines += 1;
+        self.words.drain(..p + LINE_WORDS - MARGIN);
+    }
+
+    // Output a line, if there are enough words. Returns true if a line was
+    // output, or words dropped.
+    fn line(&mut self) -> bool {
+        if self.locked {
+            if self.words.len() < LINE_WORDS + 2 * MARGIN {
+                return false;
+            }
+            let (mut p, score) = find_sync(&self.words, 2 * MARGIN + 1);
+            if score >= SYNC_THRESHOLD {
+                self.misses = 0;
+            } else {
+                p = MARGIN;
+                self.misses += 1;
+                if self.misses >= MAX_MISSES {
+                    info!("AptDecode: lost sync after line {}", self.lines);
+                    self.locked = false;
+                }
+            }
+            let score = sync_score(&self.words[p..]);
+            self.emit(p, score);
+            return true;
+        }
+        if self.words.len() < 2 * LINE_WORDS {
+            return false;
+        }
+        let (p, score) = find_sync(&self.words, LINE_WORDS);
+        if score < SYNC_THRESHOLD {
+            debug!("AptDecode: no sync, best {score}");
+            self.words.drain(..LINE_WORDS);
+            return true;
+        }
+        info!(
+            "AptDecode: found sync at line {}, score {score}",
+            self.lines
+        );
+        self.locked = true;
+        self.misses = 0;
+        self.emit(p, score);
+        true
+    }
+}
+
+impl Block for AptDecode {
+    fn work(&mut self) -> Result<BlockRet<'_>> {
+        if self.dst.remaining() == 0 {
+            return Ok(BlockRet::WaitForStream(&self.dst, 1));
+        }
+        if self.line() {
+            return Ok(BlockRet::Again);
+        }
+        let (i, _tags) = self.src.read_buf()?;
+        if i.is_empty() {
+            return Ok(BlockRet::WaitForStream(&self.src, 1));
+        }
+        // Enough for about one line at a time.
+        let n = std::cmp::min(i.len(), (self.step * LINE_WORDS as f64) as usize);
+        for &s in &i.slice()[..n] {
+            let e = self.envelope(s);
+            self.env.push(e);
+        }
+        i.consume(n);
+        self.resample();
+        Ok(BlockRet::Again)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A line of words, 0.0 to 1.0, with a gradient in image A, and the
+    // line number in image B.
+    fn line(n: usize) -> Vec<Float> {
+        let level = |b: &u8| if *b == 1 { WHITE } else { BLACK } / 255.0;
+        let mut out: Vec<Float> = SYNC_A.iter().map(level).collect();
+        out.extend([0.0; 47]);
+        out.extend((0..909).map(|i| i as Float / 909.0));
+        out.extend([0.5; 45]);
+        out.extend(SYNC_B.iter().map(level));
+        out.extend([1.0; 47]);
+        out.extend([(n % 256) as Float / 255.0; 909]);
+        out.extend([0.5; 45]);
+        out
+    }
+
+    // Modulate words onto the subcarrier.
+    fn modulate(words: &[Float], samp_rate: Float) -> Vec<Float> {
+        let n = (words.len() as Float * samp_rate / WORD_RATE) as usize;
+        (0..n)
+            .map(|i| {
+                let t = i as Float / samp_rate;
+                let amp = words[(t * WORD_RATE) as usize];
+                amp * (2.0 * std::f64::consts::PI as Float * CARRIER * t).sin()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn sync_patterns() {
+        assert_eq!(SYNC_A.iter().filter(|&&b| b == 1).count(), 14);
+        assert_eq!(SYNC_B.iter().filter(|&&b| b == 1).count(), 21);
+        let words: Vec<Float> = SYNC_A.iter().map(|&b| Float::from(b)).collect();
+        assert!((correlate(&words, &SYNC_A) - 1.0).abs() < 1e-5);
+        assert!(correlate(&words, &SYNC_B) < 0.5);
+    }
+
+    #[test]
+    fn decode() -> Result<()> {
+        let samp_rate = 20_800.0;
+        // Start mid line, to need searching for sync.
+        let mut words: Vec<Float> = vec![0.3; 777];
+        for n in 0..10 {
+            words.extend(line(n));
+        }
+        let audio = modulate(&words, samp_rate);
+        let (mut src, prev) = crate::blocks::VectorSource::new(audio);
+        src.work()?;
+        let (mut b, out) = AptDecode::new(prev, samp_rate)?;
+        for _ in 0..100 {
+            b.work()?;
+        }
+        let mut lines = Vec::new();
+        while let Some((l, tags)) = out.pop() {
+            assert_eq!(l.len(), LINE_WORDS);
+            let TagValue::Float(score) = tags[0].val() else {
+                panic!("bad tag {tags:?}");
+            };
+            assert!(*score > 0.7, "line {}: score {score}", lines.len());
+            lines.push(l);
+        }
+        // The last line may not be output, as it's not followed by enough
+        // words to search for sync.
+        assert!(lines.len() >= 9, "got {} lines", lines.len());
+        for (n, l) in lines.iter().enumerate().take(9) {
+            // Sync A, give or take half a word of sampling phase.
+            assert!(
+                l[4].max(l[5]) > 200 && l[6].min(l[7]) < 50,
+                "line {n}: {:?}",
+                &l[..39]
+            );
+            // Gradient in image A.
+            for x in [100, 450, 800] {
+                let want = (x - 86) as Float / 909.0 * 255.0;
+                assert!(
+                    (Float::from(l[x]) - want).abs() < 5.0,
+                    "line {n} x {x}: {} vs {want}",
+                    l[x]
+                );
+            }
+            // Line number in image B.
+            let want = n as Float;
+            assert!(
+                (Float::from(l[1500]) - want).abs() < 4.0,
+                "line {n}: {} vs {want}",
+                l[1500]
+            );
+        }
+        Ok(())
+    }
+}
@@ -63,7 +63,8 @@ impl AuEncode {
     ///
     /// * `encoding`: currently only `Encoding::Pcm16` is implemented.
     /// * `bitrate`: E.g. 48000,
-    /// * `channels`: Currently only mono (1) is implemented.
+    /// * `channels`: E.g. 1 for mono. With more than one channel, input
+    ///   samples must be interleaved.
     #[must_use]
     pub fn new(
         src: ReadStream<Float>,
@@ -76,7 +77,7 @@ impl AuEncode {
             Encoding::Pcm16,
             "only encoding supported is PCM16"
         );
-        assert_eq!(channels, 1, "only mono supported at the moment");
+        assert_ne!(channels, 0, "AU needs at least one channel");
 
         let mut v = Vec::with_capacity(28);
 
@@ -26,7 +26,7 @@ crate::error_from!(
 );
 
 impl CpalOutput {
-    fn new(sample_rate: u32, device_name: Option<&str>) -> Result<Self> {
+    fn new(sample_rate: u32, channels: u16, device_name: Option<&str>) -> Result<Self> {
         for host in cpal::platform::ALL_HOSTS {
             debug!("Audio sink host: {host:?}, name: {}", host.name());
         }
@@ -61,15 +61,17 @@ impl CpalOutput {
         let mut config: cpal::StreamConfig = config.into();
 
         config.sample_rate = cpal::SampleRate(sample_rate);
-        config.channels = 1;
+        config.channels = channels;
 
         Ok(Self { device, config })
     }
 
     fn start(&self) -> Result<(SyncSender<f32>, cpal::Stream)> {
-        let (sender, receiver) = sync_channel::<f32>(self.config.sample_rate.0 as usize * 3); // 3 seconds buffer
+        // 3 seconds buffer.
+        let (sender, receiver) = sync_channel::<f32>(
+            self.config.sample_rate.0 as usize * self.config.channels as usize * 3,
+        );
 
-        let channels = self.config.channels as usize;
         let err_fn = |err| error!("an error occurred on stream: {err}");
 
         let device = self.device.clone();
@@ -79,17 +81,13 @@ impl CpalOutput {
         let stream = device.build_output_stream(
             &config,
             move |data: &mut [f32], _: &cpal::OutputCallbackInfo| {
-                for frame in data.chunks_mut(channels) {
+                // Input is already interleaved, if more than one channel.
+                for sample in data.iter_mut() {
                     match receiver.recv() {
                         Err(e) => {
                             info!("Failed to read audio samples: {e:?}");
                         }
-                        Ok(v) => {
-                            let value = f32::from_sample(v);
-                            for sample in frame.iter_mut() {
-                                *sample = value;
-                            }
-                        }
+                        Ok(v) => *sample = f32::from_sample(v),
                     }
                 }
             },
@@ -102,9 +100,18 @@ impl CpalOutput {
 }
 
 /// Audio sink builder.
-#[derive(Default)]
 pub struct AudioSinkBuilder {
     dev: Option<String>,
+    channels: u16,
+}
+
+impl Default for AudioSinkBuilder {
+    fn default() -> Self {
+        Self {
+            dev: None,
+            channels: 1,
+        }
+    }
 }
 
 impl AudioSinkBuilder {
@@ -112,9 +119,18 @@ impl AudioSinkBuilder {
     pub fn new() -> Self {
         Self::default()
     }
+    /// Set number of channels. Default 1 (mono).
+    ///
+    /// With more than one channel, input samples are interleaved, e.g. as
+    /// output by [`Interleave`](crate::interleave::Interleave).
+    #[must_use]
+    pub fn channels(mut self, channels: u16) -> Self {
+        self.channels = channels;
+        self
+    }
     /// Build the AudioSink.
     pub fn build(self, prev: ReadStream<Float>, sample_rate: u64) -> Result<AudioSink> {
-        AudioSink::new_opts(prev, sample_rate, self.dev.as_deref())
+        AudioSink::new_opts(prev, sample_rate, self.channels, self.dev.as_deref())
     }
 }
 
@@ -139,17 +155,25 @@ impl AudioSink {
     ///
     /// Input is a single channel (mono), with values between -1.0 and +1.0.
     pub fn new(src: ReadStream<Float>, sample_rate: u64) -> Result<Self> {
-        Self::new_opts(src, sample_rate, None)
+        Self::new_opts(src, sample_rate, 1, None)
     }
     /// Create a builder.
     #[must_use]
     pub fn builder() -> AudioSinkBuilder {
         AudioSinkBuilder::default()
     }
-    fn new_opts(src: ReadStream<Float>, sample_rate: u64, dev: Option<&str>) -> Result<Self> {
+    fn new_opts(
+        src: ReadStream<Float>,
+        sample_rate: u64,
+        channels: u16,
+        dev: Option<&str>,
+    ) -> Result<Self> {
         let sample_rate =
             u32::try_from(sample_rate).map_err(|_| Error::msg("audio sample rate exceeds u32"))?;
-        let output = CpalOutput::new(sample_rate, dev)?;
+        if channels == 0 {
+            return Err(Error::msg("audio sink needs at least one channel"));
+        }
+        let output = CpalOutput::new(sample_rate, channels, dev)?;
         let (tx, rx) = std::sync::mpsc::channel();
         let cancel = CancellationToken::new();
         let c2 = cancel.clone();
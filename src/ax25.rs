@@ -0,0 +1,784 @@
+/*! AX.25 frame parsing and construction.
+
+[`Ax25Frame`] is a typed representation of an AX.25 frame, as output by
+`HdlcDeframer` (with the FCS already checked and removed) and as taken by
+`FcsAdder`.
+
+[`Ax25Decode`] and [`Ax25Encode`] convert between the two in a graph.
+
+<https://www.ax25.net/AX25.2.2-Jul%2098-2.pdf>
+*/
+use log::debug;
+
+use crate::block::{Block, BlockRet};
+use crate::stream::{NCReadStream, NCWriteStream};
+use crate::{Error, Result};
+
+const ADDR_LEN: usize = 7;
+const MAX_CALL_LEN: usize = 6;
+const MAX_DIGIS: usize = 8;
+
+/// PID for "no layer 3 protocol", used by APRS.
+pub const PID_NO_L3: u8 = 0xF0;
+
+/// AX.25 address.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Address {
+    /// Callsign, at most 6 characters.
+    pub call: String,
+
+    /// SSID, 0-15.
+    pub ssid: u8,
+
+    /// The top bit of the SSID byte.
+    ///
+    /// For the source and destination this is the command/response bit. For
+    /// digipeaters it's the "has been repeated" bit.
+    pub flag: bool,
+
+    /// The two reserved bits. Normally both set.
+    pub reserved: u8,
+}
+
+impl Address {
+    /// Create a new address.
+    #[must_use]
+    pub fn new(call: impl Into<String>, ssid: u8) -> Self {
+        Self {
+            call: call.into(),
+            ssid,
+            flag: false,
+            reserved: 0b11,
+        }
+    }
+
+    /// Parse an address, also returning if it's the last one.
+    fn parse(data: &[u8]) -> Result<(Self, bool)> {
+        let mut call = String::with_capacity(MAX_CALL_LEN);
+        for &b in &data[..MAX_CALL_LEN] {
+            if b & 1 != 0 {
+                return Err(Error::msg(format!(
+                    "AX.25 callsign byte has extension bit set: {b:#04x}"
+                )));
+            }
+            call.push(char::from(b >> 1));
+        }
+        let call = call.trim_end().to_string();
+        if call.is_empty() {
+            return Err(Error::msg("AX.25 address with empty callsign"));
+        }
+        let s = data[MAX_CALL_LEN];
+        Ok((
+            Self {
+                call,
+                ssid: (s >> 1) & 0xF,
+                flag: s & 0x80 != 0,
+                reserved: (s >> 5) & 0b11,
+            },
+            s & 1 != 0,
+        ))
+    }
+
+    /// Serialize the address, appending it to `out`.
+    fn serialize(&self, last: bool, out: &mut Vec<u8>) -> Result<()> {
+        if self.call.is_empty()
+            || self.call.len() > MAX_CALL_LEN
+            || !self.call.bytes().all(|b| b.is_ascii_graphic())
+        {
+            return Err(Error::msg(format!(
+                "invalid AX.25 callsign: {:?}",
+                self.call
+            )));
+        }
+        if self.ssid > 15 {
+            return Err(Error::msg(format!("invalid AX.25 SSID: {}", self.ssid)));
+        }
+        out.extend(
+            format!("{:6}", self.call)
+                .bytes()
+                .map(|b| b << 1)
+                .chain(std::iter::once(
+                    (u8::from(self.flag) << 7)
+                        | ((self.reserved & 0b11) << 5)
+                        | (self.ssid << 1)
+                        | u8::from(last),
+                )),
+        );
+        Ok(())
+    }
+}
+
+/// Format as `CALL-SSID`, omitting the SSID if it's 0.
+///
+/// The flag is not included.
+impl std::fmt::Display for Address {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.ssid == 0 {
+            write!(f, "{}", self.call)
+        } else {
+            write!(f, "{}-{}", self.call, self.ssid)
+        }
+    }
+}
+
+/// Parse `CALL` or `CALL-SSID`, with an optional trailing `*` setting the
+/// flag.
+impl std::str::FromStr for Address {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (s, flag) = match s.strip_suffix('*') {
+            Some(s) => (s, true),
+            None => (s, false),
+        };
+        let (call, ssid) = match s.split_once('-') {
+            Some((call, ssid)) => (
+                call,
+                ssid.parse::<u8>()
+                    .map_err(|e| Error::wrap(e, format!("invalid AX.25 SSID in {s:?}")))?,
+            ),
+            None => (s, 0),
+        };
+        if call.is_empty() || call.len() > MAX_CALL_LEN || ssid > 15 {
+            return Err(Error::msg(format!("invalid AX.25 address: {s:?}")));
+        }
+        let mut ret = Self::new(call.to_ascii_uppercase(), ssid);
+        ret.flag = flag;
+        Ok(ret)
+    }
+}
+
+/// Sequence number modulo.
+///
+/// This is not visible in the frame itself, but negotiated at connection
+/// setup (SABM vs SABME).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum Modulo {
+    /// One byte control field for I and S frames.
+    #[default]
+    Mod8,
+
+    /// Two byte control field for I and S frames.
+    Mod128,
+}
+
+impl Modulo {
+    fn max(self) -> u8 {
+        match self {
+            Modulo::Mod8 => 7,
+            Modulo::Mod128 => 127,
+        }
+    }
+}
+
+/// Supervisory frame type.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum Supervisory {
+    /// Receive Ready.
+    Rr,
+    /// Receive Not Ready.
+    Rnr,
+    /// Reject.
+    Rej,
+    /// Selective Reject.
+    Srej,
+}
+
+/// Unnumbered frame type.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum Unnumbered {
+    /// Set Asynchronous Balanced Mode (modulo 8).
+    Sabm,
+    /// Set Asynchronous Balanced Mode Extended (modulo 128).
+    Sabme,
+    /// Disconnect.
+    Disc,
+    /// Disconnected Mode.
+    Dm,
+    /// Unnumbered Acknowledge.
+    Ua,
+    /// Frame Reject.
+    Frmr,
+    /// Unnumbered Information.
+    Ui,
+    /// Exchange Identification.
+    Xid,
+    /// Test.
+    Test,
+    /// Unknown. Contains the control byte without the P/F bit.
+    Other(u8),
+}
+
+impl Unnumbered {
+    fn from_byte(b: u8) -> Self {
+        match b & 0xEF {
+            0x2F => Self::Sabm,
+            0x6F => Self::Sabme,
+            0x43 => Self::Disc,
+            0x0F => Self::Dm,
+            0x63 => Self::Ua,
+            0x87 => Self::Frmr,
+            0x03 => Self::Ui,
+            0xAF => Self::Xid,
+            0xE3 => Self::Test,
+            other => Self::Other(other),
+        }
+    }
+    fn to_byte(self) -> u8 {
+        match self {
+            Self::Sabm => 0x2F,
+            Self::Sabme => 0x6F,
+            Self::Disc => 0x43,
+            Self::Dm => 0x0F,
+            Self::Ua => 0x63,
+            Self::Frmr => 0x87,
+            Self::Ui => 0x03,
+            Self::Xid => 0xAF,
+            Self::Test => 0xE3,
+            Self::Other(b) => (b & 0xEF) | 0x03,
+        }
+    }
+}
+
+/// Control field.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum Control {
+    /// Information frame.
+    I {
+        /// Send sequence number.
+        ns: u8,
+        /// Receive sequence number.
+        nr: u8,
+        /// Poll bit.
+        poll: bool,
+    },
+    /// Supervisory frame.
+    S {
+        /// Frame type.
+        kind: Supervisory,
+        /// Receive sequence number.
+        nr: u8,
+        /// Poll/final bit.
+        pf: bool,
+    },
+    /// Unnumbered frame.
+    U {
+        /// Frame type.
+        kind: Unnumbered,
+        /// Poll/final bit.
+        pf: bool,
+    },
+}
+
+impl Control {
+    /// Parse control field, returning it and its length.
+    fn parse(data: &[u8], modulo: Modulo) -> Result<(Self, usize)> {
+        let Some(&c) = data.first() else {
+            return Err(Error::msg("AX.25 frame missing control field"));
+        };
+        if c & 3 == 3 {
+            return Ok((
+                Self::U {
+                    kind: Unnumbered::from_byte(c),
+                    pf: c & 0x10 != 0,
+                },
+                1,
+            ));
+        }
+        let (ns, nr, pf, len) = match modulo {
+            Modulo::Mod8 => ((c >> 1) & 7, c >> 5, c & 0x10 != 0, 1),
+            Modulo::Mod128 => {
+                let Some(&c2) = data.get(1) else {
+                    return Err(Error::msg("AX.25 frame with truncated control field"));
+                };
+                (c >> 1, c2 >> 1, c2 & 1 != 0, 2)
+            }
+        };
+        if c & 1 == 0 {
+            return Ok((Self::I { ns, nr, poll: pf }, len));
+        }
+        let kind = match (c >> 2) & 3 {
+            0 => Supervisory::Rr,
+            1 => Supervisory::Rnr,
+            2 => Supervisory::Rej,
+            _ => Supervisory::Srej,
+        };
+        Ok((Self::S { kind, nr, pf }, len))
+    }
+
+    /// Serialize the control field, appending it to `out`.
+    fn serialize(&self, modulo: Modulo, out: &mut Vec<u8>) -> Result<()> {
+        let (low, nr, pf) = match *self {
+            Self::U { kind, pf } => {
+                out.push(kind.to_byte() | (u8::from(pf) << 4));
+                return Ok(());
+            }
+            Self::I { ns, nr, poll } => {
+                if ns > modulo.max() {
+                    return Err(Error::msg(format!("AX.25 N(S) {ns} out of range")));
+                }
+                (ns << 1, nr, poll)
+            }
+            Self::S { kind, nr, pf } => {
+                let ss = match kind {
+                    Supervisory::Rr => 0,
+                    Supervisory::Rnr => 1,
+                    Supervisory::Rej => 2,
+                    Supervisory::Srej => 3,
+                };
+                ((ss << 2) | 1, nr, pf)
+            }
+        };
+        if nr > modulo.max() {
+            return Err(Error::msg(format!("AX.25 N(R) {nr} out of range")));
+        }
+        match modulo {
+            Modulo::Mod8 => out.push(low | (u8::from(pf) << 4) | (nr << 5)),
+            Modulo::Mod128 => out.extend([low, (nr << 1) | u8::from(pf)]),
+        }
+        Ok(())
+    }
+
+    /// Return true if frames with this control field have a PID byte.
+    #[must_use]
+    pub fn has_pid(&self) -> bool {
+        matches!(
+            self,
+            Self::I { .. }
+                | Self::U {
+                    kind: Unnumbered::Ui,
+                    ..
+                }
+        )
+    }
+}
+
+/// Format like `I R2 S1 P`, `RR R3`, or `UI`.
+impl std::fmt::Display for Control {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let pf = |pf: bool| if pf { " P/F" } else { "" };
+        match self {
+            Self::I { ns, nr, poll } => write!(f, "I R{nr} S{ns}{}", pf(*poll)),
+            Self::S { kind, nr, pf: p } => {
+                let kind = match kind {
+                    Supervisory::Rr => "RR",
+                    Supervisory::Rnr => "RNR",
+                    Supervisory::Rej => "REJ",
+                    Supervisory::Srej => "SREJ",
+                };
+                write!(f, "{kind} R{nr}{}", pf(*p))
+            }
+            Self::U { kind, pf: p } => {
+                match kind {
+                    Unnumbered::Sabm => write!(f, "SABM"),
+                    Unnumbered::Sabme => write!(f, "SABME"),
+                    Unnumbered::Disc => write!(f, "DISC"),
+                    Unnumbered::Dm => write!(f, "DM"),
+                    Unnumbered::Ua => write!(f, "UA"),
+                    Unnumbered::Frmr => write!(f, "FRMR"),
+                    Unnumbered::Ui => write!(f, "UI"),
+                    Unnumbered::Xid => write!(f, "XID"),
+                    Unnumbered::Test => write!(f, "TEST"),
+                    Unnumbered::Other(b) => write!(f, "U{b:02X}"),
+                }?;
+                write!(f, "{}", pf(*p))
+            }
+        }
+    }
+}
+
+/// AX.25 frame, without FCS.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Ax25Frame {
+    /// Destination address.
+    pub dst: Address,
+
+    /// Source address.
+    pub src: Address,
+
+    /// Digipeater path, at most 8.
+    pub digis: Vec<Address>,
+
+    /// Control field.
+    pub control: Control,
+
+    /// Modulo for sequence numbers in the control field.
+    pub modulo: Modulo,
+
+    /// Protocol identifier. Present for I and UI frames.
+    pub pid: Option<u8>,
+
+    /// Information field.
+    pub info: Vec<u8>,
+}
+
+impl Ax25Frame {
+    /// Create a UI command frame, as used by APRS.
+    #[must_use]
+    pub fn ui(src: Address, dst: Address, digis: Vec<Address>, info: Vec<u8>) -> Self {
+        let mut dst = dst;
+        let mut src = src;
+        dst.flag = true;
+        src.flag = false;
+        Self {
+            dst,
+            src,
+            digis,
+            control: Control::U {
+                kind: Unnumbered::Ui,
+                pf: false,
+            },
+            modulo: Modulo::Mod8,
+            pid: Some(PID_NO_L3),
+            info,
+        }
+    }
+
+    /// Parse a frame, assuming modulo 8.
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        Self::parse_with_modulo(data, Modulo::Mod8)
+    }
+
+    /// Parse a frame, with the given sequence number modulo.
+    pub fn parse_with_modulo(data: &[u8], modulo: Modulo) -> Result<Self> {
+        let mut addrs = Vec::with_capacity(2);
+        let mut pos = 0;
+        loop {
+            if addrs.len() == MAX_DIGIS + 2 {
+                return Err(Error::msg("AX.25 frame has too many digipeaters"));
+            }
+            let Some(a) = data.get(pos..pos + ADDR_LEN) else {
+                return Err(Error::msg("AX.25 frame with truncated address field"));
+            };
+            let (addr, last) = Address::parse(a)?;
+            addrs.push(addr);
+            pos += ADDR_LEN;
+            if last {
+                break;
+            }
+        }
+        if addrs.len() < 2 {
+            return Err(Error::msg("AX.25 frame missing source address"));
+        }
+        let (control, len) = Control::parse(&data[pos..], modulo)?;
+        pos += len;
+        let pid = if control.has_pid() {
+            let Some(&pid) = data.get(pos) else {
+                return Err(Error::msg("AX.25 frame missing PID"));
+            };
+            pos += 1;
+            Some(pid)
+        } else {
+            None
+        };
+        let mut addrs = addrs.into_iter();
+        let dst = addrs.next().unwrap();
+        let src = addrs.next().unwrap();
+        Ok(Self {
+            dst,
+            src,
+            digis: addrs.collect(),
+            control,
+            modulo,
+            pid,
+            info: data[pos..].to_vec(),
+        })
+    }
+
+    /// Serialize the frame into bytes, without FCS.
+    pub fn serialize(&self) -> Result<Vec<u8>> {
+        if self.digis.len() > MAX_DIGIS {
+            return Err(Error::msg(format!(
+                "AX.25 frame has too many digipeaters: {}",
+                self.digis.len()
+            )));
+        }
+        if self.pid.is_some() != self.control.has_pid() {
+            return Err(Error::msg(format!(
+                "AX.25 PID {:?} doesn't match control field {}",
+                self.pid, self.control
+            )));
+        }
+        let mut out = Vec::with_capacity(ADDR_LEN * (2 + self.digis.len()) + 3 + self.info.len());
+        self.dst.serialize(false, &mut out)?;
+        self.src.serialize(self.digis.is_empty(), &mut out)?;
+        for (n, digi) in self.digis.iter().enumerate() {
+            digi.serialize(n + 1 == self.digis.len(), &mut out)?;
+        }
+        self.control.serialize(self.modulo, &mut out)?;
+        out.extend(self.pid);
+        out.extend(&self.info);
+        Ok(out)
+    }
+
+    /// Return true if the frame is a command, false if it's a response.
+    ///
+    /// Old (AX.25 v1) frames, with both or neither bit set, count as
+    /// commands.
+    #[must_use]
+    pub fn is_command(&self) -> bool {
+        self.dst.flag || !self.src.flag
+    }
+}
+
+/// Format in TNC2 monitor format, like `M0THC-2>APZ001,WIDE1-1*:hello`.
+///
+/// Non-UI frames have the control field and PID added, like
+/// `M0THC>N0CALL <I R0 S1 pid=F0>:hello`. Non-printable characters in the
+/// info field are escaped.
+impl std::fmt::Display for Ax25Frame {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}>{}", self.src, self.dst)?;
+        for digi in &self.digis {
+            write!(f, ",{digi}{}", if digi.flag { "*" } else { "" })?;
+        }
+        let ui = matches!(
+            self.control,
+            Control::U {
+                kind: Unnumbered::Ui,
+                ..
+            }
+        );
+        if !ui {
+            write!(f, " <{}", self.control)?;
+            if let Some(pid) = self.pid {
+                write!(f, " pid={pid:02X}")?;
+            }
+            write!(f, ">")?;
+        }
+        if ui || !self.info.is_empty() {
+            write!(f, ":{}", self.info.escape_ascii())?;
+        }
+        Ok(())
+    }
+}
+
+/// Decode AX.25 frames.
+///
+/// Takes frames from e.g. `HdlcDeframer`, and outputs [`Ax25Frame`]s. Frames
+/// that fail to parse are dropped.
+#[derive(rustradio_macros::Block)]
+#[rustradio(crate, new)]
+pub struct Ax25Decode {
+    #[rustradio(in)]
+    src: NCReadStream<Vec<u8>>,
+    #[rustradio(out)]
+    dst: NCWriteStream<Ax25Frame>,
+    #[rustradio(default)]
+    modulo: Modulo,
+}
+
+impl Ax25Decode {
+    /// Set the sequence number modulo to assume for I and S frames.
+    pub fn set_modulo(&mut self, modulo: Modulo) {
+        self.modulo = modulo;
+    }
+}
+
+impl Block for Ax25Decode {
+    fn work(&mut self) -> Result<BlockRet<'_>> {
+        loop {
+            if self.dst.remaining() == 0 {
+                return Ok(BlockRet::WaitForStream(&self.dst, 1));
+            }
+            let Some((x, tags)) = self.src.pop() else {
+                return Ok(BlockRet::WaitForStream(&self.src, 1));
+            };
+            match Ax25Frame::parse_with_modulo(&x, self.modulo) {
+                Ok(frame) => self.dst.push(frame, tags),
+                Err(e) => debug!("Ax25Decode: bad frame: {e}"),
+            }
+        }
+    }
+}
+
+/// Encode AX.25 frames.
+///
+/// Takes [`Ax25Frame`]s and outputs bytes, e.g. for `FcsAdder`. Frames that
+/// fail to serialize are dropped.
+#[derive(rustradio_macros::Block)]
+#[rustradio(crate, new)]
+pub struct Ax25Encode {
+    #[rustradio(in)]
+    src: NCReadStream<Ax25Frame>,
+    #[rustradio(out)]
+    dst: NCWriteStream<Vec<u8>>,
+}
+
+impl Block for Ax25Encode {
+    fn work(&mut self) -> Result<BlockRet<'_>> {
+        loop {
+            if self.dst.remaining() == 0 {
+                return Ok(BlockRet::WaitForStream(&self.dst, 1));
+            }
+            let Some((x, tags)) = self.src.pop() else {
+                return Ok(BlockRet::WaitForStream(&self.src, 1));
+            };
+            match x.serialize() {
+                Ok(out) => self.dst.push(out, tags),
+                Err(e) => debug!("Ax25Encode: bad frame: {e}"),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stream::{Tag, TagValue, new_nocopy_stream};
+
+    // M0THC-2>APZ001,WIDE1-1*:hello
+    const UI_FRAME: &[u8] = b"\x82\xa0\xb4\x60\x60\x62\xe0\x9a\x60\xa8\x90\x86\x40\x64\xae\x92\x88\x8a\x62\x40\xe3\x03\xf0hello";
+
+    #[test]
+    fn parse_ui() -> Result<()> {
+        let f = Ax25Frame::parse(UI_FRAME)?;
+        assert_eq!(f.dst.to_string(), "APZ001");
+        assert_eq!(f.src.to_string(), "M0THC-2");
+        assert_eq!(f.digis.len(), 1);
+        assert_eq!(f.digis[0].to_string(), "WIDE1-1");
+        assert!(f.digis[0].flag);
+        assert!(f.is_command());
+        assert_eq!(f.pid, Some(PID_NO_L3));
+        assert_eq!(f.info, b"hello");
+        assert_eq!(f.to_string(), "M0THC-2>APZ001,WIDE1-1*:hello");
+        assert_eq!(f.serialize()?, UI_FRAME);
+
+        let built = Ax25Frame::ui(
+            "M0THC-2".parse()?,
+            "APZ001".parse()?,
+            vec!["WIDE1-1*".parse()?],
+            b"hello".to_vec(),
+        );
+        assert_eq!(built, f);
+        Ok(())
+    }
+
+    #[test]
+    fn control_roundtrip() -> Result<()> {
+        for (modulo, control, pid, want) in [
+            (
+                Modulo::Mod8,
+                Control::I {
+                    ns: 3,
+                    nr: 5,
+                    poll: true,
+                },
+                Some(0xF0),
+                vec![0xB6, 0xF0],
+            ),
+            (
+                Modulo::Mod8,
+                Control::S {
+                    kind: Supervisory::Rej,
+                    nr: 7,
+                    pf: false,
+                },
+                None,
+                vec![0xE9],
+            ),
+            (
+                Modulo::Mod8,
+                Control::U {
+                    kind: Unnumbered::Sabm,
+                    pf: true,
+                },
+                None,
+                vec![0x3F],
+            ),
+            (
+                Modulo::Mod128,
+                Control::I {
+                    ns: 100,
+                    nr: 127,
+                    poll: false,
+                },
+                Some(0xCF),
+                vec![0xC8, 0xFE, 0xCF],
+            ),
+            (
+                Modulo::Mod128,
+                Control::S {
+                    kind: Supervisory::Srej,
+                    nr: 64,
+                    pf: true,
+                },
+                None,
+                vec![0x0D, 0x81],
+            ),
+            (
+                Modulo::Mod128,
+                Control::U {
+                    kind: Unnumbered::Ua,
+                    pf: false,
+                },
+                None,
+                vec![0x63],
+            ),
+        ] {
+            let frame = Ax25Frame {
+                dst: Address::new("N0CALL", 0),
+                src: Address::new("M0THC", 15),
+                digis: vec![],
+                control,
+                modulo,
+                pid,
+                info: b"data".to_vec(),
+            };
+            let bytes = frame.serialize()?;
+            assert_eq!(&bytes[14..bytes.len() - 4], want, "{control:?}");
+            assert_eq!(bytes[13] & 1, 1);
+            assert_eq!(Ax25Frame::parse_with_modulo(&bytes, modulo)?, frame);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn bad_frames() -> Result<()> {
+        // Truncated.
+        for n in [0, 7, 13, 14, 22] {
+            assert!(Ax25Frame::parse(&UI_FRAME[..n]).is_err(), "{n}");
+        }
+        // No end of address field.
+        let mut f = UI_FRAME.to_vec();
+        f[20] &= 0xFE;
+        assert!(Ax25Frame::parse(&f).is_err());
+
+        // Sequence number out of range.
+        let frame = Ax25Frame {
+            control: Control::I {
+                ns: 8,
+                nr: 0,
+                poll: false,
+            },
+            ..Ax25Frame::parse(UI_FRAME)?
+        };
+        assert!(frame.serialize().is_err());
+
+        // Bad addresses.
+        for a in ["", "TOOLONG", "M0THC-16", "M0THC-X"] {
+            assert!(a.parse::<Address>().is_err(), "{a}");
+        }
+        let mut frame = Ax25Frame::parse(UI_FRAME)?;
+        frame.src.call = "TOOLONG".to_string();
+        assert!(frame.serialize().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn blocks() -> Result<()> {
+        let (tx, rx) = new_nocopy_stream();
+        let (mut dec, rx) = Ax25Decode::new(rx);
+        let (mut enc, rx) = Ax25Encode::new(rx);
+        let tags = [Tag::new(0, "foo", TagValue::Bool(true))];
+        tx.push(UI_FRAME.to_vec(), &tags);
+        tx.push(vec![1, 2, 3], &[]);
+        tx.push(UI_FRAME.to_vec(), &[]);
+        dec.work()?;
+        enc.work()?;
+        assert_eq!(rx.pop(), Some((UI_FRAME.to_vec(), tags.to_vec())));
+        assert_eq!(rx.pop(), Some((UI_FRAME.to_vec(), vec![])));
+        assert!(rx.pop().is_none());
+        Ok(())
+    }
+}
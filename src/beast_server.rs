@@ -0,0 +1,227 @@
+/*! Beast and AVR TCP server, for Mode S frames.
+
+Serves Mode S frames in the formats `dump1090` and `readsb` use on ports
+30005 (Beast binary) and 30002 (AVR raw), so that e.g. `readsb` with
+`--net-connector` can feed `tar1090`, or frames can be shared with
+aggregators.
+
+Any number of clients can connect. Anything they send is ignored.
+
+<https://github.com/firestuff/adsb-tools/blob/master/protocols/beast.md>
+*/
+use std::net::{SocketAddr, ToSocketAddrs};
+
+use crate::Result;
+use crate::adsb::{SAMPLE_TAG, SIGNAL_TAG};
+use crate::block::{Block, BlockRet};
+use crate::stream::{NCReadStream, NCWriteStream, Tag, TagValue};
+use crate::tcp_server::TcpServer;
+
+const BEAST_ESCAPE: u8 = 0x1A;
+
+// Beast timestamps are in 12MHz ticks.
+const TICKS_PER_SAMPLE: u64 = (12_000_000.0 / crate::adsb::SAMPLE_RATE) as u64;
+
+/// Format to send frames in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BeastFormat {
+    /// Beast binary format, with timestamp and signal level.
+    Binary,
+    /// AVR text format, e.g. `*8D4840D6202CC371C32CE0576098;`.
+    Avr,
+}
+
+/// Encode a frame in Beast binary format.
+///
+/// `timestamp` is in 12MHz ticks, and `signal` is the signal level, where
+/// 255 is full scale.
+#[must_use]
+pub fn beast_encode(frame: &[u8], timestamp: u64, signal: u8) -> Vec<u8> {
+    let kind = if frame.len() == 7 { b'2' } else { b'3' };
+    let mut out = vec![BEAST_ESCAPE, kind];
+    let timestamp = timestamp.to_be_bytes();
+    let escaped = timestamp[2..]
+        .iter()
+        .chain(std::iter::once(&signal))
+        .chain(frame);
+    for &b in escaped {
+        out.push(b);
+        if b == BEAST_ESCAPE {
+            out.push(b);
+        }
+    }
+    out
+}
+
+/// Encode a frame in AVR text format.
+#[must_use]
+pub fn avr_encode(frame: &[u8]) -> String {
+    let hex: String = frame.iter().map(|b| format!("{b:02X}")).collect();
+    format!("*{hex};\n")
+}
+
+fn tags_to_beast(tags: &[Tag]) -> (u64, u8) {
+    let mut timestamp = 0;
+    let mut signal = 0;
+    for tag in tags {
+        match (tag.key(), tag.val()) {
+            (SAMPLE_TAG, TagValue::U64(pos)) => timestamp = pos * TICKS_PER_SAMPLE,
+            // Power to amplitude, where 1.0 is full scale.
+            (SIGNAL_TAG, TagValue::Float(power)) => {
+                signal = (power.sqrt() * 255.0).clamp(0.0, 255.0) as u8;
+            }
+            _ => {}
+        }
+    }
+    (timestamp, signal)
+}
+
+/// Beast and AVR TCP server.
+///
+/// Takes Mode S frames, e.g. from [`ModeSDemod`](crate::adsb::ModeSDemod),
+/// and sends them to all connected clients. The frames are also passed
+/// through to the output, unchanged.
+///
+/// ```no_run
+/// use rustradio::blocks::BeastServer;
+/// use rustradio::stream::new_nocopy_stream;
+/// let (_tx, rx) = new_nocopy_stream();
+/// let (server, frames) = BeastServer::new(rx, "[::]:30005")?;
+/// # Ok::<(), rustradio::Error>(())
+/// ```
+#[derive(rustradio_macros::Block)]
+#[rustradio(crate)]
+pub struct BeastServer {
+    #[rustradio(in)]
+    src: NCReadStream<Vec<u8>>,
+    #[rustradio(out)]
+    dst: NCWriteStream<Vec<u8>>,
+    server: TcpServer<()>,
+    format: BeastFormat,
+}
+
+impl BeastServer {
+    /// Create a new Beast server listening on the given address.
+    pub fn new<A: ToSocketAddrs>(
+        src: NCReadStream<Vec<u8>>,
+        addr: A,
+    ) -> Result<(Self, NCReadStream<Vec<u8>>)> {
+        let server = TcpServer::new("BeastServer", addr)?;
+        let (dst, dr) = crate::stream::new_nocopy_stream();
+        Ok((
+            Self {
+                src,
+                dst,
+                server,
+                format: BeastFormat::Binary,
+            },
+            dr,
+        ))
+    }
+
+    /// Set the format to send frames in. Default is Beast binary.
+    pub fn set_format(&mut self, format: BeastFormat) {
+        self.format = format;
+    }
+
+    /// Return the address the server is listening on.
+    ///
+    /// Useful when binding to port 0.
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        self.server.local_addr()
+    }
+
+    /// Return the number of currently connected clients.
+    #[must_use]
+    pub fn clients(&self) -> usize {
+        self.server.clients.len()
+    }
+}
+
+impl Block for BeastServer {
+    fn work(&mut self) -> Result<BlockRet<'_>> {
+        let mut active = self.server.accept()?;
+        while self.dst.remaining() > 0 {
+            let Some((frame, tags)) = self.src.pop() else {
+                break;
+            };
+            active = true;
+            let out = match self.format {
+                BeastFormat::Binary => {
+                    let (timestamp, signal) = tags_to_beast(&tags);
+                    beast_encode(&frame, timestamp, signal)
+                }
+                BeastFormat::Avr => avr_encode(&frame).into_bytes(),
+            };
+            for client in &mut self.server.clients {
+                client.outbuf.extend(&out);
+            }
+            self.dst.push(frame, tags);
+        }
+        self.server.flush_and_fill();
+        for client in &mut self.server.clients {
+            client.inbuf.clear();
+        }
+        Ok(if active {
+            BlockRet::Again
+        } else {
+            BlockRet::Pending
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+    use std::net::TcpStream;
+
+    use crate::Error;
+    use crate::stream::new_nocopy_stream;
+
+    const FRAME: [u8; 14] = [
+        0x8D, 0x48, 0x40, 0xD6, 0x20, 0x2C, 0xC3, 0x71, 0xC3, 0x2C, 0xE0, 0x57, 0x60, 0x98,
+    ];
+
+    #[test]
+    fn encode() {
+        assert_eq!(
+            beast_encode(&FRAME[..7], 0x0102_0304_1A06, 0x1A),
+            [
+                0x1A, b'2', 0x01, 0x02, 0x03, 0x04, 0x1A, 0x1A, 0x06, 0x1A, 0x1A, 0x8D, 0x48, 0x40,
+                0xD6, 0x20, 0x2C, 0xC3
+            ]
+        );
+        let out = beast_encode(&FRAME, 0, 0xFF);
+        assert_eq!(out.len(), 2 + 6 + 1 + 14);
+        assert_eq!(out[1], b'3');
+        assert_eq!(avr_encode(&FRAME), "*8D4840D6202CC371C32CE0576098;\n");
+    }
+
+    #[test]
+    fn serve() -> Result<()> {
+        let (tx, rx) = new_nocopy_stream();
+        let (mut b, out) = BeastServer::new(rx, "[::1]:0")?;
+        b.set_format(BeastFormat::Avr);
+        let mut c = TcpStream::connect(b.local_addr()?)?;
+        for _ in 0..200 {
+            b.work()?;
+            if b.clients() == 1 {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        if b.clients() != 1 {
+            return Err(Error::msg("timed out"));
+        }
+        let tags = vec![Tag::new(0, SAMPLE_TAG, TagValue::U64(1))];
+        tx.push(FRAME.to_vec(), tags.clone());
+        b.work()?;
+        let want = b"*8D4840D6202CC371C32CE0576098;\n";
+        let mut got = [0u8; 31];
+        c.read_exact(&mut got)?;
+        assert_eq!(&got, want);
+        assert_eq!(out.pop(), Some((FRAME.to_vec(), tags)));
+        Ok(())
+    }
+}
@@ -1,18 +1,35 @@
 //! Convenient mod collecting all standard library blocks for import.
 pub use crate::add::Add;
 pub use crate::add_const::{AddConst, add_const};
+pub use crate::adsb::{AdsbDecode, ModeSDemod};
+pub use crate::afsk::AfskModulator;
+pub use crate::agwpe::AgwpeServer;
+pub use crate::ais::{AisDecode, AisDemod};
+pub use crate::am::{AmDemod, AmModulator, SyncAmDemod};
+pub use crate::aprs::AprsDecode;
+pub use crate::apt::AptDecode;
 pub use crate::au::{AuDecode, AuEncode};
+pub use crate::ax25::{Ax25Decode, Ax25Encode};
+pub use crate::beast_server::BeastServer;
 pub use crate::binary_slicer::BinarySlicer;
 pub use crate::burst_tagger::BurstTagger;
 pub use crate::canary::Canary;
+pub use crate::ccsds::{AsmCorrelate, TmFrameDecode};
 pub use crate::cma::CmaEqualizer;
 pub use crate::complex_to_mag2::ComplexToMag2;
 pub use crate::constant_source::ConstantSource;
+pub use crate::constellation::ConstellationModulator;
 pub use crate::convert::{ComplexToFloat, FloatToComplex, Inspect, Map, NCMap, Parse};
+pub use crate::convolutional::{
+    ConvolutionalEncode, ConvolutionalEncodePdu, ViterbiDecode, ViterbiDecodePdu,
+};
 pub use crate::correlate_access_code::{CorrelateAccessCode, CorrelateAccessCodeTag};
+pub use crate::cw_decode::CwDecode;
+pub use crate::cw_keyer::CwKeyer;
 pub use crate::debug_sink::{DebugFilter, DebugSink, DebugSinkNoCopy};
 pub use crate::delay::Delay;
 pub use crate::descrambler::{Descrambler, Scrambler};
+pub use crate::emphasis::{Deemphasis, Preemphasis};
 pub use crate::fft::Fft;
 pub use crate::fft_filter::FftFilter;
 pub use crate::fft_filter::FftFilterFloat;
@@ -20,23 +37,33 @@ pub use crate::fft_stream::FftStream;
 pub use crate::file_sink::{FileSink, NoCopyFileSink};
 pub use crate::file_source::FileSource;
 pub use crate::fir::FirFilter;
+pub use crate::fir::InterpFirFilter;
+pub use crate::gfsk::GfskModulator;
 pub use crate::hasher::{Hasher, sha512};
 pub use crate::hdlc_deframer::HdlcDeframer;
 pub use crate::hdlc_framer::{FcsAdder, HdlcFramer};
 pub use crate::head::Head;
 pub use crate::hilbert::Hilbert;
 pub use crate::il2p_deframer::Il2pDeframer;
+pub use crate::image_sink::ImageSink;
+pub use crate::interleave::Interleave;
 pub use crate::iq_balance::IqBalance;
 pub use crate::kiss::{KissDecode, KissEncode, KissFrame};
+pub use crate::kiss_server::KissServer;
+pub use crate::manchester::{ManchesterDecode, ManchesterDecodePdu, ManchesterEncode};
 pub use crate::morse_encode::MorseEncode;
 pub use crate::multiply_const::MultiplyConst;
 pub use crate::nrzi::{NrziDecode, NrziEncode};
 pub use crate::null_sink::NullSink;
+pub use crate::ook::{OokDetect, OokSlicer};
 pub use crate::pdu_average::PduAverage;
 pub use crate::pdu_to_stream::PduToStream;
 pub use crate::pdu_writer::PduWriter;
+pub use crate::pll::{PllCarrierTracking, PllFreqDet};
+pub use crate::pocsag::PocsagDecode;
 pub use crate::quadrature_demod::{FastFM, QuadratureDemod};
 pub use crate::rational_resampler::RationalResampler;
+pub use crate::rds::{RdsDecode, RdsDemod};
 pub use crate::reader_source::ReaderSource;
 pub use crate::rtlsdr_decode::RtlSdrDecode;
 pub use crate::rtlsdr_encode::RtlSdrEncode;
@@ -44,16 +71,19 @@ pub use crate::sigmf::SigMFSource;
 pub use crate::signal_source::{SignalSourceComplex, SignalSourceFloat};
 pub use crate::single_pole_iir_filter::SinglePoleIirFilter;
 pub use crate::skip::Skip;
+pub use crate::ssb::{SsbDemod, SsbModulator};
 pub use crate::stream_to_pdu::StreamToPdu;
 pub use crate::strobe::Strobe;
 pub use crate::symbol_sync::SymbolSync;
 pub use crate::tcp_source::TcpSource;
 pub use crate::tee::Tee;
+pub use crate::to_json::ToJson;
 pub use crate::to_text::ToText;
 pub use crate::vco::Vco;
 pub use crate::vec_to_stream::VecToStream;
 pub use crate::vector_sink::{VectorSink, VectorSinkNoCopy};
 pub use crate::vector_source::VectorSource;
+pub use crate::wbfm_stereo::WbfmStereoDemod;
 pub use crate::wpcr::{Midpointer, Wpcr};
 pub use crate::writer_sink::WriterSink;
 pub use crate::xor::Xor;
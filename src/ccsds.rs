@@ -0,0 +1,756 @@
+/*! CCSDS telemetry (TM) synchronization and channel coding.
+
+Many satellites, cubesats included, send CCSDS TM transfer frames. Each
+frame, possibly Reed-Solomon encoded and randomized, is a "codeblock". The
+codeblocks are sent after the attached sync marker (ASM) `0x1ACFFC1D`, and
+the whole bit stream is often also convolutionally encoded.
+
+The receive chain is:
+* [`ViterbiDecode`] with [`ConvolutionalCode::ccsds()`], if convolutionally
+  encoded.
+* [`AsmCorrelate`], finding the ASM in soft bits, and cutting out
+  codeblocks.
+* [`TmFrameDecode`], removing the pseudo-randomizer, correcting errors with
+  RS(255,223), and tagging the frame with the primary header fields.
+
+## Example
+
+```
+use rustradio::blockchain;
+use rustradio::blocks::{AsmCorrelate, Map, TmFrameDecode, ViterbiDecode};
+use rustradio::convolutional::ConvolutionalCode;
+use rustradio::graph::{Graph, GraphRunner};
+# fn main() -> rustradio::Result<()> {
+// Soft symbols from a BPSK demodulator.
+let (_, prev) = rustradio::stream::new_stream();
+let mut g = Graph::new();
+let frames = blockchain![
+    g,
+    prev,
+    ViterbiDecode::new(prev, ConvolutionalCode::ccsds()),
+    Map::keep_tags(prev, "soft", |b: u8| if b == 1 { 1.0 } else { -1.0 }),
+    // 1115 byte frames, and RS interleave depth 5.
+    AsmCorrelate::new(prev, 1275)?,
+    TmFrameDecode::builder(prev).interleave(5).build()?,
+];
+# Ok(())
+# }
+```
+
+The convolutional code has no way to know where symbol pairs start, so the
+soft symbols must be aligned. Both polynomials have an odd number of taps,
+so inverted symbols decode into inverted bits, which [`AsmCorrelate`]
+handles.
+
+If the bits are known not to be inverted, e.g. from an FSK demodulator, the
+randomizer can instead be removed from the bit stream, by tagging the ASM
+with [`CorrelateAccessCodeTag`] and derandomizing with
+[`Descrambler::ccsds()`]:
+
+```
+use rustradio::blockchain;
+use rustradio::blocks::{
+    AsmCorrelate, CorrelateAccessCodeTag, Descrambler, Map, TmFrameDecode, ViterbiDecode,
+};
+use rustradio::ccsds::ASM;
+use rustradio::convolutional::ConvolutionalCode;
+use rustradio::graph::{Graph, GraphRunner};
+# fn main() -> rustradio::Result<()> {
+let (_, prev) = rustradio::stream::new_stream();
+let asm: Vec<u8> = (0..32).rev().map(|i| (ASM >> i) as u8 & 1).collect();
+let mut g = Graph::new();
+let frames = blockchain![
+    g,
+    prev,
+    ViterbiDecode::new(prev, ConvolutionalCode::ccsds()),
+    CorrelateAccessCodeTag::new(prev, asm, "asm", 2),
+    Descrambler::ccsds(prev, "asm", 1275),
+    Map::keep_tags(prev, "soft", |b: u8| if b == 1 { 1.0 } else { -1.0 }),
+    AsmCorrelate::new(prev, 1275)?,
+    TmFrameDecode::builder(prev)
+        .interleave(5)
+        .randomizer(false)
+        .build()?,
+];
+# Ok(())
+# }
+```
+
+References:
+* CCSDS 131.0-B, TM Synchronization and Channel Coding.
+* CCSDS 132.0-B, TM Space Data Link Protocol.
+
+[`ViterbiDecode`]: crate::blocks::ViterbiDecode
+[`ConvolutionalCode::ccsds()`]: crate::convolutional::ConvolutionalCode::ccsds
+[`CorrelateAccessCodeTag`]: crate::blocks::CorrelateAccessCodeTag
+[`Descrambler::ccsds()`]: crate::blocks::Descrambler::ccsds
+*/
+use std::collections::VecDeque;
+
+use log::debug;
+
+use crate::block::{Block, BlockRet};
+use crate::descrambler::ccsds_sequence;
+use crate::reed_solomon::ReedSolomon;
+use crate::stream::{NCReadStream, NCWriteStream, ReadStream, Tag, TagValue};
+use crate::{Error, Float, Result};
+
+/// Attached sync marker.
+pub const ASM: u32 = 0x1ACF_FC1D;
+
+/// Tag with the ASM correlation, as Float.
+///
+/// Negative if the bits were inverted.
+pub const ASM_TAG: &str = "Ccsds:asm";
+
+/// Tag with the spacecraft ID, as U64.
+pub const SCID_TAG: &str = "Ccsds:scid";
+
+/// Tag with the virtual channel ID, as U64.
+pub const VCID_TAG: &str = "Ccsds:vcid";
+
+/// Tag with the master channel frame count, as U64.
+pub const MC_COUNT_TAG: &str = "Ccsds:mc_count";
+
+/// Tag with the virtual channel frame count, as U64.
+pub const VC_COUNT_TAG: &str = "Ccsds:vc_count";
+
+/// Tag with the number of symbols corrected by Reed-Solomon, as U64.
+pub const RS_ERRORS_TAG: &str = "Ccsds:rs_errors";
+
+// Largest supported Reed-Solomon interleaving depth.
+const MAX_INTERLEAVE: usize = 8;
+
+// Transfer frame primary header size.
+const HEADER_LEN: usize = 6;
+
+// Basis conversion matrix, from CCSDS 131.0-B annex F, as used by libfec.
+const TAL: [u8; 8] = [0x8d, 0xef, 0xec, 0x86, 0xfa, 0x99, 0xaf, 0x7b];
+
+// Return tables converting symbols from conventional to dual basis, and
+// back.
+fn dual_basis_tables() -> ([u8; 256], [u8; 256]) {
+    let mut to_dual = [0u8; 256];
+    let mut from_dual = [0u8; 256];
+    for (i, d) in to_dual.iter_mut().enumerate() {
+        *d = (0..8)
+            .filter(|k| i & (1 << k) != 0)
+            .fold(0, |acc, k| acc ^ TAL[7 - k]);
+        from_dual[*d as usize] = i as u8;
+    }
+    (to_dual, from_dual)
+}
+
+/// Encode a transfer frame into a codeblock, not including the ASM.
+///
+/// The frame is Reed-Solomon encoded with dual basis RS(255,223), unless
+/// `interleave` is zero, and then optionally randomized. With RS, the frame
+/// length must be a multiple of `interleave`, and at most 223 times it.
+/// Shorter frames use virtual fill.
+///
+/// This is the transmit side of [`TmFrameDecode`], and useful for testing.
+///
+/// # Errors
+///
+/// Errors if the interleaving depth or frame length is invalid.
+pub fn encode_codeblock(frame: &[u8], interleave: usize, randomize: bool) -> Result<Vec<u8>> {
+    check_interleave(interleave)?;
+    let mut out = frame.to_vec();
+    if interleave > 0 {
+        if !frame.len().is_multiple_of(interleave) {
+            return Err(Error::msg(format!(
+                "frame length {} not a multiple of interleave depth {interleave}",
+                frame.len()
+            )));
+        }
+        let rs = ReedSolomon::ccsds();
+        let (to_dual, from_dual) = dual_basis_tables();
+        let parity: Vec<Vec<u8>> = (0..interleave)
+            .map(|j| {
+                let data: Vec<u8> = frame[j..]
+                    .iter()
+                    .step_by(interleave)
+                    .map(|&b| from_dual[b as usize])
+                    .collect();
+                rs.encode(&data)
+            })
+            .collect::<Result<_>>()?;
+        for k in 0..rs.nroots() {
+            out.extend(parity.iter().map(|p| to_dual[p[k] as usize]));
+        }
+    }
+    if randomize {
+        for (b, r) in out
+            .iter_mut()
+            .zip(ccsds_sequence(frame.len() + 32 * interleave))
+        {
+            *b ^= r;
+        }
+    }
+    Ok(out)
+}
+
+fn check_interleave(interleave: usize) -> Result<()> {
+    if interleave > MAX_INTERLEAVE {
+        return Err(Error::msg(format!(
+            "interleave depth {interleave} out of range 0..={MAX_INTERLEAVE}"
+        )));
+    }
+    Ok(())
+}
+
+/// Builder for [`AsmCorrelate`].
+pub struct AsmCorrelateBuilder {
+    src: ReadStream<Float>,
+    len: usize,
+    asm: u32,
+    threshold: Float,
+}
+
+impl AsmCorrelateBuilder {
+    /// Set the sync marker. Default [`ASM`].
+    #[must_use]
+    pub fn asm(mut self, asm: u32) -> Self {
+        self.asm = asm;
+        self
+    }
+
+    /// Set the correlation needed, between 0.0 and 1.0. Default 0.75, which
+    /// for hard bits allows four bit errors.
+    #[must_use]
+    pub fn threshold(mut self, threshold: Float) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    /// Build the block.
+    ///
+    /// # Errors
+    ///
+    /// Errors if the codeblock length is zero.
+    pub fn build(self) -> Result<(AsmCorrelate, NCReadStream<Vec<u8>>)> {
+        if self.len == 0 {
+            return Err(Error::msg("ASM codeblock length must not be zero"));
+        }
+        let (dst, dr) = crate::stream::new_nocopy_stream();
+        Ok((
+            AsmCorrelate {
+                src: self.src,
+                dst,
+                code: (0..32)
+                    .rev()
+                    .map(|i| if (self.asm >> i) & 1 == 1 { 1.0 } else { -1.0 })
+                    .collect(),
+                len: self.len,
+                threshold: self.threshold,
+                window: VecDeque::with_capacity(32),
+                frame: None,
+            },
+            dr,
+        ))
+    }
+}
+
+// Codeblock being received.
+struct Frame {
+    bytes: Vec<u8>,
+    bits: usize,
+    score: Float,
+}
+
+/// Find the ASM in soft bits, and output the codeblocks following it.
+///
+/// Soft bits are positive for 1, like the input to
+/// [`BinarySlicer`](crate::blocks::BinarySlicer).
+/// The correlation is normalized by the soft bit magnitudes, so it doesn't
+/// depend on signal level. If the ASM is found inverted, then the codeblock
+/// is inverted back.
+#[derive(rustradio_macros::Block)]
+#[rustradio(crate)]
+pub struct AsmCorrelate {
+    #[rustradio(in)]
+    src: ReadStream<Float>,
+    #[rustradio(out)]
+    dst: NCWriteStream<Vec<u8>>,
+    code: Vec<Float>,
+    len: usize,
+    threshold: Float,
+    window: VecDeque<Float>,
+    frame: Option<Frame>,
+}
+
+impl AsmCorrelate {
+    /// Create a new ASM correlator, for codeblocks of `len` bytes.
+    ///
+    /// # Errors
+    ///
+    /// Errors if `len` is zero.
+    pub fn new(src: ReadStream<Float>, len: usize) -> Result<(Self, NCReadStream<Vec<u8>>)> {
+        Self::builder(src, len).build()
+    }
+
+    /// Create a builder, for non-default ASM or threshold.
+    #[must_use]
+    pub fn builder(src: ReadStream<Float>, len: usize) -> AsmCorrelateBuilder {
+        AsmCorrelateBuilder {
+            src,
+            len,
+            asm: ASM,
+            threshold: 0.75,
+        }
+    }
+
+    fn correlation(&self) -> Float {
+        let (sum, mag) = self
+            .window
+            .iter()
+            .zip(&self.code)
+            .fold((0.0, 0.0), |(sum, mag), (&s, &c)| {
+                (sum + s * c, mag + s.abs())
+            });
+        if mag == 0.0 { 0.0 } else { sum / mag }
+    }
+}
+
+impl Block for AsmCorrelate {
+    fn work(&mut self) -> Result<BlockRet<'_>> {
+        if self.dst.remaining() == 0 {
+            return Ok(BlockRet::WaitForStream(&self.dst, 1));
+        }
+        let (i, _tags) = self.src.read_buf()?;
+        if i.is_empty() {
+            return Ok(BlockRet::WaitForStream(&self.src, 1));
+        }
+        let mut n = 0;
+        for &s in i.iter() {
+            n += 1;
+            if let Some(f) = &mut self.frame {
+                let bit = u8::from((s > 0.0) != (f.score < 0.0));
+                f.bytes[f.bits / 8] |= bit << (7 - f.bits % 8);
+                f.bits += 1;
+                if f.bits == self.len * 8 {
+                    let f = self.frame.take().expect("can't happen: checked above");
+                    self.dst
+                        .push(f.bytes, [Tag::new(0, ASM_TAG, TagValue::Float(f.score))]);
+                    if self.dst.remaining() == 0 {
+                        break;
+                    }
+                }
+                continue;
+            }
+            if self.window.len() == self.code.len() {
+                self.window.pop_front();
+            }
+            self.window.push_back(s);
+            if self.window.len() < self.code.len() {
+                continue;
+            }
+            let score = self.correlation();
+            if score.abs() >= self.threshold {
+                debug!("AsmCorrelate: found ASM with correlation {score}");
+                self.window.clear();
+                self.frame = Some(Frame {
+                    bytes: vec![0; self.len],
+                    bits: 0,
+                    score,
+                });
+            }
+        }
+        i.consume(n);
+        Ok(BlockRet::Again)
+    }
+}
+
+/// TM transfer frame primary header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PrimaryHeader {
+    /// Transfer frame version number. Zero for TM.
+    pub version: u8,
+    /// Spacecraft ID.
+    pub scid: u16,
+    /// Virtual channel ID.
+    pub vcid: u8,
+    /// Operational control field present.
+    pub ocf: bool,
+    /// Master channel frame count.
+    pub mc_count: u8,
+    /// Virtual channel frame count.
+    pub vc_count: u8,
+    /// Transfer frame data field status.
+    pub status: u16,
+}
+
+impl PrimaryHeader {
+    /// Parse the primary header from the start of a frame.
+    #[must_use]
+    pub fn parse(frame: &[u8]) -> Option<Self> {
+        let h = frame.get(..HEADER_LEN)?;
+        Some(Self {
+            version: h[0] >> 6,
+            scid: (u16::from(h[0] & 0x3F) << 4) | u16::from(h[1] >> 4),
+            vcid: (h[1] >> 1) & 7,
+            ocf: h[1] & 1 == 1,
+            mc_count: h[2],
+            vc_count: h[3],
+            status: u16::from_be_bytes([h[4], h[5]]),
+        })
+    }
+}
+
+/// Builder for [`TmFrameDecode`].
+pub struct TmFrameDecodeBuilder {
+    src: NCReadStream<Vec<u8>>,
+    interleave: usize,
+    randomizer: bool,
+}
+
+impl TmFrameDecodeBuilder {
+    /// Set Reed-Solomon interleaving depth. Default 1. Zero means the
+    /// frames are not Reed-Solomon encoded.
+    #[must_use]
+    pub fn interleave(mut self, interleave: usize) -> Self {
+        self.interleave = interleave;
+        self
+    }
+
+    /// Set if the pseudo-randomizer is used. Default true.
+    #[must_use]
+    pub fn randomizer(mut self, randomizer: bool) -> Self {
+        self.randomizer = randomizer;
+        self
+    }
+
+    /// Build the block.
+    ///
+    /// # Errors
+    ///
+    /// Errors if the interleaving depth is out of range.
+    pub fn build(self) -> Result<(TmFrameDecode, NCReadStream<Vec<u8>>)> {
+        check_interleave(self.interleave)?;
+        let (dst, dr) = crate::stream::new_nocopy_stream();
+        let (to_dual, from_dual) = dual_basis_tables();
+        Ok((
+            TmFrameDecode {
+                src: self.src,
+                dst,
+                rs: ReedSolomon::ccsds(),
+                interleave: self.interleave,
+                randomizer: self.randomizer,
+                sequence: Vec::new(),
+                to_dual,
+                from_dual,
+            },
+            dr,
+        ))
+    }
+}
+
+/// Decode codeblocks into TM transfer frames.
+///
+/// Frames that Reed-Solomon can't correct, or that are too short, are
+/// dropped. Output frames don't include the Reed-Solomon parity, and are
+/// tagged with the primary header fields and corrected symbol count.
+#[derive(rustradio_macros::Block)]
+#[rustradio(crate)]
+pub struct TmFrameDecode {
+    #[rustradio(in)]
+    src: NCReadStream<Vec<u8>>,
+    #[rustradio(out)]
+    dst: NCWriteStream<Vec<u8>>,
+    rs: ReedSolomon,
+    interleave: usize,
+    randomizer: bool,
+    sequence: Vec<u8>,
+    to_dual: [u8; 256],
+    from_dual: [u8; 256],
+}
+
+impl TmFrameDecode {
+    /// Create a builder.
+    #[must_use]
+    pub fn builder(src: NCReadStream<Vec<u8>>) -> TmFrameDecodeBuilder {
+        TmFrameDecodeBuilder {
+            src,
+            interleave: 1,
+            randomizer: true,
+        }
+    }
+
+    // Return the frame, and number of corrected symbols.
+    fn decode(&mut self, mut data: Vec<u8>) -> Result<Option<(Vec<u8>, usize)>> {
+        if self.randomizer {
+            if self.sequence.len() < data.len() {
+                self.sequence = ccsds_sequence(data.len());
+            }
+            for (b, r) in data.iter_mut().zip(&self.sequence) {
+                *b ^= r;
+            }
+        }
+        let depth = self.interleave;
+        let mut errors = 0;
+        if let Some(n) = data.len().checked_div(depth) {
+            if !data.len().is_multiple_of(depth) || n <= self.rs.nroots() || n > 255 {
+                debug!(
+                    "TmFrameDecode: codeblock length {} invalid for interleave depth {depth}",
+                    data.len()
+                );
+                return Ok(None);
+            }
+            for j in 0..depth {
+                let mut cw: Vec<u8> = data[j..]
+                    .iter()
+                    .step_by(depth)
+                    .map(|&b| self.from_dual[b as usize])
+                    .collect();
+                let Some(e) = self.rs.decode(&mut cw)? else {
+                    debug!("TmFrameDecode: uncorrectable codeword {j}");
+                    return Ok(None);
+                };
+                errors += e;
+                for (k, b) in cw.iter().enumerate() {
+                    data[j + k * depth] = self.to_dual[*b as usize];
+                }
+            }
+            data.truncate(data.len() - self.rs.nroots() * depth);
+        }
+        Ok(Some((data, errors)))
+    }
+}
+
+impl Block for TmFrameDecode {
+    fn work(&mut self) -> Result<BlockRet<'_>> {
+        loop {
+            if self.dst.remaining() == 0 {
+                return Ok(BlockRet::WaitForStream(&self.dst, 1));
+            }
+            let Some((data, mut tags)) = self.src.pop() else {
+                return Ok(BlockRet::WaitForStream(&self.src, 1));
+            };
+            let Some((frame, errors)) = self.decode(data)? else {
+                continue;
+            };
+            let Some(h) = PrimaryHeader::parse(&frame) else {
+                debug!("TmFrameDecode: frame too short: {} bytes", frame.len());
+                continue;
+            };
+            tags.extend([
+                Tag::new(0, SCID_TAG, TagValue::U64(h.scid.into())),
+                Tag::new(0, VCID_TAG, TagValue::U64(h.vcid.into())),
+                Tag::new(0, MC_COUNT_TAG, TagValue::U64(h.mc_count.into())),
+                Tag::new(0, VC_COUNT_TAG, TagValue::U64(h.vc_count.into())),
+                Tag::new(0, RS_ERRORS_TAG, TagValue::U64(errors as u64)),
+            ]);
+            self.dst.push(frame, tags);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::{CorrelateAccessCodeTag, Descrambler, VectorSource, ViterbiDecode};
+    use crate::convolutional::ConvolutionalCode;
+    use rand::{Rng, SeedableRng};
+
+    fn frame(rng: &mut impl Rng, len: usize, vcid: u8, count: u8) -> Vec<u8> {
+        // Version 0, SCID 0x123.
+        let mut f = vec![
+            0x12,
+            0x30 | (vcid << 1),
+            count,
+            count.wrapping_add(100),
+            0x18,
+            0,
+        ];
+        f.extend((HEADER_LEN..len).map(|_| rng.random::<u8>()));
+        f
+    }
+
+    fn to_bits(bytes: &[u8]) -> Vec<u8> {
+        bytes
+            .iter()
+            .flat_map(|b| (0..8).rev().map(move |i| (b >> i) & 1))
+            .collect()
+    }
+
+    fn tag(tags: &[Tag], key: &str) -> Option<TagValue> {
+        tags.iter()
+            .find(|t| t.key() == key)
+            .map(|t| t.val().clone())
+    }
+
+    #[test]
+    fn dual_basis() {
+        let (to_dual, from_dual) = dual_basis_tables();
+        assert_eq!(&to_dual[..5], &[0x00, 0x7b, 0xaf, 0xd4, 0x99]);
+        for i in 0..=255u8 {
+            assert_eq!(from_dual[to_dual[i as usize] as usize], i);
+        }
+    }
+
+    #[test]
+    fn header() {
+        let h = PrimaryHeader::parse(&[0x12, 0x35, 7, 8, 0x18, 0x00]).unwrap();
+        assert_eq!(
+            h,
+            PrimaryHeader {
+                version: 0,
+                scid: 0x123,
+                vcid: 2,
+                ocf: true,
+                mc_count: 7,
+                vc_count: 8,
+                status: 0x1800,
+            }
+        );
+        assert!(PrimaryHeader::parse(&[0; 5]).is_none());
+    }
+
+    #[test]
+    fn frame_decode() -> Result<()> {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(1);
+        for interleave in 0..=5 {
+            let len = if interleave == 0 {
+                100
+            } else {
+                223 * interleave
+            };
+            let f = frame(&mut rng, len, 3, 42);
+            let mut cb = encode_codeblock(&f, interleave, true)?;
+            assert_eq!(cb.len(), len + 32 * interleave);
+            // Corrupt up to 16 bytes per codeword.
+            let errors = 16 * interleave;
+            for b in cb.iter_mut().take(errors) {
+                *b ^= 0x5A;
+            }
+            let (tx, rx) = crate::stream::new_nocopy_stream();
+            let (mut b, out) = TmFrameDecode::builder(rx).interleave(interleave).build()?;
+            tx.push(cb.clone(), []);
+            b.work()?;
+            let (got, tags) = out.pop().unwrap();
+            assert_eq!(got, f, "interleave {interleave}");
+            assert_eq!(tag(&tags, VCID_TAG), Some(TagValue::U64(3)));
+            assert_eq!(tag(&tags, SCID_TAG), Some(TagValue::U64(0x123)));
+            assert_eq!(tag(&tags, MC_COUNT_TAG), Some(TagValue::U64(42)));
+            assert_eq!(tag(&tags, VC_COUNT_TAG), Some(TagValue::U64(142)));
+            assert_eq!(
+                tag(&tags, RS_ERRORS_TAG),
+                Some(TagValue::U64(errors as u64))
+            );
+
+            if interleave > 0 {
+                // Too many errors.
+                for b in cb.iter_mut().take(interleave * 20) {
+                    *b ^= 0x33;
+                }
+                tx.push(cb, []);
+                b.work()?;
+                assert!(out.pop().is_none());
+            }
+        }
+        assert!(
+            TmFrameDecode::builder(crate::stream::new_nocopy_stream().1)
+                .interleave(9)
+                .build()
+                .is_err()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn asm_invalid() {
+        assert!(AsmCorrelate::new(crate::stream::new_stream().1, 0).is_err());
+        assert!(AsmCorrelate::new(crate::stream::new_stream().1, 1).is_ok());
+    }
+
+    #[test]
+    fn virtual_fill() -> Result<()> {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(2);
+        let f = frame(&mut rng, 2 * 100, 1, 0);
+        let mut cb = encode_codeblock(&f, 2, false)?;
+        cb[10] ^= 1;
+        let (tx, rx) = crate::stream::new_nocopy_stream();
+        let (mut b, out) = TmFrameDecode::builder(rx)
+            .interleave(2)
+            .randomizer(false)
+            .build()?;
+        tx.push(cb, []);
+        b.work()?;
+        assert_eq!(out.pop().unwrap().0, f);
+        Ok(())
+    }
+
+    // Run the whole receive chain. Either inverted, with the randomizer
+    // removed by TmFrameDecode, or not inverted, with the randomizer removed
+    // by Descrambler.
+    fn full_chain(descrambler: bool) -> Result<()> {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(3);
+        let interleave = 2;
+        let len = 255 * interleave;
+        let frames: Vec<_> = (0..3)
+            .map(|n| frame(&mut rng, 223 * interleave, n, n))
+            .collect();
+        let mut bits: Vec<u8> = (0..100).map(|_| rng.random_range(0..=1)).collect();
+        for f in &frames {
+            bits.extend(to_bits(&ASM.to_be_bytes()));
+            bits.extend(to_bits(&encode_codeblock(f, interleave, true)?));
+        }
+        bits.extend(vec![0; 100]);
+        let code = ConvolutionalCode::ccsds();
+        // Without the descrambler, inverted, as from a BPSK demodulator
+        // locked 180° off.
+        let one = if descrambler { 1.0 } else { -1.0 };
+        let soft: Vec<Float> = code
+            .encode(&bits)
+            .iter()
+            .map(|&b| if b == 1 { one } else { -one } + rng.random_range(-1.2..1.2))
+            .collect();
+
+        let (mut src, prev) = VectorSource::new(soft);
+        src.work()?;
+        drop(src);
+        let (mut vit, prev) = ViterbiDecode::new(prev, code);
+        vit.work()?;
+        let prev = if descrambler {
+            let (mut b, prev) =
+                CorrelateAccessCodeTag::new(prev, to_bits(&ASM.to_be_bytes()), "asm", 2);
+            b.work()?;
+            let (mut b, prev) = Descrambler::ccsds(prev, "asm", len);
+            b.work()?;
+            prev
+        } else {
+            prev
+        };
+        let (mut map, prev) =
+            crate::blocks::Map::keep_tags(prev, "soft", |b: u8| if b == 1 { 1.0 } else { -1.0 });
+        let (mut asm, prev) = AsmCorrelate::new(prev, len)?;
+        let (mut dec, out) = TmFrameDecode::builder(prev)
+            .interleave(interleave)
+            .randomizer(!descrambler)
+            .build()?;
+        map.work()?;
+        asm.work()?;
+        dec.work()?;
+        for (n, f) in frames.iter().enumerate() {
+            let (got, tags) = out.pop().unwrap();
+            assert_eq!(&got, f);
+            assert_eq!(tag(&tags, VCID_TAG), Some(TagValue::U64(n as u64)));
+            let Some(TagValue::Float(score)) = tag(&tags, ASM_TAG) else {
+                panic!("no ASM tag");
+            };
+            assert!(score * one > 0.75, "{score}");
+        }
+        assert!(out.pop().is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn full_chain_inverted() -> Result<()> {
+        full_chain(false)
+    }
+
+    #[test]
+    fn full_chain_descrambler() -> Result<()> {
+        full_chain(true)
+    }
+}
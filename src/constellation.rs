@@ -0,0 +1,483 @@
+/*! PSK and QAM modulation.
+
+## Example
+
+Send bytes as QPSK, root raised cosine shaped at 8 samples per symbol:
+
+```
+use rustradio::blocks::{ConstellationModulator, NullSink};
+use rustradio::constellation::Constellation;
+# fn main() -> rustradio::Result<()> {
+let (_, prev) = rustradio::stream::new_stream::<u8>();
+let (block, prev) = ConstellationModulator::builder(prev, Constellation::Qpsk)
+    .packed()
+    .rrc(8, 0.35)
+    .build()?;
+let sink = NullSink::new(prev);
+# Ok(())
+# }
+```
+*/
+use std::collections::VecDeque;
+
+use crate::block::{Block, BlockRet};
+use crate::stream::{ReadStream, Tag, WriteStream};
+use crate::{Complex, Error, Float, Result};
+
+/// Constellation, i.e. mapping from symbols to points.
+///
+/// All mappings are Gray coded, so that neighbouring points differ by one bit,
+/// and scaled to an average power of 1.0.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Constellation {
+    /// Binary phase shift keying. 0 is -1, 1 is +1.
+    Bpsk,
+
+    /// Quadrature phase shift keying. The low bit is the sign of I, the high
+    /// bit the sign of Q.
+    Qpsk,
+
+    /// 8PSK, starting at phase 0.
+    Psk8,
+
+    /// 16QAM. The low two bits are I, the high two Q.
+    Qam16,
+}
+
+// Gray coded amplitude levels, for QAM.
+const QAM_LEVELS: [Float; 4] = [-3.0, -1.0, 3.0, 1.0];
+
+impl Constellation {
+    /// Number of bits per symbol.
+    #[must_use]
+    pub fn bits_per_symbol(&self) -> usize {
+        match self {
+            Self::Bpsk => 1,
+            Self::Qpsk => 2,
+            Self::Psk8 => 3,
+            Self::Qam16 => 4,
+        }
+    }
+
+    /// Number of points.
+    #[must_use]
+    pub fn order(&self) -> usize {
+        1 << self.bits_per_symbol()
+    }
+
+    /// Return true if this is a phase shift keying constellation.
+    ///
+    /// Only PSK constellations can be differentially encoded.
+    #[must_use]
+    pub fn is_psk(&self) -> bool {
+        !matches!(self, Self::Qam16)
+    }
+
+    /// Map a symbol to its point.
+    ///
+    /// Only the low `bits_per_symbol()` bits of the symbol are used.
+    #[must_use]
+    pub fn map(&self, sym: u8) -> Complex {
+        let sym = sym as usize & (self.order() - 1);
+        let sign = |b: usize| if sym & b == 0 { -1.0 } else { 1.0 };
+        match self {
+            Self::Qpsk => Complex::new(sign(1), sign(2)) / (2.0 as Float).sqrt(),
+            Self::Qam16 => {
+                Complex::new(QAM_LEVELS[sym & 3], QAM_LEVELS[sym >> 2]) / (10.0 as Float).sqrt()
+            }
+            _ => self.phase_point(gray_decode(sym)),
+        }
+    }
+
+    /// All points, indexed by symbol.
+    #[must_use]
+    pub fn points(&self) -> Vec<Complex> {
+        (0..self.order()).map(|s| self.map(s as u8)).collect()
+    }
+
+    // Point at phase index `k`, for PSK. Used directly for differential
+    // encoding, where the symbol is the phase change.
+    fn phase_point(&self, k: usize) -> Complex {
+        if *self == Self::Bpsk {
+            // -1 for 0, to match GNU Radio.
+            return Complex::new(if k == 0 { -1.0 } else { 1.0 }, 0.0);
+        }
+        let m = self.order() as Float;
+        let offset = if *self == Self::Qpsk { 0.5 } else { 0.0 };
+        let pi = std::f64::consts::PI as Float;
+        Complex::from_polar(1.0, 2.0 * pi * (k as Float + offset) / m)
+    }
+}
+
+// Turn Gray code into the position on the circle.
+fn gray_decode(mut g: usize) -> usize {
+    let mut n = 0;
+    while g != 0 {
+        n ^= g;
+        g >>= 1;
+    }
+    n
+}
+
+/// Builder for [`ConstellationModulator`].
+pub struct ConstellationModulatorBuilder {
+    src: ReadStream<u8>,
+    constellation: Constellation,
+    packed: bool,
+    differential: bool,
+    samples_per_symbol: usize,
+    taps: Vec<Float>,
+}
+
+impl ConstellationModulatorBuilder {
+    /// Take packed bytes as input, most significant bit first.
+    ///
+    /// The default is one bit per input byte.
+    #[must_use]
+    pub fn packed(mut self) -> Self {
+        self.packed = true;
+        self
+    }
+
+    /// Encode symbols as phase changes, instead of absolute phases.
+    ///
+    /// This allows the receiver to not care about phase ambiguity. Only
+    /// supported for PSK.
+    #[must_use]
+    pub fn differential(mut self) -> Self {
+        self.differential = true;
+        self
+    }
+
+    /// Pulse shape using the given taps, at the given samples per symbol.
+    ///
+    /// Like for [`InterpFirFilter`](crate::fir::InterpFirFilter), the taps
+    /// need to sum to `samples_per_symbol` for unity gain.
+    #[must_use]
+    pub fn pulse_shape(mut self, samples_per_symbol: usize, taps: &[Float]) -> Self {
+        self.samples_per_symbol = samples_per_symbol;
+        self.taps = taps.to_vec();
+        self
+    }
+
+    /// Pulse shape using a root raised cosine filter, spanning 8 symbols.
+    #[must_use]
+    pub fn rrc(self, samples_per_symbol: usize, alpha: Float) -> Self {
+        let sps = samples_per_symbol as Float;
+        let taps: Vec<Float> =
+            crate::fir::root_raised_cosine(sps, alpha, 8 * samples_per_symbol + 1)
+                .into_iter()
+                .map(|t| t * sps)
+                .collect();
+        self.pulse_shape(samples_per_symbol, &taps)
+    }
+
+    /// Build the `ConstellationModulator`.
+    ///
+    /// # Errors
+    ///
+    /// Errors if differential encoding is requested for a constellation that
+    /// doesn't support it, or if the pulse shape is invalid.
+    pub fn build(self) -> Result<(ConstellationModulator, ReadStream<Complex>)> {
+        if self.differential && !self.constellation.is_psk() {
+            return Err(Error::msg(format!(
+                "ConstellationModulator: differential encoding not supported for {:?}",
+                self.constellation
+            )));
+        }
+        if self.samples_per_symbol == 0 || self.taps.is_empty() {
+            return Err(Error::msg(
+                "ConstellationModulator: pulse shape needs samples per symbol and taps",
+            ));
+        }
+        let sps = self.samples_per_symbol;
+        let ntaps = self.taps.len().div_ceil(sps);
+        let phases = (0..sps)
+            .map(|p| {
+                (0..ntaps)
+                    .map(|j| self.taps.get(j * sps + p).copied().unwrap_or_default())
+                    .collect()
+            })
+            .collect();
+        let (dst, dr) = crate::stream::new_stream();
+        Ok((
+            ConstellationModulator {
+                src: self.src,
+                dst,
+                constellation: self.constellation,
+                packed: self.packed,
+                differential: self.differential,
+                phases,
+                history: std::iter::repeat_n(Complex::default(), ntaps).collect(),
+                acc: 0,
+                nacc: 0,
+                last: 0,
+            },
+            dr,
+        ))
+    }
+}
+
+/// PSK and QAM modulator.
+///
+/// Takes bits or packed bytes, and outputs one `Complex` point per symbol. If
+/// pulse shaping is enabled, `samples_per_symbol` samples are output per
+/// symbol, delayed by half the filter length.
+///
+/// Tags are moved to the first sample of the first symbol that uses bits
+/// from the tagged input.
+#[derive(rustradio_macros::Block)]
+#[rustradio(crate)]
+pub struct ConstellationModulator {
+    #[rustradio(in)]
+    src: ReadStream<u8>,
+    #[rustradio(out)]
+    dst: WriteStream<Complex>,
+
+    constellation: Constellation,
+    packed: bool,
+    differential: bool,
+
+    // Pulse shaping filter, one set of taps per output sample of a symbol.
+    phases: Vec<Vec<Float>>,
+    // Most recent symbol at the front.
+    history: VecDeque<Complex>,
+
+    // Bits not yet making up a symbol.
+    acc: u8,
+    nacc: usize,
+    // Last phase index, for differential encoding.
+    last: usize,
+}
+
+impl ConstellationModulator {
+    /// Create a modulator with one bit per input byte, and no pulse shaping.
+    #[must_use]
+    pub fn new(src: ReadStream<u8>, constellation: Constellation) -> (Self, ReadStream<Complex>) {
+        Self::builder(src, constellation)
+            .build()
+            .expect("default ConstellationModulator is always valid")
+    }
+
+    /// Create a builder.
+    #[must_use]
+    pub fn builder(
+        src: ReadStream<u8>,
+        constellation: Constellation,
+    ) -> ConstellationModulatorBuilder {
+        ConstellationModulatorBuilder {
+            src,
+            constellation,
+            packed: false,
+            differential: false,
+            samples_per_symbol: 1,
+            taps: vec![1.0],
+        }
+    }
+
+    // Turn a complete symbol into its point.
+    fn point(&mut self, sym: u8) -> Complex {
+        let c = self.constellation;
+        if !self.differential {
+            return c.map(sym);
+        }
+        let k = gray_decode(sym as usize & (c.order() - 1));
+        self.last = (self.last + k) % c.order();
+        c.phase_point(self.last)
+    }
+}
+
+impl Block for ConstellationModulator {
+    fn work(&mut self) -> Result<BlockRet<'_>> {
+        let (input, tags) = self.src.read_buf()?;
+        if input.is_empty() {
+            return Ok(BlockRet::WaitForStream(&self.src, 1));
+        }
+        let sps = self.phases.len();
+        let bps = self.constellation.bits_per_symbol();
+        let bits_per_input: usize = if self.packed { 8 } else { 1 };
+        let max_per_input = sps * bits_per_input.div_ceil(bps);
+        let mut out = self.dst.write_buf()?;
+        let n = input.len().min(out.len() / max_per_input);
+        if n == 0 {
+            return Ok(BlockRet::WaitForStream(&self.dst, max_per_input));
+        }
+
+        let mut opos = 0;
+        let mut otags = Vec::new();
+        let mut pending: Vec<Tag> = Vec::new();
+        for (pos, &byte) in input.slice()[..n].iter().enumerate() {
+            pending.extend(tags.iter().filter(|t| t.pos() == pos).cloned());
+            for b in (0..bits_per_input).rev() {
+                self.acc = (self.acc << 1) | ((byte >> b) & 1);
+                self.nacc += 1;
+                if self.nacc < bps {
+                    continue;
+                }
+                let sym = self.acc;
+                self.acc = 0;
+                self.nacc = 0;
+                let point = self.point(sym);
+                self.history.pop_back();
+                self.history.push_front(point);
+                let o = &mut out.slice()[opos..opos + sps];
+                for (o, taps) in o.iter_mut().zip(&self.phases) {
+                    *o = self.history.iter().zip(taps).map(|(s, &t)| s * t).sum();
+                }
+                otags.extend(pending.drain(..).map(|mut t| {
+                    t.set_pos(opos);
+                    t
+                }));
+                opos += sps;
+            }
+        }
+        // Tags on bits not yet sent go on the next symbol.
+        otags.extend(pending.into_iter().map(|mut t| {
+            t.set_pos(opos);
+            t
+        }));
+        input.consume(n);
+        out.produce(opos, &otags);
+        Ok(BlockRet::Again)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::VectorSource;
+    use crate::stream::TagValue;
+
+    const ALL: [Constellation; 4] = [
+        Constellation::Bpsk,
+        Constellation::Qpsk,
+        Constellation::Psk8,
+        Constellation::Qam16,
+    ];
+
+    fn run(b: ConstellationModulatorBuilder) -> Result<Vec<Complex>> {
+        let (mut m, out) = b.build()?;
+        m.work()?;
+        let (o, _) = out.read_buf()?;
+        Ok(o.slice().to_vec())
+    }
+
+    #[test]
+    fn points() {
+        for c in ALL {
+            let points = c.points();
+            assert_eq!(points.len(), c.order());
+            let power: Float =
+                points.iter().map(Complex::norm_sqr).sum::<Float>() / c.order() as Float;
+            assert!((power - 1.0).abs() < 1e-5, "{c:?}");
+
+            // Gray coded: the nearest neighbours differ by one bit.
+            let min = points
+                .iter()
+                .enumerate()
+                .flat_map(|(i, a)| points[i + 1..].iter().map(move |b| (a - b).norm()))
+                .fold(Float::MAX, Float::min);
+            for (i, a) in points.iter().enumerate() {
+                for (j, b) in points.iter().enumerate() {
+                    if i != j && (a - b).norm() < min + 1e-4 {
+                        assert_eq!((i ^ j).count_ones(), 1, "{c:?} {i} {j}");
+                    }
+                }
+            }
+        }
+        assert_eq!(Constellation::Bpsk.map(0), Complex::new(-1.0, 0.0));
+        let q = Constellation::Qpsk.map(1);
+        assert!(q.re > 0.0 && q.im < 0.0, "{q}");
+    }
+
+    #[test]
+    fn packed_and_unpacked() -> Result<()> {
+        let bytes = vec![0x1b, 0xe4, 0x5a];
+        let bits: Vec<u8> = bytes
+            .iter()
+            .flat_map(|b| (0..8).rev().map(move |n| (b >> n) & 1))
+            .collect();
+        for c in ALL {
+            let (mut src, prev) = VectorSource::new(bytes.clone());
+            src.work()?;
+            let packed = run(ConstellationModulator::builder(prev, c).packed())?;
+            let (mut src, prev) = VectorSource::new(bits.clone());
+            src.work()?;
+            let unpacked = run(ConstellationModulator::builder(prev, c))?;
+            assert_eq!(packed, unpacked, "{c:?}");
+            assert_eq!(packed.len(), 24 / c.bits_per_symbol());
+        }
+        // QPSK 0x1b is symbols 0, 1, 2, 3.
+        let (mut src, prev) = VectorSource::new(vec![0x1b]);
+        src.work()?;
+        let got = run(ConstellationModulator::builder(prev, Constellation::Qpsk).packed())?;
+        assert_eq!(got, Constellation::Qpsk.points());
+        Ok(())
+    }
+
+    #[test]
+    fn differential() -> Result<()> {
+        let (mut src, prev) = VectorSource::new(vec![1u8, 0, 0, 1, 1]);
+        src.work()?;
+        let got = run(ConstellationModulator::builder(prev, Constellation::Bpsk).differential())?;
+        let (p, n) = (Complex::new(1.0, 0.0), Complex::new(-1.0, 0.0));
+        let want = [p, p, p, n, p];
+        for (g, w) in got.iter().zip(&want) {
+            assert!((g - w).norm() < 1e-5, "{got:?}");
+        }
+
+        // Differential QPSK: each symbol is a phase change.
+        let (mut src, prev) = VectorSource::new(vec![0x55]);
+        src.work()?;
+        let got = run(ConstellationModulator::builder(prev, Constellation::Qpsk)
+            .packed()
+            .differential())?;
+        for w in got.windows(2) {
+            let d = (w[1] * w[0].conj()).arg();
+            assert!((d - std::f32::consts::FRAC_PI_2).abs() < 1e-5, "{d}");
+        }
+
+        let (_, prev) = crate::stream::new_stream::<u8>();
+        assert!(
+            ConstellationModulator::builder(prev, Constellation::Qam16)
+                .differential()
+                .build()
+                .is_err()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn pulse_shaping() -> Result<()> {
+        // With raised cosine shaping there's no intersymbol interference, so
+        // the symbol points come out at the center of each pulse.
+        let sps = 8;
+        let taps: Vec<Float> = crate::fir::raised_cosine(sps as Float, 0.35, 8 * sps + 1)
+            .into_iter()
+            .map(|t| t * sps as Float)
+            .collect();
+        let syms: Vec<u8> = (0..40).map(|n| (n * 7 % 16) as u8).collect();
+        let (mut src, prev) = VectorSource::builder(syms.clone())
+            .tags(&[Tag::new(3, "foo", TagValue::Bool(true))])
+            .build()?;
+        src.work()?;
+        let (mut b, out) = ConstellationModulator::builder(prev, Constellation::Qam16)
+            .packed()
+            .pulse_shape(sps, &taps)
+            .build()?;
+        b.work()?;
+        let (o, tags) = out.read_buf()?;
+        // Packed, so each byte is two symbols.
+        assert_eq!(o.len(), 80 * sps);
+        assert!(tags.contains(&Tag::new(6 * sps, "foo", TagValue::Bool(true))));
+        let gain = taps[4 * sps];
+        for k in 0..70 {
+            let byte = syms[k / 2];
+            let sym = if k % 2 == 0 { byte >> 4 } else { byte & 0xf };
+            let want = Constellation::Qam16.map(sym) * gain;
+            let got = o.slice()[(k + 4) * sps];
+            assert!((got - want).norm() < 1e-4, "{k}: {got} != {want}");
+        }
+        Ok(())
+    }
+}
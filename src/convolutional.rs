@@ -0,0 +1,901 @@
+/*! Convolutional codes, and soft decision Viterbi decoding.
+
+A code is defined by its constraint length K, and one generator polynomial
+per output bit. The polynomials are written the way the standards write
+them, e.g. CCSDS `0o171` and `0o133`: the most significant bit (bit K-1)
+taps the newest input bit.
+
+Higher rates are made by puncturing, i.e. not sending some of the output
+bits. The decoder fills them back in as unknown.
+
+Both the encoder and decoder come as stream blocks,
+[`ConvolutionalEncode`] and [`ViterbiDecode`], and as packet blocks,
+[`ConvolutionalEncodePdu`] and [`ViterbiDecodePdu`]. Packets start with the
+encoder all zeros, and end with K-1 zero bits, bringing it back to zero.
+
+Soft bits are `Float` log likelihood ratios, positive meaning 1, like the
+input to [`BinarySlicer`](crate::blocks::BinarySlicer). Only the sign and
+relative size matter, so ±1 works for hard bits, and 0 means no
+information.
+*/
+use std::collections::VecDeque;
+
+use crate::block::{Block, BlockEOF, BlockRet};
+use crate::stream::{NCReadStream, NCWriteStream, ReadStream, Tag, WriteStream};
+use crate::{Error, Float, Result};
+
+// Largest supported constraint length.
+const MAX_K: usize = 16;
+
+// Most supported outputs per input bit, i.e. polynomials.
+const MAX_N: usize = 8;
+
+/// Convolutional code.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConvolutionalCode {
+    k: usize,
+    polys: Vec<u32>,
+    inverted: Vec<bool>,
+    // Which outputs are sent, `n` per input bit. Empty if not punctured.
+    puncture: Vec<bool>,
+}
+
+// Encoder shift register, and position in the puncturing pattern.
+#[derive(Debug, Default)]
+struct EncoderState {
+    reg: usize,
+    phase: usize,
+}
+
+impl ConvolutionalCode {
+    /// Create a new code with constraint length `k`.
+    ///
+    /// `inverted` lists which outputs are inverted, and can be empty if
+    /// none are.
+    ///
+    /// # Errors
+    ///
+    /// Errors if `k` is out of range, there are more than 8 polynomials, a
+    /// polynomial doesn't fit in `k` bits, or `inverted` is not empty and not
+    /// the same length as `polys`.
+    pub fn new(k: usize, polys: &[u32], inverted: &[bool]) -> Result<Self> {
+        if !(2..=MAX_K).contains(&k) {
+            return Err(Error::msg(format!(
+                "constraint length {k} out of range 2..={MAX_K}"
+            )));
+        }
+        if polys.len() > MAX_N {
+            return Err(Error::msg(format!(
+                "{} polynomials, max is {MAX_N}",
+                polys.len()
+            )));
+        }
+        if polys.is_empty() || polys.iter().any(|&p| p == 0 || p >> k != 0) {
+            return Err(Error::msg(format!(
+                "invalid polynomials {polys:?} for constraint length {k}"
+            )));
+        }
+        let inverted = match inverted.len() {
+            0 => vec![false; polys.len()],
+            n if n == polys.len() => inverted.to_vec(),
+            n => {
+                return Err(Error::msg(format!(
+                    "{n} inversion flags for {} polynomials",
+                    polys.len()
+                )));
+            }
+        };
+        Ok(Self {
+            k,
+            polys: polys.to_vec(),
+            inverted,
+            puncture: Vec::new(),
+        })
+    }
+
+    /// Puncture the code, not sending some of the outputs.
+    ///
+    /// The pattern has one entry per output bit, for a whole number of
+    /// input bits, with zero meaning not sent. E.g. rate 3/4 from a rate
+    /// 1/2 code, as used by CCSDS and DVB-S, is `[1, 1, 0, 1, 1, 0]`.
+    ///
+    /// # Errors
+    ///
+    /// Errors if the pattern length is not a multiple of the number of
+    /// outputs, or if all outputs for some input bit are dropped.
+    pub fn punctured(mut self, pattern: &[u8]) -> Result<Self> {
+        let n = self.n();
+        if pattern.is_empty()
+            || !pattern.len().is_multiple_of(n)
+            || pattern.chunks(n).any(|c| c.iter().all(|&p| p == 0))
+        {
+            return Err(Error::msg(format!(
+                "invalid puncturing pattern {pattern:?} for {n} outputs"
+            )));
+        }
+        self.puncture = pattern.iter().map(|&p| p != 0).collect();
+        Ok(self)
+    }
+
+    /// The CCSDS K=7 rate 1/2 code, with the second output inverted.
+    #[must_use]
+    pub fn ccsds() -> Self {
+        Self::new(7, &[0o171, 0o133], &[false, true]).expect("can't happen: valid code")
+    }
+
+    /// Constraint length.
+    #[must_use]
+    pub fn k(&self) -> usize {
+        self.k
+    }
+
+    /// Output bits per input bit, before puncturing.
+    #[must_use]
+    pub fn n(&self) -> usize {
+        self.polys.len()
+    }
+
+    /// Code rate, as input bits and sent output bits per puncturing period.
+    #[must_use]
+    pub fn rate(&self) -> (usize, usize) {
+        if self.puncture.is_empty() {
+            (1, self.n())
+        } else {
+            (
+                self.puncture.len() / self.n(),
+                self.puncture.iter().filter(|&&p| p).count(),
+            )
+        }
+    }
+
+    // Number of input bits in the puncturing pattern.
+    fn period(&self) -> usize {
+        self.rate().0
+    }
+
+    // Return true if output `i` for input bit `phase` of the puncturing
+    // pattern is sent.
+    fn sent(&self, phase: usize, i: usize) -> bool {
+        self.puncture.is_empty() || self.puncture[phase * self.n() + i]
+    }
+
+    // Number of outputs sent for input bit `phase` of the puncturing
+    // pattern.
+    fn sent_count(&self, phase: usize) -> usize {
+        (0..self.n()).filter(|&i| self.sent(phase, i)).count()
+    }
+
+    fn encode_bit(&self, state: &mut EncoderState, bit: u8, out: &mut Vec<u8>) {
+        state.reg = (state.reg >> 1) | (usize::from(bit & 1) << (self.k - 1));
+        let o = self.output(state.reg);
+        out.extend(
+            (0..self.n())
+                .filter(|&i| self.sent(state.phase, i))
+                .map(|i| ((o >> i) & 1) as u8),
+        );
+        state.phase = (state.phase + 1) % self.period();
+    }
+
+    // Output bits for a full shift register, first output in bit 0.
+    fn output(&self, reg: usize) -> u32 {
+        self.polys
+            .iter()
+            .zip(&self.inverted)
+            .enumerate()
+            .fold(0, |acc, (i, (&p, &inv))| {
+                let bit = ((reg as u32 & p).count_ones() & 1) ^ u32::from(inv);
+                acc | (bit << i)
+            })
+    }
+
+    /// Encode bits, starting with the shift register all zero.
+    ///
+    /// No tail is added. To terminate the code, append K-1 zeros.
+    #[must_use]
+    pub fn encode(&self, bits: &[u8]) -> Vec<u8> {
+        let mut state = EncoderState::default();
+        let mut out = Vec::with_capacity(bits.len() * self.n());
+        for &bit in bits {
+            self.encode_bit(&mut state, bit, &mut out);
+        }
+        out
+    }
+}
+
+/// Viterbi decoder state.
+///
+/// The state is the last K-1 input bits, the newest in bit K-2.
+#[derive(Debug, Clone)]
+pub struct Viterbi {
+    code: ConvolutionalCode,
+    // Expected outputs for each full shift register.
+    outputs: Vec<u32>,
+    metrics: Vec<Float>,
+    next: Vec<Float>,
+    branch: Vec<Float>,
+    // Soft bits for one step, with punctured ones filled in.
+    soft: Vec<Float>,
+    phase: usize,
+    // Survivor decisions, `words` u64s per step, oldest first.
+    decisions: Vec<u64>,
+    words: usize,
+    depth: usize,
+}
+
+impl Viterbi {
+    /// Create a new decoder, outputting bits `depth` steps behind the
+    /// input.
+    ///
+    /// A depth of about five times K is enough for unpunctured codes, and
+    /// punctured codes need more. See [`Viterbi::default_depth()`].
+    #[must_use]
+    pub fn new(code: ConvolutionalCode, depth: usize) -> Self {
+        let nstates = 1 << (code.k - 1);
+        let outputs = (0..1 << code.k).map(|r| code.output(r)).collect();
+        let branch = vec![0.0; 1 << code.n()];
+        Self {
+            outputs,
+            metrics: vec![0.0; nstates],
+            next: vec![0.0; nstates],
+            branch,
+            soft: vec![0.0; code.n()],
+            phase: 0,
+            decisions: Vec::new(),
+            words: nstates.div_ceil(64),
+            depth: depth.max(1),
+            code,
+        }
+    }
+
+    /// Return a traceback depth comfortably long enough for the code.
+    #[must_use]
+    pub fn default_depth(code: &ConvolutionalCode) -> usize {
+        if code.puncture.is_empty() {
+            10 * code.k
+        } else {
+            20 * code.k
+        }
+    }
+
+    /// The code being decoded.
+    #[must_use]
+    pub fn code(&self) -> &ConvolutionalCode {
+        &self.code
+    }
+
+    /// Forget everything.
+    ///
+    /// If `zero_state`, then the encoder is known to start all zeros.
+    /// Otherwise all starting states are equally likely.
+    pub fn reset(&mut self, zero_state: bool) {
+        self.decisions.clear();
+        self.phase = 0;
+        self.metrics.fill(if zero_state { -1e9 } else { 0.0 });
+        self.metrics[0] = 0.0;
+    }
+
+    /// Number of decoded bits not yet returned.
+    #[must_use]
+    pub fn pending(&self) -> usize {
+        self.decisions.len() / self.words
+    }
+
+    /// Number of soft bits needed for the next step.
+    ///
+    /// This is `n`, unless the code is punctured.
+    #[must_use]
+    pub fn step_len(&self) -> usize {
+        self.code.sent_count(self.phase)
+    }
+
+    /// Add one step of [`Viterbi::step_len()`] soft bits.
+    pub fn step(&mut self, soft: &[Float]) {
+        assert_eq!(soft.len(), self.step_len());
+        let mut sent = soft.iter();
+        for (i, s) in self.soft.iter_mut().enumerate() {
+            *s = if self.code.sent(self.phase, i) {
+                *sent.next().expect("can't happen: length checked")
+            } else {
+                0.0
+            };
+        }
+        self.phase = (self.phase + 1) % self.code.period();
+        for (pattern, m) in self.branch.iter_mut().enumerate() {
+            *m = self
+                .soft
+                .iter()
+                .enumerate()
+                .map(|(i, &s)| if pattern & (1 << i) == 0 { -s } else { s })
+                .sum();
+        }
+        let nstates = self.metrics.len();
+        let shift = self.code.k - 2;
+        let start = self.decisions.len();
+        self.decisions.resize(start + self.words, 0);
+        let mut best = Float::MIN;
+        for ns in 0..nstates {
+            let input = ns >> shift;
+            let s0 = (ns << 1) & (nstates - 1);
+            let s1 = s0 | 1;
+            let reg0 = (input << (self.code.k - 1)) | s0;
+            let m0 = self.metrics[s0] + self.branch[self.outputs[reg0] as usize];
+            let m1 = self.metrics[s1] + self.branch[self.outputs[reg0 | 1] as usize];
+            let m = if m1 > m0 {
+                self.decisions[start + ns / 64] |= 1 << (ns % 64);
+                m1
+            } else {
+                m0
+            };
+            best = best.max(m);
+            self.next[ns] = m;
+        }
+        // Keep the metrics from growing without bound.
+        for (m, &n) in self.metrics.iter_mut().zip(&self.next) {
+            *m = n - best;
+        }
+    }
+
+    fn best_state(&self) -> usize {
+        self.metrics
+            .iter()
+            .enumerate()
+            .fold(
+                (0, Float::MIN),
+                |acc, (i, &m)| if m > acc.1 { (i, m) } else { acc },
+            )
+            .0
+    }
+
+    /// Decode bits older than `keep` steps, into `out`.
+    ///
+    /// If `end` is given, then trace back from that state, e.g. 0 for a
+    /// terminated code. Otherwise from the most likely state.
+    pub fn traceback(&mut self, out: &mut Vec<u8>, keep: usize, end: Option<usize>) {
+        let steps = self.pending();
+        if steps <= keep {
+            return;
+        }
+        let nstates = self.metrics.len();
+        let shift = self.code.k - 2;
+        let mut state = end.unwrap_or_else(|| self.best_state());
+        let start = out.len();
+        out.resize(start + steps - keep, 0);
+        for t in (0..steps).rev() {
+            if t < steps - keep {
+                out[start + t] = (state >> shift) as u8;
+            }
+            let d = (self.decisions[t * self.words + state / 64] >> (state % 64)) & 1;
+            state = ((state << 1) & (nstates - 1)) | d as usize;
+        }
+        self.decisions.drain(..(steps - keep) * self.words);
+    }
+
+    /// Decode a whole block of soft bits, starting in the zero state.
+    ///
+    /// If `terminated`, then the block ends with K-1 zero tail bits, which
+    /// are not included in the output. Trailing soft bits not making up a
+    /// whole step are ignored.
+    #[must_use]
+    pub fn decode_block(&mut self, soft: &[Float], terminated: bool) -> Vec<u8> {
+        self.reset(true);
+        let mut out = Vec::new();
+        let mut pos = 0;
+        while pos + self.step_len() <= soft.len() {
+            let len = self.step_len();
+            self.step(&soft[pos..pos + len]);
+            pos += len;
+            if self.pending() >= 2 * self.depth {
+                self.traceback(&mut out, self.depth, None);
+            }
+        }
+        self.traceback(&mut out, 0, terminated.then_some(0));
+        if terminated {
+            out.truncate(out.len().saturating_sub(self.code.k - 1));
+        }
+        out
+    }
+}
+
+/// Viterbi decoder for a stream of soft bits.
+///
+/// The input must be aligned to the code, i.e. the first soft bit is the
+/// first output of the encoder for some input bit, at the start of the
+/// puncturing pattern if any. Tags are moved to the bit they belong to.
+#[derive(rustradio_macros::Block)]
+#[rustradio(crate, noeof)]
+pub struct ViterbiDecode {
+    #[rustradio(in)]
+    src: ReadStream<Float>,
+    #[rustradio(out)]
+    dst: WriteStream<u8>,
+    viterbi: Viterbi,
+    // Decoded bits not yet written, and their stream position.
+    out: VecDeque<u8>,
+    out_pos: u64,
+    // Input bit count, and tags with their output position.
+    in_pos: u64,
+    tags: VecDeque<(u64, Tag)>,
+    flushed: bool,
+}
+
+impl ViterbiDecode {
+    /// Create a new Viterbi decoder block.
+    #[must_use]
+    pub fn new(src: ReadStream<Float>, code: ConvolutionalCode) -> (Self, ReadStream<u8>) {
+        let depth = Viterbi::default_depth(&code);
+        let (dst, dr) = crate::stream::new_stream();
+        let mut viterbi = Viterbi::new(code, depth);
+        viterbi.reset(false);
+        (
+            Self {
+                src,
+                dst,
+                viterbi,
+                out: VecDeque::new(),
+                out_pos: 0,
+                in_pos: 0,
+                tags: VecDeque::new(),
+                flushed: false,
+            },
+            dr,
+        )
+    }
+
+    // Write out as much as possible of the decoded bits.
+    fn write(&mut self) -> Result<()> {
+        let mut o = self.dst.write_buf()?;
+        let n = o.len().min(self.out.len());
+        if n == 0 {
+            return Ok(());
+        }
+        for (dst, src) in o.slice()[..n].iter_mut().zip(self.out.drain(..n)) {
+            *dst = src;
+        }
+        let end = self.out_pos + n as u64;
+        let mut tags = Vec::new();
+        while let Some((pos, _)) = self.tags.front() {
+            if *pos >= end {
+                break;
+            }
+            let (pos, mut tag) = self.tags.pop_front().expect("can't happen: peeked");
+            tag.set_pos((pos - self.out_pos) as usize);
+            tags.push(tag);
+        }
+        o.produce(n, &tags);
+        self.out_pos = end;
+        Ok(())
+    }
+}
+
+impl BlockEOF for ViterbiDecode {
+    fn eof(&mut self) -> bool {
+        self.flushed && self.out.is_empty()
+    }
+}
+
+impl Block for ViterbiDecode {
+    fn work(&mut self) -> Result<BlockRet<'_>> {
+        let depth = self.viterbi.depth;
+        loop {
+            self.write()?;
+            if !self.out.is_empty() {
+                return Ok(BlockRet::WaitForStream(&self.dst, 1));
+            }
+            if self.flushed {
+                return Ok(BlockRet::EOF);
+            }
+            // Check before reading, since the read buffer holds a reference.
+            let writer_done = self.src.refcount() == 1;
+            let (i, mut tags) = self.src.read_buf()?;
+            let need = self.viterbi.step_len();
+            if i.len() < need {
+                if !writer_done {
+                    return Ok(BlockRet::WaitForStream(&self.src, need));
+                }
+                // Input is done. Drop any partial step, and decode the rest.
+                let len = i.len();
+                i.consume(len);
+                let mut bits = Vec::new();
+                self.viterbi.traceback(&mut bits, 0, None);
+                self.out.extend(bits);
+                self.flushed = true;
+                continue;
+            }
+            tags.sort_by_key(Tag::pos);
+            let mut tags = tags.into_iter().peekable();
+            let input = i.slice();
+            let mut bits = Vec::new();
+            let mut pos = 0;
+            while pos + self.viterbi.step_len() <= input.len() {
+                let len = self.viterbi.step_len();
+                while let Some(tag) = tags.next_if(|t| t.pos() < pos + len) {
+                    self.tags.push_back((self.in_pos, tag));
+                }
+                self.viterbi.step(&input[pos..pos + len]);
+                self.in_pos += 1;
+                pos += len;
+                if self.viterbi.pending() >= 2 * depth {
+                    self.viterbi.traceback(&mut bits, depth, None);
+                }
+            }
+            self.out.extend(bits);
+            i.consume(pos);
+        }
+    }
+}
+
+/// Convolutional encoder for a stream of bits.
+///
+/// Tags are moved to the first output bit of their input bit.
+#[derive(rustradio_macros::Block)]
+#[rustradio(crate)]
+pub struct ConvolutionalEncode {
+    #[rustradio(in)]
+    src: ReadStream<u8>,
+    #[rustradio(out)]
+    dst: WriteStream<u8>,
+    code: ConvolutionalCode,
+    state: EncoderState,
+}
+
+impl ConvolutionalEncode {
+    /// Create a new convolutional encoder block.
+    #[must_use]
+    pub fn new(src: ReadStream<u8>, code: ConvolutionalCode) -> (Self, ReadStream<u8>) {
+        let (dst, dr) = crate::stream::new_stream();
+        (
+            Self {
+                src,
+                dst,
+                code,
+                state: EncoderState::default(),
+            },
+            dr,
+        )
+    }
+}
+
+impl Block for ConvolutionalEncode {
+    fn work(&mut self) -> Result<BlockRet<'_>> {
+        let n = self.code.n();
+        let (i, mut tags) = self.src.read_buf()?;
+        if i.is_empty() {
+            return Ok(BlockRet::WaitForStream(&self.src, 1));
+        }
+        let mut o = self.dst.write_buf()?;
+        let len = i.len().min(o.len() / n);
+        if len == 0 {
+            return Ok(BlockRet::WaitForStream(&self.dst, n));
+        }
+        tags.sort_by_key(Tag::pos);
+        let mut tags = tags.into_iter().peekable();
+        let mut out = Vec::with_capacity(len * n);
+        let mut otags = Vec::new();
+        for (pos, &bit) in i.slice()[..len].iter().enumerate() {
+            while let Some(mut tag) = tags.next_if(|t| t.pos() <= pos) {
+                tag.set_pos(out.len());
+                otags.push(tag);
+            }
+            self.code.encode_bit(&mut self.state, bit, &mut out);
+        }
+        o.slice()[..out.len()].copy_from_slice(&out);
+        o.produce(out.len(), &otags);
+        i.consume(len);
+        Ok(BlockRet::Again)
+    }
+}
+
+/// Convolutional encoder for packets of bits.
+///
+/// Each packet is encoded from the zero state, with K-1 zero bits added to
+/// the end. Tags are kept as is.
+#[derive(rustradio_macros::Block)]
+#[rustradio(crate)]
+pub struct ConvolutionalEncodePdu {
+    #[rustradio(in)]
+    src: NCReadStream<Vec<u8>>,
+    #[rustradio(out)]
+    dst: NCWriteStream<Vec<u8>>,
+    code: ConvolutionalCode,
+}
+
+impl ConvolutionalEncodePdu {
+    /// Create a new convolutional packet encoder block.
+    #[must_use]
+    pub fn new(
+        src: NCReadStream<Vec<u8>>,
+        code: ConvolutionalCode,
+    ) -> (Self, NCReadStream<Vec<u8>>) {
+        let (dst, dr) = crate::stream::new_nocopy_stream();
+        (Self { src, dst, code }, dr)
+    }
+}
+
+impl Block for ConvolutionalEncodePdu {
+    fn work(&mut self) -> Result<BlockRet<'_>> {
+        loop {
+            if self.dst.remaining() == 0 {
+                return Ok(BlockRet::WaitForStream(&self.dst, 1));
+            }
+            let Some((mut bits, tags)) = self.src.pop() else {
+                return Ok(BlockRet::WaitForStream(&self.src, 1));
+            };
+            bits.resize(bits.len() + self.code.k() - 1, 0);
+            self.dst.push(self.code.encode(&bits), tags);
+        }
+    }
+}
+
+/// Viterbi decoder for packets of soft bits.
+///
+/// The packets must be as from [`ConvolutionalEncodePdu`], i.e. start and
+/// end in the zero state. The tail is removed. Tags are kept as is.
+#[derive(rustradio_macros::Block)]
+#[rustradio(crate)]
+pub struct ViterbiDecodePdu {
+    #[rustradio(in)]
+    src: NCReadStream<Vec<Float>>,
+    #[rustradio(out)]
+    dst: NCWriteStream<Vec<u8>>,
+    viterbi: Viterbi,
+}
+
+impl ViterbiDecodePdu {
+    /// Create a new Viterbi packet decoder block.
+    #[must_use]
+    pub fn new(
+        src: NCReadStream<Vec<Float>>,
+        code: ConvolutionalCode,
+    ) -> (Self, NCReadStream<Vec<u8>>) {
+        let (dst, dr) = crate::stream::new_nocopy_stream();
+        let depth = Viterbi::default_depth(&code);
+        (
+            Self {
+                src,
+                dst,
+                viterbi: Viterbi::new(code, depth),
+            },
+            dr,
+        )
+    }
+}
+
+impl Block for ViterbiDecodePdu {
+    fn work(&mut self) -> Result<BlockRet<'_>> {
+        loop {
+            if self.dst.remaining() == 0 {
+                return Ok(BlockRet::WaitForStream(&self.dst, 1));
+            }
+            let Some((soft, tags)) = self.src.pop() else {
+                return Ok(BlockRet::WaitForStream(&self.src, 1));
+            };
+            self.dst.push(self.viterbi.decode_block(&soft, true), tags);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::VectorSource;
+    use crate::stream::TagValue;
+    use rand::{Rng, SeedableRng};
+
+    fn soft(bits: &[u8], noise: Float, rng: &mut impl Rng) -> Vec<Float> {
+        bits.iter()
+            .map(|&b| if b == 1 { 1.0 } else { -1.0 } + rng.random_range(-noise..noise))
+            .collect()
+    }
+
+    #[test]
+    fn encode_ccsds() {
+        let code = ConvolutionalCode::ccsds();
+        // An impulse gives the generator polynomials, with G2 inverted.
+        let out = code.encode(&[1, 0, 0, 0, 0, 0, 0]);
+        let g1: Vec<u8> = out.iter().step_by(2).copied().collect();
+        let g2: Vec<u8> = out.iter().skip(1).step_by(2).map(|b| b ^ 1).collect();
+        assert_eq!(g1, [1, 1, 1, 1, 0, 0, 1]);
+        assert_eq!(g2, [1, 0, 1, 1, 0, 1, 1]);
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(ConvolutionalCode::new(1, &[1], &[]).is_err());
+        assert!(ConvolutionalCode::new(3, &[0o7, 0o10], &[]).is_err());
+        assert!(ConvolutionalCode::new(3, &[0o7, 0o5], &[true]).is_err());
+        assert!(ConvolutionalCode::new(3, &[0o7, 0o5], &[]).is_ok());
+        assert!(ConvolutionalCode::new(3, &[0o7; 8], &[]).is_ok());
+        assert!(ConvolutionalCode::new(3, &[0o7; 9], &[]).is_err());
+        assert!(ConvolutionalCode::new(3, &[0o7; 64], &[]).is_err());
+        let code = ConvolutionalCode::ccsds();
+        assert!(code.clone().punctured(&[]).is_err());
+        assert!(code.clone().punctured(&[1, 1, 0]).is_err());
+        assert!(code.clone().punctured(&[1, 1, 0, 0]).is_err());
+    }
+
+    #[test]
+    fn punctured() -> Result<()> {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(3);
+        let code = ConvolutionalCode::ccsds();
+        assert_eq!(code.rate(), (1, 2));
+        for (pattern, rate, noise) in [
+            (vec![1, 1, 0, 1], (2, 3), 1.0),
+            (vec![1, 1, 0, 1, 1, 0], (3, 4), 0.9),
+        ] {
+            let code = code.clone().punctured(&pattern)?;
+            assert_eq!(code.rate(), rate);
+            let mut bits: Vec<u8> = (0..999).map(|_| rng.random_range(0..=1)).collect();
+            bits.extend(vec![0; code.k() - 1]);
+            let enc = code.encode(&bits);
+            let full = ConvolutionalCode::ccsds().encode(&bits);
+            let kept: Vec<u8> = full
+                .iter()
+                .zip(pattern.iter().cycle())
+                .filter(|(_, p)| **p == 1)
+                .map(|(b, _)| *b)
+                .collect();
+            assert_eq!(enc, kept);
+            let sym = soft(&enc, noise, &mut rng);
+            let mut v = Viterbi::new(code.clone(), Viterbi::default_depth(&code));
+            let got = v.decode_block(&sym, true);
+            assert_eq!(got.len(), 999);
+            let errors = got.iter().zip(&bits).filter(|(a, b)| a != b).count();
+            assert_eq!(errors, 0, "{pattern:?}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn decode_block() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(1);
+        // Weaker codes get less noise.
+        for (code, noise) in [
+            (ConvolutionalCode::ccsds(), 1.2),
+            (ConvolutionalCode::new(3, &[0o7, 0o5], &[]).unwrap(), 1.1),
+            (
+                ConvolutionalCode::new(9, &[0o753, 0o561], &[]).unwrap(),
+                1.2,
+            ),
+            (
+                ConvolutionalCode::new(7, &[0o171, 0o133, 0o165], &[]).unwrap(),
+                1.4,
+            ),
+        ] {
+            let mut bits: Vec<u8> = (0..1000).map(|_| rng.random_range(0..=1)).collect();
+            bits.extend(vec![0; code.k() - 1]);
+            let sym = soft(&code.encode(&bits), noise, &mut rng);
+            let mut v = Viterbi::new(code.clone(), 5 * code.k());
+            let got = v.decode_block(&sym, true);
+            assert_eq!(got.len(), 1000);
+            let errors = got.iter().zip(&bits).filter(|(a, b)| a != b).count();
+            assert_eq!(errors, 0, "{code:?}");
+        }
+    }
+
+    #[test]
+    fn decode_stream() -> Result<()> {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(2);
+        let code = ConvolutionalCode::ccsds();
+        let bits: Vec<u8> = (0..5000).map(|_| rng.random_range(0..=1)).collect();
+        let sym = soft(&code.encode(&bits), 1.2, &mut rng);
+        let (mut b, prev) = VectorSource::builder(sym)
+            .tags(&[Tag::new(2001, "mark", TagValue::Bool(true))])
+            .build()?;
+        b.work()?;
+        drop(b);
+        let (mut b, prev) = ViterbiDecode::new(prev, code);
+        assert!(matches!(b.work()?, BlockRet::EOF));
+        assert!(b.eof());
+        let (out, tags) = prev.read_buf()?;
+        assert_eq!(out.slice(), bits);
+        assert!(tags.contains(&Tag::new(1000, "mark", TagValue::Bool(true))));
+        Ok(())
+    }
+
+    #[test]
+    fn stream_arbitrary() -> Result<()> {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(6);
+        for (code, noise) in [
+            (
+                ConvolutionalCode::new(5, &[0o23, 0o35], &[true, false])?,
+                1.1,
+            ),
+            (ConvolutionalCode::new(9, &[0o557, 0o663, 0o711], &[])?, 1.4),
+        ] {
+            let bits: Vec<u8> = (0..3000).map(|_| rng.random_range(0..=1)).collect();
+            let (mut b, prev) = VectorSource::new(bits.clone());
+            b.work()?;
+            drop(b);
+            let (mut b, prev) = ConvolutionalEncode::new(prev, code.clone());
+            b.work()?;
+            drop(b);
+            let (enc, _) = prev.read_buf()?;
+            assert_eq!(enc.slice(), code.encode(&bits), "{code:?}");
+            let sym = soft(enc.slice(), noise, &mut rng);
+            drop(enc);
+            drop(prev);
+
+            let (mut b, prev) = VectorSource::new(sym);
+            b.work()?;
+            drop(b);
+            let (mut b, prev) = ViterbiDecode::new(prev, code.clone());
+            assert!(matches!(b.work()?, BlockRet::EOF));
+            let (out, _) = prev.read_buf()?;
+            assert_eq!(out.len(), bits.len(), "{code:?}");
+            let errors = out
+                .slice()
+                .iter()
+                .zip(&bits)
+                .filter(|(a, b)| a != b)
+                .count();
+            assert_eq!(errors, 0, "{code:?}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn stream_punctured() -> Result<()> {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(4);
+        let code = ConvolutionalCode::ccsds().punctured(&[1, 1, 0, 1, 1, 0])?;
+        let bits: Vec<u8> = (0..3000).map(|_| rng.random_range(0..=1)).collect();
+        let (mut b, prev) = VectorSource::builder(bits.clone())
+            .tags(&[Tag::new(2, "mark", TagValue::Bool(true))])
+            .build()?;
+        b.work()?;
+        drop(b);
+        let (mut b, prev) = ConvolutionalEncode::new(prev, code.clone());
+        b.work()?;
+        drop(b);
+        let (enc, tags) = prev.read_buf()?;
+        assert_eq!(enc.slice(), code.encode(&bits));
+        // Bits 0 and 1 send 2+1 symbols.
+        assert!(tags.contains(&Tag::new(3, "mark", TagValue::Bool(true))));
+        let sym = soft(enc.slice(), 0.9, &mut rng);
+        drop(enc);
+        drop(prev);
+
+        let (mut b, prev) = VectorSource::builder(sym)
+            .tags(&[Tag::new(3, "mark", TagValue::Bool(true))])
+            .build()?;
+        b.work()?;
+        drop(b);
+        let (mut b, prev) = ViterbiDecode::new(prev, code);
+        assert!(matches!(b.work()?, BlockRet::EOF));
+        let (out, tags) = prev.read_buf()?;
+        assert_eq!(out.slice(), bits);
+        assert!(tags.contains(&Tag::new(2, "mark", TagValue::Bool(true))));
+        Ok(())
+    }
+
+    #[test]
+    fn pdu() -> Result<()> {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(5);
+        let code = ConvolutionalCode::new(5, &[0o23, 0o35], &[])?;
+        let (tx, rx) = crate::stream::new_nocopy_stream();
+        let (mut enc, prev) = ConvolutionalEncodePdu::new(rx, code.clone());
+        let packets: Vec<Vec<u8>> = [10, 1, 300]
+            .iter()
+            .map(|&len| (0..len).map(|_| rng.random_range(0..=1)).collect())
+            .collect();
+        for p in &packets {
+            tx.push(
+                p.clone(),
+                [Tag::new(0, "len", TagValue::U64(p.len() as u64))],
+            );
+        }
+        enc.work()?;
+        let (stx, srx) = crate::stream::new_nocopy_stream();
+        while let Some((bits, tags)) = prev.pop() {
+            stx.push(soft(&bits, 1.0, &mut rng), tags);
+        }
+        let (mut dec, out) = ViterbiDecodePdu::new(srx, code);
+        dec.work()?;
+        for p in &packets {
+            let (got, tags) = out.pop().unwrap();
+            assert_eq!(&got, p);
+            assert_eq!(tags, [Tag::new(0, "len", TagValue::U64(p.len() as u64))]);
+        }
+        assert!(out.pop().is_none());
+        Ok(())
+    }
+}
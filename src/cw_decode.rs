@@ -0,0 +1,370 @@
+/*! Decode morse code.
+
+## Example
+
+Decoding morse from an I/Q capture, with the carrier near 0Hz:
+
+```
+use rustradio::graph::{Graph, GraphRunner};
+use rustradio::blocks::*;
+use rustradio::window::WindowType;
+# fn main() -> rustradio::Result<()> {
+let samp_rate = 50_000.0;
+let (_, prev) = rustradio::stream::new_stream();
+let mut g = Graph::new();
+let taps = rustradio::fir::low_pass_complex(samp_rate, 200.0, 100.0, &WindowType::Hamming);
+let prev = rustradio::blockchain![
+    g,
+    prev,
+    FftFilter::new(prev, taps),
+    ComplexToMag2::new(prev),
+    CwDecode::new(prev, samp_rate.into()),
+];
+# Ok(())
+# }
+```
+*/
+use log::debug;
+
+use crate::block::{Block, BlockRet};
+use crate::stream::{NCWriteStream, ReadStream, Tag, TagValue};
+use crate::{Float, Result};
+
+/// Tag on each decoded character, with the start time in milliseconds since
+/// the start of the stream.
+pub const TIME_TAG: &str = "CwDecode:time-ms";
+
+/// Tag on each decoded character, with the estimated speed in words per
+/// minute.
+pub const WPM_TAG: &str = "CwDecode:wpm";
+
+// Output when the dits and dahs are not a known character.
+const UNKNOWN: &str = "*";
+
+// Speed range to track.
+const MIN_WPM: f64 = 5.0;
+const MAX_WPM: f64 = 60.0;
+
+// Peak must be this much above the noise floor to count as signal.
+const MIN_CONTRAST: Float = 3.0;
+
+/// Builder for [`CwDecode`].
+pub struct CwDecodeBuilder {
+    block: CwDecode,
+    out: crate::stream::NCReadStream<String>,
+}
+
+impl CwDecodeBuilder {
+    /// Set the initial speed estimate, in words per minute. Default 20.
+    #[must_use]
+    pub fn wpm(mut self, wpm: f64) -> Self {
+        self.block.dit = dit_samples(self.block.sample_rate, wpm);
+        self
+    }
+
+    /// Set the time constant, in seconds, for tracking signal and noise
+    /// levels. Default 1.0.
+    #[must_use]
+    pub fn level_time_constant(mut self, seconds: f64) -> Self {
+        self.block.decay = (1.0 / (seconds * self.block.sample_rate)) as Float;
+        self
+    }
+
+    /// Build the `CwDecode`.
+    #[must_use]
+    pub fn build(self) -> (CwDecode, crate::stream::NCReadStream<String>) {
+        (self.block, self.out)
+    }
+}
+
+fn dit_samples(sample_rate: f64, wpm: f64) -> f64 {
+    sample_rate * 1.2 / wpm
+}
+
+/// Morse code decoder.
+///
+/// Takes an envelope, such as the magnitude or power of a narrowly filtered
+/// signal, and outputs one string per decoded character. Letters are in
+/// uppercase, and prosigns without an equivalent punctuation character are in
+/// angle brackets, e.g. `<SK>`. Word gaps are output as a single space, and
+/// unknown characters as `*`.
+///
+/// The key down threshold adapts to the signal and noise levels, and the speed
+/// adapts to the length of received dits and dahs. Each character is tagged
+/// with [`TIME_TAG`] and [`WPM_TAG`].
+#[derive(rustradio_macros::Block)]
+#[rustradio(crate)]
+pub struct CwDecode {
+    #[rustradio(in)]
+    src: ReadStream<Float>,
+    #[rustradio(out)]
+    dst: NCWriteStream<String>,
+
+    sample_rate: f64,
+
+    // Signal and noise level trackers.
+    hi: Float,
+    lo: Float,
+    decay: Float,
+
+    // Estimated dit length, in samples.
+    dit: f64,
+
+    // Sample counter, since start.
+    pos: u64,
+    key: bool,
+    // Start of the current mark.
+    mark_start: u64,
+    // End of the last accepted mark.
+    mark_end: u64,
+    // Dits and dahs of the current character, and its start.
+    pattern: String,
+    char_start: u64,
+    // True if a character has been output since the last space.
+    in_word: bool,
+}
+
+impl CwDecode {
+    /// Create a new morse decoder.
+    #[must_use]
+    pub fn new(
+        src: ReadStream<Float>,
+        sample_rate: f64,
+    ) -> (Self, crate::stream::NCReadStream<String>) {
+        let (dst, dr) = crate::stream::new_nocopy_stream();
+        (
+            Self {
+                src,
+                dst,
+                sample_rate,
+                hi: 0.0,
+                lo: 0.0,
+                decay: (1.0 / sample_rate) as Float,
+                dit: dit_samples(sample_rate, 20.0),
+                pos: 0,
+                key: false,
+                mark_start: 0,
+                mark_end: 0,
+                pattern: String::new(),
+                char_start: 0,
+                in_word: false,
+            },
+            dr,
+        )
+    }
+
+    /// Create a builder, for non-default settings.
+    #[must_use]
+    pub fn builder(src: ReadStream<Float>, sample_rate: f64) -> CwDecodeBuilder {
+        let (block, out) = Self::new(src, sample_rate);
+        CwDecodeBuilder { block, out }
+    }
+
+    fn push(&mut self, s: &str, start: u64) {
+        let ms = (start as f64 * 1000.0 / self.sample_rate) as u64;
+        let wpm = self.sample_rate * 1.2 / self.dit;
+        self.dst.push(
+            s.to_string(),
+            &[
+                Tag::new(0, TIME_TAG, TagValue::U64(ms)),
+                Tag::new(0, WPM_TAG, TagValue::Float(wpm as Float)),
+            ],
+        );
+    }
+
+    // Output the current character, if any.
+    fn flush_char(&mut self) {
+        if self.pattern.is_empty() {
+            return;
+        }
+        let s = crate::morse_encode::decode(&self.pattern).unwrap_or_else(|| {
+            debug!("CwDecode: unknown character {}", self.pattern);
+            UNKNOWN
+        });
+        self.push(s, self.char_start);
+        self.pattern.clear();
+        self.in_word = true;
+    }
+
+    // A mark of `len` samples ended.
+    fn mark(&mut self, len: f64) {
+        if self.pattern.is_empty() {
+            self.char_start = self.mark_start;
+        }
+        let est = if len < 2.0 * self.dit {
+            self.pattern.push('.');
+            len
+        } else {
+            self.pattern.push('-');
+            len / 3.0
+        };
+        self.dit += 0.25 * (est - self.dit);
+        self.dit = self.dit.clamp(
+            dit_samples(self.sample_rate, MAX_WPM),
+            dit_samples(self.sample_rate, MIN_WPM),
+        );
+    }
+
+    // Update levels and key state, for one sample.
+    fn sample(&mut self, x: Float) {
+        // Fast attack, slow decay.
+        if x > self.hi {
+            self.hi = x;
+        } else {
+            self.hi -= (self.hi - x) * self.decay;
+        }
+        if x < self.lo {
+            self.lo = x;
+        } else {
+            self.lo += (x - self.lo) * self.decay;
+        }
+        let mid = (self.hi + self.lo) / 2.0;
+        let hyst = (self.hi - self.lo) / 10.0;
+        let key = if self.key {
+            x > mid - hyst
+        } else {
+            x > mid + hyst && self.hi > MIN_CONTRAST * self.lo
+        };
+        if key != self.key {
+            self.key = key;
+            if key {
+                self.mark_start = self.pos;
+            } else {
+                let len = (self.pos - self.mark_start) as f64;
+                // Shorter than this is noise.
+                if len > self.dit / 4.0 {
+                    self.mark(len);
+                    self.mark_end = self.pos;
+                }
+            }
+        }
+        if !self.key {
+            let space = (self.pos - self.mark_end) as f64;
+            if space > 2.0 * self.dit {
+                self.flush_char();
+            }
+            if space > 5.0 * self.dit && self.in_word {
+                self.push(" ", self.mark_end);
+                self.in_word = false;
+            }
+        }
+        self.pos += 1;
+    }
+}
+
+impl Block for CwDecode {
+    fn work(&mut self) -> Result<BlockRet<'_>> {
+        let (i, _) = self.src.read_buf()?;
+        if i.is_empty() {
+            if self.src.eof() {
+                self.flush_char();
+                return Ok(BlockRet::EOF);
+            }
+            return Ok(BlockRet::WaitForStream(&self.src, 1));
+        }
+        // Each sample outputs at most a character and a space.
+        if self.dst.remaining() < 2 {
+            return Ok(BlockRet::WaitForStream(&self.dst, 2));
+        }
+        let mut n = 0;
+        for &x in i.slice() {
+            self.sample(x);
+            n += 1;
+            if self.dst.remaining() < 2 {
+                break;
+            }
+        }
+        i.consume(n);
+        Ok(BlockRet::Again)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    use crate::blocks::{CwKeyer, Map, MorseEncode, PduToStream};
+    use crate::graph::{Graph, GraphRunner};
+    use crate::stream::new_nocopy_stream;
+
+    fn decode(
+        msg: &str,
+        sample_rate: f64,
+        wpm: f64,
+        noise: Float,
+    ) -> Result<Vec<(String, Vec<Tag>)>> {
+        let (tx, prev) = new_nocopy_stream();
+        tx.push(msg.to_string(), &[]);
+        drop(tx);
+        // Deterministic noise.
+        let seed = AtomicU32::new(1);
+        let mut g = Graph::new();
+        let prev = crate::blockchain![
+            g,
+            prev,
+            MorseEncode::new(prev),
+            PduToStream::new(prev),
+            CwKeyer::new(prev, sample_rate, wpm),
+            Map::keep_tags(prev, "noise", move |x| {
+                let r = seed
+                    .load(Ordering::Relaxed)
+                    .wrapping_mul(1_103_515_245)
+                    .wrapping_add(12345);
+                seed.store(r, Ordering::Relaxed);
+                x + noise * (0.1 + (r >> 16) as Float / 65536.0)
+            }),
+            CwDecode::new(prev, sample_rate),
+        ];
+        g.run()?;
+        let mut ret = Vec::new();
+        while let Some(x) = prev.pop() {
+            ret.push(x);
+        }
+        Ok(ret)
+    }
+
+    fn text(d: &[(String, Vec<Tag>)]) -> String {
+        d.iter().map(|(s, _)| s.as_str()).collect()
+    }
+
+    #[test]
+    fn clean() -> Result<()> {
+        let got = decode("CQ DE M0THC K", 4000.0, 20.0, 0.0)?;
+        assert_eq!(text(&got), "CQ DE M0THC K ");
+        // C starts at 0, Q after 11 units and 3 units of gap, at 60ms units.
+        // The keyer rise time delays it a few ms.
+        for (n, want) in [(0, 0), (1, 14 * 60)] {
+            let TagValue::U64(ms) = got[n].1[0].val() else {
+                panic!("bad time tag {:?}", got[n].1);
+            };
+            assert!((want..want + 5).contains(ms), "{n}: {ms}");
+        }
+        let TagValue::Float(wpm) = got[1].1[1].val() else {
+            panic!("bad wpm tag {:?}", got[1].1);
+        };
+        assert!((wpm - 20.0).abs() < 1.0, "{wpm}");
+        Ok(())
+    }
+
+    #[test]
+    fn adapts_speed() -> Result<()> {
+        for wpm in [12.0, 30.0] {
+            let got = decode("VVV TEST 73, <SK>", 4000.0, wpm, 0.0)?;
+            // Speed isn't known for the first character.
+            assert!(
+                text(&got).ends_with("VV TEST 73, <SK> "),
+                "{wpm}: {}",
+                text(&got)
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn noise() -> Result<()> {
+        let got = decode("PARIS PARIS", 8000.0, 20.0, 0.3)?;
+        assert_eq!(text(&got), "PARIS PARIS ");
+        Ok(())
+    }
+}
@@ -0,0 +1,177 @@
+//! Turn morse code key up/down into a shaped envelope.
+use crate::block::{Block, BlockRet};
+use crate::stream::{ReadStream, WriteStream};
+use crate::{Float, Result};
+
+/// Builder for [`CwKeyer`].
+pub struct CwKeyerBuilder {
+    block: CwKeyer,
+    out: ReadStream<Float>,
+}
+
+impl CwKeyerBuilder {
+    /// Set rise and fall time, in seconds. Default 5ms.
+    ///
+    /// This should be well below the length of a dit. 20 WPM has 60ms dits.
+    #[must_use]
+    pub fn rise_time(mut self, seconds: f64) -> Self {
+        self.block.rise = ((seconds * self.block.sample_rate).round() as usize).max(1);
+        self
+    }
+
+    /// Build the `CwKeyer`.
+    #[must_use]
+    pub fn build(self) -> (CwKeyer, ReadStream<Float>) {
+        (self.block, self.out)
+    }
+}
+
+/// CW keyer.
+///
+/// Takes one value per morse time unit, such as from
+/// [`MorseEncode`](crate::blocks::MorseEncode) and `PduToStream`, and outputs
+/// the envelope at the given sample rate. Nonzero is key down.
+///
+/// The key down and key up edges are raised cosine shaped, to avoid key
+/// clicks. The envelope is 0.0 for key up, and 1.0 for key down.
+///
+/// Tags are passed on, at the first sample of the time unit they were on.
+#[derive(rustradio_macros::Block)]
+#[rustradio(crate)]
+pub struct CwKeyer {
+    #[rustradio(in)]
+    src: ReadStream<u8>,
+    #[rustradio(out)]
+    dst: WriteStream<Float>,
+
+    sample_rate: f64,
+    samples_per_unit: f64,
+    // Rise time, in samples.
+    rise: usize,
+    // Position on the edge, from 0 (key up) to `rise` (key down).
+    pos: usize,
+    // Fraction of a sample left over from the previous unit.
+    clock: f64,
+}
+
+impl CwKeyer {
+    /// Create a new CW keyer, with the default rise time.
+    #[must_use]
+    pub fn new(src: ReadStream<u8>, sample_rate: f64, wpm: f64) -> (Self, ReadStream<Float>) {
+        let (dst, dr) = crate::stream::new_stream();
+        (
+            Self {
+                src,
+                dst,
+                sample_rate,
+                // PARIS is 50 units.
+                samples_per_unit: sample_rate * 60.0 / (50.0 * wpm),
+                rise: ((0.005 * sample_rate).round() as usize).max(1),
+                pos: 0,
+                clock: 0.0,
+            },
+            dr,
+        )
+    }
+
+    /// Create a builder, to set the rise time.
+    #[must_use]
+    pub fn builder(src: ReadStream<u8>, sample_rate: f64, wpm: f64) -> CwKeyerBuilder {
+        let (block, out) = Self::new(src, sample_rate, wpm);
+        CwKeyerBuilder { block, out }
+    }
+}
+
+impl Block for CwKeyer {
+    fn work(&mut self) -> Result<BlockRet<'_>> {
+        let (i, tags) = self.src.read_buf()?;
+        if i.is_empty() {
+            return Ok(BlockRet::WaitForStream(&self.src, 1));
+        }
+        let mut o = self.dst.write_buf()?;
+        let max = (self.clock + self.samples_per_unit).ceil() as usize;
+        if o.len() < max {
+            return Ok(BlockRet::WaitForStream(&self.dst, max));
+        }
+        let pi = std::f64::consts::PI;
+        let mut opos = 0;
+        let mut otags = Vec::new();
+        let mut consumed = 0;
+        for (ipos, &key) in i.slice().iter().enumerate() {
+            let n = (self.clock + self.samples_per_unit) as usize;
+            if opos + n > o.len() {
+                break;
+            }
+            for s in &mut o.slice()[opos..opos + n] {
+                if key != 0 {
+                    self.pos = (self.pos + 1).min(self.rise);
+                } else {
+                    self.pos = self.pos.saturating_sub(1);
+                }
+                *s = (0.5 - 0.5 * (pi * self.pos as f64 / self.rise as f64).cos()) as Float;
+            }
+            otags.extend(tags.iter().filter(|t| t.pos() == ipos).map(|t| {
+                let mut t = t.clone();
+                t.set_pos(opos);
+                t
+            }));
+            self.clock += self.samples_per_unit - n as f64;
+            opos += n;
+            consumed += 1;
+        }
+        i.consume(consumed);
+        o.produce(opos, &otags);
+        Ok(BlockRet::Again)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::VectorSource;
+    use crate::stream::{Tag, TagValue};
+
+    #[test]
+    fn envelope() -> Result<()> {
+        let (mut src, prev) = VectorSource::builder(vec![1u8, 0, 0, 1, 1, 1, 0])
+            .tags(&[Tag::new(3, "foo", TagValue::Bool(true))])
+            .build()?;
+        src.work()?;
+        // 20 WPM is 60ms units, so 480 samples at 8kHz. 40 sample edges.
+        let (mut b, out) = CwKeyer::new(prev, 8000.0, 20.0);
+        b.work()?;
+        let (o, tags) = out.read_buf()?;
+        let o = o.slice();
+        assert_eq!(o.len(), 7 * 480);
+        assert!(tags.contains(&Tag::new(3 * 480, "foo", TagValue::Bool(true))));
+
+        // Raised cosine edges.
+        assert!((o[19] - 0.5).abs() < 1e-5, "{}", o[19]);
+        assert_eq!(o[39], 1.0);
+        assert_eq!(o[479], 1.0);
+        assert!((o[499] - 0.5).abs() < 1e-5, "{}", o[499]);
+        assert_eq!(o[519], 0.0);
+        assert_eq!(o[3 * 480 - 1], 0.0);
+        assert_eq!(o[6 * 480 - 1], 1.0);
+        for w in o.windows(2) {
+            assert!((w[0] - w[1]).abs() < 0.05, "{w:?}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn fractional() -> Result<()> {
+        let (mut src, prev) = VectorSource::new(vec![1u8; 60]);
+        src.work()?;
+        // 18 WPM at 1kHz is 66.67 samples per unit.
+        let (mut b, out) = CwKeyer::builder(prev, 1000.0, 18.0)
+            .rise_time(0.002)
+            .build();
+        b.work()?;
+        let (o, _) = out.read_buf()?;
+        assert_eq!(o.len(), 4000);
+        assert_eq!(o.slice()[0], 0.5);
+        assert_eq!(o.slice()[1], 1.0);
+        Ok(())
+    }
+}
@@ -3,8 +3,13 @@
 AX.25 G3RUH uses mask 0x21 and length 16. Seed doesn't matter, since
 by the time the packet arrives the original seed will be shifted out
 anyway.
+
+CCSDS uses an additive, not self synchronizing, randomizer instead. It's
+reset to all ones at the start of every codeblock, and doesn't cover the
+ASM, so the descrambler needs to know where codeblocks start and how long
+they are.
  */
-use crate::stream::{ReadStream, WriteStream};
+use crate::stream::{ReadStream, Tag, WriteStream};
 
 /// LFSR as used by G3RUH.
 ///
@@ -30,6 +35,10 @@ impl Lfsr {
     fn g3ruh() -> Self {
         Self::new(0x21, 0, 16)
     }
+    /// Create new CCSDS LFSR, h(x) = x^8+x^7+x^5+x^3+1.
+    fn ccsds() -> Self {
+        Self::new(0xA9, 0xFF, 7)
+    }
     /// Clock the LFSR.
     fn next_descramble(&mut self, i: u8) -> u8 {
         assert!(i <= 1);
@@ -45,17 +54,54 @@ impl Lfsr {
         self.shift_reg = (self.shift_reg >> 1) | (u64::from(tmp) << self.len);
         ret
     }
+    /// Clock the LFSR, adding the sequence to the input.
+    ///
+    /// Adding is its own inverse, so this both scrambles and descrambles.
+    fn next_additive(&mut self, i: u8) -> u8 {
+        assert!(i <= 1);
+        let ret = (self.shift_reg & 1) as u8 ^ i;
+        let tmp = 1 & (self.shift_reg & self.mask).count_ones() as u8;
+        self.shift_reg = (self.shift_reg >> 1) | (u64::from(tmp) << self.len);
+        ret
+    }
+}
+
+/// Return the first `len` bytes of the CCSDS pseudo-randomizer sequence.
+///
+/// XOR with this to randomize or derandomize a frame, not including the
+/// ASM.
+#[must_use]
+pub fn ccsds_sequence(len: usize) -> Vec<u8> {
+    let mut lfsr = Lfsr::ccsds();
+    (0..len)
+        .map(|_| (0..8).fold(0, |acc, _| (acc << 1) | lfsr.next_additive(0)))
+        .collect()
+}
+
+// How the descrambler uses the LFSR.
+enum Mode {
+    // Self synchronizing, like G3RUH.
+    Multiplicative,
+    // Additive, reset to the seed after every sample tagged with the tag,
+    // and then used for `len` bits.
+    Additive {
+        seed: u64,
+        reset_tag: String,
+        len: usize,
+        left: usize,
+    },
 }
 
 /// Descrambler uses an LFSR to descramble bits.
 #[derive(rustradio_macros::Block)]
-#[rustradio(crate, sync)]
+#[rustradio(crate, sync_tag)]
 pub struct Descrambler {
     #[rustradio(in)]
     src: ReadStream<u8>,
     #[rustradio(out)]
     dst: WriteStream<u8>,
     lfsr: Lfsr,
+    mode: Mode,
 }
 impl Descrambler {
     /// Create new descrambler.
@@ -68,6 +114,7 @@ impl Descrambler {
                 src,
                 dst,
                 lfsr: Lfsr::new(mask, seed, len),
+                mode: Mode::Multiplicative,
             },
             dr,
         )
@@ -82,13 +129,64 @@ impl Descrambler {
                 src,
                 dst,
                 lfsr: Lfsr::g3ruh(),
+                mode: Mode::Multiplicative,
             },
             dr,
         )
     }
 
-    fn process_sync(&mut self, bit: u8) -> u8 {
-        self.lfsr.next_descramble(bit)
+    /// Create a CCSDS derandomizer, for codeblocks of `len` bytes.
+    ///
+    /// The sequence restarts after every sample tagged with `reset_tag`,
+    /// e.g. the last bit of the ASM, as tagged by
+    /// [`CorrelateAccessCodeTag`](crate::blocks::CorrelateAccessCodeTag).
+    /// Outside of codeblocks, the input is passed through unchanged.
+    #[must_use]
+    pub fn ccsds<S: Into<String>>(
+        src: ReadStream<u8>,
+        reset_tag: S,
+        len: usize,
+    ) -> (Self, ReadStream<u8>) {
+        let (dst, dr) = crate::stream::new_stream();
+        (
+            Self {
+                src,
+                dst,
+                lfsr: Lfsr::new(0xA9, 0, 7),
+                mode: Mode::Additive {
+                    seed: 0xFF,
+                    reset_tag: reset_tag.into(),
+                    len: 8 * len,
+                    left: 0,
+                },
+            },
+            dr,
+        )
+    }
+
+    fn process_sync_tags(&mut self, bit: u8, tags: &[Tag]) -> (u8, Vec<Tag>) {
+        let out = match &mut self.mode {
+            Mode::Multiplicative => self.lfsr.next_descramble(bit),
+            Mode::Additive {
+                seed,
+                reset_tag,
+                len,
+                left,
+            } => {
+                let out = if *left > 0 {
+                    *left -= 1;
+                    self.lfsr.next_additive(bit)
+                } else {
+                    bit
+                };
+                if tags.iter().any(|t| t.key() == reset_tag) {
+                    self.lfsr.shift_reg = *seed;
+                    *left = *len;
+                }
+                out
+            }
+        };
+        (out, tags.to_vec())
     }
 }
 
@@ -126,7 +224,7 @@ impl Scrambler {
 mod tests {
     use super::*;
     use crate::block::Block;
-    use crate::blocks::{NrziDecode, NrziEncode, VectorSource};
+    use crate::blocks::{CorrelateAccessCodeTag, NrziDecode, NrziEncode, VectorSource};
 
     #[test]
     fn known_good_test1() {
@@ -249,4 +347,56 @@ mod tests {
         let descrambled: Vec<_> = out.iter().copied().skip(17).collect();
         assert_eq!(descrambled, input);
     }
+
+    #[test]
+    fn ccsds_sequence_start() {
+        assert_eq!(
+            ccsds_sequence(10),
+            vec![0xFF, 0x48, 0x0E, 0xC0, 0x9A, 0x0D, 0x70, 0xBC, 0x8E, 0x2C]
+        );
+        // The sequence repeats every 255 bits.
+        let bits: Vec<_> = ccsds_sequence(64)
+            .iter()
+            .flat_map(|b| (0..8).rev().map(move |i| (b >> i) & 1))
+            .collect();
+        assert_eq!(bits[..255], bits[255..510]);
+    }
+
+    #[test]
+    fn ccsds_block() -> crate::Result<()> {
+        use crate::stream::TagValue;
+        let data = [1u8, 0, 1, 1, 0, 0, 1, 0, 1, 1, 1, 1, 0, 0, 0, 0];
+        let seq = ccsds_sequence(2);
+        let asm: Vec<u8> = (0..32)
+            .rev()
+            .map(|i| (0x1ACF_FC1D >> i) as u8 & 1)
+            .collect();
+        let randomized: Vec<u8> = data
+            .iter()
+            .enumerate()
+            .map(|(n, b)| b ^ ((seq[n / 8] >> (7 - n % 8)) & 1))
+            .collect();
+        // Two codeblocks, and some trailing bits.
+        let mut input = Vec::new();
+        for _ in 0..2 {
+            input.extend(&asm);
+            input.extend(&randomized);
+        }
+        input.extend(&data);
+        let (mut b, prev) = VectorSource::new(input);
+        b.work()?;
+        let (mut b, prev) = CorrelateAccessCodeTag::new(prev, asm.clone(), "asm", 0);
+        b.work()?;
+        let (mut b, prev) = Descrambler::ccsds(prev, "asm", data.len() / 8);
+        b.work()?;
+        let (out, tags) = prev.read_buf()?;
+        for block in out.slice()[..96].chunks(48) {
+            assert_eq!(&block[..32], &asm);
+            assert_eq!(&block[32..], &data);
+        }
+        assert_eq!(&out.slice()[96..], &data);
+        assert!(tags.contains(&Tag::new(31, "asm", TagValue::U64(0))));
+        assert!(tags.contains(&Tag::new(79, "asm", TagValue::U64(0))));
+        Ok(())
+    }
 }
@@ -0,0 +1,266 @@
+/*! FM pre-emphasis and de-emphasis.
+
+FM noise rises with audio frequency, so the transmitter boosts the treble
+before modulating (pre-emphasis), and the receiver cuts it back after
+demodulating (de-emphasis), taking the noise down with it.
+
+The amount is given as the time constant of the filter. Broadcast FM uses
+[`TAU_75US`] in the Americas and South Korea, and [`TAU_50US`] in most of the
+rest of the world.
+
+## Example
+
+```
+use rustradio::blocks::{Deemphasis, QuadratureDemod};
+# fn main() -> rustradio::Result<()> {
+let samp_rate = 200_000.0;
+let (_, prev) = rustradio::stream::new_stream();
+let (demod, prev) = QuadratureDemod::new(prev, 1.0);
+let (deemph, prev) = Deemphasis::new(prev, samp_rate, rustradio::emphasis::TAU_50US)?;
+# Ok(())
+# }
+```
+*/
+use crate::iir_filter::{Filter, IirFilter};
+use crate::stream::{ReadStream, WriteStream};
+use crate::{Error, Float, Result};
+
+/// 50µs time constant, used by broadcast FM in Europe and most other places.
+pub const TAU_50US: f64 = 50e-6;
+
+/// 75µs time constant, used by broadcast FM in the Americas and South Korea.
+pub const TAU_75US: f64 = 75e-6;
+
+// Check that the time constant is positive, and that the corner frequency is
+// below Nyquist.
+fn check(sample_rate: f64, tau: f64) -> Result<()> {
+    if !tau.is_finite() || tau <= 0.0 {
+        return Err(Error::msg(format!(
+            "emphasis time constant must be positive, got {tau}"
+        )));
+    }
+    let corner = 1.0 / (2.0 * std::f64::consts::PI * tau);
+    if sample_rate.is_nan() || corner >= sample_rate / 2.0 {
+        return Err(Error::msg(format!(
+            "emphasis corner frequency {corner}Hz not below Nyquist for sample rate {sample_rate}"
+        )));
+    }
+    Ok(())
+}
+
+// Analog corner frequency in rad/s, to bilinear transform constant.
+//
+// Prewarped, so that the digital filter has the same corner.
+fn warped(sample_rate: f64, w: f64) -> f64 {
+    1.0 / (2.0 * sample_rate * (w / (2.0 * sample_rate)).tan())
+}
+
+/// First order filter, with one zero and one pole.
+///
+/// The feedforward part is done here, and the feedback by an `IirFilter`.
+pub(crate) struct FirstOrder {
+    b0: Float,
+    b1: Float,
+    prev: Float,
+    iir: IirFilter<Float>,
+}
+
+impl FirstOrder {
+    // Bilinear transform of (1 + s*tz) / (1 + s*tp), with the time constants
+    // already prewarped.
+    fn new(sample_rate: f64, tz: f64, tp: f64) -> Self {
+        let k = 2.0 * sample_rate;
+        let d = 1.0 + k * tp;
+        Self {
+            b0: ((1.0 + k * tz) / d) as Float,
+            b1: ((1.0 - k * tz) / d) as Float,
+            prev: 0.0,
+            iir: IirFilter::new(&[1.0, ((k * tp - 1.0) / d) as Float]),
+        }
+    }
+
+    /// De-emphasis filter, with time constant `tau`.
+    pub(crate) fn deemphasis(sample_rate: f64, tau: f64) -> Result<Self> {
+        check(sample_rate, tau)?;
+        Ok(Self::new(sample_rate, 0.0, warped(sample_rate, 1.0 / tau)))
+    }
+
+    pub(crate) fn filter(&mut self, x: Float) -> Float {
+        let v = self.b0 * x + self.b1 * self.prev;
+        self.prev = x;
+        self.iir.filter(v)
+    }
+}
+
+/// FM de-emphasis filter.
+///
+/// Single pole low pass filter, with the corner frequency at `1/(2π·tau)`,
+/// e.g. 2122Hz for 75µs. Gain at DC is 1.0.
+#[derive(rustradio_macros::Block)]
+#[rustradio(crate, sync)]
+pub struct Deemphasis {
+    #[rustradio(in)]
+    src: ReadStream<Float>,
+    #[rustradio(out)]
+    dst: WriteStream<Float>,
+    filter: FirstOrder,
+}
+
+impl Deemphasis {
+    /// Create new de-emphasis filter, with time constant `tau`, in seconds.
+    ///
+    /// # Errors
+    ///
+    /// Errors if `tau` is not positive, or the corner frequency is not below
+    /// Nyquist.
+    pub fn new(
+        src: ReadStream<Float>,
+        sample_rate: f64,
+        tau: f64,
+    ) -> Result<(Self, ReadStream<Float>)> {
+        let filter = FirstOrder::deemphasis(sample_rate, tau)?;
+        let (dst, dr) = crate::stream::new_stream();
+        Ok((Self { src, dst, filter }, dr))
+    }
+
+    fn process_sync(&mut self, s: Float) -> Float {
+        self.filter.filter(s)
+    }
+}
+
+/// FM pre-emphasis filter.
+///
+/// The inverse of [`Deemphasis`], boosting frequencies above `1/(2π·tau)`. To
+/// keep the filter stable, the boost levels off near the Nyquist frequency,
+/// so run it at a sample rate well above the audio bandwidth, e.g. just
+/// before the FM modulator. Gain at DC is 1.0.
+#[derive(rustradio_macros::Block)]
+#[rustradio(crate, sync)]
+pub struct Preemphasis {
+    #[rustradio(in)]
+    src: ReadStream<Float>,
+    #[rustradio(out)]
+    dst: WriteStream<Float>,
+    filter: FirstOrder,
+}
+
+impl Preemphasis {
+    /// Create new pre-emphasis filter, with time constant `tau`, in seconds.
+    ///
+    /// # Errors
+    ///
+    /// Errors if `tau` is not positive, or the corner frequency is not below
+    /// Nyquist.
+    pub fn new(
+        src: ReadStream<Float>,
+        sample_rate: f64,
+        tau: f64,
+    ) -> Result<(Self, ReadStream<Float>)> {
+        check(sample_rate, tau)?;
+        let (dst, dr) = crate::stream::new_stream();
+        // Level off just below Nyquist.
+        let high = 0.925 * std::f64::consts::PI * sample_rate;
+        let tz = warped(sample_rate, 1.0 / tau);
+        let tp = warped(sample_rate, high);
+        Ok((
+            Self {
+                src,
+                dst,
+                filter: FirstOrder::new(sample_rate, tz, tp),
+            },
+            dr,
+        ))
+    }
+
+    fn process_sync(&mut self, s: Float) -> Float {
+        self.filter.filter(s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::Block;
+    use crate::blocks::VectorSource;
+
+    const TAU: f64 = 2.0 * std::f64::consts::PI;
+
+    fn tone(sample_rate: f64, freq: f64) -> Vec<Float> {
+        (0..(sample_rate as usize))
+            .map(|n| (TAU * freq * n as f64 / sample_rate).sin() as Float)
+            .collect()
+    }
+
+    fn gain(out: &[Float]) -> f64 {
+        // Skip settling.
+        let out = &out[out.len() / 2..];
+        let rms = (out.iter().map(|&x| f64::from(x * x)).sum::<f64>() / out.len() as f64).sqrt();
+        rms * 2.0f64.sqrt()
+    }
+
+    fn deemph(sample_rate: f64, tau: f64, freq: f64) -> Result<f64> {
+        let (mut src, prev) = VectorSource::new(tone(sample_rate, freq));
+        src.work()?;
+        let (mut b, out) = Deemphasis::new(prev, sample_rate, tau)?;
+        b.work()?;
+        let (o, _) = out.read_buf()?;
+        Ok(gain(o.slice()))
+    }
+
+    #[test]
+    fn deemphasis() -> Result<()> {
+        for sr in [48000.0, 200_000.0] {
+            for tau in [TAU_50US, TAU_75US] {
+                let corner = 1.0 / (TAU * tau);
+                // The bilinear transform squeezes the response near Nyquist.
+                for freq in [10.0, corner, 5000.0, 10000.0]
+                    .into_iter()
+                    .filter(|&f| f < sr / 8.0)
+                {
+                    let want = 1.0 / (1.0 + (freq / corner).powi(2)).sqrt();
+                    let got = deemph(sr, tau, freq)?;
+                    assert!(
+                        (got - want).abs() < 0.01,
+                        "{sr} {tau} {freq}: got {got} want {want}"
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn preemphasis_inverse() -> Result<()> {
+        let sr = 200_000.0;
+        for freq in [100.0, 1000.0, 5000.0, 15000.0] {
+            let (mut src, prev) = VectorSource::new(tone(sr, freq));
+            src.work()?;
+            let (mut pre, prev) = Preemphasis::new(prev, sr, TAU_75US)?;
+            pre.work()?;
+            let (mut de, out) = Deemphasis::new(prev, sr, TAU_75US)?;
+            de.work()?;
+            let (o, _) = out.read_buf()?;
+            let got = gain(o.slice());
+            assert!((got - 1.0).abs() < 0.01, "{freq}: {got}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn invalid() {
+        for (sr, tau) in [
+            (48000.0, 0.0),
+            (48000.0, -TAU_75US),
+            (48000.0, Float::NAN.into()),
+            // Corner at 15.9kHz.
+            (30000.0, 10e-6),
+        ] {
+            let (_, prev) = crate::stream::new_stream();
+            assert!(Deemphasis::new(prev, sr, tau).is_err(), "{sr} {tau}");
+            let (_, prev) = crate::stream::new_stream();
+            assert!(Preemphasis::new(prev, sr, tau).is_err(), "{sr} {tau}");
+        }
+        let (_, prev) = crate::stream::new_stream();
+        assert!(Deemphasis::new(prev, 48000.0, 10e-6).is_ok());
+    }
+}
@@ -290,6 +290,110 @@ where
     }
 }
 
+/// Interpolating finite impulse response filter block.
+///
+/// Upsamples by an integer factor, and filters, in one polyphase step. This is
+/// the same as inserting `interp-1` zeros after every input sample, and then
+/// running a `FirFilter`, but without wasting time multiplying by zero.
+///
+/// Like for `FirFilter`, the output starts once the first taps worth of input
+/// has arrived.
+///
+/// Inserting zeros divides the signal level by `interp`, so for unity gain the
+/// taps need to sum to `interp`. The tap designers in this module all create
+/// taps with a sum of 1, so they need to be scaled up.
+///
+/// Tags are moved to the first output sample created from the tagged input
+/// sample.
+///
+/// ```
+/// use rustradio::fir::{InterpFirFilter, root_raised_cosine};
+/// use rustradio::Float;
+/// let (_, prev) = rustradio::stream::new_stream::<Float>();
+/// let sps = 8;
+/// let taps: Vec<Float> = root_raised_cosine(sps as Float, 0.35, 8 * sps + 1)
+///     .into_iter()
+///     .map(|t| t * sps as Float)
+///     .collect();
+/// let (block, prev) = InterpFirFilter::new(prev, &taps, sps);
+/// ```
+#[derive(rustradio_macros::Block)]
+#[rustradio(crate)]
+pub struct InterpFirFilter<T: Sample> {
+    // One filter per output phase.
+    phases: Vec<Fir<T>>,
+    // Taps per phase.
+    ntaps: usize,
+    #[rustradio(in)]
+    src: ReadStream<T>,
+    #[rustradio(out)]
+    dst: WriteStream<T>,
+}
+
+impl<T> InterpFirFilter<T>
+where
+    T: Sample + std::ops::Mul<T, Output = T> + std::ops::Add<T, Output = T>,
+{
+    /// Create new interpolating FIR block, given taps and interpolation.
+    pub fn new(src: ReadStream<T>, taps: &[T], interp: usize) -> (Self, ReadStream<T>) {
+        assert!(!taps.is_empty());
+        assert_ne!(interp, 0);
+        let ntaps = taps.len().div_ceil(interp);
+        let phases = (0..interp)
+            .map(|p| {
+                let t: Vec<T> = (0..ntaps)
+                    .map(|j| taps.get(j * interp + p).copied().unwrap_or_default())
+                    .collect();
+                Fir::new(&t)
+            })
+            .collect();
+        let (dst, dr) = crate::stream::new_stream();
+        (
+            Self {
+                phases,
+                ntaps,
+                src,
+                dst,
+            },
+            dr,
+        )
+    }
+}
+
+impl<T> Block for InterpFirFilter<T>
+where
+    T: Sample + std::ops::Mul<T, Output = T> + std::ops::Add<T, Output = T>,
+{
+    fn work(&mut self) -> Result<BlockRet<'_>> {
+        let (input, mut tags) = self.src.read_buf()?;
+        if input.len() < self.ntaps {
+            return Ok(BlockRet::WaitForStream(&self.src, self.ntaps));
+        }
+        let interp = self.phases.len();
+        let mut out = self.dst.write_buf()?;
+        if out.len() < interp {
+            return Ok(BlockRet::WaitForStream(&self.dst, interp));
+        }
+        let n = std::cmp::min(input.len() - self.ntaps + 1, out.len() / interp);
+        let i = input.slice();
+        for (k, o) in out.slice()[..n * interp]
+            .chunks_exact_mut(interp)
+            .enumerate()
+        {
+            for (o, fir) in o.iter_mut().zip(&self.phases) {
+                *o = fir.filter(&i[k..]);
+            }
+        }
+        tags.retain(|tag| tag.pos() < n);
+        for t in &mut tags {
+            t.set_pos(t.pos() * interp);
+        }
+        input.consume(n);
+        out.produce(n * interp, &tags);
+        Ok(BlockRet::Again)
+    }
+}
+
 /// Create a multiband filter.
 ///
 /// TODO: this is untested.
@@ -390,10 +494,103 @@ pub fn low_pass(
     taps.into_iter().map(|t| t * gain).collect()
 }
 
+/// Create taps for a Gaussian pulse shaping filter.
+///
+/// `bt` is the bandwidth-time product, e.g. 0.5 for G3RUH compatible GMSK, or
+/// 0.3 for GSM. Taps are normalized to a sum of 1.0, so the filter has unity
+/// gain at DC.
+///
+/// A span of three or four symbols is normally enough, so `ntaps` is typically
+/// `4 * samples_per_symbol + 1`.
+#[must_use]
+pub fn gaussian(samples_per_symbol: Float, bt: Float, ntaps: usize) -> Vec<Float> {
+    assert!(ntaps > 0);
+    assert!(bt > 0.0);
+    let pi = std::f64::consts::PI as Float;
+    // Standard deviation, in samples.
+    let sigma = samples_per_symbol * (2.0 as Float).ln().sqrt() / (2.0 * pi * bt);
+    let mid = (ntaps - 1) as Float / 2.0;
+    let taps: Vec<Float> = (0..ntaps)
+        .map(|n| {
+            let t = (n as Float - mid) / sigma;
+            (-0.5 * t * t).exp()
+        })
+        .collect();
+    let sum: Float = taps.iter().sum();
+    taps.into_iter().map(|t| t / sum).collect()
+}
+
+/// Create taps for a raised cosine filter.
+///
+/// `alpha` is the excess bandwidth (roll-off), between 0 and 1. The impulse
+/// response is zero at every multiple of `samples_per_symbol` from the center,
+/// so there's no intersymbol interference. Taps are normalized to a sum of
+/// 1.0.
+#[must_use]
+pub fn raised_cosine(samples_per_symbol: Float, alpha: Float, ntaps: usize) -> Vec<Float> {
+    assert!(ntaps > 0);
+    assert!((0.0..=1.0).contains(&alpha));
+    let pi = std::f64::consts::PI as Float;
+    let sinc = |x: Float| {
+        if x == 0.0 {
+            1.0
+        } else {
+            (pi * x).sin() / (pi * x)
+        }
+    };
+    let mid = (ntaps - 1) as Float / 2.0;
+    let taps: Vec<Float> = (0..ntaps)
+        .map(|n| {
+            // Time, in symbols.
+            let t = (n as Float - mid) / samples_per_symbol;
+            let d = 1.0 - (2.0 * alpha * t).powi(2);
+            if d.abs() < 1e-6 {
+                pi / 4.0 * sinc(1.0 / (2.0 * alpha))
+            } else {
+                sinc(t) * (pi * alpha * t).cos() / d
+            }
+        })
+        .collect();
+    let sum: Float = taps.iter().sum();
+    taps.into_iter().map(|t| t / sum).collect()
+}
+
+/// Create taps for a root raised cosine filter.
+///
+/// Used as a pair, one on the transmitter and a matched one on the receiver,
+/// to together form a raised cosine filter. `alpha` is the excess bandwidth
+/// (roll-off), between 0 and 1. Taps are normalized to a sum of 1.0.
+#[must_use]
+pub fn root_raised_cosine(samples_per_symbol: Float, alpha: Float, ntaps: usize) -> Vec<Float> {
+    assert!(ntaps > 0);
+    assert!((0.0..=1.0).contains(&alpha));
+    let pi = std::f64::consts::PI as Float;
+    let mid = (ntaps - 1) as Float / 2.0;
+    let taps: Vec<Float> = (0..ntaps)
+        .map(|n| {
+            // Time, in symbols.
+            let t = (n as Float - mid) / samples_per_symbol;
+            if t == 0.0 {
+                return 1.0 - alpha + 4.0 * alpha / pi;
+            }
+            let d = 1.0 - (4.0 * alpha * t).powi(2);
+            if d.abs() < 1e-6 {
+                let x = pi / (4.0 * alpha);
+                return alpha / (2.0 as Float).sqrt()
+                    * ((1.0 + 2.0 / pi) * x.sin() + (1.0 - 2.0 / pi) * x.cos());
+            }
+            ((pi * t * (1.0 - alpha)).sin() + 4.0 * alpha * t * (pi * t * (1.0 + alpha)).cos())
+                / (pi * t * d)
+        })
+        .collect();
+    let sum: Float = taps.iter().sum();
+    taps.into_iter().map(|t| t / sum).collect()
+}
+
 /// Generate hilbert transformer filter.
 #[must_use]
 pub fn hilbert(window: &Window) -> Vec<Float> {
-    assert!(window.0.is_empty());
+    assert!(!window.0.is_empty());
     assert_ne!(window.0.len(), 1);
     let ntaps = window.0.len();
     let mid = (ntaps - 1) / 2;
@@ -625,4 +822,125 @@ mod tests {
         assert!(multiband(&[(0.0, 1.0)], 0, &Window(vec![])).is_none());
         assert!(multiband(&[(0.0, 3.0)], 8, &Window(vec![1.0; 8])).is_none());
     }
+
+    #[test]
+    fn hilbert_taps() {
+        let taps = hilbert(&crate::window::WindowType::Hamming.make_window(65));
+        assert_eq!(taps.len(), 65);
+        let mid = taps.len() / 2;
+        assert_eq!(taps[mid], 0.0);
+        for i in 1..=mid {
+            // Odd symmetric, with only odd offsets from the center set.
+            assert!((taps[mid + i] + taps[mid - i]).abs() < 1e-6, "offset {i}");
+            assert_eq!(taps[mid + i] == 0.0, i % 2 == 0, "offset {i}");
+        }
+        assert!(taps[mid + 1] > 0.0);
+    }
+
+    #[test]
+    fn gaussian_taps() {
+        let taps = gaussian(8.0, 0.5, 33);
+        assert_eq!(taps.len(), 33);
+        assert!((taps.iter().sum::<Float>() - 1.0).abs() < 1e-5);
+        // Symmetric, peaking in the middle.
+        for i in 0..16 {
+            assert!((taps[i] - taps[32 - i]).abs() < 1e-7);
+            assert!(taps[i] < taps[i + 1]);
+        }
+        // At BT 0.5, sigma is 0.265 symbols. So half a symbol away is
+        // exp(-0.5*(0.5/0.265)^2).
+        let want = (-0.5 * (0.5f64 / 0.265_01).powi(2)).exp() as Float;
+        assert!(
+            (taps[12] / taps[16] - want).abs() < 1e-4,
+            "{}",
+            taps[12] / taps[16]
+        );
+    }
+
+    #[test]
+    fn raised_cosine_taps() {
+        for alpha in [0.0, 0.25, 0.35, 0.5, 1.0] {
+            let taps = raised_cosine(4.0, alpha, 41);
+            assert!((taps.iter().sum::<Float>() - 1.0).abs() < 1e-5);
+            // Zero crossings every symbol.
+            for k in [1, 2, 3, 4, 5] {
+                assert!(taps[20 + 4 * k].abs() < 1e-6, "{alpha} {k}");
+                assert!(taps[20 - 4 * k].abs() < 1e-6, "{alpha} {k}");
+            }
+            assert!(taps[20] > 0.2);
+        }
+    }
+
+    #[test]
+    fn root_raised_cosine_taps() {
+        // Matched pair makes a raised cosine.
+        let sps = 8;
+        for alpha in [0.25, 0.35, 0.5, 1.0] {
+            let taps = root_raised_cosine(sps as Float, alpha, 16 * sps + 1);
+            assert!((taps.iter().sum::<Float>() - 1.0).abs() < 1e-5);
+            for i in 0..taps.len() {
+                assert!((taps[i] - taps[taps.len() - 1 - i]).abs() < 1e-6);
+            }
+            let conv: Vec<Float> = (0..2 * taps.len() - 1)
+                .map(|n| {
+                    (0..taps.len())
+                        .filter(|&k| n >= k && n - k < taps.len())
+                        .map(|k| taps[k] * taps[n - k])
+                        .sum()
+                })
+                .collect();
+            let mid = taps.len() - 1;
+            for k in 1..6 {
+                assert!(
+                    conv[mid + k * sps].abs() < 0.01 * conv[mid],
+                    "{alpha} {k}: {} vs {}",
+                    conv[mid + k * sps],
+                    conv[mid]
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn interp_fir() -> Result<()> {
+        let input: Vec<Float> = (0..50).map(|n| ((n * 7) % 11) as Float - 5.0).collect();
+        let taps: Vec<Float> = (0..13).map(|n| (n as Float * 0.3).sin()).collect();
+        for interp in [1, 2, 3, 5, 13, 20] {
+            let (mut src, prev) = VectorSource::builder(input.clone())
+                .tags(&[Tag::new(20, "foo", TagValue::Bool(true))])
+                .build()?;
+            src.work()?;
+            let (mut b, out) = InterpFirFilter::new(prev, &taps, interp);
+            b.work()?;
+            let (res, tags) = out.read_buf()?;
+
+            // Reference: zero stuffing and a regular FIR.
+            let ntaps = taps.len().div_ceil(interp);
+            let stuffed: Vec<Float> = input
+                .iter()
+                .flat_map(|&x| std::iter::once(x).chain(std::iter::repeat_n(0.0, interp - 1)))
+                .collect();
+            let want: Vec<Float> = (0..(input.len() - ntaps + 1) * interp)
+                .map(|m| {
+                    let m = m + (ntaps - 1) * interp;
+                    (0..taps.len())
+                        .filter(|&k| k <= m)
+                        .map(|k| taps[k] * stuffed[m - k])
+                        .sum()
+                })
+                .collect();
+            assert_eq!(res.len(), want.len(), "interp {interp}");
+            for (got, want) in res.slice().iter().zip(&want) {
+                assert!(
+                    (got - want).abs() < 1e-4,
+                    "interp {interp}: {got} != {want}"
+                );
+            }
+            assert!(
+                tags.contains(&Tag::new(20 * interp, "foo", TagValue::Bool(true))),
+                "{tags:?}"
+            );
+        }
+        Ok(())
+    }
 }
@@ -8,11 +8,14 @@
 //! [aprs]: https://en.wikipedia.org/wiki/Automatic_Packet_Reporting_System
 use crate::Result;
 use crate::block::{Block, BlockRet};
-use crate::stream::{NCReadStream, NCWriteStream};
+use crate::stream::{NCReadStream, NCWriteStream, Tag, TagValue};
 
 const SYNC_BYTES: usize = 20;
 const SYNC: &[u8] = &[0, 1, 1, 1, 1, 1, 1, 0];
 
+/// Tag added by [`HdlcFramer`] on the last bit of each frame.
+pub const FRAME_END_TAG: &str = "HdlcFramer:frame-end";
+
 /// FCS adder.
 ///
 /// Takes a packet, and adds 16 bit CRC to it.
@@ -47,6 +50,9 @@ impl Block for FcsAdder {
 ///
 /// It has to be a bunch of bits, because bit stuffing makes the output not
 /// necessarily be byte aligned.
+///
+/// The last bit is tagged with [`FRAME_END_TAG`], so that the end of the frame
+/// can be found after `PduToStream`.
 #[derive(rustradio_macros::Block)]
 #[rustradio(crate, new)]
 pub struct HdlcFramer {
@@ -91,10 +97,11 @@ impl Block for HdlcFramer {
             if self.dst.remaining() == 0 {
                 return Ok(BlockRet::WaitForStream(&self.dst, 1));
             }
-            let Some((x, tags)) = self.src.pop() else {
+            let Some((x, mut tags)) = self.src.pop() else {
                 return Ok(BlockRet::WaitForStream(&self.src, 1));
             };
             let out = hdlc_encode(&x);
+            tags.push(Tag::new(out.len() - 1, FRAME_END_TAG, TagValue::Bool(true)));
             self.dst.push(out, tags);
         }
     }
@@ -0,0 +1,211 @@
+/*! Write lines of greyscale pixels as an image file.
+
+Each input packet is one line, e.g. from
+[`AptDecode`](crate::blocks::AptDecode). The image is written when the input
+ends, or when the block is dropped.
+
+The format is PGM, unless the file name ends in `.png`, which needs the `png`
+feature.
+*/
+use std::path::PathBuf;
+
+use log::{error, info};
+
+use crate::block::{Block, BlockRet};
+use crate::stream::NCReadStream;
+use crate::{Error, Result};
+
+/// Image file format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    /// Binary PGM, i.e. `P5`.
+    Pgm,
+    /// PNG.
+    #[cfg(feature = "png")]
+    Png,
+}
+
+impl ImageFormat {
+    /// Pick format from a file name extension.
+    ///
+    /// # Errors
+    ///
+    /// Errors if the extension is `.png`, but the `png` feature is not
+    /// enabled.
+    pub fn from_path(path: &std::path::Path) -> Result<Self> {
+        match path.extension().and_then(|e| e.to_str()) {
+            #[cfg(feature = "png")]
+            Some(e) if e.eq_ignore_ascii_case("png") => Ok(ImageFormat::Png),
+            #[cfg(not(feature = "png"))]
+            Some(e) if e.eq_ignore_ascii_case("png") => {
+                Err(Error::msg("PNG output needs the png feature"))
+            }
+            _ => Ok(ImageFormat::Pgm),
+        }
+    }
+}
+
+/// Encode greyscale pixels as binary PGM.
+#[must_use]
+pub fn encode_pgm(pixels: &[u8], width: usize) -> Vec<u8> {
+    let height = pixels.len().checked_div(width).unwrap_or(0);
+    let mut out = format!("P5\n{width} {height}\n255\n").into_bytes();
+    out.extend(&pixels[..width * height]);
+    out
+}
+
+/// Image sink.
+///
+/// Lines are padded with black, or truncated, to the width of the first
+/// line.
+#[derive(rustradio_macros::Block)]
+#[rustradio(crate)]
+pub struct ImageSink {
+    #[rustradio(in)]
+    src: NCReadStream<Vec<u8>>,
+    path: PathBuf,
+    format: ImageFormat,
+    width: usize,
+    pixels: Vec<u8>,
+    saved: bool,
+}
+
+impl ImageSink {
+    /// Create a new image sink, with the format given by the file name.
+    ///
+    /// # Errors
+    ///
+    /// Errors if the format is not supported.
+    pub fn new<P: Into<PathBuf>>(src: NCReadStream<Vec<u8>>, path: P) -> Result<Self> {
+        let path = path.into();
+        let format = ImageFormat::from_path(&path)?;
+        Ok(Self {
+            src,
+            path,
+            format,
+            width: 0,
+            pixels: Vec::new(),
+            saved: true,
+        })
+    }
+
+    /// Return the number of lines received so far.
+    #[must_use]
+    pub fn lines(&self) -> usize {
+        self.pixels.len().checked_div(self.width).unwrap_or(0)
+    }
+
+    fn encode(&self) -> Result<Vec<u8>> {
+        Ok(match self.format {
+            ImageFormat::Pgm => encode_pgm(&self.pixels, self.width),
+            #[cfg(feature = "png")]
+            ImageFormat::Png => {
+                let mut out = Vec::new();
+                let mut enc = png::Encoder::new(&mut out, self.width as u32, self.lines() as u32);
+                enc.set_color(png::ColorType::Grayscale);
+                enc.set_depth(png::BitDepth::Eight);
+                enc.write_header()
+                    .and_then(|mut w| w.write_image_data(&self.pixels))
+                    .map_err(|e| Error::wrap(e, "encoding PNG"))?;
+                out
+            }
+        })
+    }
+
+    fn save(&mut self) -> Result<()> {
+        if self.saved {
+            return Ok(());
+        }
+        self.saved = true;
+        if self.pixels.is_empty() {
+            return Ok(());
+        }
+        std::fs::write(&self.path, self.encode()?).map_err(|e| Error::file_io(e, &self.path))?;
+        info!(
+            "ImageSink: wrote {}x{} image to {}",
+            self.width,
+            self.lines(),
+            self.path.display()
+        );
+        Ok(())
+    }
+}
+
+impl Drop for ImageSink {
+    fn drop(&mut self) {
+        if let Err(e) = self.save() {
+            error!("ImageSink: {e}");
+        }
+    }
+}
+
+impl Block for ImageSink {
+    fn work(&mut self) -> Result<BlockRet<'_>> {
+        while let Some((mut line, _tags)) = self.src.pop() {
+            if self.width == 0 {
+                self.width = line.len();
+            }
+            line.resize(self.width, 0);
+            self.pixels.extend(line);
+            self.saved = false;
+        }
+        if self.src.eof() {
+            self.save()?;
+            return Ok(BlockRet::EOF);
+        }
+        Ok(BlockRet::WaitForStream(&self.src, 1))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pgm() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("test.pgm");
+        let (tx, rx) = crate::stream::new_nocopy_stream();
+        let mut b = ImageSink::new(rx, &path)?;
+        tx.push(vec![1, 2, 3], []);
+        tx.push(vec![4, 5, 6, 7], []);
+        tx.push(vec![8], []);
+        b.work()?;
+        assert_eq!(b.lines(), 3);
+        drop(tx);
+        assert!(matches!(b.work()?, BlockRet::EOF));
+        assert_eq!(
+            std::fs::read(&path)?,
+            b"P5\n3 3\n255\n\x01\x02\x03\x04\x05\x06\x08\0\0"
+        );
+        Ok(())
+    }
+
+    #[cfg(feature = "png")]
+    #[test]
+    fn png() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("test.png");
+        let (tx, rx) = crate::stream::new_nocopy_stream();
+        let mut b = ImageSink::new(rx, &path)?;
+        tx.push(vec![1, 2, 3], []);
+        tx.push(vec![4, 5, 6], []);
+        drop(tx);
+        assert!(matches!(b.work()?, BlockRet::EOF));
+        let data = std::fs::read(&path)?;
+        assert_eq!(&data[..8], b"\x89PNG\r\n\x1a\n");
+        Ok(())
+    }
+
+    #[test]
+    fn format() -> Result<()> {
+        let f = |p: &str| ImageFormat::from_path(std::path::Path::new(p));
+        assert_eq!(f("a.pgm")?, ImageFormat::Pgm);
+        assert_eq!(f("a")?, ImageFormat::Pgm);
+        #[cfg(feature = "png")]
+        assert_eq!(f("a.PNG")?, ImageFormat::Png);
+        #[cfg(not(feature = "png"))]
+        assert!(f("a.png").is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,82 @@
+//! Interleave two streams.
+use crate::block::{Block, BlockRet};
+use crate::stream::{ReadStream, WriteStream};
+use crate::{Result, Sample};
+
+/// Interleave two streams into one, alternating samples from each.
+///
+/// Useful for stereo audio, such as turning left and right channels into
+/// input for a two channel `AuEncode`.
+///
+/// Tags are taken from the first stream. Tags from the other input stream
+/// are discarded.
+#[derive(rustradio_macros::Block)]
+#[rustradio(crate, new)]
+pub struct Interleave<T: Sample> {
+    #[rustradio(in)]
+    a: ReadStream<T>,
+    #[rustradio(in)]
+    b: ReadStream<T>,
+    #[rustradio(out)]
+    dst: WriteStream<T>,
+}
+
+impl<T: Sample> Block for Interleave<T> {
+    fn work(&mut self) -> Result<BlockRet<'_>> {
+        let (a, tags) = self.a.read_buf()?;
+        let (b, _) = self.b.read_buf()?;
+        if a.is_empty() {
+            return Ok(BlockRet::WaitForStream(&self.a, 1));
+        }
+        if b.is_empty() {
+            return Ok(BlockRet::WaitForStream(&self.b, 1));
+        }
+        let mut o = self.dst.write_buf()?;
+        let n = a.len().min(b.len()).min(o.len() / 2);
+        if n == 0 {
+            return Ok(BlockRet::WaitForStream(&self.dst, 2));
+        }
+        for (i, out) in o.slice()[..2 * n].chunks_exact_mut(2).enumerate() {
+            out[0] = a.slice()[i];
+            out[1] = b.slice()[i];
+        }
+        let tags: Vec<_> = tags
+            .into_iter()
+            .filter(|t| t.pos() < n)
+            .map(|mut t| {
+                t.set_pos(t.pos() * 2);
+                t
+            })
+            .collect();
+        a.consume(n);
+        b.consume(n);
+        o.produce(2 * n, &tags);
+        Ok(BlockRet::Again)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::VectorSource;
+    use crate::stream::{Tag, TagValue};
+
+    #[test]
+    fn interleave() -> Result<()> {
+        let (mut a, ap) = VectorSource::builder(vec![1u8, 3, 5])
+            .tags(&[Tag::new(1, "foo", TagValue::Bool(true))])
+            .build()?;
+        let (mut b, bp) = VectorSource::builder(vec![2u8, 4])
+            .tags(&[Tag::new(0, "bar", TagValue::Bool(true))])
+            .build()?;
+        a.work()?;
+        b.work()?;
+        let (mut il, out) = Interleave::new(ap, bp);
+        il.work()?;
+        let (o, tags) = out.read_buf()?;
+        assert_eq!(o.slice(), &[1, 2, 3, 4]);
+        assert!(tags.contains(&Tag::new(2, "foo", TagValue::Bool(true))));
+        assert!(!tags.iter().any(|t| t.key() == "bar"));
+        Ok(())
+    }
+}
@@ -4,13 +4,13 @@ use crate::{Error, Result};
 use log::debug;
 
 const MAX_LEN: usize = 10_000;
-const KISS_FEND: u8 = 0xC0;
+pub(crate) const KISS_FEND: u8 = 0xC0;
 const KISS_FESC: u8 = 0xDB;
 const KISS_TFEND: u8 = 0xDC;
 const KISS_TFESC: u8 = 0xDD;
 const ENCODE_PORT_TAG: &str = "KissEncode:port";
 
-fn strip_fend(data: &[u8]) -> &[u8] {
+pub(crate) fn strip_fend(data: &[u8]) -> &[u8] {
     let start = data
         .iter()
         .position(|&b| b != KISS_FEND)
@@ -25,14 +25,13 @@ fn strip_fend(data: &[u8]) -> &[u8] {
     &data[start..end]
 }
 
-/// Escape KISS data stream.
+/// Escape and add `FEND` around the concatenation of the two slices.
 #[must_use]
-fn escape(bytes: &[u8], port: u8) -> Vec<u8> {
+fn escape_frame(head: &[u8], bytes: &[u8]) -> Vec<u8> {
     // Add 10% capacity to leave room for escaped
-    let mut ret = Vec::with_capacity((3 + bytes.len()) * 110 / 100);
+    let mut ret = Vec::with_capacity((2 + head.len() + bytes.len()) * 110 / 100);
     ret.push(KISS_FEND);
-    ret.push(port << 4);
-    for &b in bytes {
+    for &b in head.iter().chain(bytes) {
         match b {
             KISS_FEND => ret.extend(vec![KISS_FESC, KISS_TFEND]),
             KISS_FESC => ret.extend(vec![KISS_FESC, KISS_TFESC]),
@@ -76,14 +75,303 @@ fn unescape(data: &[u8]) -> Result<Vec<u8>> {
     }
 }
 
+/// CRC-16 as used by SMACK. Also known as CRC-16/ARC.
+#[must_use]
+fn crc16_smack(data: &[u8]) -> u16 {
+    let mut crc = 0u16;
+    for &b in data {
+        crc ^= u16::from(b);
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xA001
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    crc
+}
+
+/// CRC-16 as used by FlexNet's FlexKISS.
+///
+/// The FlexNet table is the reflected CCITT table, XORed with 0x0f87, but then
+/// used in a non-reflected CRC loop.
+#[must_use]
+fn crc16_flex(data: &[u8]) -> u16 {
+    let table = |i: u8| -> u16 {
+        let mut crc = u16::from(i);
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0x8408
+            } else {
+                crc >> 1
+            };
+        }
+        crc ^ 0x0f87
+    };
+    data.iter().fold(0xffff, |crc: u16, &b| {
+        (crc << 8) ^ table(((crc >> 8) as u8) ^ b)
+    })
+}
+
+/// CRC protection of KISS frames.
+///
+/// Plain KISS has no CRC, but some TNCs and drivers (e.g. Linux `mkiss`)
+/// support adding one.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum KissCrc {
+    /// Plain KISS.
+    #[default]
+    None,
+
+    /// SMACK, signalled by the high bit of the command byte. Only supports
+    /// ports 0-7.
+    ///
+    /// When decoding, frames without the SMACK bit are accepted as plain KISS,
+    /// since that's how SMACK is negotiated.
+    ///
+    /// <https://www.symek.com/g/smack.html>
+    Smack,
+
+    /// FlexNet FlexKISS, signalled by bit 5 of the command byte. Only supports
+    /// ports 0, 1, 4, and 5, since the other ports overlap with the CRC bit.
+    ///
+    /// As with SMACK, frames without the CRC bit are accepted as plain KISS.
+    Flex,
+}
+
+const CMD_DATA: u8 = 0;
+const CMD_TXDELAY: u8 = 1;
+const CMD_PERSISTENCE: u8 = 2;
+const CMD_SLOT_TIME: u8 = 3;
+const CMD_TXTAIL: u8 = 4;
+const CMD_FULL_DUPLEX: u8 = 5;
+const CMD_SET_HARDWARE: u8 = 6;
+const CMD_RETURN: u8 = 0xFF;
+const SMACK_BIT: u8 = 0x80;
+const FLEX_BIT: u8 = 0x20;
+
+/// KISS command.
+///
+/// Times are in units of 10ms, as sent on the wire.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KissCommand {
+    /// Data frame.
+    Data(Vec<u8>),
+
+    /// Time to wait between keying the transmitter and sending data.
+    TxDelay(u8),
+
+    /// Persistence parameter `p` for CSMA, scaled to 0-255.
+    Persistence(u8),
+
+    /// Slot interval for CSMA.
+    SlotTime(u8),
+
+    /// Time to hold the transmitter after the data. Obsolete.
+    TxTail(u8),
+
+    /// Full duplex, if true. Otherwise half duplex.
+    FullDuplex(bool),
+
+    /// Hardware specific command.
+    SetHardware(Vec<u8>),
+
+    /// Exit KISS mode.
+    Return,
+}
+
+/// KISS message, meaning a KISS command on a port.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KissMessage {
+    /// KISS port, 0-15.
+    ///
+    /// Called "channel" by some. For [`KissCommand::Return`] it's ignored.
+    pub port: u8,
+
+    /// The command.
+    pub command: KissCommand,
+}
+
+impl KissMessage {
+    /// Create new KISS message.
+    #[must_use]
+    pub fn new(port: u8, command: KissCommand) -> Self {
+        Self { port, command }
+    }
+
+    /// Decode a KISS frame that has had the `FEND` stripped, but is still
+    /// escaped.
+    pub fn decode(frame: &[u8], crc: KissCrc) -> Result<Self> {
+        let frame = unescape(frame)?;
+        if frame.is_empty() {
+            return Err(Error::msg("KissDecode: empty frame"));
+        }
+        let cmd = frame[0];
+        let (cmd, data) = match crc {
+            KissCrc::Smack if cmd != CMD_RETURN && cmd & SMACK_BIT != 0 => {
+                if frame.len() < 3 || crc16_smack(&frame) != 0 {
+                    return Err(Error::msg("KissDecode: SMACK CRC error"));
+                }
+                (cmd & !SMACK_BIT, &frame[1..frame.len() - 2])
+            }
+            KissCrc::Flex if cmd != CMD_RETURN && cmd & FLEX_BIT != 0 => {
+                let n = frame.len();
+                if n < 3
+                    || crc16_flex(&frame[..n - 2])
+                        != u16::from_be_bytes([frame[n - 2], frame[n - 1]])
+                {
+                    return Err(Error::msg("KissDecode: FlexKISS CRC error"));
+                }
+                (cmd & !FLEX_BIT, &frame[1..n - 2])
+            }
+            _ => (cmd, &frame[1..]),
+        };
+        if cmd == CMD_RETURN {
+            return Ok(Self::new(0, KissCommand::Return));
+        }
+        let param = || {
+            data.first()
+                .copied()
+                .ok_or_else(|| Error::msg(format!("KissDecode: command {cmd:02x} without value")))
+        };
+        let command = match cmd & 0xF {
+            CMD_DATA => KissCommand::Data(data.to_vec()),
+            CMD_TXDELAY => KissCommand::TxDelay(param()?),
+            CMD_PERSISTENCE => KissCommand::Persistence(param()?),
+            CMD_SLOT_TIME => KissCommand::SlotTime(param()?),
+            CMD_TXTAIL => KissCommand::TxTail(param()?),
+            CMD_FULL_DUPLEX => KissCommand::FullDuplex(param()? != 0),
+            CMD_SET_HARDWARE => KissCommand::SetHardware(data.to_vec()),
+            other => {
+                return Err(Error::msg(format!(
+                    "KissDecode: unknown command {other:02x}"
+                )));
+            }
+        };
+        Ok(Self::new(cmd >> 4, command))
+    }
+
+    /// Encode into a complete KISS frame, including `FEND`.
+    pub fn encode(&self, crc: KissCrc) -> Result<Vec<u8>> {
+        if self.port > 0xF {
+            return Err(Error::msg(format!(
+                "KissEncode: invalid port {}",
+                self.port
+            )));
+        }
+        let (cmd, data): (u8, &[u8]) = match &self.command {
+            KissCommand::Data(d) => (CMD_DATA, d),
+            KissCommand::TxDelay(v) => (CMD_TXDELAY, std::slice::from_ref(v)),
+            KissCommand::Persistence(v) => (CMD_PERSISTENCE, std::slice::from_ref(v)),
+            KissCommand::SlotTime(v) => (CMD_SLOT_TIME, std::slice::from_ref(v)),
+            KissCommand::TxTail(v) => (CMD_TXTAIL, std::slice::from_ref(v)),
+            KissCommand::FullDuplex(v) => (CMD_FULL_DUPLEX, &[u8::from(*v)]),
+            KissCommand::SetHardware(d) => (CMD_SET_HARDWARE, d),
+            KissCommand::Return => return Ok(escape_frame(&[CMD_RETURN], &[])),
+        };
+        let cmd = cmd | (self.port << 4);
+        Ok(match crc {
+            KissCrc::None => escape_frame(&[cmd], data),
+            KissCrc::Smack => {
+                if self.port > 7 {
+                    return Err(Error::msg(format!(
+                        "KissEncode: SMACK does not support port {}",
+                        self.port
+                    )));
+                }
+                let mut v = Vec::with_capacity(data.len() + 3);
+                v.push(cmd | SMACK_BIT);
+                v.extend(data);
+                let c = crc16_smack(&v);
+                v.extend(c.to_le_bytes());
+                escape_frame(&v, &[])
+            }
+            KissCrc::Flex => {
+                if cmd & FLEX_BIT != 0 || self.port > 7 {
+                    return Err(Error::msg(format!(
+                        "KissEncode: FlexKISS does not support port {}",
+                        self.port
+                    )));
+                }
+                let mut v = Vec::with_capacity(data.len() + 3);
+                v.push(cmd | FLEX_BIT);
+                v.extend(data);
+                let c = crc16_flex(&v);
+                v.extend(c.to_be_bytes());
+                escape_frame(&v, &[])
+            }
+        })
+    }
+}
+
+/// Latest TX parameters received for a KISS port.
+///
+/// Kept so that data frames can be tagged with them, for the benefit of the TX
+/// chain.
+#[derive(Debug, Default, Clone, Copy)]
+pub(crate) struct KissParams {
+    txdelay: Option<u8>,
+    persistence: Option<u8>,
+    slot_time: Option<u8>,
+    txtail: Option<u8>,
+    full_duplex: Option<bool>,
+}
+
+impl KissParams {
+    /// Update the parameters from the command. Return false if the command is
+    /// not a parameter.
+    pub(crate) fn update(&mut self, cmd: &KissCommand) -> bool {
+        match cmd {
+            KissCommand::TxDelay(v) => self.txdelay = Some(*v),
+            KissCommand::Persistence(v) => self.persistence = Some(*v),
+            KissCommand::SlotTime(v) => self.slot_time = Some(*v),
+            KissCommand::TxTail(v) => self.txtail = Some(*v),
+            KissCommand::FullDuplex(v) => self.full_duplex = Some(*v),
+            KissCommand::Data(_) | KissCommand::SetHardware(_) | KissCommand::Return => {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Tags for all parameters that have been set, prefixed with the block
+    /// name.
+    #[must_use]
+    pub(crate) fn tags(&self, prefix: &str) -> Vec<Tag> {
+        let ms = |v: u8| TagValue::U64(10 * u64::from(v));
+        [
+            self.txdelay.map(|v| ("txdelay-ms", ms(v))),
+            self.persistence
+                .map(|v| ("persistence", TagValue::U64(v.into()))),
+            self.slot_time.map(|v| ("slot-time-ms", ms(v))),
+            self.txtail.map(|v| ("txtail-ms", ms(v))),
+            self.full_duplex.map(|v| ("full-duplex", TagValue::Bool(v))),
+        ]
+        .into_iter()
+        .flatten()
+        .map(|(k, v)| Tag::new(0, format!("{prefix}:{k}"), v))
+        .collect()
+    }
+}
+
 /// Decode KISS frame.
 ///
 /// This means trimming away `FEND` from both sides, and unescaping `HDLC`
 /// bytes.
 ///
-/// <https://en.wikipedia.org/wiki/KISS_(amateur_radio_protocol)>
+/// Data frames are output, tagged with `KissDecode:port`, and any TX
+/// parameters previously set for that port:
+/// * `KissDecode:txdelay-ms`
+/// * `KissDecode:persistence` (0-255)
+/// * `KissDecode:slot-time-ms`
+/// * `KissDecode:txtail-ms`
+/// * `KissDecode:full-duplex`
+///
+/// All commands, including data frames, are also sent to the control output,
+/// if [`out_control`](KissDecode::out_control) has been called.
 ///
-/// TODO: Tag with other KISS stuff like channel.
+/// <https://en.wikipedia.org/wiki/KISS_(amateur_radio_protocol)>
 #[derive(rustradio_macros::Block)]
 #[rustradio(crate, new)]
 pub struct KissDecode {
@@ -91,6 +379,33 @@ pub struct KissDecode {
     src: NCReadStream<Vec<u8>>,
     #[rustradio(out)]
     dst: NCWriteStream<Vec<u8>>,
+    #[rustradio(default)]
+    control: Option<NCWriteStream<KissMessage>>,
+    #[rustradio(default)]
+    crc: KissCrc,
+    #[rustradio(default)]
+    params: [KissParams; 16],
+}
+
+impl KissDecode {
+    /// Set which CRC variant to accept.
+    pub fn set_crc(&mut self, crc: KissCrc) {
+        self.crc = crc;
+    }
+
+    /// Return control stream, getting all decoded KISS messages.
+    ///
+    /// The output stream can only be created once, so if called a second time,
+    /// just returns None.
+    pub fn out_control(&mut self) -> Option<NCReadStream<KissMessage>> {
+        if self.control.is_some() {
+            log::warn!("KissDecode::out_control() called more than once");
+            return None;
+        }
+        let (tx, rx) = crate::stream::new_nocopy_stream();
+        self.control = Some(tx);
+        Some(rx)
+    }
 }
 
 impl Block for KissDecode {
@@ -99,6 +414,11 @@ impl Block for KissDecode {
             if self.dst.remaining() == 0 {
                 return Ok(BlockRet::WaitForStream(&self.dst, 1));
             }
+            if let Some(control) = &self.control
+                && control.remaining() == 0
+            {
+                return Ok(BlockRet::WaitForStream(control, 1));
+            }
             let Some((x, mut tags)) = self.src.pop() else {
                 return Ok(BlockRet::WaitForStream(&self.src, 1));
             };
@@ -106,16 +426,22 @@ impl Block for KissDecode {
             if x.is_empty() {
                 continue;
             }
-            let (port, x) = (x[0], &x[1..]);
-            if port & 0xF != 0 {
-                debug!("KissDecode: non-data packet: {port:02x} {x:02x?}");
-                continue;
-            }
-            let port = (port >> 4) & 0xf;
-            let out = match unescape(x) {
-                Ok(o) => o,
+            let msg = match KissMessage::decode(x, self.crc) {
+                Ok(msg) => msg,
                 Err(e) => {
-                    log::debug!("Bad KISS packet: {e}");
+                    debug!("Bad KISS packet: {e}");
+                    continue;
+                }
+            };
+            if let Some(control) = &self.control {
+                control.push(msg.clone(), tags.clone());
+            }
+            let KissMessage { port, command } = msg;
+            let out = match command {
+                KissCommand::Data(out) => out,
+                other => {
+                    debug!("KissDecode: non-data packet on port {port}: {other:?}");
+                    self.params[usize::from(port)].update(&other);
                     continue;
                 }
             };
@@ -124,7 +450,7 @@ impl Block for KissDecode {
                 Tag::new(
                     0,
                     "KissDecode:input-bytes",
-                    TagValue::U64(x.len().try_into().unwrap()),
+                    TagValue::U64((x.len() - 1).try_into().unwrap()),
                 ),
                 Tag::new(
                     0,
@@ -132,6 +458,7 @@ impl Block for KissDecode {
                     TagValue::U64(out.len().try_into().unwrap()),
                 ),
             ]);
+            tags.extend(self.params[usize::from(port)].tags("KissDecode"));
             self.dst.push(out, tags);
         }
     }
@@ -232,9 +559,11 @@ impl Block for KissFrame {
 ///
 /// Takes bytes and creates a KISS frame.
 ///
-/// <https://en.wikipedia.org/wiki/KISS_(amateur_radio_protocol)>
+/// The port is taken from the tag `KissEncode:port`, defaulting to 0.
 ///
-/// TODO: Take channel from a tag.
+/// To send other KISS commands, use [`KissMessage::encode`].
+///
+/// <https://en.wikipedia.org/wiki/KISS_(amateur_radio_protocol)>
 #[derive(rustradio_macros::Block)]
 #[rustradio(crate, new)]
 pub struct KissEncode {
@@ -242,6 +571,15 @@ pub struct KissEncode {
     src: NCReadStream<Vec<u8>>,
     #[rustradio(out)]
     dst: NCWriteStream<Vec<u8>>,
+    #[rustradio(default)]
+    crc: KissCrc,
+}
+
+impl KissEncode {
+    /// Set which CRC variant, if any, to add.
+    pub fn set_crc(&mut self, crc: KissCrc) {
+        self.crc = crc;
+    }
 }
 
 impl Block for KissEncode {
@@ -264,7 +602,16 @@ impl Block for KissEncode {
                     0
                 }
             };
-            let out = escape(&x, port.try_into().unwrap());
+            let input_bytes = x.len();
+            let out = match KissMessage::new(port.try_into().unwrap(), KissCommand::Data(x))
+                .encode(self.crc)
+            {
+                Ok(out) => out,
+                Err(e) => {
+                    debug!("KissEncode: failed to encode: {e}");
+                    continue;
+                }
+            };
             let tags: Vec<_> = tags
                 .into_iter()
                 .filter(|t| t.key() != ENCODE_PORT_TAG)
@@ -272,7 +619,7 @@ impl Block for KissEncode {
                     Tag::new(
                         0,
                         "KissEncode:input-bytes",
-                        TagValue::U64(x.len().try_into().unwrap()),
+                        TagValue::U64(input_bytes.try_into().unwrap()),
                     ),
                     Tag::new(
                         0,
@@ -339,6 +686,117 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn decode_commands() -> Result<()> {
+        let (tx, rx) = new_nocopy_stream();
+        let (mut b, out) = KissDecode::new(rx);
+        let control = b.out_control().unwrap();
+        assert!(b.out_control().is_none());
+        tx.push(b"\xC0\x11\x32\xC0".to_vec(), &[]);
+        tx.push(b"\xC0\x12\x3f\xC0".to_vec(), &[]);
+        tx.push(b"\xC0\x05\x01\xC0".to_vec(), &[]);
+        tx.push(b"\xC0\x16\x01\x02\xC0".to_vec(), &[]);
+        tx.push(b"\xC0\xFF\xC0".to_vec(), &[]);
+        tx.push(b"\xC0\x10ab\xC0".to_vec(), &[]);
+        assert!(matches![b.work()?, BlockRet::WaitForStream(_, 1)]);
+        let (o, tags) = out.pop().unwrap();
+        assert_eq!(o, b"ab");
+        assert_eq!(
+            tags,
+            &[
+                Tag::new(0, "KissDecode:port", TagValue::U64(1)),
+                Tag::new(0, "KissDecode:input-bytes", TagValue::U64(2)),
+                Tag::new(0, "KissDecode:output-bytes", TagValue::U64(2)),
+                Tag::new(0, "KissDecode:txdelay-ms", TagValue::U64(500)),
+                Tag::new(0, "KissDecode:persistence", TagValue::U64(63)),
+            ]
+        );
+        assert!(out.pop().is_none());
+        let got: Vec<_> = std::iter::from_fn(|| control.pop().map(|(m, _)| m)).collect();
+        assert_eq!(
+            got,
+            &[
+                KissMessage::new(1, KissCommand::TxDelay(50)),
+                KissMessage::new(1, KissCommand::Persistence(63)),
+                KissMessage::new(0, KissCommand::FullDuplex(true)),
+                KissMessage::new(1, KissCommand::SetHardware(vec![1, 2])),
+                KissMessage::new(0, KissCommand::Return),
+                KissMessage::new(1, KissCommand::Data(b"ab".to_vec())),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn command_roundtrip() -> Result<()> {
+        for crc in [KissCrc::None, KissCrc::Smack, KissCrc::Flex] {
+            for msg in [
+                KissMessage::new(0, KissCommand::Data(vec![])),
+                KissMessage::new(1, KissCommand::Data(b"\xC0\xDB\x00foo".to_vec())),
+                KissMessage::new(4, KissCommand::TxDelay(0xC0)),
+                KissMessage::new(5, KissCommand::Persistence(0xDB)),
+                KissMessage::new(0, KissCommand::SlotTime(10)),
+                KissMessage::new(0, KissCommand::TxTail(1)),
+                KissMessage::new(0, KissCommand::FullDuplex(false)),
+                KissMessage::new(0, KissCommand::SetHardware(vec![1, 2, 3])),
+                KissMessage::new(0, KissCommand::Return),
+            ] {
+                let enc = msg.encode(crc)?;
+                assert_eq!(enc[0], KISS_FEND);
+                assert_eq!(enc[enc.len() - 1], KISS_FEND);
+                assert!(!enc[1..enc.len() - 1].contains(&KISS_FEND));
+                let got = KissMessage::decode(strip_fend(&enc), crc)?;
+                assert_eq!(got, msg, "crc {crc:?}");
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn crc() -> Result<()> {
+        assert_eq!(crc16_smack(b"123456789"), 0xBB3D);
+
+        // First entries of the FlexNet table, as found in e.g. Linux mkiss.
+        assert_eq!(crc16_flex(&[0xff]), 0x0f87 ^ 0xff00);
+        assert_eq!(crc16_flex(&[0xfe]), 0x1e0e ^ 0xff00);
+        assert_eq!(crc16_flex(&[0xfd]), 0x2c95 ^ 0xff00);
+
+        let msg = KissMessage::new(2, KissCommand::Data(b"hello".to_vec()));
+        let enc = msg.encode(KissCrc::Smack)?;
+        assert_eq!(enc[1], 0xA0);
+        assert_eq!(KissMessage::decode(strip_fend(&enc), KissCrc::Smack)?, msg);
+
+        // Plain KISS is accepted in CRC modes.
+        let plain = msg.encode(KissCrc::None)?;
+        assert_eq!(
+            KissMessage::decode(strip_fend(&plain), KissCrc::Smack)?,
+            msg
+        );
+
+        // Corruption is detected.
+        let mut bad = enc.clone();
+        bad[3] ^= 1;
+        assert!(KissMessage::decode(strip_fend(&bad), KissCrc::Smack).is_err());
+        let msg = KissMessage::new(1, KissCommand::Data(b"hello".to_vec()));
+        let mut bad = msg.encode(KissCrc::Flex)?;
+        assert_eq!(bad[1], 0x30);
+        bad[3] ^= 1;
+        assert!(KissMessage::decode(strip_fend(&bad), KissCrc::Flex).is_err());
+
+        // Ports that collide with the CRC bits are rejected.
+        assert!(
+            KissMessage::new(8, KissCommand::TxTail(1))
+                .encode(KissCrc::Smack)
+                .is_err()
+        );
+        assert!(
+            KissMessage::new(2, KissCommand::TxTail(1))
+                .encode(KissCrc::Flex)
+                .is_err()
+        );
+        Ok(())
+    }
+
     #[test]
     fn encode_nothing() -> Result<()> {
         let (_tx, rx) = new_nocopy_stream();
@@ -0,0 +1,272 @@
+/*! KISS TCP server.
+
+Serves KISS over TCP, like Direwolf's port 8001, turning a receive graph
+into a TNC usable by APRS clients such as Xastir and YAAC.
+
+Any number of clients can connect. Frames received over the air are sent to
+all of them, and frames any client sends are output for transmission.
+
+<https://en.wikipedia.org/wiki/KISS_(amateur_radio_protocol)>
+*/
+use std::net::{SocketAddr, ToSocketAddrs};
+
+use log::{debug, warn};
+
+use crate::Result;
+use crate::block::{Block, BlockRet};
+use crate::kiss::{KISS_FEND, KissCommand, KissCrc, KissMessage, KissParams, strip_fend};
+use crate::stream::{NCReadStream, NCWriteStream, Tag, TagValue};
+use crate::tcp_server::TcpServer;
+
+/// Tag used to get and set the KISS port of a frame.
+///
+/// Frames to send to clients are sent with this port, or 0 if missing. Frames
+/// received from clients are tagged with the port the client sent them on.
+///
+/// Frames from clients are also tagged with the latest TX parameters sent by
+/// clients for that port, using the same tag names as
+/// [`KissDecode`](crate::kiss::KissDecode), but prefixed `KissServer:`.
+pub const PORT_TAG: &str = "KissServer:port";
+
+const MAX_FRAME_LEN: usize = 10_000;
+
+/// Extract complete (still escaped) frames from the buffer.
+fn take_frames(inbuf: &mut Vec<u8>) -> Vec<Vec<u8>> {
+    let mut frames = Vec::new();
+    while let Some(end) = inbuf
+        .iter()
+        .skip(1)
+        .position(|&b| b == KISS_FEND)
+        .map(|p| p + 1)
+    {
+        let frame: Vec<u8> = inbuf.drain(..end).collect();
+        let frame = strip_fend(&frame);
+        if !frame.is_empty() {
+            frames.push(frame.to_vec());
+        }
+    }
+    if inbuf.len() > MAX_FRAME_LEN {
+        debug!("KissServer: discarding oversized frame");
+        inbuf.clear();
+    }
+    frames
+}
+
+/// KISS TCP server.
+///
+/// Takes frames (e.g. from `HdlcDeframer`) and sends them KISS encoded to all
+/// connected clients. Data frames sent by clients are decoded, tagged with
+/// [`PORT_TAG`], and output for transmission (e.g. to `FcsAdder` and
+/// `HdlcFramer`).
+///
+/// ```no_run
+/// use rustradio::blocks::KissServer;
+/// use rustradio::stream::new_nocopy_stream;
+/// let (_tx, rx) = new_nocopy_stream();
+/// let (server, to_transmit) = KissServer::new(rx, "[::]:8001")?;
+/// # Ok::<(), rustradio::Error>(())
+/// ```
+#[derive(rustradio_macros::Block)]
+#[rustradio(crate)]
+pub struct KissServer {
+    #[rustradio(in)]
+    src: NCReadStream<Vec<u8>>,
+    #[rustradio(out)]
+    dst: NCWriteStream<Vec<u8>>,
+    server: TcpServer<()>,
+    crc: KissCrc,
+    params: [KissParams; 16],
+}
+
+impl KissServer {
+    /// Create a new KISS server listening on the given address.
+    pub fn new<A: ToSocketAddrs>(
+        src: NCReadStream<Vec<u8>>,
+        addr: A,
+    ) -> Result<(Self, NCReadStream<Vec<u8>>)> {
+        let server = TcpServer::new("KissServer", addr)?;
+        let (dst, dr) = crate::stream::new_nocopy_stream();
+        Ok((
+            Self {
+                src,
+                dst,
+                server,
+                crc: KissCrc::None,
+                params: Default::default(),
+            },
+            dr,
+        ))
+    }
+
+    /// Set which CRC variant, if any, to use with clients.
+    pub fn set_crc(&mut self, crc: KissCrc) {
+        self.crc = crc;
+    }
+
+    /// Return the address the server is listening on.
+    ///
+    /// Useful when binding to port 0.
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        self.server.local_addr()
+    }
+
+    /// Return the number of currently connected clients.
+    #[must_use]
+    pub fn clients(&self) -> usize {
+        self.server.clients.len()
+    }
+}
+
+fn port_from_tags(tags: &[Tag]) -> u8 {
+    match tags
+        .iter()
+        .find(|t| t.key() == PORT_TAG)
+        .map_or(&TagValue::U64(0), Tag::val)
+    {
+        TagValue::U64(port) if *port < 0x10 => *port as u8,
+        other => {
+            debug!("KissServer: invalid port tag value: {other:?}");
+            0
+        }
+    }
+}
+
+impl Block for KissServer {
+    fn work(&mut self) -> Result<BlockRet<'_>> {
+        let mut active = self.server.accept()?;
+
+        // Fan out received frames to all clients.
+        while let Some((frame, tags)) = self.src.pop() {
+            active = true;
+            let msg = KissMessage::new(port_from_tags(&tags), KissCommand::Data(frame));
+            let out = match msg.encode(self.crc) {
+                Ok(out) => out,
+                Err(e) => {
+                    debug!("KissServer: failed to encode frame: {e}");
+                    continue;
+                }
+            };
+            for client in &mut self.server.clients {
+                client.outbuf.extend(&out);
+            }
+        }
+
+        // Flush output, and read frames to transmit.
+        self.server.flush_and_fill();
+        let frames: Vec<_> = self
+            .server
+            .clients
+            .iter_mut()
+            .flat_map(|c| take_frames(&mut c.inbuf))
+            .collect();
+        for frame in frames {
+            let KissMessage { port, command } = match KissMessage::decode(&frame, self.crc) {
+                Ok(msg) => msg,
+                Err(e) => {
+                    debug!("KissServer: bad KISS frame: {e}");
+                    continue;
+                }
+            };
+            let params = &mut self.params[usize::from(port)];
+            let out = match command {
+                KissCommand::Data(out) => out,
+                other => {
+                    if !params.update(&other) {
+                        debug!("KissServer: ignoring command on port {port}: {other:?}");
+                    }
+                    continue;
+                }
+            };
+            if self.dst.remaining() == 0 {
+                warn!("KissServer: output full, dropping frame from client");
+                continue;
+            }
+            active = true;
+            let mut tags = vec![Tag::new(0, PORT_TAG, TagValue::U64(port.into()))];
+            tags.extend(params.tags("KissServer"));
+            self.dst.push(out, tags);
+        }
+        Ok(if active {
+            BlockRet::Again
+        } else {
+            BlockRet::Pending
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+
+    use crate::Error;
+    use crate::stream::new_nocopy_stream;
+
+    // Run the block until the condition is true, or give up.
+    fn work_until<F: FnMut(&mut KissServer) -> bool>(b: &mut KissServer, mut f: F) -> Result<()> {
+        for _ in 0..200 {
+            b.work()?;
+            if f(b) {
+                return Ok(());
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        Err(Error::msg("timed out"))
+    }
+
+    #[test]
+    fn send_and_receive() -> Result<()> {
+        let (tx, rx) = new_nocopy_stream();
+        let (mut b, out) = KissServer::new(rx, "[::1]:0")?;
+        let addr = b.local_addr()?;
+        let mut c1 = TcpStream::connect(addr)?;
+        let mut c2 = TcpStream::connect(addr)?;
+        work_until(&mut b, |b| b.clients() == 2)?;
+
+        // Over the air to clients.
+        tx.push(
+            b"fo\xC0o".to_vec(),
+            &[Tag::new(0, PORT_TAG, TagValue::U64(2))],
+        );
+        b.work()?;
+        let want = b"\xC0\x20fo\xDB\xDCo\xC0";
+        for c in [&mut c1, &mut c2] {
+            let mut got = [0u8; 8];
+            c.read_exact(&mut got)?;
+            assert_eq!(&got, want);
+        }
+
+        // Client to transmit. Split over two writes, with a TXDELAY frame
+        // that should be turned into a tag.
+        c2.write_all(b"\xC0\x01\x32\xC0\xC0\x10bar\xDB")?;
+        c2.flush()?;
+        work_until(&mut b, |b| {
+            b.server.clients.iter().any(|c| c.inbuf.len() == 6)
+        })?;
+        assert!(out.pop().is_none());
+        c2.write_all(b"\xDD\xC0")?;
+        work_until(&mut b, |_| !out.is_empty())?;
+        let (got, tags) = out.pop().unwrap();
+        assert_eq!(got, b"bar\xDB");
+        assert_eq!(tags, &[Tag::new(0, PORT_TAG, TagValue::U64(1))]);
+
+        // TXDELAY was for port 0.
+        c1.write_all(b"\xC0\x00baz\xC0")?;
+        work_until(&mut b, |_| !out.is_empty())?;
+        let (got, tags) = out.pop().unwrap();
+        assert_eq!(got, b"baz");
+        assert_eq!(
+            tags,
+            &[
+                Tag::new(0, PORT_TAG, TagValue::U64(0)),
+                Tag::new(0, "KissServer:txdelay-ms", TagValue::U64(500)),
+            ]
+        );
+
+        // Disconnect.
+        drop(c1);
+        work_until(&mut b, |b| b.clients() == 1)?;
+        Ok(())
+    }
+}
@@ -107,6 +107,7 @@ g.run()?;
 * `fast-math`: Add a dependency in order to speed up some math.
 * `fftw`: GPL code only: Add support to use `libfftw` instead of `rustfft`.
 * `pipewire`: Add support for pipewire blocks (adds dependency).
+* `png`: Allow `ImageSink` to write PNG (adds dependency).
 * `rtlsdr`: Enable `RtlSdrSource` block, and adds the `rtlsdr` crate as a
 * `simd` (only with `nightly` Rust): Enable some code using `std::simd`.
   dependency at build time, and thus `librtlsdr.so` as a dependency at runtime.
@@ -140,25 +141,41 @@ pub use rustradio_macros;
 // Blocks.
 pub mod add;
 pub mod add_const;
+pub mod adsb;
+pub mod afsk;
+pub mod agwpe;
+pub mod ais;
+pub mod am;
+pub mod aprs;
+pub mod apt;
 pub mod au;
+pub mod ax25;
+pub mod beast_server;
 pub mod binary_slicer;
 pub mod burst_tagger;
 pub mod canary;
+pub mod ccsds;
 pub mod cma;
 pub mod complex_to_mag2;
 pub mod constant_source;
+pub mod constellation;
 pub mod convert;
+pub mod convolutional;
 pub mod correlate_access_code;
+pub mod cw_decode;
+pub mod cw_keyer;
 pub mod data_stream;
 pub mod debug_sink;
 pub mod delay;
 pub mod descrambler;
+pub mod emphasis;
 pub mod fft;
 pub mod fft_filter;
 pub mod fft_stream;
 pub mod file_sink;
 pub mod file_source;
 pub mod fir;
+pub mod gfsk;
 pub mod hasher;
 pub mod hdlc_deframer;
 pub mod hdlc_framer;
@@ -166,34 +183,46 @@ pub mod head;
 pub mod hilbert;
 pub mod iir_filter;
 pub mod il2p_deframer;
+pub mod image_sink;
+pub mod interleave;
 pub mod iq_balance;
 pub mod kiss;
+pub mod kiss_server;
+pub mod manchester;
 pub mod morse_encode;
 pub mod multiply_const;
 pub mod nrzi;
 pub mod null_sink;
+pub mod ook;
 pub mod pdu_average;
 pub mod pdu_to_stream;
 pub mod pdu_writer;
+pub mod pll;
+pub mod pocsag;
 pub mod quadrature_demod;
 pub mod rational_resampler;
+pub mod rds;
 pub mod reader_source;
+pub mod reed_solomon;
 pub mod rtlsdr_decode;
 pub mod rtlsdr_encode;
 pub mod sigmf;
 pub mod signal_source;
 pub mod single_pole_iir_filter;
 pub mod skip;
+pub mod ssb;
 pub mod stream_to_pdu;
 pub mod strobe;
 pub mod symbol_sync;
 pub mod tcp_source;
 pub mod tee;
+pub mod to_json;
 pub mod to_text;
 pub mod vco;
 pub mod vec_to_stream;
 pub mod vector_sink;
 pub mod vector_source;
+pub mod wbfm_stereo;
 pub mod wpcr;
 pub mod writer_sink;
 pub mod xor;
@@ -221,6 +250,8 @@ pub mod soapysdr_source;
 pub mod block;
 pub mod blocks;
 
+mod tcp_server;
+
 #[cfg(not(feature = "wasm"))]
 pub mod nowasm;
 
@@ -0,0 +1,500 @@
+/*! Manchester and differential Manchester coding.
+
+Manchester sends each bit as two chips, with a transition in the middle of
+every bit, so that the receiver can recover the clock, and the signal has no
+DC component. Which way the transition goes for a 1 differs between
+conventions:
+
+* G.E. Thomas: 1 is high then low, 0 is low then high.
+* IEEE 802.3: 1 is low then high, 0 is high then low.
+
+Differential Manchester also always transitions in the middle of the bit,
+but the bit value is whether there is a transition at the start of the bit,
+so it is insensitive to polarity. Here no transition is a 1, and a
+transition a 0.
+
+Chips and bits are both `u8` with values 0 and 1.
+
+<https://en.wikipedia.org/wiki/Manchester_code>
+<https://en.wikipedia.org/wiki/Differential_Manchester_encoding>
+*/
+use crate::Result;
+use crate::block::{Block, BlockRet};
+use crate::stream::{NCReadStream, NCWriteStream, ReadStream, Tag, TagValue, WriteStream};
+
+/// Tag on decoded bits where the chips were not a valid Manchester pair.
+///
+/// The value is true if the decoder also slipped half a bit to resync,
+/// meaning one chip was dropped before this bit.
+pub const VIOLATION_TAG: &str = "Manchester:violation";
+
+// Slip half a bit if there are this many violations in the last eight
+// bits.
+const SLIP_VIOLATIONS: u32 = 2;
+
+/// Manchester convention.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ManchesterCoding {
+    /// G.E. Thomas: 1 is high then low.
+    Thomas,
+    /// IEEE 802.3: 1 is low then high.
+    Ieee,
+    /// Differential Manchester: 1 is no transition at the start of the bit.
+    Differential,
+}
+
+enum Step {
+    Bit(u8, bool),
+    Slip,
+}
+
+// Decoder state, shared between the stream and PDU decoders.
+struct Decoder {
+    coding: ManchesterCoding,
+    // Last chip of the previous bit, for differential.
+    last: u8,
+    // Violations in the last eight bits, one bit each.
+    history: u8,
+    slipped: bool,
+}
+
+impl Decoder {
+    fn new(coding: ManchesterCoding) -> Self {
+        Self {
+            coding,
+            last: 0,
+            history: 0,
+            slipped: false,
+        }
+    }
+
+    fn step(&mut self, a: u8, b: u8) -> Step {
+        let violation = a == b;
+        self.history = (self.history << 1) | u8::from(violation);
+        if violation && self.history.count_ones() >= SLIP_VIOLATIONS {
+            self.history = 0;
+            self.last = a;
+            self.slipped = true;
+            return Step::Slip;
+        }
+        let bit = match self.coding {
+            ManchesterCoding::Thomas => a,
+            ManchesterCoding::Ieee => b,
+            ManchesterCoding::Differential => u8::from(a == self.last),
+        };
+        self.last = b;
+        Step::Bit(bit, violation)
+    }
+
+    // Decode as many chips as fit in `out`, adding violation tags to
+    // `tags`, and the first chip of each bit to `starts`. Returns chips
+    // consumed and bits output.
+    fn decode(
+        &mut self,
+        chips: &[u8],
+        out: &mut [u8],
+        tags: &mut Vec<Tag>,
+        starts: &mut Vec<usize>,
+    ) -> (usize, usize) {
+        let mut n = 0;
+        let mut opos = 0;
+        while n + 1 < chips.len() && opos < out.len() {
+            match self.step(chips[n], chips[n + 1]) {
+                Step::Slip => n += 1,
+                Step::Bit(bit, violation) => {
+                    if violation || self.slipped {
+                        tags.push(Tag::new(opos, VIOLATION_TAG, TagValue::Bool(self.slipped)));
+                        self.slipped = false;
+                    }
+                    out[opos] = bit;
+                    starts.push(n);
+                    opos += 1;
+                    n += 2;
+                }
+            }
+        }
+        (n, opos)
+    }
+}
+
+/// Manchester encoder.
+///
+/// Outputs two chips for every input bit. Tags are moved to the first chip
+/// of their bit.
+#[derive(rustradio_macros::Block)]
+#[rustradio(crate)]
+pub struct ManchesterEncode {
+    #[rustradio(in)]
+    src: ReadStream<u8>,
+    #[rustradio(out)]
+    dst: WriteStream<u8>,
+    coding: ManchesterCoding,
+    // Current line level, for differential.
+    level: u8,
+}
+
+impl ManchesterEncode {
+    /// Create a new Manchester encoder.
+    ///
+    /// For differential Manchester the line is assumed to start low.
+    #[must_use]
+    pub fn new(src: ReadStream<u8>, coding: ManchesterCoding) -> (Self, ReadStream<u8>) {
+        let (dst, dr) = crate::stream::new_stream();
+        (
+            Self {
+                src,
+                dst,
+                coding,
+                level: 0,
+            },
+            dr,
+        )
+    }
+
+    fn encode(&mut self, bit: u8) -> [u8; 2] {
+        match self.coding {
+            ManchesterCoding::Thomas => [bit, bit ^ 1],
+            ManchesterCoding::Ieee => [bit ^ 1, bit],
+            ManchesterCoding::Differential => {
+                if bit == 0 {
+                    self.level ^= 1;
+                }
+                let chips = [self.level, self.level ^ 1];
+                self.level ^= 1;
+                chips
+            }
+        }
+    }
+}
+
+impl Block for ManchesterEncode {
+    fn work(&mut self) -> Result<BlockRet<'_>> {
+        let (i, tags) = self.src.read_buf()?;
+        if i.is_empty() {
+            return Ok(BlockRet::WaitForStream(&self.src, 1));
+        }
+        let mut o = self.dst.write_buf()?;
+        if o.len() < 2 {
+            return Ok(BlockRet::WaitForStream(&self.dst, 2));
+        }
+        let n = std::cmp::min(i.len(), o.len() / 2);
+        for (pos, &bit) in i.slice()[..n].iter().enumerate() {
+            let chips = self.encode(bit);
+            o.slice()[2 * pos..2 * pos + 2].copy_from_slice(&chips);
+        }
+        let tags: Vec<Tag> = tags
+            .into_iter()
+            .filter(|t| t.pos() < n)
+            .map(|mut t| {
+                t.set_pos(2 * t.pos());
+                t
+            })
+            .collect();
+        o.produce(2 * n, &tags);
+        i.consume(n);
+        Ok(BlockRet::Again)
+    }
+}
+
+/// Manchester decoder.
+///
+/// Takes chips, two per bit, and outputs bits. Chip pairs without a
+/// transition in the middle are violations, and are tagged with
+/// [`VIOLATION_TAG`]. If there are two violations within eight bits, the
+/// decoder assumes it is on the wrong clock phase, and slips by one chip.
+///
+/// Other tags are moved to the bit of the chip they were on, or the next
+/// bit if the chip was dropped.
+///
+/// For differential Manchester, the first bit assumes the line was low
+/// before it.
+#[derive(rustradio_macros::Block)]
+#[rustradio(crate)]
+pub struct ManchesterDecode {
+    #[rustradio(in)]
+    src: ReadStream<u8>,
+    #[rustradio(out)]
+    dst: WriteStream<u8>,
+    decoder: Decoder,
+    // Tags on dropped chips at the end of the input, for the next bit.
+    pending: Vec<Tag>,
+}
+
+impl ManchesterDecode {
+    /// Create a new Manchester decoder.
+    #[must_use]
+    pub fn new(src: ReadStream<u8>, coding: ManchesterCoding) -> (Self, ReadStream<u8>) {
+        let (dst, dr) = crate::stream::new_stream();
+        (
+            Self {
+                src,
+                dst,
+                decoder: Decoder::new(coding),
+                pending: Vec::new(),
+            },
+            dr,
+        )
+    }
+}
+
+impl Block for ManchesterDecode {
+    fn work(&mut self) -> Result<BlockRet<'_>> {
+        let (i, in_tags) = self.src.read_buf()?;
+        if i.len() < 2 {
+            return Ok(BlockRet::WaitForStream(&self.src, 2));
+        }
+        let mut o = self.dst.write_buf()?;
+        if o.is_empty() {
+            return Ok(BlockRet::WaitForStream(&self.dst, 1));
+        }
+        let mut tags = Vec::new();
+        let mut starts = Vec::new();
+        let (n, opos) = self
+            .decoder
+            .decode(i.slice(), o.slice(), &mut tags, &mut starts);
+        if opos > 0 {
+            tags.append(&mut self.pending);
+        }
+        for mut tag in in_tags.into_iter().filter(|t| t.pos() < n) {
+            // First bit ending at or after the chip.
+            let pos = starts.partition_point(|&s| s + 1 < tag.pos());
+            tag.set_pos(pos.min(opos));
+            if pos < opos {
+                tags.push(tag);
+            } else {
+                tag.set_pos(0);
+                self.pending.push(tag);
+            }
+        }
+        tags.sort_by_key(Tag::pos);
+        o.produce(opos, &tags);
+        i.consume(n);
+        Ok(BlockRet::Again)
+    }
+}
+
+/// Manchester decoder for packets.
+///
+/// Like [`ManchesterDecode`], but decodes each packet of chips separately,
+/// starting from the first chip. Violation tags have the position of the
+/// bit in the packet, and are added after the packet's existing tags. A
+/// trailing odd chip is dropped.
+#[derive(rustradio_macros::Block)]
+#[rustradio(crate)]
+pub struct ManchesterDecodePdu {
+    #[rustradio(in)]
+    src: NCReadStream<Vec<u8>>,
+    #[rustradio(out)]
+    dst: NCWriteStream<Vec<u8>>,
+    coding: ManchesterCoding,
+}
+
+impl ManchesterDecodePdu {
+    /// Create a new Manchester packet decoder.
+    #[must_use]
+    pub fn new(
+        src: NCReadStream<Vec<u8>>,
+        coding: ManchesterCoding,
+    ) -> (Self, NCReadStream<Vec<u8>>) {
+        let (dst, dr) = crate::stream::new_nocopy_stream();
+        (Self { src, dst, coding }, dr)
+    }
+}
+
+impl Block for ManchesterDecodePdu {
+    fn work(&mut self) -> Result<BlockRet<'_>> {
+        loop {
+            if self.dst.remaining() == 0 {
+                return Ok(BlockRet::WaitForStream(&self.dst, 1));
+            }
+            let Some((chips, mut tags)) = self.src.pop() else {
+                return Ok(BlockRet::WaitForStream(&self.src, 1));
+            };
+            let mut bits = vec![0; chips.len() / 2];
+            let (_, len) =
+                Decoder::new(self.coding).decode(&chips, &mut bits, &mut tags, &mut Vec::new());
+            bits.truncate(len);
+            self.dst.push(bits, tags);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{Rng, SeedableRng, rngs::StdRng};
+
+    use crate::blocks::VectorSource;
+
+    const ALL: [ManchesterCoding; 3] = [
+        ManchesterCoding::Thomas,
+        ManchesterCoding::Ieee,
+        ManchesterCoding::Differential,
+    ];
+
+    fn encode(bits: Vec<u8>, coding: ManchesterCoding) -> Result<Vec<u8>> {
+        let (mut src, prev) = VectorSource::new(bits);
+        src.work()?;
+        let (mut b, out) = ManchesterEncode::new(prev, coding);
+        b.work()?;
+        let (o, _) = out.read_buf()?;
+        Ok(o.slice().to_vec())
+    }
+
+    fn decode(chips: Vec<u8>, coding: ManchesterCoding) -> Result<(Vec<u8>, Vec<Tag>)> {
+        let (mut src, prev) = VectorSource::new(chips);
+        src.work()?;
+        let (mut b, out) = ManchesterDecode::new(prev, coding);
+        b.work()?;
+        let (o, tags) = out.read_buf()?;
+        // Only the decoder's own tags.
+        let tags = tags
+            .into_iter()
+            .filter(|t| !t.key().starts_with("VectorSource"))
+            .collect();
+        Ok((o.slice().to_vec(), tags))
+    }
+
+    #[test]
+    fn encode_conventions() -> Result<()> {
+        let bits = vec![1, 0, 0, 1, 1];
+        assert_eq!(
+            encode(bits.clone(), ManchesterCoding::Thomas)?,
+            [1, 0, 0, 1, 0, 1, 1, 0, 1, 0]
+        );
+        assert_eq!(
+            encode(bits.clone(), ManchesterCoding::Ieee)?,
+            [0, 1, 1, 0, 1, 0, 0, 1, 0, 1]
+        );
+        assert_eq!(
+            encode(bits, ManchesterCoding::Differential)?,
+            [0, 1, 0, 1, 0, 1, 1, 0, 0, 1]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn roundtrip() -> Result<()> {
+        let mut rng = StdRng::seed_from_u64(1);
+        let bits: Vec<u8> = (0..1000).map(|_| rng.random_range(0..=1)).collect();
+        for coding in ALL {
+            let (got, tags) = decode(encode(bits.clone(), coding)?, coding)?;
+            assert_eq!(got, bits, "{coding:?}");
+            assert!(tags.is_empty(), "{coding:?}: {tags:?}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn differential_inverted() -> Result<()> {
+        let bits = vec![1, 0, 0, 1, 1, 0, 1];
+        let chips: Vec<u8> = encode(bits.clone(), ManchesterCoding::Differential)?
+            .into_iter()
+            .map(|c| c ^ 1)
+            .collect();
+        let (got, _) = decode(chips, ManchesterCoding::Differential)?;
+        // Only the first bit depends on the initial line level.
+        assert_eq!(got[1..], bits[1..]);
+        Ok(())
+    }
+
+    #[test]
+    fn violation() -> Result<()> {
+        let mut chips = encode(vec![1, 0, 1, 1, 0, 0, 1, 0], ManchesterCoding::Thomas)?;
+        chips[6] ^= 1;
+        let (got, tags) = decode(chips, ManchesterCoding::Thomas)?;
+        assert_eq!(got, [1, 0, 1, 0, 0, 0, 1, 0]);
+        assert_eq!(tags, [Tag::new(3, VIOLATION_TAG, TagValue::Bool(false))]);
+        Ok(())
+    }
+
+    #[test]
+    fn resync() -> Result<()> {
+        let mut rng = StdRng::seed_from_u64(2);
+        let bits: Vec<u8> = (0..200).map(|_| rng.random_range(0..=1)).collect();
+        for coding in ALL {
+            // Start on the wrong phase.
+            let mut chips = vec![0];
+            chips.extend(encode(bits.clone(), coding)?);
+            let (got, tags) = decode(chips, coding)?;
+            let slip = tags
+                .iter()
+                .find(|t| t.val() == &TagValue::Bool(true))
+                .unwrap_or_else(|| panic!("{coding:?}: no slip in {tags:?}"));
+            // After resync, the bits line up with the tail of the input.
+            let tail = &got[slip.pos() + 1..];
+            assert!(tail.len() > 180, "{coding:?}: slipped late: {tags:?}");
+            assert_eq!(tail, &bits[bits.len() - tail.len()..], "{coding:?}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn tags() -> Result<()> {
+        let bits: Vec<u8> = (0..20).map(|n| n % 3 % 2).collect();
+        let mark = |pos| Tag::new(pos, "mark", TagValue::U64(pos as u64));
+        let (mut src, prev) = VectorSource::builder(bits.clone())
+            .tags(&[mark(0), mark(5), mark(19)])
+            .build()?;
+        src.work()?;
+        let (mut b, prev) = ManchesterEncode::new(prev, ManchesterCoding::Ieee);
+        b.work()?;
+        drop(b);
+        let (o, tags) = prev.read_buf()?;
+        for pos in [0, 5, 19] {
+            let t = tags
+                .iter()
+                .find(|t| t.val() == &TagValue::U64(pos))
+                .unwrap();
+            assert_eq!(t.pos(), 2 * pos as usize);
+        }
+        let mut chips = o.slice().to_vec();
+        // Violation on bit 7, and tags on both chips of bits 5 and 12.
+        chips[14] ^= 1;
+        let in_tags = [
+            mark(10),
+            Tag::new(11, "other", TagValue::Bool(true)),
+            Tag::new(25, "other", TagValue::Bool(false)),
+        ];
+        drop(o);
+        let (mut src, prev) = VectorSource::builder(chips).tags(&in_tags).build()?;
+        src.work()?;
+        let (mut b, out) = ManchesterDecode::new(prev, ManchesterCoding::Ieee);
+        b.work()?;
+        let (_, tags) = out.read_buf()?;
+        let got: Vec<_> = tags
+            .iter()
+            .filter(|t| !t.key().starts_with("VectorSource"))
+            .map(|t| (t.pos(), t.key().to_string()))
+            .collect();
+        assert_eq!(
+            got,
+            [
+                (5, "mark".to_string()),
+                (5, "other".to_string()),
+                (7, VIOLATION_TAG.to_string()),
+                (12, "other".to_string()),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn pdu() -> Result<()> {
+        let (tx, rx) = crate::stream::new_nocopy_stream();
+        let (mut b, out) = ManchesterDecodePdu::new(rx, ManchesterCoding::Ieee);
+        let start = Tag::new(0, "start", TagValue::U64(1));
+        tx.push(vec![0, 1, 1, 0, 1, 1, 0, 1, 0], vec![start.clone()]);
+        tx.push(vec![1, 0], vec![]);
+        b.work()?;
+        assert_eq!(
+            out.pop(),
+            Some((
+                vec![1, 0, 1, 1],
+                vec![start, Tag::new(2, VIOLATION_TAG, TagValue::Bool(false))]
+            ))
+        );
+        assert_eq!(out.pop(), Some((vec![0], vec![])));
+        Ok(())
+    }
+}
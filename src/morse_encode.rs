@@ -1,9 +1,82 @@
 //! Generate a morse code signal.
+//!
+//! Output is one value per morse time unit (dit length), 1 for key down and
+//! 0 for key up. Use [`CwKeyer`](crate::blocks::CwKeyer) to turn it into a
+//! shaped envelope at a given sample rate.
 use std::borrow::Cow;
 
+use log::warn;
+
 use crate::stream::{NCReadStream, NCWriteStream, Tag, TagValue};
 
+/// Builder for [`MorseEncode`].
+pub struct MorseEncodeBuilder {
+    block: MorseEncode,
+    out: NCReadStream<Vec<u8>>,
+    wpm: f32,
+    farnsworth: Option<f32>,
+}
+
+impl MorseEncodeBuilder {
+    /// Set character speed in words per minute. Default 20.
+    ///
+    /// Only used to calculate Farnsworth spacing, since the output is in time
+    /// units anyway.
+    #[must_use]
+    pub fn wpm(mut self, wpm: f32) -> Self {
+        self.wpm = wpm;
+        self
+    }
+
+    /// Use Farnsworth spacing for the given overall speed.
+    ///
+    /// Characters are sent at the character speed, but the gaps between
+    /// characters and words are stretched so that the overall speed becomes
+    /// `wpm`. Gaps are rounded to whole time units.
+    #[must_use]
+    pub fn farnsworth(mut self, wpm: f32) -> Self {
+        self.farnsworth = Some(wpm);
+        self
+    }
+
+    /// Build the `MorseEncode`.
+    #[must_use]
+    pub fn build(mut self) -> (MorseEncode, NCReadStream<Vec<u8>>) {
+        if let Some(s) = self.farnsworth.filter(|&s| s < self.wpm) {
+            // Per ARRL: total spacing delay for "PARIS ", spread over the 19
+            // units of character and word gaps.
+            let c = self.wpm;
+            let ta = (60.0 * c - 37.2 * s) / (s * c);
+            let unit = 1.2 / c;
+            self.block.timing = Timing {
+                char_gap: (3.0 * ta / 19.0 / unit).round() as usize,
+                word_gap: (7.0 * ta / 19.0 / unit).round() as usize,
+            };
+        }
+        (self.block, self.out)
+    }
+}
+
+// Gaps, in time units.
+struct Timing {
+    char_gap: usize,
+    word_gap: usize,
+}
+
+impl Default for Timing {
+    fn default() -> Self {
+        Self {
+            char_gap: 3,
+            word_gap: 7,
+        }
+    }
+}
+
 /// Generate looping morse code signal.
+///
+/// Letters, digits, and common punctuation are supported. Prosigns are
+/// written in angle brackets, e.g. `<AR>` or `<SK>`, and sent as one
+/// character.
 #[derive(rustradio_macros::Block)]
 #[rustradio(crate, new, sync_nocopy_tag)]
 pub struct MorseEncode {
@@ -12,6 +85,9 @@ pub struct MorseEncode {
 
     #[rustradio(out)]
     dst: NCWriteStream<Vec<u8>>,
+
+    #[rustradio(default)]
+    timing: Timing,
 }
 
 const MORSE_AZ_TABLE: [&str; 26] = [
@@ -21,68 +97,141 @@ const MORSE_AZ_TABLE: [&str; 26] = [
 const MORSE_DIGIT_TABLE: [&str; 10] = [
     "-----", ".----", "..---", "...--", "....-", ".....", "-....", "--...", "---..", "----.",
 ];
+const MORSE_PUNCTUATION_TABLE: [(&str, &str); 18] = [
+    (".", ".-.-.-"),
+    (",", "--..--"),
+    ("?", "..--.."),
+    ("'", ".----."),
+    ("!", "-.-.--"),
+    ("/", "-..-."),
+    ("(", "-.--."),
+    (")", "-.--.-"),
+    ("&", ".-..."),
+    (":", "---..."),
+    (";", "-.-.-."),
+    ("=", "-...-"),
+    ("+", ".-.-."),
+    ("-", "-....-"),
+    ("_", "..--.-"),
+    ("\"", ".-..-."),
+    ("$", "...-..-"),
+    ("@", ".--.-."),
+];
 
-// TODO: make this spacing configurable.
 const DIT: &[u8] = &[1, 0];
 const DAH: &[u8] = &[1, 1, 1, 0];
-const CHAR_GAP: &[u8] = &[0, 0];
-const WORD_GAP: &[u8] = &[0, 0, 0, 0, 0, 0];
 
-fn encode(msg: &str) -> Vec<u8> {
+fn lookup(c: char) -> Option<&'static str> {
+    let c = c.to_ascii_lowercase();
+    match c {
+        '0'..='9' => Some(MORSE_DIGIT_TABLE[(c as u8 - b'0') as usize]),
+        'a'..='z' => Some(MORSE_AZ_TABLE[(c as u8 - b'a') as usize]),
+        _ => MORSE_PUNCTUATION_TABLE
+            .iter()
+            .find(|(p, _)| p.starts_with(c))
+            .map(|(_, m)| *m),
+    }
+}
+
+// Prosigns that don't coincide with punctuation.
+const MORSE_PROSIGN_TABLE: [(&str, &str); 5] = [
+    ("<SK>", "...-.-"),
+    ("<SN>", "...-."),
+    ("<KA>", "-.-.-"),
+    ("<SOS>", "...---..."),
+    ("<HH>", "........"),
+];
+
+/// Turn dits and dahs, e.g. `.-`, into text.
+///
+/// Letters are returned as uppercase, and prosigns in angle brackets.
+pub(crate) fn decode(morse: &str) -> Option<&'static str> {
+    const AZ: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+    const DIGITS: &str = "0123456789";
+    if let Some(n) = MORSE_AZ_TABLE.iter().position(|m| *m == morse) {
+        return Some(&AZ[n..=n]);
+    }
+    if let Some(n) = MORSE_DIGIT_TABLE.iter().position(|m| *m == morse) {
+        return Some(&DIGITS[n..=n]);
+    }
+    MORSE_PUNCTUATION_TABLE
+        .iter()
+        .chain(&MORSE_PROSIGN_TABLE)
+        .find(|(_, m)| *m == morse)
+        .map(|(p, _)| *p)
+}
+
+fn encode(msg: &str, timing: &Timing) -> Vec<u8> {
     // Longest normal character may be 5 followed by word boundary => 20+7?
     let mut out = Vec::with_capacity(msg.len() * 32);
 
+    // Every element is followed by one zero, so gaps need one less.
+    let char_gap = timing.char_gap.saturating_sub(1);
+    let word_gap = timing.word_gap.saturating_sub(1);
+
     let mut chars = msg.chars().peekable();
     while let Some(c) = chars.next() {
-        match c.to_ascii_lowercase() {
-            '0'..='9' => {
-                let morse = MORSE_DIGIT_TABLE[(c as u8 - b'0') as usize];
-                for sym in morse.chars() {
-                    out.extend(match sym {
-                        '.' => DIT,
-                        '-' => DAH,
-                        other => panic!("can't happen, got {other}"),
-                    });
-                }
-                // Inter-character gap: 3 zeros, unless next is space or end
-                if let Some(next) = chars.peek()
-                    && *next != ' '
-                {
-                    out.extend(CHAR_GAP);
-                }
+        let morse: Cow<str> = match c {
+            // Inter-word gap.
+            // TODO: but what about two spaces in a row?
+            ' ' => {
+                out.extend(std::iter::repeat_n(0, word_gap));
+                continue;
             }
-            'a'..='z' => {
-                let c = c.to_ascii_lowercase();
-                let morse = MORSE_AZ_TABLE[(c as u8 - b'a') as usize];
-                for sym in morse.chars() {
-                    out.extend(match sym {
-                        '.' => DIT,
-                        '-' => DAH,
-                        other => panic!("can't happen, got {other}"),
-                    });
-                }
-                // Inter-character gap: 3 zeros, unless next is space or end
-                if let Some(next) = chars.peek()
-                    && *next != ' '
-                {
-                    out.extend(CHAR_GAP);
+            '<' => {
+                let prosign: String = chars.by_ref().take_while(|&c| c != '>').collect();
+                match prosign.chars().map(lookup).collect::<Option<String>>() {
+                    Some(m) if !m.is_empty() => Cow::Owned(m),
+                    // Probably want a better solution to this.
+                    _ => {
+                        warn!("morse code got invalid prosign '<{prosign}>'. Ignoring");
+                        continue;
+                    }
                 }
             }
-            // Inter-word gap: 7 zeros (we already have one 0 from last symbol)
-            // TODO: but what about two spaces in a row?
-            ' ' => out.extend(WORD_GAP),
-            // Probably want a better solution to this.
-            other => log::warn!("morse code got invalid character '{other}'. Ignoring"),
+            other => {
+                let Some(m) = lookup(other) else {
+                    // Probably want a better solution to this.
+                    warn!("morse code got invalid character '{other}'. Ignoring");
+                    continue;
+                };
+                Cow::Borrowed(m)
+            }
+        };
+        for sym in morse.chars() {
+            out.extend(match sym {
+                '.' => DIT,
+                '-' => DAH,
+                other => panic!("can't happen, got {other}"),
+            });
+        }
+        // Inter-character gap, unless next is space or end.
+        if let Some(next) = chars.peek()
+            && *next != ' '
+        {
+            out.extend(std::iter::repeat_n(0, char_gap));
         }
     }
-    out.extend(WORD_GAP);
+    out.extend(std::iter::repeat_n(0, word_gap));
     out
 }
 
 impl MorseEncode {
+    /// Create a builder, for Farnsworth spacing.
+    #[must_use]
+    pub fn builder(src: NCReadStream<String>) -> MorseEncodeBuilder {
+        let (block, out) = Self::new(src);
+        MorseEncodeBuilder {
+            block,
+            out,
+            wpm: 20.0,
+            farnsworth: None,
+        }
+    }
+
     fn process_sync_tags<'a>(&mut self, msg: String, tags: &'a [Tag]) -> (Vec<u8>, Cow<'a, [Tag]>) {
         let mut tags = tags.to_vec();
-        let enc = encode(&msg);
+        let enc = encode(&msg, &self.timing);
         tags.push(Tag::new(0, "MorseEncode::message", TagValue::String(msg)));
         (enc, Cow::Owned(tags))
     }
@@ -136,4 +285,57 @@ mod tests {
             assert_eq!(&o, want, "For input {i}");
         }
     }
+
+    #[test]
+    fn punctuation_and_prosigns() {
+        let t = Timing::default();
+        assert_eq!(
+            encode("?", &t),
+            vec![
+                1, 0, 1, 0, 1, 1, 1, 0, 1, 1, 1, 0, 1, 0, 1, 0, 0, 0, 0, 0, 0, 0
+            ]
+        );
+        // AR as one character, compared to A R.
+        assert_eq!(
+            encode("<AR>", &t),
+            vec![1, 0, 1, 1, 1, 0, 1, 0, 1, 1, 1, 0, 1, 0, 0, 0, 0, 0, 0, 0]
+        );
+        assert_eq!(encode("<ar>", &t), encode("+", &t));
+        assert_eq!(
+            encode("<SK>E", &t),
+            vec![
+                1, 0, 1, 0, 1, 0, 1, 1, 1, 0, 1, 0, 1, 1, 1, 0, 0, 0, // SK
+                1, 0, 0, 0, 0, 0, 0, 0, // e EOW
+            ]
+        );
+        // Invalid prosigns are skipped.
+        assert_eq!(encode("<A%>E", &t), encode("E", &t));
+    }
+
+    #[test]
+    fn farnsworth() {
+        let (_, rx) = new_nocopy_stream();
+        let (b, _) = MorseEncode::builder(rx).wpm(18.0).farnsworth(5.0).build();
+        // Spacing delay 9.93s, so 1.568s and 3.66s. 66.7ms units.
+        assert_eq!(b.timing.char_gap, 24);
+        assert_eq!(b.timing.word_gap, 55);
+        assert_eq!(
+            encode("ee e", &b.timing),
+            [
+                &[1u8, 0][..],
+                &[0; 23],
+                &[1, 0],
+                &[0; 54],
+                &[1, 0],
+                &[0; 54]
+            ]
+            .concat()
+        );
+
+        // Not slower than character speed means standard spacing.
+        let (_, rx) = new_nocopy_stream();
+        let (b, _) = MorseEncode::builder(rx).wpm(18.0).farnsworth(18.0).build();
+        assert_eq!(b.timing.char_gap, 3);
+        assert_eq!(b.timing.word_gap, 7);
+    }
 }
@@ -0,0 +1,779 @@
+/*! OOK/ASK pulse detection and slicing, for ISM band devices.
+
+Weather sensors, doorbells, remote controls and the like mostly send on-off
+keyed bursts, where the data is in the timing of the pulses and gaps, not in
+the individual samples. Decoding is therefore done in two steps, like
+[rtl_433][rtl_433] does:
+
+1. [`OokDetect`] turns signal power into [`Pulse`] packets: pulse and gap
+   widths in microseconds, for each burst of pulses.
+2. [`OokSlicer`] turns the pulse timings into a [`BitBuffer`] of rows of
+   bits, given a [`Coding`] with the expected timings.
+
+Application code then matches rows on length and content, and extracts
+fields with [`BitBuffer::get`].
+
+## Example
+
+```
+use rustradio::graph::{Graph, GraphRunner};
+use rustradio::blocks::*;
+use rustradio::ook::Coding;
+# fn main() -> rustradio::Result<()> {
+let samp_rate = 250_000.0;
+let (_, prev) = rustradio::stream::new_stream();
+let mut g = Graph::new();
+let prev = rustradio::blockchain![
+    g,
+    prev,
+    ComplexToMag2::new(prev),
+    OokDetect::builder(prev, samp_rate).reset_limit(5_000).build()?,
+    OokSlicer::new(
+        prev,
+        Coding::Ppm {
+            short: 1_000,
+            long: 2_000,
+            gap_limit: 3_000,
+        }
+    ),
+];
+# Ok(())
+# }
+```
+
+[rtl_433]: https://github.com/merbanan/rtl_433
+*/
+use log::debug;
+
+use crate::block::{Block, BlockRet};
+use crate::stream::{NCReadStream, NCWriteStream, ReadStream, Tag, TagValue};
+use crate::{Error, Float, Result};
+
+/// Tag on pulse packets, with the sample position of the first pulse.
+pub const START_TAG: &str = "Ook:start";
+
+// Packets are cut at this many pulses, to not grow without bound on noise.
+const MAX_PULSES: usize = 1_000;
+
+/// A pulse, and the gap after it, in microseconds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Pulse {
+    /// Width of the pulse.
+    pub pulse: u32,
+    /// Width of the gap after the pulse.
+    ///
+    /// For the last pulse of a packet this is the reset limit, or however
+    /// much was seen before end of stream.
+    pub gap: u32,
+}
+
+/// Builder for [`OokDetect`].
+pub struct OokDetectBuilder {
+    src: ReadStream<Float>,
+    samp_rate: Float,
+    threshold: Float,
+    min_snr: Float,
+    hysteresis: Float,
+    min_pulse: u32,
+    reset_limit: u32,
+}
+
+impl OokDetectBuilder {
+    /// Set fixed minimum pulse level. Default 0.0, meaning only the
+    /// automatic noise floor tracking is used.
+    #[must_use]
+    pub fn threshold(mut self, threshold: Float) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    /// Set how much stronger than the noise floor a pulse needs to be to
+    /// start a packet, as a power ratio. Default 10.0, i.e. 10dB.
+    #[must_use]
+    pub fn min_snr(mut self, min_snr: Float) -> Self {
+        self.min_snr = min_snr;
+        self
+    }
+
+    /// Set hysteresis, as the fraction below the threshold the signal needs
+    /// to drop to end a pulse. Default 0.2.
+    #[must_use]
+    pub fn hysteresis(mut self, hysteresis: Float) -> Self {
+        self.hysteresis = hysteresis;
+        self
+    }
+
+    /// Set the shortest pulse, in microseconds. Shorter pulses are ignored
+    /// as glitches. Default 30µs.
+    #[must_use]
+    pub fn min_pulse(mut self, us: u32) -> Self {
+        self.min_pulse = us;
+        self
+    }
+
+    /// Set the gap, in microseconds, that ends a packet. Default 10ms.
+    #[must_use]
+    pub fn reset_limit(mut self, us: u32) -> Self {
+        self.reset_limit = us;
+        self
+    }
+
+    /// Build the `OokDetect`.
+    ///
+    /// # Errors
+    ///
+    /// Errors if the parameters are out of range.
+    pub fn build(self) -> Result<(OokDetect, NCReadStream<Vec<Pulse>>)> {
+        if self.samp_rate <= 0.0 {
+            return Err(Error::msg(format!(
+                "OokDetect: sample rate must be positive, got {}",
+                self.samp_rate
+            )));
+        }
+        if !(0.0..1.0).contains(&self.hysteresis) {
+            return Err(Error::msg(format!(
+                "OokDetect: hysteresis must be in [0,1), got {}",
+                self.hysteresis
+            )));
+        }
+        if self.reset_limit == 0 {
+            return Err(Error::msg("OokDetect: reset limit is 0"));
+        }
+        let us_per_sample = 1e6 / self.samp_rate;
+        let (dst, dr) = crate::stream::new_nocopy_stream();
+        Ok((
+            OokDetect {
+                src: self.src,
+                dst,
+                us_per_sample,
+                threshold: self.threshold,
+                min_snr: self.min_snr,
+                hysteresis: self.hysteresis,
+                reset_samples: (self.reset_limit as Float / us_per_sample) as u64,
+                min_pulse: (self.min_pulse as Float / us_per_sample).ceil() as u64,
+                // Noise floor adapts over about 10ms, and the pulse level
+                // over about 100µs.
+                low_alpha: (us_per_sample / 10_000.0).min(1.0),
+                high_alpha: (us_per_sample / 100.0).min(1.0),
+                low: None,
+                high: 0.0,
+                pos: 0,
+                in_pulse: false,
+                run: 0,
+                gap_before: 0,
+                pending: None,
+                start: 0,
+                pulses: Vec::new(),
+            },
+            dr,
+        ))
+    }
+}
+
+/// OOK pulse detector.
+///
+/// Takes signal power, e.g. from
+/// [`ComplexToMag2`](crate::blocks::ComplexToMag2), and outputs the pulse
+/// and gap widths of each packet. A packet ends when a gap is longer than
+/// the reset limit.
+///
+/// A packet starts when the power exceeds the noise floor by the minimum
+/// SNR. Within a packet the threshold is halfway between the noise floor and
+/// the pulse level, so that the pulse widths are not skewed by the signal
+/// strength.
+///
+/// Packets are tagged with [`START_TAG`].
+#[derive(rustradio_macros::Block)]
+#[rustradio(crate)]
+pub struct OokDetect {
+    #[rustradio(in)]
+    src: ReadStream<Float>,
+    #[rustradio(out)]
+    dst: NCWriteStream<Vec<Pulse>>,
+    us_per_sample: Float,
+    threshold: Float,
+    min_snr: Float,
+    hysteresis: Float,
+    reset_samples: u64,
+    min_pulse: u64,
+    low_alpha: Float,
+    high_alpha: Float,
+
+    // Noise floor, and pulse level.
+    low: Option<Float>,
+    high: Float,
+
+    // Sample position, and samples since last transition.
+    pos: u64,
+    in_pulse: bool,
+    run: u64,
+    // Width, in samples, of the gap before the current pulse.
+    gap_before: u64,
+
+    // Width, in samples, of the last pulse, while in a gap within a packet.
+    pending: Option<u64>,
+    start: u64,
+    pulses: Vec<Pulse>,
+}
+
+impl OokDetect {
+    /// Create a new `OokDetect`, with default settings.
+    ///
+    /// # Errors
+    ///
+    /// Errors if the sample rate is not positive.
+    pub fn new(
+        src: ReadStream<Float>,
+        samp_rate: Float,
+    ) -> Result<(Self, NCReadStream<Vec<Pulse>>)> {
+        Self::builder(src, samp_rate).build()
+    }
+
+    /// Create a builder, for non-default settings.
+    #[must_use]
+    pub fn builder(src: ReadStream<Float>, samp_rate: Float) -> OokDetectBuilder {
+        OokDetectBuilder {
+            src,
+            samp_rate,
+            threshold: 0.0,
+            min_snr: 10.0,
+            hysteresis: 0.2,
+            min_pulse: 30,
+            reset_limit: 10_000,
+        }
+    }
+
+    fn us(&self, samples: u64) -> u32 {
+        (samples as Float * self.us_per_sample).round() as u32
+    }
+
+    fn in_packet(&self) -> bool {
+        self.in_pulse || self.pending.is_some()
+    }
+
+    fn emit(&mut self) {
+        if self.pulses.is_empty() {
+            return;
+        }
+        debug!(
+            "OokDetect: packet of {} pulses at sample {}",
+            self.pulses.len(),
+            self.start
+        );
+        self.dst.push(
+            std::mem::take(&mut self.pulses),
+            [Tag::new(0, START_TAG, TagValue::U64(self.start))],
+        );
+    }
+
+    // Add a pulse. Returns true if the packet was cut at max length.
+    fn push(&mut self, pulse: u64, gap: u64) -> bool {
+        self.pulses.push(Pulse {
+            pulse: self.us(pulse),
+            gap: self.us(gap),
+        });
+        if self.pulses.len() < MAX_PULSES {
+            return false;
+        }
+        self.emit();
+        self.start = self.pos;
+        true
+    }
+
+    // End the current pulse. Returns true if a packet was output.
+    fn end_pulse(&mut self) -> bool {
+        self.in_pulse = false;
+        if self.run < self.min_pulse {
+            // A glitch. Back to the gap it interrupted.
+            self.run += self.gap_before;
+            return false;
+        }
+        let emitted = match self.pending.replace(self.run) {
+            Some(pulse) => self.push(pulse, self.gap_before),
+            None => false,
+        };
+        self.run = 0;
+        emitted
+    }
+
+    // Process one sample. Returns true if a packet was output.
+    fn sample(&mut self, s: Float) -> bool {
+        let low = *self.low.get_or_insert(s);
+        let on = if self.in_packet() {
+            (low + (self.high - low) / 2.0).max(self.threshold)
+        } else {
+            (low * self.min_snr).max(self.threshold)
+        };
+        let mut emitted = false;
+        self.run += 1;
+        if self.in_pulse {
+            self.high += (s - self.high) * self.high_alpha;
+            if s < on * (1.0 - self.hysteresis) {
+                emitted = self.end_pulse();
+            } else if self.run > self.reset_samples {
+                // A carrier, not a pulse. Make it the new noise floor.
+                debug!("OokDetect: dropping carrier at sample {}", self.pos);
+                self.in_pulse = false;
+                self.pending = None;
+                self.pulses.clear();
+                self.low = Some(s);
+                self.run = 0;
+            }
+        } else if s > on {
+            if !self.in_packet() {
+                self.start = self.pos;
+                self.high = s;
+            }
+            self.in_pulse = true;
+            self.gap_before = self.run;
+            self.run = 0;
+        } else {
+            self.low = Some(low + (s - low) * self.low_alpha);
+            if self.run > self.reset_samples
+                && let Some(pulse) = self.pending.take()
+            {
+                self.pulses.push(Pulse {
+                    pulse: self.us(pulse),
+                    gap: self.us(self.run),
+                });
+                self.emit();
+                emitted = true;
+            }
+        }
+        self.pos += 1;
+        emitted
+    }
+
+    fn flush(&mut self) {
+        if self.in_pulse {
+            self.end_pulse();
+        }
+        if let Some(pulse) = self.pending.take() {
+            self.pulses.push(Pulse {
+                pulse: self.us(pulse),
+                gap: self.us(self.run),
+            });
+        }
+        self.emit();
+    }
+}
+
+impl Block for OokDetect {
+    fn work(&mut self) -> Result<BlockRet<'_>> {
+        let (i, _tags) = self.src.read_buf()?;
+        if i.is_empty() {
+            if self.src.eof() {
+                self.flush();
+            }
+            return Ok(BlockRet::WaitForStream(&self.src, 1));
+        }
+        // Each sample outputs at most one packet.
+        if self.dst.remaining() == 0 {
+            return Ok(BlockRet::WaitForStream(&self.dst, 1));
+        }
+        let mut n = 0;
+        for &s in i.iter() {
+            n += 1;
+            if self.sample(s) && self.dst.remaining() == 0 {
+                break;
+            }
+        }
+        i.consume(n);
+        Ok(BlockRet::Again)
+    }
+}
+
+/// Rows of bits, sliced from a pulse packet.
+///
+/// Devices usually repeat their message a few times per packet, separated
+/// by a longer gap, so each repeat ends up as its own row.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BitBuffer {
+    /// Rows of bits, one bit per byte.
+    pub rows: Vec<Vec<u8>>,
+}
+
+impl BitBuffer {
+    /// Get `len` bits, up to 64, from a row, MSB first.
+    ///
+    /// Returns `None` if the row is too short.
+    #[must_use]
+    pub fn get(&self, row: usize, start: usize, len: usize) -> Option<u64> {
+        assert!(len <= 64, "BitBuffer::get: {len} bits is more than 64");
+        let bits = self.rows.get(row)?.get(start..start + len)?;
+        Some(bits.iter().fold(0, |acc, &b| (acc << 1) | u64::from(b)))
+    }
+
+    /// Get a row packed into bytes, MSB first, with the last byte padded
+    /// with zeroes.
+    #[must_use]
+    pub fn bytes(&self, row: usize) -> Vec<u8> {
+        self.rows.get(row).map_or_else(Vec::new, |bits| {
+            bits.chunks(8)
+                .map(|c| {
+                    c.iter()
+                        .enumerate()
+                        .fold(0, |acc, (n, &b)| acc | (b << (7 - n)))
+                })
+                .collect()
+        })
+    }
+
+    /// Invert all bits.
+    pub fn invert(&mut self) {
+        for row in &mut self.rows {
+            for b in row {
+                *b ^= 1;
+            }
+        }
+    }
+
+    /// Find a row of at least `min_bits` bits that is repeated in at least
+    /// `min_repeats` rows, and return its index.
+    #[must_use]
+    pub fn find_repeated_row(&self, min_repeats: usize, min_bits: usize) -> Option<usize> {
+        self.rows.iter().enumerate().position(|(n, row)| {
+            row.len() >= min_bits
+                && self.rows[n..].iter().filter(|r| *r == row).count() >= min_repeats
+        })
+    }
+}
+
+impl std::fmt::Display for BitBuffer {
+    /// Rows as `{bits}hex`, e.g. `{36}5a2f0f4a0`, separated by spaces.
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for (n, row) in self.rows.iter().enumerate() {
+            if n > 0 {
+                write!(f, " ")?;
+            }
+            write!(f, "{{{}}}", row.len())?;
+            for b in self.bytes(n) {
+                write!(f, "{b:02x}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Line coding of the pulses, with timings in microseconds.
+///
+/// Pulses and gaps are classified as short or long by which side of the
+/// halfway point between `short` and `long` they are.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Coding {
+    /// Pulse width modulation. A short pulse is a 1, a long pulse a 0. A
+    /// gap longer than `gap_limit` ends the row.
+    Pwm {
+        /// Width of a short pulse.
+        short: u32,
+        /// Width of a long pulse.
+        long: u32,
+        /// Gap that ends a row.
+        gap_limit: u32,
+    },
+    /// Pulse position modulation. A short gap is a 0, a long gap a 1. A gap
+    /// longer than `gap_limit` ends the row.
+    Ppm {
+        /// Width of a short gap.
+        short: u32,
+        /// Width of a long gap.
+        long: u32,
+        /// Gap that ends a row.
+        gap_limit: u32,
+    },
+    /// Manchester, where high then low is a 1, and low then high a 0.
+    ///
+    /// Since a packet starts with a pulse, the first bit of each row is a
+    /// 1. A gap longer than two half bits ends the row.
+    Manchester {
+        /// Width of half a bit.
+        half: u32,
+    },
+}
+
+impl Coding {
+    /// Slice a pulse packet into rows of bits.
+    ///
+    /// Empty rows are dropped.
+    #[must_use]
+    pub fn slice(&self, pulses: &[Pulse]) -> BitBuffer {
+        let mut rows = vec![vec![]];
+        let end_row = |rows: &mut Vec<Vec<u8>>| {
+            if !rows.last().is_some_and(Vec::is_empty) {
+                rows.push(vec![]);
+            }
+        };
+        match *self {
+            Coding::Pwm {
+                short,
+                long,
+                gap_limit,
+            } => {
+                let mid = short.midpoint(long);
+                for p in pulses {
+                    rows.last_mut().unwrap().push(u8::from(p.pulse < mid));
+                    if p.gap > gap_limit {
+                        end_row(&mut rows);
+                    }
+                }
+            }
+            Coding::Ppm {
+                short,
+                long,
+                gap_limit,
+            } => {
+                let mid = short.midpoint(long);
+                for p in pulses {
+                    if p.gap > gap_limit {
+                        end_row(&mut rows);
+                    } else {
+                        rows.last_mut().unwrap().push(u8::from(p.gap >= mid));
+                    }
+                }
+            }
+            Coding::Manchester { half } => {
+                // Widths in half bits: 1, 2, or too long.
+                let halves = |w: u32| -> Option<usize> {
+                    match w {
+                        w if w * 2 < half * 3 => Some(1),
+                        w if w * 2 <= half * 5 => Some(2),
+                        _ => None,
+                    }
+                };
+                // First half of the current bit.
+                let mut first: Option<u8> = None;
+                let level = |rows: &mut Vec<Vec<u8>>, first: &mut Option<u8>, l: u8| {
+                    match first.take() {
+                        None => *first = Some(l),
+                        Some(f) if f != l => rows.last_mut().unwrap().push(f),
+                        Some(_) => {
+                            // Not a valid bit. Resync on this half bit.
+                            end_row(rows);
+                            *first = Some(l);
+                        }
+                    }
+                };
+                for p in pulses {
+                    let Some(n) = halves(p.pulse) else {
+                        end_row(&mut rows);
+                        first = None;
+                        continue;
+                    };
+                    for _ in 0..n {
+                        level(&mut rows, &mut first, 1);
+                    }
+                    match halves(p.gap) {
+                        Some(n) => {
+                            for _ in 0..n {
+                                level(&mut rows, &mut first, 0);
+                            }
+                        }
+                        None => {
+                            level(&mut rows, &mut first, 0);
+                            end_row(&mut rows);
+                            first = None;
+                        }
+                    }
+                }
+            }
+        }
+        rows.retain(|r| !r.is_empty());
+        BitBuffer { rows }
+    }
+}
+
+/// OOK slicer.
+///
+/// Takes pulse packets from [`OokDetect`], and slices them into rows of bits
+/// according to the [`Coding`]. Packets with no bits are dropped. Tags are
+/// passed through.
+#[derive(rustradio_macros::Block)]
+#[rustradio(crate)]
+pub struct OokSlicer {
+    #[rustradio(in)]
+    src: NCReadStream<Vec<Pulse>>,
+    #[rustradio(out)]
+    dst: NCWriteStream<BitBuffer>,
+    coding: Coding,
+}
+
+impl OokSlicer {
+    /// Create a new `OokSlicer`.
+    #[must_use]
+    pub fn new(src: NCReadStream<Vec<Pulse>>, coding: Coding) -> (Self, NCReadStream<BitBuffer>) {
+        let (dst, dr) = crate::stream::new_nocopy_stream();
+        (Self { src, dst, coding }, dr)
+    }
+}
+
+impl Block for OokSlicer {
+    fn work(&mut self) -> Result<BlockRet<'_>> {
+        loop {
+            if self.dst.remaining() == 0 {
+                return Ok(BlockRet::WaitForStream(&self.dst, 1));
+            }
+            let Some((pulses, tags)) = self.src.pop() else {
+                return Ok(BlockRet::WaitForStream(&self.src, 1));
+            };
+            let bits = self.coding.slice(&pulses);
+            if !bits.rows.is_empty() {
+                self.dst.push(bits, tags);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{Rng, SeedableRng, rngs::StdRng};
+
+    use crate::blocks::VectorSource;
+
+    const SAMP_RATE: Float = 250_000.0;
+
+    // Nexus style PPM: 500us pulses, with gaps of 1ms for 0 and 2ms for 1,
+    // and 4ms between repeats.
+    fn ppm(bits: &[u8], repeats: usize) -> Vec<Pulse> {
+        let mut pulses = Vec::new();
+        for _ in 0..repeats {
+            pulses.extend(bits.iter().map(|&b| Pulse {
+                pulse: 500,
+                gap: if b == 1 { 2_000 } else { 1_000 },
+            }));
+            pulses.push(Pulse {
+                pulse: 500,
+                gap: 4_000,
+            });
+        }
+        pulses
+    }
+
+    fn to_power(pulses: &[Pulse], level: Float, seed: u64) -> Vec<Float> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let samples = |us: u32| (us as Float * SAMP_RATE / 1e6) as usize;
+        let mut out = Vec::new();
+        let mut noise = |n: usize, level: Float, out: &mut Vec<Float>| {
+            out.extend((0..n).map(|_| level + rng.random_range(0.0..0.01)));
+        };
+        noise(5_000, 0.0, &mut out);
+        for p in pulses {
+            noise(samples(p.pulse), level, &mut out);
+            noise(samples(p.gap), 0.0, &mut out);
+        }
+        noise(5_000, 0.0, &mut out);
+        out
+    }
+
+    #[test]
+    fn detect() -> Result<()> {
+        let bits = [1, 0, 1, 1, 0, 0, 1, 0, 1, 1, 1, 0];
+        let want = ppm(&bits, 3);
+        let mut input = to_power(&want, 0.5, 1);
+        // Second packet, after a long gap.
+        input.extend(to_power(&want[..10], 0.05, 2));
+        let (mut src, prev) = VectorSource::new(input);
+        src.work()?;
+        let (mut b, out) = OokDetect::builder(prev, SAMP_RATE)
+            .reset_limit(10_000)
+            .build()?;
+        b.work()?;
+        b.work()?;
+        for (n, expect) in [&want[..], &want[..10]].into_iter().enumerate() {
+            let (got, tags) = out.pop().unwrap();
+            assert_eq!(tags[0].key(), START_TAG);
+            assert_eq!(got.len(), expect.len(), "packet {n}");
+            for (i, (g, w)) in got.iter().zip(expect).enumerate() {
+                assert!(g.pulse.abs_diff(w.pulse) <= 12, "{n}/{i}: {g:?} vs {w:?}");
+                if i + 1 < got.len() {
+                    assert!(g.gap.abs_diff(w.gap) <= 12, "{n}/{i}: {g:?} vs {w:?}");
+                } else {
+                    assert!(g.gap > 10_000, "{n}/{i}: {g:?}");
+                }
+            }
+        }
+        assert!(out.pop().is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn detect_ignores_carrier() -> Result<()> {
+        let mut input = vec![0.001; 1_000];
+        input.extend(vec![1.0; 10_000]);
+        let (mut src, prev) = VectorSource::new(input);
+        src.work()?;
+        let (mut b, out) = OokDetect::builder(prev, SAMP_RATE)
+            .reset_limit(10_000)
+            .build()?;
+        b.work()?;
+        b.work()?;
+        assert!(out.pop().is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn slice_ppm() {
+        let bits = vec![1, 0, 1, 1, 0, 0, 1];
+        let got = Coding::Ppm {
+            short: 1_000,
+            long: 2_000,
+            gap_limit: 3_000,
+        }
+        .slice(&ppm(&bits, 2));
+        assert_eq!(got.rows, vec![bits.clone(), bits]);
+    }
+
+    #[test]
+    fn slice_pwm() {
+        let pulses: Vec<_> = [(300, 900), (900, 300), (300, 900), (300, 5_000), (900, 300)]
+            .into_iter()
+            .map(|(pulse, gap)| Pulse { pulse, gap })
+            .collect();
+        let got = Coding::Pwm {
+            short: 300,
+            long: 900,
+            gap_limit: 2_000,
+        }
+        .slice(&pulses);
+        assert_eq!(got.rows, vec![vec![1, 0, 1, 1], vec![0]]);
+    }
+
+    #[test]
+    fn slice_manchester() {
+        // 1 1 0 0 1 0 1, high then low being 1: 10 10 01 01 10 01 10.
+        let pulses: Vec<_> = [
+            (500, 500),
+            (500, 1_000),
+            (500, 500),
+            (1_000, 1_000),
+            (1_000, 10_000),
+        ]
+        .into_iter()
+        .map(|(pulse, gap)| Pulse { pulse, gap })
+        .collect();
+        let got = Coding::Manchester { half: 500 }.slice(&pulses);
+        assert_eq!(got.rows, vec![vec![1, 1, 0, 0, 1, 0, 1]]);
+    }
+
+    #[test]
+    fn bitbuffer() {
+        let mut b = BitBuffer {
+            rows: vec![
+                vec![1, 0, 1, 0, 0, 1, 0, 1, 1, 1, 1],
+                vec![1, 1],
+                vec![1, 0, 1, 0, 0, 1, 0, 1, 1, 1, 1],
+            ],
+        };
+        assert_eq!(b.get(0, 0, 8), Some(0xA5));
+        assert_eq!(b.get(0, 8, 3), Some(7));
+        assert_eq!(b.get(0, 9, 3), None);
+        assert_eq!(b.get(5, 0, 1), None);
+        assert_eq!(b.bytes(0), vec![0xA5, 0xE0]);
+        assert_eq!(b.to_string(), "{11}a5e0 {2}c0 {11}a5e0");
+        assert_eq!(b.find_repeated_row(2, 8), Some(0));
+        assert_eq!(b.find_repeated_row(3, 8), None);
+        assert_eq!(b.find_repeated_row(1, 12), None);
+        b.invert();
+        assert_eq!(b.get(1, 0, 2), Some(0));
+    }
+}
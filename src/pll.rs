@@ -0,0 +1,327 @@
+/*! Phase locked loops.
+
+[`PllCarrierTracking`] locks onto a carrier, and outputs a clean copy of it.
+[`PllFreqDet`] locks the same way, but outputs the carrier frequency.
+
+The input should be filtered to only contain the carrier to track, such as
+an FM stereo pilot tone, or a satellite beacon.
+
+## Example
+
+```
+use rustradio::blocks::PllFreqDet;
+let (_, prev) = rustradio::stream::new_stream();
+let (pll, prev) = PllFreqDet::builder(prev, 48000.0)
+    .loop_bandwidth(20.0)
+    .freq_range(-5000.0, 5000.0)
+    .build();
+```
+*/
+use crate::stream::{ReadStream, WriteStream};
+use crate::{Complex, Float};
+
+const TAU: f64 = 2.0 * std::f64::consts::PI;
+
+/// Second order phase locked loop.
+///
+/// Call `update()` with the phase error of each sample.
+pub(crate) struct Pll {
+    alpha: f64,
+    beta: f64,
+    /// Current phase, in radians.
+    pub(crate) phase: f64,
+    /// Current frequency, in radians per sample.
+    pub(crate) freq: f64,
+    min_freq: f64,
+    max_freq: f64,
+}
+
+impl Pll {
+    /// Create new PLL, with the given loop bandwidth and frequency range, in
+    /// radians per sample.
+    pub(crate) fn new(loop_bw: f64, min_freq: f64, max_freq: f64) -> Self {
+        // Damping factor ζ = 1/√2, fast settling with little overshoot.
+        let damping = std::f64::consts::FRAC_1_SQRT_2;
+        let denom = 1.0 + 2.0 * damping * loop_bw + loop_bw * loop_bw;
+        Self {
+            alpha: 4.0 * damping * loop_bw / denom,
+            beta: 4.0 * loop_bw * loop_bw / denom,
+            phase: 0.0,
+            freq: (min_freq + max_freq) / 2.0,
+            min_freq,
+            max_freq,
+        }
+    }
+
+    /// Update the loop with the phase error, and advance one sample.
+    pub(crate) fn update(&mut self, error: f64) {
+        self.freq = (self.freq + self.beta * error).clamp(self.min_freq, self.max_freq);
+        self.phase = (self.phase + self.freq + self.alpha * error).rem_euclid(TAU);
+    }
+
+    /// Update the loop with the next input sample, and return the phase
+    /// error, in radians.
+    pub(crate) fn track(&mut self, s: Complex) -> f64 {
+        let (sin, cos) = self.phase.sin_cos();
+        let error = f64::from((s * Complex::new(cos as Float, -sin as Float)).arg());
+        self.update(error);
+        error
+    }
+}
+
+/// Builder for [`PllCarrierTracking`] and [`PllFreqDet`].
+pub struct PllBuilder<T> {
+    src: ReadStream<Complex>,
+    sample_rate: f64,
+    loop_bandwidth: f64,
+    min_freq: f64,
+    max_freq: f64,
+    _t: std::marker::PhantomData<T>,
+}
+
+impl<T> PllBuilder<T> {
+    fn new(src: ReadStream<Complex>, sample_rate: f64) -> Self {
+        Self {
+            src,
+            sample_rate,
+            loop_bandwidth: 50.0,
+            min_freq: -sample_rate / 10.0,
+            max_freq: sample_rate / 10.0,
+            _t: std::marker::PhantomData,
+        }
+    }
+
+    /// Set loop bandwidth, in Hz. Default 50.
+    ///
+    /// A wider loop locks faster and follows faster changes, such as Doppler
+    /// shift, but is more affected by noise.
+    #[must_use]
+    pub fn loop_bandwidth(mut self, hz: f64) -> Self {
+        self.loop_bandwidth = hz;
+        self
+    }
+
+    /// Set the frequency range to track, in Hz. Default is a tenth of the
+    /// sample rate on either side of 0Hz.
+    ///
+    /// The loop starts in the middle of the range. It only pulls in carriers
+    /// a limited distance from there, so for a known carrier such as a 19kHz
+    /// stereo pilot, center the range on it.
+    #[must_use]
+    pub fn freq_range(mut self, min: f64, max: f64) -> Self {
+        self.min_freq = min;
+        self.max_freq = max;
+        self
+    }
+
+    fn pll(&self) -> Pll {
+        Pll::new(
+            TAU * self.loop_bandwidth / self.sample_rate,
+            TAU * self.min_freq / self.sample_rate,
+            TAU * self.max_freq / self.sample_rate,
+        )
+    }
+}
+
+impl PllBuilder<PllCarrierTracking> {
+    /// Build the `PllCarrierTracking`.
+    #[must_use]
+    pub fn build(self) -> (PllCarrierTracking, ReadStream<Complex>) {
+        let (dst, dr) = crate::stream::new_stream();
+        (
+            PllCarrierTracking {
+                pll: self.pll(),
+                src: self.src,
+                dst,
+            },
+            dr,
+        )
+    }
+}
+
+impl PllBuilder<PllFreqDet> {
+    /// Build the `PllFreqDet`.
+    #[must_use]
+    pub fn build(self) -> (PllFreqDet, ReadStream<Float>) {
+        let (dst, dr) = crate::stream::new_stream();
+        (
+            PllFreqDet {
+                pll: self.pll(),
+                scale: self.sample_rate / TAU,
+                src: self.src,
+                dst,
+            },
+            dr,
+        )
+    }
+}
+
+/// PLL carrier tracking.
+///
+/// Outputs a unit amplitude carrier, locked to the input. To move the input
+/// carrier to 0Hz, multiply the input with the complex conjugate of the
+/// output.
+#[derive(rustradio_macros::Block)]
+#[rustradio(crate, sync)]
+pub struct PllCarrierTracking {
+    #[rustradio(in)]
+    src: ReadStream<Complex>,
+    #[rustradio(out)]
+    dst: WriteStream<Complex>,
+    pll: Pll,
+}
+
+impl PllCarrierTracking {
+    /// Create a builder.
+    #[must_use]
+    pub fn builder(src: ReadStream<Complex>, sample_rate: f64) -> PllBuilder<Self> {
+        PllBuilder::new(src, sample_rate)
+    }
+
+    /// Create new PLL, with default settings.
+    #[must_use]
+    pub fn new(src: ReadStream<Complex>, sample_rate: f64) -> (Self, ReadStream<Complex>) {
+        Self::builder(src, sample_rate).build()
+    }
+
+    fn process_sync(&mut self, s: Complex) -> Complex {
+        let (sin, cos) = self.pll.phase.sin_cos();
+        self.pll.track(s);
+        Complex::new(cos as Float, sin as Float)
+    }
+}
+
+/// PLL frequency detector.
+///
+/// Outputs the frequency of the carrier that the loop is locked to, in Hz.
+#[derive(rustradio_macros::Block)]
+#[rustradio(crate, sync)]
+pub struct PllFreqDet {
+    #[rustradio(in)]
+    src: ReadStream<Complex>,
+    #[rustradio(out)]
+    dst: WriteStream<Float>,
+    pll: Pll,
+    // Radians per sample to Hz.
+    scale: f64,
+}
+
+impl PllFreqDet {
+    /// Create a builder.
+    #[must_use]
+    pub fn builder(src: ReadStream<Complex>, sample_rate: f64) -> PllBuilder<Self> {
+        PllBuilder::new(src, sample_rate)
+    }
+
+    /// Create new PLL frequency detector, with default settings.
+    #[must_use]
+    pub fn new(src: ReadStream<Complex>, sample_rate: f64) -> (Self, ReadStream<Float>) {
+        Self::builder(src, sample_rate).build()
+    }
+
+    fn process_sync(&mut self, s: Complex) -> Float {
+        self.pll.track(s);
+        (self.pll.freq * self.scale) as Float
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Result;
+    use crate::block::Block;
+    use crate::blocks::VectorSource;
+
+    // Carrier starting at `freq`, changing by `rate` Hz per second.
+    fn carrier(sample_rate: f64, freq: f64, rate: f64, len: usize) -> Vec<Complex> {
+        (0..len)
+            .map(|n| {
+                let t = n as f64 / sample_rate;
+                let p = TAU * (freq * t + rate * t * t / 2.0) + 1.0;
+                Complex::new(p.cos() as Float, p.sin() as Float)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn freq_det() -> Result<()> {
+        let sr = 48000.0;
+        for freq in [-1234.0, 0.0, 345.0] {
+            let (mut src, prev) = VectorSource::new(carrier(sr, freq, 0.0, 24000));
+            src.work()?;
+            let (mut b, out) = PllFreqDet::new(prev, sr);
+            b.work()?;
+            let (o, _) = out.read_buf()?;
+            for &f in &o.slice()[12000..] {
+                assert!((f64::from(f) - freq).abs() < 1.0, "{freq}: {f}");
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn pilot() -> Result<()> {
+        let sr = 192000.0;
+        let (mut src, prev) = VectorSource::new(carrier(sr, 19003.0, 0.0, 96000));
+        src.work()?;
+        let (mut b, out) = PllFreqDet::builder(prev, sr)
+            .freq_range(18900.0, 19100.0)
+            .build();
+        b.work()?;
+        let (o, _) = out.read_buf()?;
+        for &f in &o.slice()[48000..] {
+            assert!((f - 19003.0).abs() < 1.0, "{f}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn tracking() -> Result<()> {
+        let sr = 48000.0;
+        let input = carrier(sr, 700.0, 0.0, 24000);
+        let (mut src, prev) = VectorSource::new(input.clone());
+        src.work()?;
+        let (mut b, out) = PllCarrierTracking::builder(prev, sr)
+            .loop_bandwidth(100.0)
+            .build();
+        b.work()?;
+        let (o, _) = out.read_buf()?;
+        for (got, want) in o.slice().iter().zip(&input).skip(12000) {
+            assert!((got.norm() - 1.0).abs() < 1e-4, "{got}");
+            assert!((got - want).norm() < 0.01, "{got} {want}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn doppler() -> Result<()> {
+        // Beacon sweeping 200Hz per second.
+        let sr = 8000.0;
+        let (mut src, prev) = VectorSource::new(carrier(sr, -300.0, 200.0, 24000));
+        src.work()?;
+        let (mut b, out) = PllFreqDet::builder(prev, sr).loop_bandwidth(20.0).build();
+        b.work()?;
+        let (o, _) = out.read_buf()?;
+        for (n, &f) in o.slice().iter().enumerate().skip(8000) {
+            let want = -300.0 + 200.0 * n as f64 / sr;
+            assert!((f64::from(f) - want).abs() < 2.0, "{n}: {f} != {want}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn freq_range() -> Result<()> {
+        let sr = 48000.0;
+        let (mut src, prev) = VectorSource::new(carrier(sr, 2000.0, 0.0, 24000));
+        src.work()?;
+        let (mut b, out) = PllFreqDet::builder(prev, sr)
+            .freq_range(-1000.0, 1000.0)
+            .build();
+        b.work()?;
+        let (o, _) = out.read_buf()?;
+        for &f in o.slice() {
+            assert!((-1000.0..=1000.0).contains(&f), "{f}");
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,594 @@
+/*! POCSAG pager decoding.
+
+POCSAG is a pager protocol, sent as 2FSK at 512, 1200, or 2400 baud. A
+transmission starts with a preamble of alternating bits, followed by batches.
+Each batch is a sync codeword, and 16 codewords of 32 bits, in eight frames
+of two codewords each.
+
+A pager only listens to the frame given by the lowest three bits of its
+address, so those bits are not sent. An address codeword is followed by any
+number of message codewords.
+
+Each codeword is protected by a BCH(31,21) code, plus a parity bit.
+
+The demodulated bits are fed through a [`CorrelateAccessCodeTag`] looking
+for [`sync_code()`], which tags the batch starts for [`PocsagDecode`].
+
+## Example
+
+```
+use rustradio::graph::{Graph, GraphRunner};
+use rustradio::blocks::*;
+use rustradio::pocsag::{SYNC_TAG, sync_code};
+# fn main() -> rustradio::Result<()> {
+let samp_rate = 48_000.0;
+let baud = 1200.0;
+let (_, prev) = rustradio::stream::new_stream();
+let mut g = Graph::new();
+let prev = rustradio::blockchain![
+    g,
+    prev,
+    // POCSAG sends a 1 as the lower frequency.
+    QuadratureDemod::new(prev, -1.0),
+    ZeroCrossing::new(prev, samp_rate / baud, 0.1),
+    BinarySlicer::new(prev),
+    CorrelateAccessCodeTag::new(prev, sync_code(), SYNC_TAG, 2),
+    PocsagDecode::new(prev),
+];
+# Ok(())
+# }
+```
+
+Reference: ITU-R M.584.
+
+[`CorrelateAccessCodeTag`]: crate::blocks::CorrelateAccessCodeTag
+*/
+use std::collections::HashMap;
+
+use log::{debug, info};
+
+use crate::Result;
+use crate::block::{Block, BlockRet};
+use crate::stream::{NCReadStream, NCWriteStream, ReadStream};
+
+/// Tag that [`PocsagDecode`] looks for, on the last bit of a sync codeword.
+pub const SYNC_TAG: &str = "Pocsag:sync";
+
+/// Sync codeword, starting each batch.
+pub const SYNC_CODEWORD: u32 = 0x7CD2_15D8;
+
+/// Idle codeword, filling frames that have nothing to send.
+pub const IDLE_CODEWORD: u32 = 0x7A89_C197;
+
+// BCH(31,21) generator polynomial, x^10+x^9+x^8+x^6+x^5+x^3+1.
+const POLY: u32 = 0x769;
+
+// Codewords in a batch, not counting the sync codeword.
+const BATCH_CODEWORDS: usize = 16;
+
+// Numeric characters. 0xA is reserved, and often shown as a period.
+const NUMERIC: &[u8; 16] = b"0123456789.U -][";
+
+/// Sync codeword as bits, for [`CorrelateAccessCodeTag`].
+///
+/// [`CorrelateAccessCodeTag`]: crate::blocks::CorrelateAccessCodeTag
+#[must_use]
+pub fn sync_code() -> Vec<u8> {
+    (0..32)
+        .rev()
+        .map(|i| ((SYNC_CODEWORD >> i) & 1) as u8)
+        .collect()
+}
+
+// Remainder of the 31 bit code, divided by the generator. Zero for a
+// correct codeword.
+fn bch_remainder(code: u32) -> u32 {
+    let mut reg = code;
+    for i in (10..31).rev() {
+        if reg & (1 << i) != 0 {
+            reg ^= POLY << (i - 10);
+        }
+    }
+    reg & 0x3FF
+}
+
+/// Decoded message content.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Content {
+    /// Address only. The pager just beeps.
+    Tone,
+    /// Numeric message.
+    Numeric(String),
+    /// Alphanumeric message.
+    Alphanumeric(String),
+}
+
+/// A POCSAG message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Message {
+    /// The 21 bit pager address, also known as RIC.
+    pub address: u32,
+    /// Function code, 0-3.
+    pub function: u8,
+    /// The 20 data bits of each message codeword.
+    pub data: Vec<u32>,
+    /// Decoded content.
+    ///
+    /// Which function codes mean numeric messages varies between networks.
+    /// This assumes the common use of function code 0 for numeric, and the
+    /// rest alphanumeric. Use [`Message::numeric`] or
+    /// [`Message::alphanumeric`] for other conventions.
+    pub content: Content,
+}
+
+impl Message {
+    fn new(address: u32, function: u8, data: Vec<u32>) -> Self {
+        let mut msg = Self {
+            address,
+            function,
+            data,
+            content: Content::Tone,
+        };
+        msg.content = match (msg.data.is_empty(), function) {
+            (true, _) => Content::Tone,
+            (false, 0) => Content::Numeric(msg.numeric()),
+            (false, _) => Content::Alphanumeric(msg.alphanumeric()),
+        };
+        msg
+    }
+
+    // Message bits, in the order sent.
+    fn bits(&self) -> impl Iterator<Item = u8> + '_ {
+        self.data
+            .iter()
+            .flat_map(|&d| (0..20).rev().map(move |i| ((d >> i) & 1) as u8))
+    }
+
+    /// Decode data as numeric. Four bits per character, least significant
+    /// bit first.
+    #[must_use]
+    pub fn numeric(&self) -> String {
+        let bits: Vec<u8> = self.bits().collect();
+        let s: String = bits
+            .chunks_exact(4)
+            .map(|c| {
+                let v = c.iter().rev().fold(0, |acc, &b| (acc << 1) | b);
+                NUMERIC[usize::from(v)] as char
+            })
+            .collect();
+        s.trim_end().to_string()
+    }
+
+    /// Decode data as alphanumeric. Seven bit ASCII, least significant bit
+    /// first.
+    #[must_use]
+    pub fn alphanumeric(&self) -> String {
+        let bits: Vec<u8> = self.bits().collect();
+        let s: String = bits
+            .chunks_exact(7)
+            .map(|c| c.iter().rev().fold(0, |acc, &b| (acc << 1) | b) as char)
+            .filter(|&c| c != '\0')
+            .collect();
+        s.trim_end_matches(|c: char| c.is_control()).to_string()
+    }
+}
+
+impl std::fmt::Display for Message {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Address {:7} function {}: ", self.address, self.function)?;
+        match &self.content {
+            Content::Tone => write!(f, "tone"),
+            Content::Numeric(s) => write!(f, "numeric '{s}'"),
+            Content::Alphanumeric(s) => write!(f, "alpha '{s}'"),
+        }
+    }
+}
+
+/// POCSAG decoder.
+///
+/// Takes bits, tagged with [`SYNC_TAG`] at the batch starts, and outputs
+/// [`Message`]s. Codewords with up to two bit errors are corrected.
+///
+/// A message ends at the next address or idle codeword, or when the
+/// transmission ends. Messages with an uncorrectable codeword are dropped.
+#[derive(rustradio_macros::Block)]
+#[rustradio(crate)]
+pub struct PocsagDecode {
+    #[rustradio(in)]
+    src: ReadStream<u8>,
+    #[rustradio(out)]
+    dst: NCWriteStream<Message>,
+
+    // Remainder of correctable errors, to the error.
+    corrections: HashMap<u32, u32>,
+
+    synced: bool,
+    reg: u32,
+    bits: usize,
+    // Codeword in the batch. `BATCH_CODEWORDS` means the sync codeword.
+    codeword: usize,
+    // Address, function, and data of the message being received.
+    current: Option<(u32, u8, Vec<u32>)>,
+
+    decoded: usize,
+    corrected: usize,
+    uncorrectable: usize,
+}
+
+impl Drop for PocsagDecode {
+    fn drop(&mut self) {
+        info!(
+            "PocsagDecode: Decoded {} messages, corrected {} codewords, {} uncorrectable",
+            self.decoded, self.corrected, self.uncorrectable
+        );
+    }
+}
+
+impl PocsagDecode {
+    /// Create new POCSAG decoder.
+    #[must_use]
+    pub fn new(src: ReadStream<u8>) -> (Self, NCReadStream<Message>) {
+        let (dst, dr) = crate::stream::new_nocopy_stream();
+        let mut corrections = HashMap::new();
+        for i in 0..31 {
+            corrections.insert(bch_remainder(1 << i), 1 << i);
+            for j in (i + 1)..31 {
+                let e = (1 << i) | (1 << j);
+                corrections.insert(bch_remainder(e), e);
+            }
+        }
+        (
+            Self {
+                src,
+                dst,
+                corrections,
+                synced: false,
+                reg: 0,
+                bits: 0,
+                codeword: 0,
+                current: None,
+                decoded: 0,
+                corrected: 0,
+                uncorrectable: 0,
+            },
+            dr,
+        )
+    }
+
+    // Return the corrected codeword, if there are at most two bit errors,
+    // counting the parity bit.
+    fn correct(&mut self, word: u32) -> Option<u32> {
+        let code = word >> 1;
+        let e = match bch_remainder(code) {
+            0 => 0,
+            r => *self.corrections.get(&r)?,
+        };
+        let code = code ^ e;
+        let parity = code.count_ones() & 1;
+        let errors = e.count_ones() + u32::from(parity != word & 1);
+        if errors > 2 {
+            return None;
+        }
+        if errors > 0 {
+            self.corrected += 1;
+        }
+        Some((code << 1) | parity)
+    }
+
+    // Output the message being received, if any.
+    fn flush(&mut self) {
+        if let Some((address, function, data)) = self.current.take() {
+            let msg = Message::new(address, function, data);
+            debug!("PocsagDecode: {msg}");
+            self.decoded += 1;
+            self.dst.push(msg, &[]);
+        }
+    }
+
+    fn handle_codeword(&mut self, word: u32) {
+        let Some(word) = self.correct(word) else {
+            self.uncorrectable += 1;
+            if self.current.take().is_some() {
+                debug!("PocsagDecode: dropping message with uncorrectable codeword");
+            }
+            return;
+        };
+        if word == IDLE_CODEWORD {
+            self.flush();
+        } else if word & 0x8000_0000 == 0 {
+            self.flush();
+            // The lowest three bits of the address are the frame number.
+            let address = ((word >> 13) << 3) | (self.codeword / 2) as u32;
+            let function = ((word >> 11) & 3) as u8;
+            self.current = Some((address, function, Vec::new()));
+        } else if let Some((_, _, data)) = &mut self.current {
+            data.push((word >> 11) & 0xF_FFFF);
+        }
+    }
+
+    // Handle a bit, with whether it's tagged as the end of a sync codeword.
+    fn bit(&mut self, bit: u8, sync: bool) {
+        if !self.synced {
+            if sync {
+                self.synced = true;
+                self.codeword = 0;
+                self.bits = 0;
+            }
+            return;
+        }
+        self.reg = (self.reg << 1) | u32::from(bit & 1);
+        self.bits += 1;
+        if self.bits < 32 {
+            return;
+        }
+        self.bits = 0;
+        if self.codeword < BATCH_CODEWORDS {
+            self.handle_codeword(self.reg);
+            self.codeword += 1;
+            return;
+        }
+        // Another batch, or end of transmission?
+        if self.correct(self.reg) == Some(SYNC_CODEWORD) {
+            self.codeword = 0;
+        } else {
+            debug!("PocsagDecode: end of transmission");
+            self.flush();
+            self.synced = false;
+        }
+    }
+}
+
+impl Block for PocsagDecode {
+    fn work(&mut self) -> Result<BlockRet<'_>> {
+        let (i, tags) = self.src.read_buf()?;
+        if i.is_empty() {
+            if self.src.eof() {
+                self.flush();
+            }
+            return Ok(BlockRet::WaitForStream(&self.src, 1));
+        }
+        // Each bit outputs at most one message.
+        if self.dst.remaining() == 0 {
+            return Ok(BlockRet::WaitForStream(&self.dst, 1));
+        }
+        let mut n = 0;
+        for (pos, &bit) in i.iter().enumerate() {
+            let sync = tags.iter().any(|t| t.pos() == pos && t.key() == SYNC_TAG);
+            self.bit(bit, sync);
+            n += 1;
+            if self.dst.remaining() == 0 {
+                break;
+            }
+        }
+        i.consume(n);
+        Ok(BlockRet::Again)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::{CorrelateAccessCodeTag, VectorSource};
+    use crate::stream::{Tag, TagValue};
+
+    fn encode(data: u32) -> u32 {
+        let code = data << 10;
+        let code = code | bch_remainder(code);
+        (code << 1) | (code.count_ones() & 1)
+    }
+
+    fn numeric_data(s: &str) -> Vec<u32> {
+        let mut bits: Vec<u8> = s
+            .bytes()
+            .flat_map(|c| {
+                let v = NUMERIC.iter().position(|&x| x == c).unwrap() as u8;
+                (0..4).map(move |i| (v >> i) & 1)
+            })
+            .collect();
+        // Pad with spaces.
+        while !bits.len().is_multiple_of(20) {
+            bits.extend([0, 0, 1, 1]);
+        }
+        pack(&bits)
+    }
+
+    fn alpha_data(s: &str) -> Vec<u32> {
+        let mut bits: Vec<u8> = s
+            .bytes()
+            .flat_map(|c| (0..7).map(move |i| (c >> i) & 1))
+            .collect();
+        while !bits.len().is_multiple_of(20) {
+            bits.push(0);
+        }
+        pack(&bits)
+    }
+
+    fn pack(bits: &[u8]) -> Vec<u32> {
+        bits.chunks(20)
+            .map(|c| c.iter().fold(0, |acc, &b| (acc << 1) | u32::from(b)))
+            .collect()
+    }
+
+    // Transmission, as bits.
+    fn transmit(msgs: &[(u32, u8, Vec<u32>)]) -> Vec<u8> {
+        let mut words = Vec::new();
+        for (address, function, data) in msgs {
+            while (words.len() % BATCH_CODEWORDS) / 2 != (*address & 7) as usize {
+                words.push(IDLE_CODEWORD);
+            }
+            words.push(encode(((address >> 3) << 2) | u32::from(*function)));
+            words.extend(data.iter().map(|d| encode((1 << 20) | d)));
+        }
+        words.push(IDLE_CODEWORD);
+        while !words.len().is_multiple_of(BATCH_CODEWORDS) {
+            words.push(IDLE_CODEWORD);
+        }
+        let mut bits: Vec<u8> = (0..576).map(|i| 1 - (i % 2) as u8).collect();
+        for batch in words.chunks(BATCH_CODEWORDS) {
+            for w in std::iter::once(&SYNC_CODEWORD).chain(batch) {
+                bits.extend((0..32).rev().map(|i| ((w >> i) & 1) as u8));
+            }
+        }
+        bits
+    }
+
+    fn test_messages() -> Vec<(u32, u8, Vec<u32>)> {
+        vec![
+            (1_234_567, 0, numeric_data("0123-456 789U")),
+            (
+                1_234_568,
+                3,
+                alpha_data("Interference study, channel 2. Over and out."),
+            ),
+            (42, 1, vec![]),
+        ]
+    }
+
+    fn check(got: &[Message]) {
+        assert_eq!(got.len(), 3, "{got:?}");
+        assert_eq!(got[0].address, 1_234_567);
+        assert_eq!(got[0].function, 0);
+        assert_eq!(got[0].content, Content::Numeric("0123-456 789U".into()));
+        assert_eq!(got[1].address, 1_234_568);
+        assert_eq!(got[1].function, 3);
+        assert_eq!(
+            got[1].content,
+            Content::Alphanumeric("Interference study, channel 2. Over and out.".into())
+        );
+        assert_eq!(got[2].address, 42);
+        assert_eq!(got[2].content, Content::Tone);
+    }
+
+    fn decode(bits: Vec<u8>) -> Result<Vec<Message>> {
+        let (mut src, prev) = VectorSource::new(bits);
+        src.work()?;
+        let (mut cac, prev) = CorrelateAccessCodeTag::new(prev, sync_code(), SYNC_TAG, 2);
+        cac.work()?;
+        let (mut b, out) = PocsagDecode::new(prev);
+        b.work()?;
+        let mut ret = Vec::new();
+        while let Some((m, _)) = out.pop() {
+            ret.push(m);
+        }
+        Ok(ret)
+    }
+
+    #[test]
+    fn codeword() {
+        assert_eq!(bch_remainder(SYNC_CODEWORD >> 1), 0);
+        assert_eq!(bch_remainder(IDLE_CODEWORD >> 1), 0);
+        assert_eq!(encode(SYNC_CODEWORD >> 11), SYNC_CODEWORD);
+
+        let (_, prev) = crate::stream::new_stream();
+        let (mut b, _) = PocsagDecode::new(prev);
+        let w = encode(0x12345);
+        assert_eq!(b.correct(w), Some(w));
+        for i in 0..32 {
+            assert_eq!(b.correct(w ^ (1 << i)), Some(w), "bit {i}");
+            for j in (i + 1)..32 {
+                assert_eq!(b.correct(w ^ (1 << i) ^ (1 << j)), Some(w), "bits {i} {j}");
+            }
+        }
+        assert_eq!(b.correct(w ^ 0b111 << 5), None);
+    }
+
+    #[test]
+    fn text() {
+        let m = Message::new(8, 0, numeric_data("911"));
+        assert_eq!(m.content, Content::Numeric("911".into()));
+        assert_eq!(m.to_string(), "Address       8 function 0: numeric '911'");
+        let m = Message::new(8, 2, alpha_data("Hi"));
+        assert_eq!(m.content, Content::Alphanumeric("Hi".into()));
+        assert_eq!(m.numeric().len(), 5);
+    }
+
+    #[test]
+    fn decode_messages() -> Result<()> {
+        check(&decode(transmit(&test_messages()))?);
+        Ok(())
+    }
+
+    #[test]
+    fn bit_errors() -> Result<()> {
+        let mut bits = transmit(&test_messages());
+        // Two errors per codeword, in every codeword after the preamble.
+        for (n, b) in bits.iter_mut().enumerate().skip(576) {
+            if n % 32 == 3 || n % 32 == 17 {
+                *b ^= 1;
+            }
+        }
+        check(&decode(bits)?);
+        Ok(())
+    }
+
+    #[test]
+    fn uncorrectable() -> Result<()> {
+        let mut bits = transmit(&test_messages());
+        // Break the first message codeword of the first message. Its
+        // address is in frame 7, so after the sync codeword and 14 idle
+        // codewords, the address is codeword 14 of the batch.
+        let start = 576 + 32 + 15 * 32;
+        for b in &mut bits[start..start + 3] {
+            *b ^= 1;
+        }
+        let got = decode(bits)?;
+        assert_eq!(got.len(), 2, "{got:?}");
+        assert_eq!(got[0].address, 1_234_568);
+        Ok(())
+    }
+
+    #[test]
+    fn fsk() -> Result<()> {
+        use crate::blocks::{BinarySlicer, QuadratureDemod, ZeroCrossing};
+        use crate::graph::{Graph, GraphRunner};
+        use crate::{Complex, Float};
+        let sr = 48_000.0;
+        for baud in [512.0, 1200.0, 2400.0] {
+            let bits = transmit(&test_messages());
+            let sps = sr / baud;
+            let mut phase = 0.0f64;
+            let samples: Vec<Complex> = (0..((bits.len() as f64 * sps) as usize))
+                .map(|n| {
+                    // A 1 is the lower frequency.
+                    let dev = if bits[(n as f64 / sps) as usize] == 1 {
+                        -4500.0
+                    } else {
+                        4500.0
+                    };
+                    phase += 2.0 * std::f64::consts::PI * dev / sr;
+                    Complex::new(phase.cos() as Float, phase.sin() as Float)
+                })
+                .collect();
+            let mut g = Graph::new();
+            let prev = crate::blockchain![
+                g,
+                prev,
+                VectorSource::new(samples),
+                QuadratureDemod::new(prev, -1.0),
+                ZeroCrossing::new(prev, sps as Float, 0.1),
+                BinarySlicer::new(prev),
+                CorrelateAccessCodeTag::new(prev, sync_code(), SYNC_TAG, 2),
+                PocsagDecode::new(prev),
+            ];
+            g.run()?;
+            let mut got = Vec::new();
+            while let Some((m, _)) = prev.pop() {
+                got.push(m);
+            }
+            check(&got);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn sync_tag() -> Result<()> {
+        // CorrelateAccessCodeTag tags the last bit of the sync codeword.
+        let (mut src, prev) = VectorSource::new(sync_code());
+        src.work()?;
+        let (mut cac, out) = CorrelateAccessCodeTag::new(prev, sync_code(), SYNC_TAG, 0);
+        cac.work()?;
+        let (_, tags) = out.read_buf()?;
+        assert!(tags.contains(&Tag::new(31, SYNC_TAG, TagValue::U64(0))));
+        Ok(())
+    }
+}
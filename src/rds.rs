@@ -0,0 +1,1009 @@
+/*! Radio Data System (RDS) decoding.
+
+RDS, and its North American variant RBDS, is digital data sent along with
+broadcast FM audio. It's on a 57kHz subcarrier in the demodulated FM
+(multiplex) signal, as differentially encoded biphase BPSK at 1187.5bps.
+
+Data is sent in groups of four 26 bit blocks. Each block has 16 bits of
+data, and a 10 bit checkword. The checkword is XORed with an offset word,
+which tells where in the group the block is, and lets the receiver find the
+block boundaries.
+
+[`RdsDemod`] turns the multiplex signal into data bits, and [`RdsDecode`]
+turns bits into [`Group`]s.
+
+## Example
+
+```
+use rustradio::graph::{Graph, GraphRunner};
+use rustradio::blocks::*;
+# fn main() -> rustradio::Result<()> {
+let samp_rate = 228_000.0;
+let (_, prev) = rustradio::stream::new_stream();
+let mut g = Graph::new();
+let prev = rustradio::blockchain![
+    g,
+    prev,
+    QuadratureDemod::new(prev, 1.0),
+    RdsDemod::new(prev, samp_rate),
+    RdsDecode::new(prev),
+];
+# Ok(())
+# }
+```
+
+Reference: IEC 62106 / EN 50067.
+*/
+use std::collections::{HashMap, VecDeque};
+
+use log::{debug, info};
+
+use crate::block::{Block, BlockRet};
+use crate::fir::Fir;
+use crate::pll::Pll;
+use crate::stream::{NCReadStream, NCWriteStream, ReadStream, Tag, TagValue, WriteStream};
+use crate::window::WindowType;
+use crate::{Complex, Float, Result};
+
+/// Tag with the PI (program identification) code, as U64, on each group.
+pub const PI_TAG: &str = "Rds:pi";
+
+/// Tag with the full program service (station) name, as String.
+///
+/// Set when all of it has been received, and it's not the same as the last
+/// time it was tagged.
+pub const PS_TAG: &str = "Rds:ps";
+
+/// Tag with the full RadioText, as String.
+///
+/// Set when all of it has been received, and it's not the same as the last
+/// time it was tagged.
+pub const RADIOTEXT_TAG: &str = "Rds:radiotext";
+
+const TAU: f64 = 2.0 * std::f64::consts::PI;
+
+const SUBCARRIER: f64 = 57_000.0;
+const BIT_RATE: f64 = 1187.5;
+
+// Candidate bit timings, as fractions of a bit.
+const TIMINGS: usize = 8;
+
+// Checkword generator polynomial, x^10+x^8+x^7+x^5+x^4+x^3+1.
+const POLY: u32 = 0x5B9;
+
+// Offset words, in group order. C' is the third block of version B groups.
+const OFFSET_A: u16 = 0x0FC;
+const OFFSET_B: u16 = 0x198;
+const OFFSET_C: u16 = 0x168;
+const OFFSET_CP: u16 = 0x350;
+const OFFSET_D: u16 = 0x1B4;
+
+// Lose sync after this many uncorrectable blocks in a row.
+const MAX_BAD_BLOCKS: usize = 10;
+
+/// RDS demodulator.
+///
+/// Takes the demodulated FM (multiplex) signal, at a sample rate of at least
+/// 120kHz, and outputs data bits, one per `u8`. The bits still need to be
+/// block synchronized, by [`RdsDecode`].
+///
+/// The 57kHz subcarrier is recovered with a Costas loop, and the 180 degree
+/// ambiguity of BPSK is removed by the differential decoding.
+#[derive(rustradio_macros::Block)]
+#[rustradio(crate)]
+pub struct RdsDemod {
+    #[rustradio(in)]
+    src: ReadStream<Float>,
+    #[rustradio(out)]
+    dst: WriteStream<u8>,
+
+    // Mix down, and low pass filter while decimating.
+    nco_phase: f64,
+    nco_step: f64,
+    history: VecDeque<Complex>,
+    fir: Fir<Complex>,
+    decim: usize,
+    decim_pos: usize,
+
+    // Costas loop.
+    pll: Pll,
+
+    // Bit phase, from 0 to 1, and its step per decimated sample.
+    clock: f64,
+    clock_step: f64,
+    // Integrate and dump of the two halves of the biphase symbol, for each
+    // candidate timing.
+    first: [Float; TIMINGS],
+    second: [Float; TIMINGS],
+    energy: [Float; TIMINGS],
+    best: usize,
+
+    // Last bit, for differential decoding.
+    last: u8,
+}
+
+impl RdsDemod {
+    /// Create new RDS demodulator.
+    #[must_use]
+    pub fn new(src: ReadStream<Float>, sample_rate: f64) -> (Self, ReadStream<u8>) {
+        let (dst, dr) = crate::stream::new_stream();
+        // Decimate to about 19kHz, or 16 samples per bit.
+        let decim = ((sample_rate / (16.0 * BIT_RATE)) as usize).max(1);
+        let rate = sample_rate / decim as f64;
+        let taps = crate::fir::low_pass_complex(
+            sample_rate as Float,
+            2400.0,
+            1000.0,
+            &WindowType::Hamming,
+        );
+        (
+            Self {
+                src,
+                dst,
+                nco_phase: 0.0,
+                nco_step: TAU * SUBCARRIER / sample_rate,
+                history: std::iter::repeat_n(Complex::default(), taps.len()).collect(),
+                fir: Fir::new(&taps),
+                decim,
+                decim_pos: 0,
+                pll: Pll::new(TAU * 10.0 / rate, -TAU * 20.0 / rate, TAU * 20.0 / rate),
+                clock: 0.0,
+                clock_step: BIT_RATE / rate,
+                first: [0.0; TIMINGS],
+                second: [0.0; TIMINGS],
+                energy: [0.0; TIMINGS],
+                best: 0,
+                last: 0,
+            },
+            dr,
+        )
+    }
+
+    // Process one decimated sample. Maybe return a bit.
+    fn symbol(&mut self, s: Complex) -> Option<u8> {
+        // Costas loop.
+        let (sin, cos) = self.pll.phase.sin_cos();
+        let x = s * Complex::new(cos as Float, -sin as Float);
+        self.pll.update(f64::from((x * x).arg()) / 2.0);
+        let x = x.re;
+
+        let old = self.clock;
+        self.clock = (self.clock + self.clock_step).fract();
+        let mut ret = None;
+        for k in 0..TIMINGS {
+            let offset = k as f64 / TIMINGS as f64;
+            let before = (old - offset).rem_euclid(1.0);
+            let after = (self.clock - offset).rem_euclid(1.0);
+            if after < before {
+                // Bit boundary for this timing.
+                let v = self.first[k] - self.second[k];
+                self.energy[k] += (v.abs() - self.energy[k]) * 0.05;
+                if k == self.best {
+                    ret = Some(u8::from(v > 0.0));
+                }
+                self.first[k] = 0.0;
+                self.second[k] = 0.0;
+                // Switch timing, with some hysteresis.
+                if self.energy[k] > 1.2 * self.energy[self.best] {
+                    debug!("RdsDemod: switching bit timing {} -> {k}", self.best);
+                    self.best = k;
+                }
+            }
+            if after < 0.5 {
+                self.first[k] += x;
+            } else {
+                self.second[k] += x;
+            }
+        }
+        let bit = ret?;
+        let data = bit ^ self.last;
+        self.last = bit;
+        Some(data)
+    }
+}
+
+impl Block for RdsDemod {
+    fn work(&mut self) -> Result<BlockRet<'_>> {
+        let (i, _) = self.src.read_buf()?;
+        if i.is_empty() {
+            return Ok(BlockRet::WaitForStream(&self.src, 1));
+        }
+        let mut o = self.dst.write_buf()?;
+        if o.is_empty() {
+            return Ok(BlockRet::WaitForStream(&self.dst, 1));
+        }
+        let mut opos = 0;
+        let mut consumed = 0;
+        for &x in i.slice() {
+            if opos == o.len() {
+                break;
+            }
+            consumed += 1;
+            let (sin, cos) = self.nco_phase.sin_cos();
+            self.nco_phase = (self.nco_phase + self.nco_step).rem_euclid(TAU);
+            self.history.pop_front();
+            self.history
+                .push_back(Complex::new(x * cos as Float, -x * sin as Float));
+            self.decim_pos += 1;
+            if self.decim_pos < self.decim {
+                continue;
+            }
+            self.decim_pos = 0;
+            let s = self.fir.filter(self.history.make_contiguous());
+            if let Some(bit) = self.symbol(s) {
+                o.slice()[opos] = bit;
+                opos += 1;
+            }
+        }
+        i.consume(consumed);
+        o.produce(opos, &[]);
+        Ok(BlockRet::Again)
+    }
+}
+
+/// Calculate the syndrome of a 26 bit block.
+///
+/// For a correct block, this is the offset word.
+fn syndrome(block: u32) -> u16 {
+    let mut reg = block;
+    for i in (10..26).rev() {
+        if reg & (1 << i) != 0 {
+            reg ^= POLY << (i - 10);
+        }
+    }
+    (reg & 0x3FF) as u16
+}
+
+/// Encode 16 bits of information into a 26 bit block, with the given offset.
+#[must_use]
+pub fn encode_block(info: u16, offset: u16) -> u32 {
+    let info = u32::from(info) << 10;
+    info | u32::from(syndrome(info) ^ offset)
+}
+
+/// Date and time from a clock time (4A) group.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClockTime {
+    /// Year.
+    pub year: u16,
+    /// Month, 1-12.
+    pub month: u8,
+    /// Day of month, 1-31.
+    pub day: u8,
+    /// UTC hour.
+    pub hour: u8,
+    /// UTC minute.
+    pub minute: u8,
+    /// Local time offset from UTC, in minutes.
+    pub offset_minutes: i16,
+}
+
+impl ClockTime {
+    fn from_blocks(b: u16, c: u16, d: u16) -> Self {
+        let mjd = (u32::from(b & 3) << 15) | u32::from(c >> 1);
+        let (year, month, day) = mjd_to_date(mjd);
+        let offset = i16::try_from(d & 0x1F).expect("5 bits fit") * 30;
+        Self {
+            year,
+            month,
+            day,
+            hour: (((c & 1) << 4) | (d >> 12)) as u8,
+            minute: ((d >> 6) & 0x3F) as u8,
+            offset_minutes: if d & 0x20 != 0 { -offset } else { offset },
+        }
+    }
+}
+
+impl std::fmt::Display for ClockTime {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let sign = if self.offset_minutes < 0 { '-' } else { '+' };
+        let offset = self.offset_minutes.unsigned_abs();
+        write!(
+            f,
+            "{:04}-{:02}-{:02} {:02}:{:02} UTC, local offset {sign}{:02}:{:02}",
+            self.year,
+            self.month,
+            self.day,
+            self.hour,
+            self.minute,
+            offset / 60,
+            offset % 60
+        )
+    }
+}
+
+// Modified Julian Date to year, month, day, per the RDS standard annex.
+fn mjd_to_date(mjd: u32) -> (u16, u8, u8) {
+    let mjd = f64::from(mjd);
+    let y = ((mjd - 15078.2) / 365.25).floor();
+    let m = ((mjd - 14956.1 - (y * 365.25).floor()) / 30.6001).floor();
+    let day = mjd - 14956.0 - (y * 365.25).floor() - (m * 30.6001).floor();
+    let k = if m == 14.0 || m == 15.0 { 1.0 } else { 0.0 };
+    (
+        (y + k + 1900.0) as u16,
+        (m - 1.0 - k * 12.0) as u8,
+        day as u8,
+    )
+}
+
+/// Decoded group content.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GroupData {
+    /// Basic tuning and switching information (0A/0B), carrying two
+    /// characters of the program service name.
+    ProgramService {
+        /// Segment, 0-3. The characters are at `2*segment`.
+        segment: u8,
+        /// Traffic announcement.
+        ta: bool,
+        /// Music (true) or speech (false).
+        music: bool,
+        /// Two characters of the name.
+        chars: [u8; 2],
+    },
+    /// RadioText (2A/2B).
+    RadioText {
+        /// Segment, 0-15. The characters are at `4*segment` for 2A, and
+        /// `2*segment` for 2B.
+        segment: u8,
+        /// Text A/B flag. Changes when the text changes.
+        ab: bool,
+        /// Four (2A) or two (2B) characters of the text.
+        chars: Vec<u8>,
+    },
+    /// Clock time and date (4A).
+    ClockTime(ClockTime),
+    /// Group type not decoded.
+    Other,
+}
+
+/// An RDS group.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Group {
+    /// The four blocks, with checkwords removed.
+    pub blocks: [u16; 4],
+    /// Program identification code.
+    pub pi: u16,
+    /// Group type, 0-15.
+    pub group_type: u8,
+    /// True for version B, false for version A.
+    pub version_b: bool,
+    /// Traffic program.
+    pub tp: bool,
+    /// Program type.
+    pub pty: u8,
+    /// Decoded content.
+    pub data: GroupData,
+}
+
+impl Group {
+    /// Parse a group from its four blocks.
+    #[must_use]
+    pub fn parse(blocks: [u16; 4]) -> Self {
+        let [_, b, c, d] = blocks;
+        let group_type = (b >> 12) as u8;
+        let version_b = b & 0x800 != 0;
+        let [d_hi, d_lo] = d.to_be_bytes();
+        let data = match (group_type, version_b) {
+            (0, _) => GroupData::ProgramService {
+                segment: (b & 3) as u8,
+                ta: b & 0x10 != 0,
+                music: b & 0x8 != 0,
+                chars: [d_hi, d_lo],
+            },
+            (2, false) => {
+                let [c_hi, c_lo] = c.to_be_bytes();
+                GroupData::RadioText {
+                    segment: (b & 0xF) as u8,
+                    ab: b & 0x10 != 0,
+                    chars: vec![c_hi, c_lo, d_hi, d_lo],
+                }
+            }
+            (2, true) => GroupData::RadioText {
+                segment: (b & 0xF) as u8,
+                ab: b & 0x10 != 0,
+                chars: vec![d_hi, d_lo],
+            },
+            (4, false) => GroupData::ClockTime(ClockTime::from_blocks(b, c, d)),
+            _ => GroupData::Other,
+        };
+        Self {
+            blocks,
+            // For version B, the PI is repeated in block C', but block A
+            // always has it.
+            pi: blocks[0],
+            group_type,
+            version_b,
+            tp: b & 0x400 != 0,
+            pty: ((b >> 5) & 0x1F) as u8,
+            data,
+        }
+    }
+}
+
+// RDS characters to a string. The basic character set is mostly ASCII.
+fn chars_to_string(chars: &[u8]) -> String {
+    chars
+        .iter()
+        .map(|&c| {
+            if (0x20..0x7F).contains(&c) {
+                c as char
+            } else {
+                '?'
+            }
+        })
+        .collect::<String>()
+        .trim_end()
+        .to_string()
+}
+
+// Program service name and RadioText being assembled.
+#[derive(Default)]
+struct Station {
+    pi: Option<u16>,
+    ps: [u8; 8],
+    ps_seen: u8,
+    ps_last: String,
+    rt: Vec<u8>,
+    rt_seen: u16,
+    // Text A/B flag, and segment length, i.e. 2A or 2B.
+    rt_version: Option<(bool, usize)>,
+    rt_last: String,
+}
+
+impl Station {
+    // Update with a group, and return tags for anything newly complete.
+    fn update(&mut self, group: &Group) -> Vec<Tag> {
+        let mut tags = vec![Tag::new(0, PI_TAG, TagValue::U64(group.pi.into()))];
+        if self.pi != Some(group.pi) {
+            *self = Self {
+                pi: Some(group.pi),
+                ..Default::default()
+            };
+        }
+        match &group.data {
+            GroupData::ProgramService { segment, chars, .. } => {
+                let pos = usize::from(*segment) * 2;
+                self.ps[pos..pos + 2].copy_from_slice(chars);
+                self.ps_seen |= 1 << segment;
+                if self.ps_seen == 0xF {
+                    self.ps_seen = 0;
+                    let ps = chars_to_string(&self.ps);
+                    if ps != self.ps_last {
+                        info!("RdsDecode: PI {:04X} PS '{ps}'", group.pi);
+                        tags.push(Tag::new(0, PS_TAG, TagValue::String(ps.clone())));
+                        self.ps_last = ps;
+                    }
+                }
+            }
+            GroupData::RadioText { segment, ab, chars } => {
+                if self.rt_version != Some((*ab, chars.len())) {
+                    self.rt_version = Some((*ab, chars.len()));
+                    self.rt.clear();
+                    self.rt_seen = 0;
+                }
+                let pos = usize::from(*segment) * chars.len();
+                if self.rt.len() < pos + chars.len() {
+                    self.rt.resize(pos + chars.len(), b' ');
+                }
+                self.rt[pos..pos + chars.len()].copy_from_slice(chars);
+                self.rt_seen |= 1 << segment;
+                if let Some(rt) = self.radiotext(chars.len()) {
+                    self.rt_seen = 0;
+                    if rt != self.rt_last {
+                        info!("RdsDecode: PI {:04X} RadioText '{rt}'", group.pi);
+                        tags.push(Tag::new(0, RADIOTEXT_TAG, TagValue::String(rt.clone())));
+                        self.rt_last = rt;
+                    }
+                }
+            }
+            GroupData::ClockTime(_) | GroupData::Other => {}
+        }
+        tags
+    }
+
+    // Return the RadioText, if all segments up to the end have been seen.
+    fn radiotext(&self, segment_len: usize) -> Option<String> {
+        // Text ends at a carriage return, or after all 16 segments.
+        let end = self
+            .rt
+            .iter()
+            .position(|&c| c == 0x0D)
+            .map_or(16, |p| p / segment_len + 1);
+        let want = ((1u32 << end) - 1) as u16;
+        if self.rt_seen & want != want {
+            return None;
+        }
+        let text: Vec<u8> = self.rt.iter().copied().take_while(|&c| c != 0x0D).collect();
+        Some(chars_to_string(&text))
+    }
+}
+
+/// RDS decoder.
+///
+/// Takes data bits, such as from [`RdsDemod`], finds the block boundaries,
+/// corrects errors, and outputs the groups. Only complete groups, where all
+/// four blocks are correct or corrected, are output.
+///
+/// Each group is tagged with [`PI_TAG`], and [`PS_TAG`] and
+/// [`RADIOTEXT_TAG`] when the station name or text is complete.
+#[derive(rustradio_macros::Block)]
+#[rustradio(crate)]
+pub struct RdsDecode {
+    #[rustradio(in)]
+    src: ReadStream<u8>,
+    #[rustradio(out)]
+    dst: NCWriteStream<Group>,
+
+    // Syndrome of correctable burst errors, to the error.
+    corrections: HashMap<u16, u32>,
+
+    reg: u32,
+    // Number of bits received.
+    bits: u64,
+    // Position of the last block seen for each offset, when not in sync.
+    seen: [Option<u64>; 4],
+
+    synced: bool,
+    // Bits received of the current block, and its index in the group.
+    block_bits: usize,
+    block: usize,
+    bad_blocks: usize,
+    group: [Option<u16>; 4],
+
+    station: Station,
+}
+
+impl RdsDecode {
+    /// Create new RDS decoder.
+    #[must_use]
+    pub fn new(src: ReadStream<u8>) -> (Self, NCReadStream<Group>) {
+        let (dst, dr) = crate::stream::new_nocopy_stream();
+        // The code corrects all burst errors up to 5 bits.
+        let mut corrections = HashMap::new();
+        for len in 1..=5 {
+            let ends = if len == 1 { 1 } else { 1 | (1 << (len - 1)) };
+            let middles = if len > 2 { 1 << (len - 2) } else { 1 };
+            for m in 0..middles {
+                let pattern: u32 = ends | (m << 1);
+                for shift in 0..=(26 - len) {
+                    let e = pattern << shift;
+                    corrections.insert(syndrome(e), e);
+                }
+            }
+        }
+        (
+            Self {
+                src,
+                dst,
+                corrections,
+                reg: 0,
+                bits: 0,
+                seen: [None; 4],
+                synced: false,
+                block_bits: 0,
+                block: 0,
+                bad_blocks: 0,
+                group: [None; 4],
+                station: Station::default(),
+            },
+            dr,
+        )
+    }
+
+    // Offset words valid for the block. The version flag in block B tells C
+    // from C'.
+    fn offsets(&self, block: usize) -> &'static [u16] {
+        match (block, self.group[1]) {
+            (0, _) => &[OFFSET_A],
+            (1, _) => &[OFFSET_B],
+            (2, Some(b)) if b & 0x800 != 0 => &[OFFSET_CP],
+            (2, Some(_)) => &[OFFSET_C],
+            (2, None) => &[OFFSET_C, OFFSET_CP],
+            _ => &[OFFSET_D],
+        }
+    }
+
+    // Block index of an offset word.
+    fn block_index(syndrome: u16) -> Option<usize> {
+        match syndrome {
+            OFFSET_A => Some(0),
+            OFFSET_B => Some(1),
+            OFFSET_C | OFFSET_CP => Some(2),
+            OFFSET_D => Some(3),
+            _ => None,
+        }
+    }
+
+    // Return the corrected information bits of the block.
+    fn correct(&self, block: u32, index: usize) -> Option<u16> {
+        let s = syndrome(block);
+        let offsets = self.offsets(index);
+        // Prefer no errors, over any correction.
+        let e = if offsets.contains(&s) {
+            0
+        } else {
+            offsets
+                .iter()
+                .find_map(|&offset| self.corrections.get(&(s ^ offset)))
+                .copied()?
+        };
+        Some(((block ^ e) >> 10) as u16)
+    }
+
+    fn bit(&mut self, bit: u8) {
+        self.reg = ((self.reg << 1) | u32::from(bit & 1)) & 0x3FF_FFFF;
+        self.bits += 1;
+        if !self.synced {
+            self.find_sync();
+            return;
+        }
+        self.block_bits += 1;
+        if self.block_bits < 26 {
+            return;
+        }
+        self.block_bits = 0;
+        let info = self.correct(self.reg, self.block);
+        self.got_block(info);
+    }
+
+    fn find_sync(&mut self) {
+        if self.bits < 26 {
+            return;
+        }
+        let Some(index) = Self::block_index(syndrome(self.reg)) else {
+            return;
+        };
+        // A previous block, a whole number of blocks ago, in the right
+        // order?
+        let synced = self.seen.iter().enumerate().any(|(prev, pos)| {
+            let Some(pos) = pos else {
+                return false;
+            };
+            let dist = self.bits - pos;
+            dist.is_multiple_of(26)
+                && dist / 26 <= 8
+                && (prev as u64 + dist / 26) % 4 == index as u64
+        });
+        self.seen[index] = Some(self.bits);
+        if synced {
+            debug!("RdsDecode: synced");
+            self.synced = true;
+            self.seen = [None; 4];
+            self.block = index;
+            self.block_bits = 0;
+            self.bad_blocks = 0;
+            self.group = [None; 4];
+            self.got_block(Some((self.reg >> 10) as u16));
+        }
+    }
+
+    fn got_block(&mut self, info: Option<u16>) {
+        self.group[self.block] = info;
+        if info.is_some() {
+            self.bad_blocks = 0;
+        } else {
+            self.bad_blocks += 1;
+            if self.bad_blocks >= MAX_BAD_BLOCKS {
+                debug!("RdsDecode: lost sync");
+                self.synced = false;
+                return;
+            }
+        }
+        if self.block == 3 {
+            if let [Some(a), Some(b), Some(c), Some(d)] = self.group {
+                let group = Group::parse([a, b, c, d]);
+                let tags = self.station.update(&group);
+                self.dst.push(group, tags);
+            }
+            self.group = [None; 4];
+        }
+        self.block = (self.block + 1) % 4;
+    }
+}
+
+impl Block for RdsDecode {
+    fn work(&mut self) -> Result<BlockRet<'_>> {
+        let (i, _) = self.src.read_buf()?;
+        if i.is_empty() {
+            return Ok(BlockRet::WaitForStream(&self.src, 1));
+        }
+        // Each bit outputs at most one group.
+        if self.dst.remaining() == 0 {
+            return Ok(BlockRet::WaitForStream(&self.dst, 1));
+        }
+        let mut n = 0;
+        for &bit in i.slice() {
+            self.bit(bit);
+            n += 1;
+            if self.dst.remaining() == 0 {
+                break;
+            }
+        }
+        i.consume(n);
+        Ok(BlockRet::Again)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OFFSETS: [u16; 4] = [OFFSET_A, OFFSET_B, OFFSET_C, OFFSET_D];
+
+    fn group_bits(blocks: [u16; 4]) -> Vec<u8> {
+        blocks
+            .iter()
+            .zip(OFFSETS)
+            .flat_map(|(&info, offset)| {
+                let b = encode_block(info, offset);
+                (0..26).rev().map(move |i| ((b >> i) & 1) as u8)
+            })
+            .collect()
+    }
+
+    // Groups sending the PS name, and a RadioText.
+    fn station_groups(pi: u16, ps: &str, rt: &str) -> Vec<[u16; 4]> {
+        let ps = ps.as_bytes();
+        let mut ret: Vec<_> = (0..4u16)
+            .map(|seg| {
+                let i = usize::from(seg) * 2;
+                let d = u16::from_be_bytes([ps[i], ps[i + 1]]);
+                // PTY 10, music.
+                [pi, (10 << 5) | 0x8 | seg, 0xE0CD, d]
+            })
+            .collect();
+        let mut rt = rt.as_bytes().to_vec();
+        rt.push(0x0D);
+        while !rt.len().is_multiple_of(4) {
+            rt.push(b' ');
+        }
+        for (seg, c) in rt.chunks(4).enumerate() {
+            ret.push([
+                pi,
+                (2 << 12) | (10 << 5) | seg as u16,
+                u16::from_be_bytes([c[0], c[1]]),
+                u16::from_be_bytes([c[2], c[3]]),
+            ]);
+        }
+        ret
+    }
+
+    fn decode(bits: &[u8]) -> Result<Vec<(Group, Vec<Tag>)>> {
+        let (mut src, prev) = crate::blocks::VectorSource::new(bits.to_vec());
+        src.work()?;
+        let (mut b, out) = RdsDecode::new(prev);
+        b.work()?;
+        let mut ret = Vec::new();
+        while let Some(x) = out.pop() {
+            ret.push(x);
+        }
+        Ok(ret)
+    }
+
+    fn tag_string(tags: &[Tag], key: &str) -> Option<String> {
+        tags.iter().find(|t| t.key() == key).map(|t| match t.val() {
+            TagValue::String(s) => s.clone(),
+            _ => panic!("bad tag {t:?}"),
+        })
+    }
+
+    #[test]
+    fn checkword() {
+        // Syndrome of a correct block is its offset word.
+        for offset in [OFFSET_A, OFFSET_B, OFFSET_C, OFFSET_CP, OFFSET_D] {
+            for info in [0, 1, 0x1234, 0xFFFF] {
+                assert_eq!(syndrome(encode_block(info, offset)), offset);
+            }
+        }
+        // Zero information, so the checkword is just the offset.
+        assert_eq!(encode_block(0, OFFSET_A), 0x0FC);
+    }
+
+    #[test]
+    fn parse() {
+        let g = Group::parse([0x1234, (10 << 5) | 0x8 | 0x10 | 2, 0, 0x4142]);
+        assert_eq!(g.pi, 0x1234);
+        assert_eq!(g.group_type, 0);
+        assert!(!g.version_b);
+        assert_eq!(g.pty, 10);
+        assert_eq!(
+            g.data,
+            GroupData::ProgramService {
+                segment: 2,
+                ta: true,
+                music: true,
+                chars: *b"AB",
+            }
+        );
+
+        // MJD 45218 is 1982-09-06. 13:45 UTC, local time +1:00.
+        let (mjd, hour): (u32, u16) = (45218, 13);
+        let b = (4 << 12) | ((mjd >> 15) as u16);
+        let c = (((mjd & 0x7FFF) as u16) << 1) | (hour >> 4);
+        let d = ((hour & 0xF) << 12) | (45 << 6) | 2;
+        let g = Group::parse([0x1234, b, c, d]);
+        let GroupData::ClockTime(ct) = g.data else {
+            panic!("not clock time: {g:?}");
+        };
+        assert_eq!(
+            ct,
+            ClockTime {
+                year: 1982,
+                month: 9,
+                day: 6,
+                hour: 13,
+                minute: 45,
+                offset_minutes: 60,
+            }
+        );
+        assert_eq!(ct.to_string(), "1982-09-06 13:45 UTC, local offset +01:00");
+    }
+
+    #[test]
+    fn station() -> Result<()> {
+        let groups = station_groups(0xC201, "RUSTY FM", "Now playing: rustradio");
+        // Start mid block, to test sync.
+        let mut bits = vec![1, 0, 1];
+        for _ in 0..2 {
+            for g in &groups {
+                bits.extend(group_bits(*g));
+            }
+        }
+        let got = decode(&bits)?;
+        // The first group is lost while syncing.
+        assert_eq!(got.len(), 2 * groups.len() - 1);
+        for (g, _) in &got {
+            assert_eq!(g.pi, 0xC201);
+            assert_eq!(g.pty, 10);
+        }
+        let ps: Vec<_> = got
+            .iter()
+            .filter_map(|(_, t)| tag_string(t, PS_TAG))
+            .collect();
+        assert_eq!(ps, vec!["RUSTY FM"]);
+        let rt: Vec<_> = got
+            .iter()
+            .filter_map(|(_, t)| tag_string(t, RADIOTEXT_TAG))
+            .collect();
+        assert_eq!(rt, vec!["Now playing: rustradio"]);
+        Ok(())
+    }
+
+    #[test]
+    fn radiotext_versions() {
+        let mut station = Station::default();
+        let mut texts = Vec::new();
+        let mut update = |blocks| {
+            let tags = station.update(&Group::parse(blocks));
+            texts.extend(tag_string(&tags, RADIOTEXT_TAG));
+        };
+        // Full length 2A text, ending in a carriage return.
+        let mut rt = [b'x'; 64];
+        rt[63] = 0x0D;
+        for (seg, c) in rt.chunks(4).enumerate() {
+            update([
+                0xC201,
+                (2 << 12) | seg as u16,
+                u16::from_be_bytes([c[0], c[1]]),
+                u16::from_be_bytes([c[2], c[3]]),
+            ]);
+        }
+        // Then 2B, with the same A/B flag.
+        for (seg, c) in b"Hi\r ".chunks(2).enumerate() {
+            update([
+                0xC201,
+                (2 << 12) | (1 << 11) | seg as u16,
+                0xC201,
+                u16::from_be_bytes([c[0], c[1]]),
+            ]);
+        }
+        assert_eq!(texts, vec!["x".repeat(63), "Hi".to_string()]);
+    }
+
+    #[test]
+    fn error_correction() -> Result<()> {
+        let groups = station_groups(0xC201, "RUSTY FM", "Hello");
+        let mut bits = Vec::new();
+        for _ in 0..2 {
+            for g in &groups {
+                bits.extend(group_bits(*g));
+            }
+        }
+        // Burst errors in the second round.
+        let second = bits.len() / 2;
+        for (start, len) in [(3, 5), (26 * 5 + 10, 3), (26 * 9 + 20, 1)] {
+            for b in &mut bits[second + start..second + start + len] {
+                *b ^= 1;
+            }
+        }
+        let got = decode(&bits)?;
+        assert_eq!(got.len(), 2 * groups.len() - 1);
+        assert_eq!(got[groups.len() - 1].0.blocks, groups[0]);
+        Ok(())
+    }
+
+    #[test]
+    fn version_b() -> Result<()> {
+        // Version B groups have the PI in block C', with its own offset.
+        let group = [0xC201, (1 << 11) | 3, 0xC201, 0x4142];
+        let mut bits = Vec::new();
+        for _ in 0..3 {
+            for (&info, offset) in group.iter().zip([OFFSET_A, OFFSET_B, OFFSET_CP, OFFSET_D]) {
+                let b = encode_block(info, offset);
+                bits.extend((0..26).rev().map(|i| ((b >> i) & 1) as u8));
+            }
+        }
+        let got = decode(&bits)?;
+        assert_eq!(got.len(), 2);
+        let g = &got[1].0;
+        assert!(g.version_b);
+        assert_eq!(g.blocks, group);
+        assert_eq!(
+            g.data,
+            GroupData::ProgramService {
+                segment: 3,
+                ta: false,
+                music: false,
+                chars: *b"AB",
+            }
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn demod() -> Result<()> {
+        use crate::graph::{Graph, GraphRunner};
+        let groups = station_groups(0xC201, "RUSTY FM", "Hello");
+        let mut bits = Vec::new();
+        for _ in 0..3 {
+            for g in &groups {
+                bits.extend(group_bits(*g));
+            }
+        }
+        // Differential encoding, and biphase symbols.
+        let mut last = 0u8;
+        let chips: Vec<Float> = bits
+            .iter()
+            .flat_map(|&b| {
+                last ^= b;
+                let v: Float = if last == 1 { 1.0 } else { -1.0 };
+                [v, -v]
+            })
+            .collect();
+        // Multiplex signal, with mono audio and a pilot.
+        let sr = 240_000.0;
+        let mpx: Vec<Float> = (0..((chips.len() as f64 * sr / 2375.0) as usize))
+            .map(|n| {
+                let t = n as f64 / sr;
+                let chip = chips[((t * 2375.0) as usize).min(chips.len() - 1)];
+                let p = TAU * 19_000.0 * t;
+                0.05 * chip * (3.0 * p + 0.3).sin() as Float
+                    + 0.5 * (TAU * 1000.0 * t).sin() as Float
+                    + 0.1 * p.sin() as Float
+            })
+            .collect();
+        let (src, prev) = crate::blocks::VectorSource::new(mpx);
+        let mut g = Graph::new();
+        g.add(Box::new(src));
+        let prev = crate::blockchain![g, prev, RdsDemod::new(prev, sr), RdsDecode::new(prev)];
+        g.run()?;
+        let mut got = Vec::new();
+        while let Some((group, tags)) = prev.pop() {
+            got.push((group, tags));
+        }
+        // Allow for loss while the loops lock.
+        assert!(got.len() >= groups.len() + 2, "only got {}", got.len());
+        let ps: Vec<_> = got
+            .iter()
+            .filter_map(|(_, t)| tag_string(t, PS_TAG))
+            .collect();
+        assert_eq!(ps, vec!["RUSTY FM"]);
+        let rt: Vec<_> = got
+            .iter()
+            .filter_map(|(_, t)| tag_string(t, RADIOTEXT_TAG))
+            .collect();
+        assert_eq!(rt, vec!["Hello"]);
+        Ok(())
+    }
+}
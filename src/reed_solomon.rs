@@ -0,0 +1,373 @@
+/*! Reed-Solomon codes with 8 bit symbols.
+
+The codes are defined by the field generator polynomial, the first
+consecutive root of the code generator polynomial (`fcr`), the primitive
+element used to generate the roots (`prim`), and the number of parity
+symbols (`nroots`). A codeword is at most 255 symbols. Shorter, "shortened",
+codewords are treated as if padded with leading zeros.
+
+The algorithm is the same as Phil Karn's `libfec`: Berlekamp-Massey, Chien
+search, and Forney.
+
+References:
+* <https://github.com/quiet/libfec>
+* <https://berthub.eu/articles/posts/reed-solomon-for-programmers/>
+*/
+use crate::{Error, Result};
+
+// Symbols per codeword, and log of zero.
+const NN: usize = 255;
+const A0: usize = NN;
+
+/// Reed-Solomon encoder and decoder.
+#[derive(Debug, Clone)]
+pub struct ReedSolomon {
+    alpha_to: [u8; 256],
+    index_of: [usize; 256],
+    // Generator polynomial, in index form.
+    genpoly: Vec<usize>,
+    fcr: usize,
+    prim: usize,
+    iprim: usize,
+    nroots: usize,
+}
+
+fn modnn(x: usize) -> usize {
+    x % NN
+}
+
+impl ReedSolomon {
+    /// Create a new Reed-Solomon code.
+    ///
+    /// # Errors
+    ///
+    /// Errors if `gfpoly` is not a primitive polynomial of degree 8, or if
+    /// the other parameters are out of range.
+    pub fn new(gfpoly: u32, fcr: usize, prim: usize, nroots: usize) -> Result<Self> {
+        if fcr >= NN || prim == 0 || prim >= NN || nroots == 0 || nroots >= NN {
+            return Err(Error::msg(format!(
+                "invalid Reed-Solomon parameters fcr={fcr} prim={prim} nroots={nroots}"
+            )));
+        }
+        let mut alpha_to = [0u8; 256];
+        let mut index_of = [A0; 256];
+        let mut sr = 1u32;
+        for (i, a) in alpha_to.iter_mut().take(NN).enumerate() {
+            index_of[sr as usize] = i;
+            *a = sr as u8;
+            sr <<= 1;
+            if sr & 0x100 != 0 {
+                sr ^= gfpoly;
+            }
+            sr &= 0xFF;
+        }
+        if sr != 1 || gfpoly & !0x1FF != 0 {
+            return Err(Error::msg(format!(
+                "Reed-Solomon field polynomial 0x{gfpoly:x} is not primitive"
+            )));
+        }
+        // Multiplicative inverse of prim, for finding the error locations.
+        let mut iprim = 1;
+        while iprim % prim != 0 {
+            iprim += NN;
+        }
+        let iprim = iprim / prim;
+
+        let mut genpoly = vec![0u8; nroots + 1];
+        genpoly[0] = 1;
+        let mut root = fcr * prim;
+        for i in 0..nroots {
+            genpoly[i + 1] = 1;
+            for j in (1..=i).rev() {
+                genpoly[j] = if genpoly[j] == 0 {
+                    genpoly[j - 1]
+                } else {
+                    genpoly[j - 1] ^ alpha_to[modnn(index_of[genpoly[j] as usize] + root)]
+                };
+            }
+            genpoly[0] = alpha_to[modnn(index_of[genpoly[0] as usize] + root)];
+            root += prim;
+        }
+        Ok(Self {
+            genpoly: genpoly.iter().map(|&g| index_of[g as usize]).collect(),
+            alpha_to,
+            index_of,
+            fcr,
+            prim,
+            iprim,
+            nroots,
+        })
+    }
+
+    /// Create the CCSDS RS(255,223) code, in conventional representation.
+    ///
+    /// CCSDS transmits symbols in the dual basis representation, which
+    /// [`TmFrameDecode`](crate::blocks::TmFrameDecode) converts.
+    #[must_use]
+    pub fn ccsds() -> Self {
+        Self::new(0x187, 112, 11, 32).expect("can't happen: CCSDS parameters are valid")
+    }
+
+    /// Number of parity symbols.
+    #[must_use]
+    pub fn nroots(&self) -> usize {
+        self.nroots
+    }
+
+    fn check_len(&self, len: usize) -> Result<()> {
+        if len > NN || len <= self.nroots {
+            return Err(Error::msg(format!(
+                "Reed-Solomon codeword length {len} out of range {}..={NN}",
+                self.nroots + 1
+            )));
+        }
+        Ok(())
+    }
+
+    /// Return the parity symbols for the data.
+    ///
+    /// # Errors
+    ///
+    /// Errors if the data is too long for the code.
+    pub fn encode(&self, data: &[u8]) -> Result<Vec<u8>> {
+        self.check_len(data.len() + self.nroots)?;
+        let nroots = self.nroots;
+        let mut parity = vec![0u8; nroots];
+        for &d in data {
+            let feedback = self.index_of[(d ^ parity[0]) as usize];
+            if feedback != A0 {
+                for (j, p) in parity.iter_mut().enumerate().skip(1) {
+                    *p ^= self.alpha_to[modnn(feedback + self.genpoly[nroots - j])];
+                }
+            }
+            parity.rotate_left(1);
+            parity[nroots - 1] = if feedback == A0 {
+                0
+            } else {
+                self.alpha_to[modnn(feedback + self.genpoly[0])]
+            };
+        }
+        Ok(parity)
+    }
+
+    /// Correct a codeword, data followed by parity, in place.
+    ///
+    /// Returns the number of corrected symbols, or `None` if the codeword
+    /// is uncorrectable. Detection of uncorrectable codewords is not
+    /// perfect, so with too many errors a codeword can also be
+    /// "corrected" into the wrong one.
+    ///
+    /// # Errors
+    ///
+    /// Errors if the codeword length is invalid for the code.
+    pub fn decode(&self, block: &mut [u8]) -> Result<Option<usize>> {
+        self.check_len(block.len())?;
+        let nroots = self.nroots;
+        let pad = NN - block.len();
+        let alpha_to = |i: usize| self.alpha_to[modnn(i)];
+        let index_of = |x: u8| self.index_of[x as usize];
+
+        // Syndromes, in index form.
+        let mut s = vec![block[0]; nroots];
+        for &b in &block[1..] {
+            for (i, si) in s.iter_mut().enumerate() {
+                *si = if *si == 0 {
+                    b
+                } else {
+                    b ^ alpha_to(index_of(*si) + (self.fcr + i) * self.prim)
+                };
+            }
+        }
+        if s.iter().all(|&x| x == 0) {
+            return Ok(Some(0));
+        }
+        let s: Vec<usize> = s.into_iter().map(index_of).collect();
+
+        // Berlekamp-Massey, finding the error locator polynomial lambda.
+        let mut lambda = vec![0u8; nroots + 1];
+        lambda[0] = 1;
+        let mut b: Vec<usize> = lambda.iter().map(|&l| index_of(l)).collect();
+        let mut el = 0;
+        for r in 1..=nroots {
+            let mut discr = 0u8;
+            for i in 0..r {
+                if lambda[i] != 0 && s[r - i - 1] != A0 {
+                    discr ^= alpha_to(index_of(lambda[i]) + s[r - i - 1]);
+                }
+            }
+            let discr = index_of(discr);
+            if discr == A0 {
+                b.rotate_right(1);
+                b[0] = A0;
+                continue;
+            }
+            let mut t = vec![0u8; nroots + 1];
+            t[0] = lambda[0];
+            for i in 0..nroots {
+                t[i + 1] = if b[i] == A0 {
+                    lambda[i + 1]
+                } else {
+                    lambda[i + 1] ^ alpha_to(discr + b[i])
+                };
+            }
+            if 2 * el < r {
+                el = r - el;
+                for (bi, &l) in b.iter_mut().zip(&lambda) {
+                    *bi = if l == 0 {
+                        A0
+                    } else {
+                        modnn(index_of(l) + NN - discr)
+                    };
+                }
+            } else {
+                b.rotate_right(1);
+                b[0] = A0;
+            }
+            lambda = t;
+        }
+        let lambda: Vec<usize> = lambda.into_iter().map(index_of).collect();
+        let deg_lambda = lambda.iter().rposition(|&l| l != A0).unwrap_or(0);
+
+        // Chien search for the roots of lambda.
+        let mut reg = lambda.clone();
+        let mut roots = Vec::new();
+        let mut locs = Vec::new();
+        let mut k = self.iprim - 1;
+        for i in 1..=NN {
+            let mut q = 1u8;
+            for j in (1..=deg_lambda).rev() {
+                if reg[j] != A0 {
+                    reg[j] = modnn(reg[j] + j);
+                    q ^= self.alpha_to[reg[j]];
+                }
+            }
+            if q == 0 {
+                roots.push(i);
+                locs.push(k);
+                if roots.len() == deg_lambda {
+                    break;
+                }
+            }
+            k = modnn(k + self.iprim);
+        }
+        if roots.len() != deg_lambda {
+            return Ok(None);
+        }
+
+        // Error evaluator polynomial omega = s*lambda mod x^nroots.
+        let deg_omega = deg_lambda - 1;
+        let omega: Vec<usize> = (0..=deg_omega)
+            .map(|i| {
+                let mut tmp = 0u8;
+                for j in 0..=i {
+                    if s[i - j] != A0 && lambda[j] != A0 {
+                        tmp ^= alpha_to(s[i - j] + lambda[j]);
+                    }
+                }
+                index_of(tmp)
+            })
+            .collect();
+
+        // Forney, for the error values.
+        let mut corrections = Vec::new();
+        for (&root, &loc) in roots.iter().zip(&locs) {
+            let mut num1 = 0u8;
+            for (i, &o) in omega.iter().enumerate() {
+                if o != A0 {
+                    num1 ^= alpha_to(o + i * root);
+                }
+            }
+            let num2 = alpha_to(root * (self.fcr + NN - 1));
+            // The formal derivative of lambda is its odd terms.
+            let mut den = 0u8;
+            for i in (0..=deg_lambda.min(nroots - 1) & !1).step_by(2) {
+                if lambda[i + 1] != A0 {
+                    den ^= alpha_to(lambda[i + 1] + i * root);
+                }
+            }
+            if den == 0 || loc < pad {
+                return Ok(None);
+            }
+            if num1 != 0 {
+                corrections.push((
+                    loc - pad,
+                    alpha_to(index_of(num1) + index_of(num2) + NN - index_of(den)),
+                ));
+            }
+        }
+        for &(pos, val) in &corrections {
+            block[pos] ^= val;
+        }
+        Ok(Some(corrections.len()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{Rng, SeedableRng};
+
+    fn roundtrip(rs: &ReedSolomon, len: usize, errors: usize, seed: u64) -> Result<()> {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+        let data: Vec<u8> = (0..len - rs.nroots()).map(|_| rng.random()).collect();
+        let mut block = data.clone();
+        block.extend(rs.encode(&data)?);
+        let good = block.clone();
+        assert_eq!(rs.decode(&mut block)?, Some(0));
+
+        let mut positions: Vec<usize> = (0..len).collect();
+        for i in 0..errors {
+            let j = rng.random_range(i..len);
+            positions.swap(i, j);
+            block[positions[i]] ^= rng.random_range(1..=255);
+        }
+        assert_eq!(rs.decode(&mut block)?, Some(errors), "len={len}");
+        assert_eq!(block, good);
+        Ok(())
+    }
+
+    #[test]
+    fn ccsds() -> Result<()> {
+        let rs = ReedSolomon::ccsds();
+        for errors in 0..=16 {
+            roundtrip(&rs, 255, errors, errors as u64)?;
+        }
+        // Shortened.
+        roundtrip(&rs, 100, 16, 1)?;
+        roundtrip(&rs, 33, 5, 2)?;
+        Ok(())
+    }
+
+    #[test]
+    fn small() -> Result<()> {
+        // Two roots, first root zero, like IL2P.
+        let rs = ReedSolomon::new(0x11d, 0, 1, 2)?;
+        for len in [3, 10, 255] {
+            roundtrip(&rs, len, 1, len as u64)?;
+        }
+        let rs = ReedSolomon::new(0x11d, 0, 1, 16)?;
+        roundtrip(&rs, 200, 8, 3)?;
+        Ok(())
+    }
+
+    #[test]
+    fn uncorrectable() -> Result<()> {
+        let rs = ReedSolomon::ccsds();
+        let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+        let mut block = vec![0u8; 255];
+        for b in block.iter_mut().take(40) {
+            *b = rng.random_range(1..=255);
+        }
+        assert_eq!(rs.decode(&mut block)?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(ReedSolomon::new(0x100, 0, 1, 2).is_err());
+        assert!(ReedSolomon::new(0x11d, 0, 1, 0).is_err());
+        let rs = ReedSolomon::ccsds();
+        assert!(rs.encode(&[0; 224]).is_err());
+        assert!(rs.decode(&mut [0; 32]).is_err());
+    }
+}
@@ -0,0 +1,499 @@
+/*! SSB modulation and demodulation.
+
+The demodulator input should be complex baseband with the suppressed carrier
+at 0Hz, at a sample rate a few times the audio bandwidth, e.g. 48kHz. The
+output is audio at the same sample rate.
+
+The modulator does the opposite, turning audio into complex baseband at the
+audio sample rate. Resample it to the SDR sample rate.
+
+## Example
+
+```
+use rustradio::blocks::SsbDemod;
+use rustradio::ssb::Sideband;
+let (_, prev) = rustradio::stream::new_stream();
+let (demod, prev) = SsbDemod::builder(prev, 48000.0, Sideband::Lsb)
+    .bandwidth(2400.0)
+    .build();
+```
+*/
+use std::collections::VecDeque;
+
+use crate::fir::Fir;
+use crate::stream::{ReadStream, WriteStream};
+use crate::window::WindowType;
+use crate::{Complex, Float};
+
+const TAU: f64 = 2.0 * std::f64::consts::PI;
+
+/// Upper or lower sideband.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sideband {
+    /// Upper sideband. Audio is above the carrier.
+    Usb,
+    /// Lower sideband. Audio is below the carrier.
+    Lsb,
+}
+
+/// SSB demodulation method.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SsbMethod {
+    /// Phasing method, using a Hilbert transform.
+    Phasing,
+
+    /// Weaver method, shifting the sideband to be centered around 0Hz,
+    /// filtering, and shifting it back.
+    Weaver,
+}
+
+/// FIR filter with its own history.
+pub(crate) struct FirState<T> {
+    fir: Fir<T>,
+    history: VecDeque<T>,
+}
+
+impl<T> FirState<T>
+where
+    T: crate::Sample + std::ops::Mul<T, Output = T> + std::ops::Add<T, Output = T>,
+{
+    pub(crate) fn new(taps: &[T]) -> Self {
+        Self {
+            fir: Fir::new(taps),
+            history: std::iter::repeat_n(T::default(), taps.len()).collect(),
+        }
+    }
+
+    pub(crate) fn filter(&mut self, x: T) -> T {
+        self.history.pop_front();
+        self.history.push_back(x);
+        self.fir.filter(self.history.make_contiguous())
+    }
+}
+
+enum Method {
+    Phasing {
+        hilbert: Vec<Float>,
+        history: VecDeque<Complex>,
+    },
+    Weaver {
+        filter: FirState<Complex>,
+        // Oscillator phase and step.
+        phase: f64,
+        step: f64,
+    },
+}
+
+/// Builder for [`SsbDemod`].
+pub struct SsbDemodBuilder {
+    src: ReadStream<Complex>,
+    sample_rate: f64,
+    sideband: Sideband,
+    bandwidth: f64,
+    method: SsbMethod,
+}
+
+impl SsbDemodBuilder {
+    /// Set audio bandwidth, in Hz. Default 2700.
+    #[must_use]
+    pub fn bandwidth(mut self, hz: f64) -> Self {
+        self.bandwidth = hz;
+        self
+    }
+
+    /// Set demodulation method. Default is phasing.
+    #[must_use]
+    pub fn method(mut self, method: SsbMethod) -> Self {
+        self.method = method;
+        self
+    }
+
+    /// Build the `SsbDemod`.
+    #[must_use]
+    pub fn build(self) -> (SsbDemod, ReadStream<Float>) {
+        let sr = self.sample_rate as Float;
+        let bw = self.bandwidth as Float;
+        let (method, audio) = match self.method {
+            SsbMethod::Phasing => {
+                // Long enough to work down to about 100Hz.
+                let ntaps = (self.sample_rate / 75.0) as usize | 1;
+                let ntaps = ntaps.max(31);
+                let hilbert = crate::fir::hilbert(&WindowType::Hamming.make_window(ntaps));
+                let audio = crate::fir::low_pass(sr, bw, bw / 10.0, &WindowType::Hamming);
+                (
+                    Method::Phasing {
+                        hilbert,
+                        history: std::iter::repeat_n(Complex::default(), ntaps).collect(),
+                    },
+                    Some(FirState::new(&audio)),
+                )
+            }
+            SsbMethod::Weaver => {
+                let taps =
+                    crate::fir::low_pass_complex(sr, bw / 2.0, bw / 20.0, &WindowType::Hamming);
+                let center = match self.sideband {
+                    Sideband::Usb => self.bandwidth / 2.0,
+                    Sideband::Lsb => -self.bandwidth / 2.0,
+                };
+                (
+                    Method::Weaver {
+                        filter: FirState::new(&taps),
+                        phase: 0.0,
+                        step: TAU * center / self.sample_rate,
+                    },
+                    None,
+                )
+            }
+        };
+        let (dst, dr) = crate::stream::new_stream();
+        (
+            SsbDemod {
+                src: self.src,
+                dst,
+                sideband: self.sideband,
+                method,
+                audio,
+            },
+            dr,
+        )
+    }
+}
+
+/// SSB demodulator.
+///
+/// Outputs the upper or lower sideband as audio, limited to the set
+/// bandwidth. The other sideband is suppressed.
+#[derive(rustradio_macros::Block)]
+#[rustradio(crate, sync)]
+pub struct SsbDemod {
+    #[rustradio(in)]
+    src: ReadStream<Complex>,
+    #[rustradio(out)]
+    dst: WriteStream<Float>,
+    sideband: Sideband,
+    method: Method,
+    audio: Option<FirState<Float>>,
+}
+
+impl SsbDemod {
+    /// Create a builder.
+    #[must_use]
+    pub fn builder(
+        src: ReadStream<Complex>,
+        sample_rate: f64,
+        sideband: Sideband,
+    ) -> SsbDemodBuilder {
+        SsbDemodBuilder {
+            src,
+            sample_rate,
+            sideband,
+            bandwidth: 2700.0,
+            method: SsbMethod::Phasing,
+        }
+    }
+
+    /// Create new SSB demodulator, with default settings.
+    #[must_use]
+    pub fn new(
+        src: ReadStream<Complex>,
+        sample_rate: f64,
+        sideband: Sideband,
+    ) -> (Self, ReadStream<Float>) {
+        Self::builder(src, sample_rate, sideband).build()
+    }
+
+    fn process_sync(&mut self, s: Complex) -> Float {
+        let v = match &mut self.method {
+            Method::Phasing { hilbert, history } => {
+                history.pop_front();
+                history.push_back(s);
+                // I delayed to match the Hilbert filter, and Hilbert of Q.
+                // Each is half of the wanted sideband.
+                let i = history[history.len() / 2].re;
+                let hq: Float = history
+                    .iter()
+                    .rev()
+                    .zip(hilbert.iter())
+                    .map(|(h, t)| h.im * t)
+                    .sum();
+                match self.sideband {
+                    Sideband::Usb => (i - hq) / 2.0,
+                    Sideband::Lsb => (i + hq) / 2.0,
+                }
+            }
+            Method::Weaver {
+                filter,
+                phase,
+                step,
+            } => {
+                let (sin, cos) = phase.sin_cos();
+                let osc = Complex::new(cos as Float, sin as Float);
+                let v = filter.filter(s * osc.conj()) * osc;
+                *phase = (*phase + *step).rem_euclid(TAU);
+                v.re
+            }
+        };
+        match &mut self.audio {
+            Some(f) => f.filter(v),
+            None => v,
+        }
+    }
+}
+
+/// Builder for [`SsbModulator`].
+pub struct SsbModulatorBuilder {
+    src: ReadStream<Float>,
+    sample_rate: f64,
+    sideband: Sideband,
+    bandwidth: f64,
+    method: SsbMethod,
+}
+
+impl SsbModulatorBuilder {
+    /// Set audio bandwidth, in Hz. Default 2700.
+    #[must_use]
+    pub fn bandwidth(mut self, hz: f64) -> Self {
+        self.bandwidth = hz;
+        self
+    }
+
+    /// Set modulation method. Default is phasing.
+    #[must_use]
+    pub fn method(mut self, method: SsbMethod) -> Self {
+        self.method = method;
+        self
+    }
+
+    /// Build the `SsbModulator`.
+    #[must_use]
+    pub fn build(self) -> (SsbModulator, ReadStream<Complex>) {
+        let sr = self.sample_rate as Float;
+        let bw = self.bandwidth as Float;
+        let method = match self.method {
+            SsbMethod::Phasing => {
+                // Same length as in the demodulator.
+                let ntaps = (self.sample_rate / 75.0) as usize | 1;
+                let ntaps = ntaps.max(31);
+                let hilbert = crate::fir::hilbert(&WindowType::Hamming.make_window(ntaps));
+                let audio = crate::fir::low_pass(sr, bw, bw / 10.0, &WindowType::Hamming);
+                ModMethod::Phasing {
+                    audio: FirState::new(&audio),
+                    hilbert: FirState::new(&hilbert),
+                    delay: std::iter::repeat_n(0.0, ntaps).collect(),
+                }
+            }
+            SsbMethod::Weaver => {
+                let taps =
+                    crate::fir::low_pass_complex(sr, bw / 2.0, bw / 20.0, &WindowType::Hamming);
+                let center = match self.sideband {
+                    Sideband::Usb => self.bandwidth / 2.0,
+                    Sideband::Lsb => -self.bandwidth / 2.0,
+                };
+                ModMethod::Weaver {
+                    filter: FirState::new(&taps),
+                    phase: 0.0,
+                    step: TAU * center / self.sample_rate,
+                }
+            }
+        };
+        let (dst, dr) = crate::stream::new_stream();
+        (
+            SsbModulator {
+                src: self.src,
+                dst,
+                sideband: self.sideband,
+                method,
+            },
+            dr,
+        )
+    }
+}
+
+enum ModMethod {
+    Phasing {
+        audio: FirState<Float>,
+        hilbert: FirState<Float>,
+        delay: VecDeque<Float>,
+    },
+    Weaver {
+        filter: FirState<Complex>,
+        phase: f64,
+        step: f64,
+    },
+}
+
+/// SSB modulator.
+///
+/// Takes audio, and outputs complex baseband with the suppressed carrier at
+/// 0Hz, and the audio in the upper or lower sideband. A tone with amplitude
+/// 1.0 becomes a carrier with amplitude 1.0.
+#[derive(rustradio_macros::Block)]
+#[rustradio(crate, sync)]
+pub struct SsbModulator {
+    #[rustradio(in)]
+    src: ReadStream<Float>,
+    #[rustradio(out)]
+    dst: WriteStream<Complex>,
+    sideband: Sideband,
+    method: ModMethod,
+}
+
+impl SsbModulator {
+    /// Create a builder.
+    #[must_use]
+    pub fn builder(
+        src: ReadStream<Float>,
+        sample_rate: f64,
+        sideband: Sideband,
+    ) -> SsbModulatorBuilder {
+        SsbModulatorBuilder {
+            src,
+            sample_rate,
+            sideband,
+            bandwidth: 2700.0,
+            method: SsbMethod::Phasing,
+        }
+    }
+
+    /// Create new SSB modulator, with default settings.
+    #[must_use]
+    pub fn new(
+        src: ReadStream<Float>,
+        sample_rate: f64,
+        sideband: Sideband,
+    ) -> (Self, ReadStream<Complex>) {
+        Self::builder(src, sample_rate, sideband).build()
+    }
+
+    fn process_sync(&mut self, s: Float) -> Complex {
+        match &mut self.method {
+            ModMethod::Phasing {
+                audio,
+                hilbert,
+                delay,
+            } => {
+                let s = audio.filter(s);
+                delay.pop_front();
+                delay.push_back(s);
+                // Delayed audio, to match the Hilbert filter.
+                let i = delay[delay.len() / 2];
+                let q = hilbert.filter(s);
+                match self.sideband {
+                    Sideband::Usb => Complex::new(i, q),
+                    Sideband::Lsb => Complex::new(i, -q),
+                }
+            }
+            ModMethod::Weaver {
+                filter,
+                phase,
+                step,
+            } => {
+                let (sin, cos) = phase.sin_cos();
+                let osc = Complex::new(cos as Float, sin as Float);
+                // The filter keeps half of the power, from one sideband.
+                let v = filter.filter(osc.conj() * s) * osc * 2.0;
+                *phase = (*phase + *step).rem_euclid(TAU);
+                v
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Result;
+    use crate::block::Block;
+    use crate::blocks::VectorSource;
+
+    fn tone(sample_rate: f64, freq: f64, len: usize) -> Vec<Complex> {
+        (0..len)
+            .map(|n| {
+                let p = TAU * freq * n as f64 / sample_rate;
+                Complex::new(p.cos() as Float, p.sin() as Float)
+            })
+            .collect()
+    }
+
+    fn rms(out: &[Float]) -> f64 {
+        (out.iter().map(|&x| f64::from(x * x)).sum::<f64>() / out.len() as f64).sqrt()
+    }
+
+    fn demod(method: SsbMethod, sideband: Sideband, freq: f64) -> Result<f64> {
+        let sr = 24000.0;
+        let (mut src, prev) = VectorSource::new(tone(sr, freq, 24000));
+        src.work()?;
+        let (mut b, out) = SsbDemod::builder(prev, sr, sideband).method(method).build();
+        b.work()?;
+        let (o, _) = out.read_buf()?;
+        Ok(rms(&o.slice()[12000..]) * 2.0f64.sqrt())
+    }
+
+    #[test]
+    fn sidebands() -> Result<()> {
+        for method in [SsbMethod::Phasing, SsbMethod::Weaver] {
+            for (sideband, sign) in [(Sideband::Usb, 1.0), (Sideband::Lsb, -1.0)] {
+                // Wanted sideband.
+                for f in [300.0, 1000.0, 2400.0] {
+                    let a = demod(method, sideband, sign * f)?;
+                    assert!((a - 1.0).abs() < 0.05, "{method:?} {sideband:?} {f}: {a}");
+                }
+                // Other sideband.
+                for f in [300.0, 1000.0, 2400.0] {
+                    let a = demod(method, sideband, -sign * f)?;
+                    assert!(a < 0.03, "{method:?} {sideband:?} {f}: {a}");
+                }
+                // Outside bandwidth.
+                let a = demod(method, sideband, sign * 4000.0)?;
+                assert!(a < 0.03, "{method:?} {sideband:?}: {a}");
+            }
+        }
+        Ok(())
+    }
+
+    fn modulate(method: SsbMethod, sideband: Sideband, freq: f64) -> Result<Vec<Complex>> {
+        let sr = 24000.0;
+        let audio: Vec<Float> = (0..24000)
+            .map(|n| (TAU * freq * f64::from(n) / sr).cos() as Float)
+            .collect();
+        let (mut src, prev) = VectorSource::new(audio);
+        src.work()?;
+        let (mut b, out) = SsbModulator::builder(prev, sr, sideband)
+            .method(method)
+            .build();
+        b.work()?;
+        let (o, _) = out.read_buf()?;
+        Ok(o.slice()[12000..].to_vec())
+    }
+
+    // Amplitude of the given frequency.
+    fn amplitude(data: &[Complex], sample_rate: f64, freq: f64) -> f64 {
+        let want = tone(sample_rate, freq, data.len());
+        let sum: Complex = data.iter().zip(&want).map(|(a, b)| a * b.conj()).sum();
+        f64::from(sum.norm()) / data.len() as f64
+    }
+
+    #[test]
+    fn modulator() -> Result<()> {
+        let sr = 24000.0;
+        for method in [SsbMethod::Phasing, SsbMethod::Weaver] {
+            for (sideband, sign) in [(Sideband::Usb, 1.0), (Sideband::Lsb, -1.0)] {
+                for f in [300.0, 1000.0, 2400.0] {
+                    let o = modulate(method, sideband, f)?;
+                    let a = amplitude(&o, sr, sign * f);
+                    assert!((a - 1.0).abs() < 0.05, "{method:?} {sideband:?} {f}: {a}");
+                    let a = amplitude(&o, sr, -sign * f);
+                    assert!(a < 0.03, "{method:?} {sideband:?} {f}: {a}");
+                }
+                let o = modulate(method, sideband, 4000.0)?;
+                let a = rms_complex(&o);
+                assert!(a < 0.03, "{method:?} {sideband:?}: {a}");
+            }
+        }
+        Ok(())
+    }
+
+    fn rms_complex(data: &[Complex]) -> f64 {
+        (data.iter().map(|x| f64::from(x.norm_sqr())).sum::<f64>() / data.len() as f64).sqrt()
+    }
+}
@@ -0,0 +1,143 @@
+/*! Helpers for blocks that are TCP servers.
+
+Blocks can't block, so everything here is non-blocking. The block calls
+[`TcpServer::accept`], reads from clients with [`TcpClient::fill`], and queues
+data to clients, which is sent with [`TcpClient::flush`].
+*/
+use std::io::{ErrorKind, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+
+use log::{debug, info};
+
+use crate::{Error, Result};
+
+// Drop clients that don't read their data.
+const MAX_CLIENT_BACKLOG: usize = 1_000_000;
+const READ_SIZE: usize = 4096;
+
+/// A connected client, with protocol specific state `S`.
+pub(crate) struct TcpClient<S> {
+    stream: TcpStream,
+    pub(crate) peer: SocketAddr,
+    pub(crate) inbuf: Vec<u8>,
+    pub(crate) outbuf: Vec<u8>,
+    pub(crate) state: S,
+}
+
+impl<S: Default> TcpClient<S> {
+    fn new(stream: TcpStream, peer: SocketAddr) -> Result<Self> {
+        stream.set_nonblocking(true)?;
+        stream.set_nodelay(true)?;
+        Ok(Self {
+            stream,
+            peer,
+            inbuf: Vec::new(),
+            outbuf: Vec::new(),
+            state: S::default(),
+        })
+    }
+}
+
+impl<S> TcpClient<S> {
+    /// Write as much as possible of the queued output.
+    ///
+    /// Return false if the client should be dropped.
+    #[must_use]
+    pub(crate) fn flush(&mut self) -> bool {
+        while !self.outbuf.is_empty() {
+            match self.stream.write(&self.outbuf) {
+                Ok(0) => return false,
+                Ok(n) => {
+                    self.outbuf.drain(..n);
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => {
+                    debug!("TCP write to {} failed: {e}", self.peer);
+                    return false;
+                }
+            }
+        }
+        self.outbuf.len() <= MAX_CLIENT_BACKLOG
+    }
+
+    /// Read all available data from the client into `inbuf`.
+    ///
+    /// Return false if the client should be dropped.
+    #[must_use]
+    pub(crate) fn fill(&mut self) -> bool {
+        let mut buf = [0u8; READ_SIZE];
+        loop {
+            match self.stream.read(&mut buf) {
+                Ok(0) => return false,
+                Ok(n) => self.inbuf.extend(&buf[..n]),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return true,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => {
+                    debug!("TCP read from {} failed: {e}", self.peer);
+                    return false;
+                }
+            }
+        }
+    }
+}
+
+/// Non-blocking TCP server, keeping track of its clients.
+pub(crate) struct TcpServer<S> {
+    name: &'static str,
+    listener: TcpListener,
+    pub(crate) clients: Vec<TcpClient<S>>,
+}
+
+impl<S: Default> TcpServer<S> {
+    /// Start listening.
+    pub(crate) fn new<A: ToSocketAddrs>(name: &'static str, addr: A) -> Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        info!("{name}: listening on {}", listener.local_addr()?);
+        Ok(Self {
+            name,
+            listener,
+            clients: Vec::new(),
+        })
+    }
+
+    /// Return the address the server is listening on.
+    pub(crate) fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    /// Accept any new clients. Return true if any were accepted.
+    pub(crate) fn accept(&mut self) -> Result<bool> {
+        let mut accepted = false;
+        loop {
+            match self.listener.accept() {
+                Ok((stream, peer)) => {
+                    info!("{}: client connected from {peer}", self.name);
+                    self.clients.push(TcpClient::new(stream, peer)?);
+                    accepted = true;
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return Ok(accepted),
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(Error::wrap(e, format!("{}: accept", self.name))),
+            }
+        }
+    }
+
+    /// Flush output to, and read input from, all clients. Drop the clients
+    /// that fail.
+    pub(crate) fn flush_and_fill(&mut self) {
+        let name = self.name;
+        self.clients.retain_mut(|client| {
+            if !client.flush() {
+                info!("{name}: dropping client {}", client.peer);
+                return false;
+            }
+            if !client.fill() {
+                info!("{name}: client {} disconnected", client.peer);
+                return false;
+            }
+            true
+        });
+    }
+}
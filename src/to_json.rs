@@ -0,0 +1,72 @@
+/*! Turn values into JSON lines.
+
+## Example
+
+```
+use rustradio::blocks::{ToJson, PduToStream, WriterSink};
+use rustradio::stream::new_nocopy_stream;
+let (tx, rx) = new_nocopy_stream::<Vec<u32>>();
+let (to_json, prev) = ToJson::new(rx);
+let (pdu, prev) = PduToStream::new(prev);
+let sink = WriterSink::new(prev, std::io::stdout());
+```
+*/
+use log::warn;
+
+use crate::Result;
+use crate::block::{Block, BlockRet};
+use crate::stream::{NCReadStream, NCWriteStream};
+
+/// Turn values into JSON lines.
+///
+/// Each value is serialized as one line of JSON, including the newline.
+#[derive(rustradio_macros::Block)]
+#[rustradio(crate, new)]
+pub struct ToJson<T: Send + Sync + 'static> {
+    #[rustradio(in)]
+    src: NCReadStream<T>,
+    #[rustradio(out)]
+    dst: NCWriteStream<Vec<u8>>,
+}
+
+impl<T> Block for ToJson<T>
+where
+    T: serde::Serialize + Send + Sync + 'static,
+{
+    fn work(&mut self) -> Result<BlockRet<'_>> {
+        loop {
+            if self.dst.remaining() == 0 {
+                return Ok(BlockRet::WaitForStream(&self.dst, 1));
+            }
+            let Some((v, tags)) = self.src.pop() else {
+                return Ok(BlockRet::WaitForStream(&self.src, 1));
+            };
+            match serde_json::to_vec(&v) {
+                Ok(mut out) => {
+                    out.push(b'\n');
+                    self.dst.push(out, tags);
+                }
+                Err(e) => warn!("ToJson: failed to serialize: {e}"),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stream::new_nocopy_stream;
+
+    #[test]
+    fn lines() -> Result<()> {
+        let (tx, rx) = new_nocopy_stream();
+        let (mut b, out) = ToJson::new(rx);
+        tx.push(vec![1, 2], &[]);
+        tx.push(vec![3], &[]);
+        b.work()?;
+        assert_eq!(out.pop().unwrap().0, b"[1,2]\n");
+        assert_eq!(out.pop().unwrap().0, b"[3]\n");
+        assert!(out.pop().is_none());
+        Ok(())
+    }
+}
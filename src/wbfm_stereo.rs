@@ -0,0 +1,320 @@
+/*! Broadcast FM stereo demodulation.
+
+Broadcast FM carries the sum of the left and right channels (L+R) as
+regular mono audio, up to 15kHz. Stereo stations add a 19kHz pilot tone, and
+the difference (L-R) as double sideband suppressed carrier audio, on a 38kHz
+subcarrier locked to the pilot.
+
+## Example
+
+```
+use rustradio::blocks::WbfmStereoDemod;
+# fn main() -> rustradio::Result<()> {
+let (_, prev) = rustradio::stream::new_stream();
+let (demod, left, right) = WbfmStereoDemod::builder(prev, 240_000.0)
+    .deemphasis(rustradio::emphasis::TAU_50US)
+    .build()?;
+# Ok(())
+# }
+```
+*/
+use std::borrow::Cow;
+use std::collections::VecDeque;
+
+use crate::emphasis::FirstOrder;
+use crate::pll::Pll;
+use crate::ssb::FirState;
+use crate::stream::{ReadStream, Tag, TagValue, WriteStream};
+use crate::window::WindowType;
+use crate::{Complex, Error, Float, Result};
+
+/// Tag set on both outputs when stereo lock is gained or lost, and at the
+/// start of the stream. True means stereo.
+pub const STEREO_TAG: &str = "WbfmStereoDemod:stereo";
+
+const TAU: f64 = 2.0 * std::f64::consts::PI;
+
+const PILOT: f64 = 19_000.0;
+
+// Broadcast FM peak deviation.
+const MAX_DEVIATION: f64 = 75_000.0;
+
+// Lowest sample rate that fits the L-R sidebands, up to 53kHz.
+const MIN_SAMPLE_RATE: f64 = 120_000.0;
+
+// Stereo lock hysteresis, on the averaged cosine of the pilot phase error.
+const LOCK: f64 = 0.9;
+const UNLOCK: f64 = 0.7;
+
+/// Builder for [`WbfmStereoDemod`].
+pub struct WbfmStereoDemodBuilder {
+    src: ReadStream<Complex>,
+    sample_rate: f64,
+    tau: f64,
+}
+
+impl WbfmStereoDemodBuilder {
+    /// Set de-emphasis time constant, in seconds. Default 75µs. 0.0 to
+    /// disable.
+    #[must_use]
+    pub fn deemphasis(mut self, tau: f64) -> Self {
+        self.tau = tau;
+        self
+    }
+
+    /// Build the `WbfmStereoDemod`.
+    ///
+    /// Returns the block, and the left and right channel outputs.
+    pub fn build(self) -> Result<(WbfmStereoDemod, ReadStream<Float>, ReadStream<Float>)> {
+        let sr = self.sample_rate;
+        if sr < MIN_SAMPLE_RATE {
+            return Err(Error::msg(format!(
+                "WbfmStereoDemod: sample rate {sr} is too low, needs at least {MIN_SAMPLE_RATE}"
+            )));
+        }
+        // Band pass around the pilot, as an analytic signal. Shifted around
+        // the center tap, to keep the phase linear.
+        let taps = crate::fir::low_pass(sr as Float, 1000.0, 2000.0, &WindowType::Hamming);
+        let center = (taps.len() / 2) as f64;
+        let pilot: Vec<Complex> = taps
+            .into_iter()
+            .enumerate()
+            .map(|(n, t)| {
+                let p = TAU * PILOT * (n as f64 - center) / sr;
+                Complex::new(p.cos() as Float, p.sin() as Float) * t
+            })
+            .collect();
+        let audio = crate::fir::low_pass(sr as Float, 15_000.0, 4_000.0, &WindowType::Hamming);
+        let deemph = || {
+            (self.tau != 0.0)
+                .then(|| FirstOrder::deemphasis(sr, self.tau))
+                .transpose()
+        };
+        let (left, lr) = crate::stream::new_stream();
+        let (right, rr) = crate::stream::new_stream();
+        Ok((
+            WbfmStereoDemod {
+                src: self.src,
+                left,
+                right,
+                last: Complex::default(),
+                gain: (sr / (TAU * MAX_DEVIATION)) as Float,
+                // The pilot filter delays the recovered carrier, so delay the
+                // audio the same.
+                delay: std::iter::repeat_n(0.0, pilot.len() / 2 + 1).collect(),
+                pilot: FirState::new(&pilot),
+                pll: Pll::new(
+                    TAU * 20.0 / sr,
+                    TAU * (PILOT - 100.0) / sr,
+                    TAU * (PILOT + 100.0) / sr,
+                ),
+                lock_alpha: 1.0 / (0.05 * sr),
+                lock_level: 0.0,
+                stereo: None,
+                sum: FirState::new(&audio),
+                diff: FirState::new(&audio),
+                left_deemph: deemph()?,
+                right_deemph: deemph()?,
+            },
+            lr,
+            rr,
+        ))
+    }
+}
+
+/// Broadcast FM stereo demodulator.
+///
+/// Takes complex baseband, with the station filtered and centered at 0Hz, at
+/// a sample rate of at least 120kHz. Outputs left and right audio, de-emphasized,
+/// at the same sample rate. Full deviation is an amplitude of 1.0.
+///
+/// The pilot is tracked with a PLL. Without stereo lock, both channels get the
+/// mono audio. Changes in lock are tagged with [`STEREO_TAG`].
+#[derive(rustradio_macros::Block)]
+#[rustradio(crate, sync_tag)]
+pub struct WbfmStereoDemod {
+    #[rustradio(in)]
+    src: ReadStream<Complex>,
+    #[rustradio(out)]
+    left: WriteStream<Float>,
+    #[rustradio(out)]
+    right: WriteStream<Float>,
+
+    // FM demodulation.
+    last: Complex,
+    gain: Float,
+
+    // Pilot recovery.
+    delay: VecDeque<Float>,
+    pilot: FirState<Complex>,
+    pll: Pll,
+    lock_alpha: f64,
+    lock_level: f64,
+    stereo: Option<bool>,
+
+    // Audio.
+    sum: FirState<Float>,
+    diff: FirState<Float>,
+    left_deemph: Option<FirstOrder>,
+    right_deemph: Option<FirstOrder>,
+}
+
+impl WbfmStereoDemod {
+    /// Create a builder.
+    #[must_use]
+    pub fn builder(src: ReadStream<Complex>, sample_rate: f64) -> WbfmStereoDemodBuilder {
+        WbfmStereoDemodBuilder {
+            src,
+            sample_rate,
+            tau: crate::emphasis::TAU_75US,
+        }
+    }
+
+    /// Create new stereo demodulator, with default settings.
+    pub fn new(
+        src: ReadStream<Complex>,
+        sample_rate: f64,
+    ) -> Result<(Self, ReadStream<Float>, ReadStream<Float>)> {
+        Self::builder(src, sample_rate).build()
+    }
+
+    fn process_sync_tags<'a>(
+        &mut self,
+        s: Complex,
+        tags: &'a [Tag],
+    ) -> (Float, Cow<'a, [Tag]>, Float, Cow<'a, [Tag]>) {
+        let mpx = (self.last.conj() * s).arg() * self.gain;
+        self.last = s;
+
+        // Track pilot.
+        let phase = self.pll.phase;
+        let error = self.pll.track(self.pilot.filter(Complex::new(mpx, 0.0)));
+        self.lock_level += (error.cos() - self.lock_level) * self.lock_alpha;
+        let stereo = match self.stereo {
+            Some(true) => self.lock_level > UNLOCK,
+            _ => self.lock_level > LOCK,
+        };
+
+        // The pilot is a sine, and the phase is of its analytic signal, so
+        // the 38kHz subcarrier sin(2(phase+π/2)) is -sin(2·phase).
+        self.delay.pop_front();
+        self.delay.push_back(mpx);
+        let mpx = self.delay[0];
+        let sum = self.sum.filter(mpx);
+        let diff = self.diff.filter(-2.0 * mpx * (2.0 * phase).sin() as Float);
+        let (l, r) = if stereo {
+            (sum + diff, sum - diff)
+        } else {
+            (sum, sum)
+        };
+        let l = self.left_deemph.as_mut().map_or(l, |f| f.filter(l));
+        let r = self.right_deemph.as_mut().map_or(r, |f| f.filter(r));
+
+        if self.stereo == Some(stereo) {
+            return (l, Cow::Borrowed(tags), r, Cow::Borrowed(tags));
+        }
+        self.stereo = Some(stereo);
+        let mut tags = tags.to_vec();
+        tags.push(Tag::new(0, STEREO_TAG, TagValue::Bool(stereo)));
+        (l, Cow::Owned(tags.clone()), r, Cow::Owned(tags))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::Block;
+    use crate::blocks::VectorSource;
+
+    const SR: f64 = 240_000.0;
+
+    // FM modulate left and right channel tones, optionally with a pilot.
+    fn signal(left: f64, right: f64, pilot: bool, len: usize) -> Vec<Complex> {
+        let mut phase = 0.0f64;
+        (0..len)
+            .map(|n| {
+                let t = n as f64 / SR;
+                let l = 0.5 * (TAU * left * t).sin();
+                let r = 0.5 * (TAU * right * t).sin();
+                let p = TAU * PILOT * t;
+                let mpx = if pilot {
+                    0.9 * ((l + r) / 2.0 + (l - r) / 2.0 * (2.0 * p).sin()) + 0.1 * p.sin()
+                } else {
+                    (l + r) / 2.0
+                };
+                phase += TAU * MAX_DEVIATION * mpx / SR;
+                Complex::new(phase.cos() as Float, phase.sin() as Float)
+            })
+            .collect()
+    }
+
+    // Amplitude of the given frequency.
+    fn amplitude(data: &[Float], freq: f64) -> f64 {
+        let (mut re, mut im) = (0.0, 0.0);
+        for (n, &x) in data.iter().enumerate() {
+            let p = TAU * freq * n as f64 / SR;
+            re += f64::from(x) * p.cos();
+            im += f64::from(x) * p.sin();
+        }
+        2.0 * (re * re + im * im).sqrt() / data.len() as f64
+    }
+
+    fn run(input: Vec<Complex>) -> Result<(Vec<Float>, Vec<Float>, Vec<Tag>)> {
+        let (mut src, prev) = VectorSource::new(input);
+        src.work()?;
+        let (mut b, left, right) = WbfmStereoDemod::new(prev, SR)?;
+        b.work()?;
+        let (l, tags) = left.read_buf()?;
+        let (r, _) = right.read_buf()?;
+        Ok((l.slice().to_vec(), r.slice().to_vec(), tags))
+    }
+
+    fn stereo_tags(tags: &[Tag]) -> Vec<(usize, bool)> {
+        tags.iter()
+            .filter(|t| t.key() == STEREO_TAG)
+            .map(|t| match t.val() {
+                TagValue::Bool(b) => (t.pos(), *b),
+                _ => panic!("bad tag {t:?}"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn stereo() -> Result<()> {
+        let (l, r, tags) = run(signal(1000.0, 400.0, true, 120_000))?;
+        let st = stereo_tags(&tags);
+        assert_eq!(st.len(), 2, "{st:?}");
+        assert_eq!(st[0], (0, false));
+        assert!(st[1].1 && st[1].0 < 30_000, "{st:?}");
+
+        // After lock. 0.5 amplitude, times 0.9 for the pilot, and de-emphasis.
+        let deemph = |f: f64| 1.0 / (1.0 + (f * TAU * 75e-6).powi(2)).sqrt();
+        let (l, r) = (&l[60_000..], &r[60_000..]);
+        for (data, want, other) in [(l, 1000.0, 400.0), (r, 400.0, 1000.0)] {
+            let a = amplitude(data, want);
+            let expected = 0.45 * deemph(want);
+            assert!((a - expected).abs() < 0.02, "{want}: {a} != {expected}");
+            // At least 25dB separation.
+            let crosstalk = amplitude(data, other);
+            assert!(crosstalk < a / 17.0, "{other}: {crosstalk}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn mono() -> Result<()> {
+        let (l, r, tags) = run(signal(1000.0, 1000.0, false, 60_000))?;
+        assert_eq!(stereo_tags(&tags), vec![(0, false)]);
+        assert_eq!(l, r);
+        let a = amplitude(&l[30_000..], 1000.0);
+        let expected = 0.5 / (1.0 + (1000.0 * TAU * 75e-6f64).powi(2)).sqrt();
+        assert!((a - expected).abs() < 0.02, "{a} != {expected}");
+        Ok(())
+    }
+
+    #[test]
+    fn low_sample_rate() {
+        let (_, prev) = crate::stream::new_stream();
+        assert!(WbfmStereoDemod::new(prev, 48_000.0).is_err());
+    }
+}
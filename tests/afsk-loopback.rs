@@ -0,0 +1,77 @@
+use anyhow::Result;
+
+use rustradio::blockchain;
+use rustradio::blocks::*;
+use rustradio::graph::{Graph, GraphRunner};
+use rustradio::stream::{Tag, TagValue, new_nocopy_stream};
+use rustradio::symbol_sync::TedZeroCrossing;
+use rustradio::window::WindowType;
+
+// Modulate AX.25 frames as Bell 202, and demodulate them like `ax25-1200-rx`.
+fn loopback(samp_rate: f32, frames: &[Vec<u8>]) -> Result<Vec<Vec<u8>>> {
+    let (tx, prev) = new_nocopy_stream();
+    for (n, frame) in frames.iter().enumerate() {
+        let tags = if n == 0 {
+            vec![Tag::new(0, "KissDecode:txdelay-ms", TagValue::U64(300))]
+        } else {
+            vec![]
+        };
+        tx.push(frame.clone(), tags);
+    }
+    drop(tx);
+
+    let mut g = Graph::new();
+    let taps = rustradio::fir::low_pass(samp_rate, 1100.0, 100.0, &WindowType::Hamming);
+    let center_freq = 1700.0;
+    let baud = 1200.0;
+    let prev = blockchain![
+        g,
+        prev,
+        FcsAdder::new(prev),
+        HdlcFramer::new(prev),
+        PduToStream::new(prev),
+        NrziEncode::new(prev),
+        AfskModulator::new(prev, samp_rate.into()),
+        Hilbert::new(prev, 65, &WindowType::Hamming),
+        QuadratureDemod::new(prev, 1.0),
+        FftFilterFloat::new(prev, &taps),
+        add_const(prev, -center_freq * 2.0 * std::f32::consts::PI / samp_rate),
+        SymbolSync::new(
+            prev,
+            samp_rate / baud,
+            0.5,
+            Box::new(TedZeroCrossing::new()),
+            Box::new(rustradio::iir_filter::IirFilter::new(&[0.5, 0.5])),
+        ),
+        BinarySlicer::new(prev),
+        NrziDecode::new(prev),
+        HdlcDeframer::new(prev, 10, 1500),
+    ];
+    g.run()?;
+    let mut got = Vec::new();
+    while let Some((frame, _)) = prev.pop() {
+        got.push(frame);
+    }
+    Ok(got)
+}
+
+#[test]
+fn afsk_loopback() -> Result<()> {
+    let frames: Vec<Vec<u8>> = ["hello", "world", &"long packet ".repeat(20)]
+        .into_iter()
+        .map(|info| {
+            rustradio::ax25::Ax25Frame::ui(
+                "M0THC-2".parse().unwrap(),
+                "APZ001".parse().unwrap(),
+                vec!["WIDE1-1".parse().unwrap()],
+                info.as_bytes().to_vec(),
+            )
+            .serialize()
+            .unwrap()
+        })
+        .collect();
+    for samp_rate in [44100.0, 48000.0] {
+        assert_eq!(loopback(samp_rate, &frames)?, frames, "{samp_rate}");
+    }
+    Ok(())
+}
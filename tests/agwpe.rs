@@ -0,0 +1,117 @@
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use anyhow::Result;
+
+use rustradio::block::Block;
+use rustradio::blocks::AgwpeServer;
+use rustradio::stream::{Tag, TagValue, new_nocopy_stream};
+
+fn header(port: u8, kind: u8, call_from: &str, data: &[u8]) -> Vec<u8> {
+    let mut out = vec![port, 0, 0, 0, kind, 0, 0, 0];
+    let mut call = [0u8; 10];
+    call[..call_from.len()].copy_from_slice(call_from.as_bytes());
+    out.extend(call);
+    out.extend([0u8; 10]);
+    out.extend(u32::try_from(data.len()).unwrap().to_le_bytes());
+    out.extend([0u8; 4]);
+    out.extend(data);
+    out
+}
+
+/// Read one message, returning kind, call_from, and data.
+fn read_msg(c: &mut TcpStream) -> Result<(u8, String, Vec<u8>)> {
+    let mut h = [0u8; 36];
+    c.read_exact(&mut h)?;
+    let len = u32::from_le_bytes(h[28..32].try_into()?) as usize;
+    let mut data = vec![0u8; len];
+    c.read_exact(&mut data)?;
+    let call: Vec<u8> = h[8..18].iter().copied().take_while(|&b| b != 0).collect();
+    Ok((h[4], String::from_utf8(call)?, data))
+}
+
+#[test]
+fn agwpe_client() -> Result<()> {
+    let (tx, rx) = new_nocopy_stream();
+    let (mut server, out) = AgwpeServer::new(rx, "[::1]:0")?;
+    let addr = server.local_addr()?;
+
+    let done = Arc::new(AtomicBool::new(false));
+    let done2 = done.clone();
+    let th = std::thread::spawn(move || -> rustradio::Result<()> {
+        while !done2.load(Ordering::Relaxed) {
+            server.work()?;
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        Ok(())
+    });
+
+    let mut c = TcpStream::connect(addr)?;
+    c.set_read_timeout(Some(Duration::from_secs(10)))?;
+
+    // Version.
+    c.write_all(&header(0, b'R', "", &[]))?;
+    let (kind, _, data) = read_msg(&mut c)?;
+    assert_eq!(kind, b'R');
+    assert_eq!(data.len(), 8);
+
+    // Port info.
+    c.write_all(&header(0, b'G', "", &[]))?;
+    let (kind, _, data) = read_msg(&mut c)?;
+    assert_eq!(kind, b'G');
+    assert_eq!(data, b"1;Port1 rustradio;\0");
+
+    // Port capabilities.
+    c.write_all(&header(0, b'g', "", &[]))?;
+    let (kind, _, data) = read_msg(&mut c)?;
+    assert_eq!(kind, b'g');
+    assert_eq!(data.len(), 12);
+
+    // Register callsign, twice.
+    c.write_all(&header(0, b'X', "M0THC", &[]))?;
+    assert_eq!(read_msg(&mut c)?, (b'X', "M0THC".to_string(), vec![1]));
+    c.write_all(&header(0, b'X', "M0THC", &[]))?;
+    assert_eq!(read_msg(&mut c)?, (b'X', "M0THC".to_string(), vec![0]));
+
+    // Ask for both monitoring and raw frames, and confirm with another
+    // request that they were processed.
+    c.write_all(&header(0, b'm', "", &[]))?;
+    c.write_all(&header(0, b'k', "", &[]))?;
+    c.write_all(&header(0, b'R', "", &[]))?;
+    assert_eq!(read_msg(&mut c)?.0, b'R');
+
+    // Frame received over the air.
+    let frame = b"\x82\xa0\xb4\x60\x60\x62\x60\x9a\x60\xa8\x90\x86\x40\xe5\x03\xf0hello".to_vec();
+    tx.push(frame.clone(), &[]);
+    let (kind, call, data) = read_msg(&mut c)?;
+    assert_eq!((kind, call.as_str()), (b'K', "M0THC-2"));
+    assert_eq!(data[0], 0);
+    assert_eq!(data[1..], frame);
+    let (kind, call, data) = read_msg(&mut c)?;
+    assert_eq!((kind, call.as_str()), (b'U', "M0THC-2"));
+    let text = String::from_utf8(data)?;
+    assert!(
+        text.starts_with(" 1:Fm M0THC-2 To APZ001 <UI pid=F0 Len=5 >["),
+        "{text}"
+    );
+
+    // Raw frame to transmit.
+    let mut data = vec![0u8];
+    data.extend(&frame);
+    c.write_all(&header(0, b'K', "", &data))?;
+    let (got, tags) = loop {
+        if let Some(v) = out.pop() {
+            break v;
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    };
+    assert_eq!(got, frame);
+    assert_eq!(tags, &[Tag::new(0, "AgwpeServer:port", TagValue::U64(0))]);
+
+    done.store(true, Ordering::Relaxed);
+    th.join().unwrap()?;
+    Ok(())
+}
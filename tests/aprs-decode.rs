@@ -0,0 +1,156 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+
+use rustradio::aprs::{AprsData, AprsPacket};
+use rustradio::au::Encoding;
+use rustradio::ax25::Ax25Frame;
+use rustradio::blockchain;
+use rustradio::blocks::*;
+use rustradio::file_sink::Mode;
+use rustradio::graph::{Graph, GraphRunner};
+use rustradio::stream::{Tag, TagValue, new_nocopy_stream};
+use rustradio::symbol_sync::TedZeroCrossing;
+use rustradio::window::WindowType;
+
+const SAMP_RATE: u32 = 44100;
+
+// Write AX.25 frames as Bell 202 audio to an .au file.
+fn write_au(path: &Path, frames: &[Ax25Frame]) -> Result<()> {
+    let (tx, prev) = new_nocopy_stream();
+    for (n, frame) in frames.iter().enumerate() {
+        let tags = if n == 0 {
+            vec![Tag::new(0, "KissDecode:txdelay-ms", TagValue::U64(300))]
+        } else {
+            vec![]
+        };
+        tx.push(frame.serialize()?, tags);
+    }
+    drop(tx);
+    let mut g = Graph::new();
+    let prev = blockchain![
+        g,
+        prev,
+        FcsAdder::new(prev),
+        HdlcFramer::new(prev),
+        PduToStream::new(prev),
+        NrziEncode::new(prev),
+        AfskModulator::new(prev, SAMP_RATE.into()),
+        MultiplyConst::new(prev, 0.5),
+        AuEncode::new(prev, Encoding::Pcm16, SAMP_RATE, 1),
+    ];
+    g.add(Box::new(FileSink::new(prev, path, Mode::Overwrite)?));
+    g.run()?;
+    Ok(())
+}
+
+// Decode APRS from an .au file, like `ax25-1200-rx -a --aprs-json`.
+fn decode_au(path: &Path) -> Result<Vec<AprsPacket>> {
+    let samp_rate = SAMP_RATE as f32;
+    let taps = rustradio::fir::low_pass(samp_rate, 1100.0, 100.0, &WindowType::Hamming);
+    let center_freq = 1700.0;
+    let baud = 1200.0;
+    let mut g = Graph::new();
+    let prev = blockchain![
+        g,
+        prev,
+        FileSource::new(path)?,
+        AuDecode::new(prev, SAMP_RATE),
+        Hilbert::new(prev, 65, &WindowType::Hamming),
+        QuadratureDemod::new(prev, 1.0),
+        FftFilterFloat::new(prev, &taps),
+        add_const(prev, -center_freq * 2.0 * std::f32::consts::PI / samp_rate),
+        SymbolSync::new(
+            prev,
+            samp_rate / baud,
+            0.5,
+            Box::new(TedZeroCrossing::new()),
+            Box::new(rustradio::iir_filter::IirFilter::new(&[0.5, 0.5])),
+        ),
+        BinarySlicer::new(prev),
+        NrziDecode::new(prev),
+        HdlcDeframer::new(prev, 10, 1500),
+        Ax25Decode::new(prev),
+        AprsDecode::new(prev),
+    ];
+    g.run()?;
+    let mut got = Vec::new();
+    while let Some((packet, _)) = prev.pop() {
+        got.push(packet);
+    }
+    Ok(got)
+}
+
+struct TempFile(PathBuf);
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+#[test]
+fn aprs_from_au() -> Result<()> {
+    let frames: Vec<Ax25Frame> = [
+        "!4903.50N/07201.75W-Test 001234",
+        ":N0CALL   :hello{42",
+        ">Testing",
+    ]
+    .into_iter()
+    .map(|info| {
+        Ax25Frame::ui(
+            "M0THC-2".parse().unwrap(),
+            "APZ001".parse().unwrap(),
+            vec!["WIDE1-1".parse().unwrap()],
+            info.as_bytes().to_vec(),
+        )
+    })
+    .collect();
+    let file = TempFile(
+        std::env::temp_dir().join(format!("rustradio-aprs-decode-{}.au", std::process::id())),
+    );
+    write_au(&file.0, &frames)?;
+    let got = decode_au(&file.0)?;
+    let want = frames
+        .iter()
+        .map(AprsPacket::from_frame)
+        .collect::<rustradio::Result<Vec<_>>>()?;
+    assert_eq!(got, want);
+
+    assert_eq!(got[0].src, "M0THC-2");
+    assert_eq!(got[0].path, ["WIDE1-1"]);
+    let AprsData::Position(p) = &got[0].data else {
+        panic!("not a position: {:?}", got[0].data);
+    };
+    assert!((p.latitude - 49.058_333).abs() < 0.0001, "{}", p.latitude);
+    assert!(
+        (p.longitude - -72.029_166).abs() < 0.0001,
+        "{}",
+        p.longitude
+    );
+    assert_eq!(
+        got[1].data,
+        AprsData::Message {
+            addressee: "N0CALL".into(),
+            text: "hello".into(),
+            id: Some("42".into()),
+        }
+    );
+    assert_eq!(
+        got[2].data,
+        AprsData::Status {
+            timestamp: None,
+            text: "Testing".into(),
+        }
+    );
+    Ok(())
+}
+
+// The only frame in this capture has bit errors in the recorded audio
+// itself (e.g. `433.775M\xc8z`), so its FCS does not match. It must be
+// dropped, not passed on as a bogus packet.
+#[test]
+fn aprs_capture_bad_fcs() -> Result<()> {
+    assert_eq!(decode_au(Path::new("testdata/aprs.au"))?, []);
+    Ok(())
+}
@@ -2,6 +2,6 @@
 set -ueo pipefail
 cd "$TICKBOX_TEMPDIR/work"
 export CARGO_TARGET_DIR="$TICKBOX_CWD/target/${TICKBOX_BRANCH}.clippy"
-exec cargo clippy --no-deps --workspace -F rtlsdr,soapysdr,fast-math,audio,fftw,async,nix,pipewire -- -D warnings
+exec cargo clippy --no-deps --workspace -F rtlsdr,soapysdr,fast-math,audio,fftw,async,nix,pipewire,png -- -D warnings
 # Was, and maybe should at some point be changed back to:
 # exec cargo clippy --all-features --all-targets
@@ -3,4 +3,4 @@ set -ueo pipefail
 cd "$TICKBOX_TEMPDIR/work"
 export CARGO_TARGET_DIR="$TICKBOX_CWD/target/${TICKBOX_BRANCH}.clippy.nightly"
 export RUSTFLAGS="--cfg tokio_unstable"
-exec cargo +nightly clippy --no-deps --workspace --all-targets -F simd,rtlsdr,soapysdr,fast-math,audio,fftw,async,tokio-unstable,nix,pipewire -- -D warnings
+exec cargo +nightly clippy --no-deps --workspace --all-targets -F simd,rtlsdr,soapysdr,fast-math,audio,fftw,async,tokio-unstable,nix,pipewire,png -- -D warnings
@@ -2,7 +2,7 @@
 set -ueo pipefail
 export CARGO_TARGET_DIR="$TICKBOX_CWD/target/${TICKBOX_BRANCH}.test.all-features"
 cd "$TICKBOX_TEMPDIR/work"
-cargo test --workspace --features rtlsdr,soapysdr,fast-math,audio,fftw,async,pipewire,volk
+cargo test --workspace --features rtlsdr,soapysdr,fast-math,audio,fftw,async,pipewire,png,volk
 if [[ ${CLEANUP:-} = true ]]; then
         rm -fr "${CARGO_TARGET_DIR?}"
 fi